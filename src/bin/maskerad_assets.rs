@@ -0,0 +1,258 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! maskerad-assets : cooks the assets of a game without launching it.
+//!
+//! Exits with 0 on success, 1 when the command failed, 2 when the command line is not valid.
+
+extern crate maskerad_resource_manager;
+
+use std::env;
+use std::fs;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process;
+use maskerad_resource_manager::pipeline::asset_database::{AssetDatabase, AssetRecord};
+use maskerad_resource_manager::pipeline::asset_pipeline::{AssetPipeline, collect_assets};
+use maskerad_resource_manager::pipeline::build_cache::BUILD_CACHE_FILE;
+use maskerad_resource_manager::resources::pak::{PakArchive, PakEntry, PakWriter, normalize_pak_path};
+use maskerad_resource_manager::resources::vfs::{VFS_SCHEME, virtual_path_relative};
+use maskerad_resource_manager::resources::resource_manager_errors::ResourceManagerResult;
+
+const USAGE: &str = "Usage: maskerad-assets <command> [arguments]
+
+Commands:
+    build <src> <out> [--rebuild]       compiles the assets of <src> in <out>, skipping the ones which did not change.
+    pack <dir> <file.pak>               packs the files of <dir> in a pak file.
+    list <file.pak>                     lists the entries of a pak file.
+    extract <file.pak> <dir>            extracts the entries of a pak file in <dir>.
+    verify <file.pak>                   reads every entry of a pak file, to find the corrupted ones.
+    deps <asset> [--root <src>]         prints the dependency tree of an asset of <src>, the current directory by default.
+    move <from> <to> [--root <src>] [--dry-run]
+                                        moves an asset of <src> with its meta file, and rewrites the references to its path.
+                                        An asset reading other files through relative paths, like a glTF file, stays in its directory.";
+
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+/// `Ok(false)` when the command ran but did not succeed, like a build with assets which could not be compiled.
+type CommandResult = ResourceManagerResult<bool>;
+
+fn build(source_root: &Path, output_root: &Path, rebuild: bool) -> CommandResult {
+    let pipeline = AssetPipeline::new();
+    let report = if rebuild {
+        pipeline.rebuild(source_root, output_root)?
+    } else {
+        pipeline.build(source_root, output_root)?
+    };
+
+    for asset in report.compiled.iter() {
+        println!("compiled    {} -> {}", asset.source.display(), asset.output.display());
+    }
+    for asset in report.copied.iter() {
+        println!("copied      {}", asset.display());
+    }
+    for &(ref asset, ref error) in report.failed.iter() {
+        eprintln!("failed      {}: {}", asset.display(), error);
+    }
+    println!("{} compiled, {} copied, {} up to date, {} failed.", report.compiled.len(), report.copied.len(), report.skipped.len(), report.failed.len());
+    Ok(report.is_success())
+}
+
+fn pack(directory: &Path, pak_path: &Path) -> CommandResult {
+    //The pak file may be written in the directory, it must not be packed in itself.
+    let pak_file = fs::canonicalize(pak_path).ok();
+    let files: Vec<PathBuf> = collect_assets(directory)?.into_iter()
+        .filter(|file| file.as_path() != Path::new(BUILD_CACHE_FILE))
+        .filter(|file| fs::canonicalize(directory.join(file)).ok() != pak_file)
+        .collect();
+
+    let mut writer = PakWriter::create(pak_path)?;
+    for file in files.iter() {
+        writer.add_file(file.as_path(), directory.join(file))?;
+    }
+    writer.finish()?;
+    println!("{} entries packed in {}.", files.len(), pak_path.display());
+    Ok(true)
+}
+
+fn list(pak_path: &Path) -> CommandResult {
+    let archive = PakArchive::open(pak_path)?;
+    let mut entries: Vec<&PakEntry> = archive.entries().iter().collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    println!("{:>12} {:>12}  {:<12} path", "size", "stored", "compression");
+    for entry in entries {
+        let compression = entry.compression().map(|compression| format!("{:?}", compression)).unwrap_or_else(|_| String::from("unknown"));
+        println!("{:>12} {:>12}  {:<12} {}", entry.uncompressed_size, entry.size, compression, entry.path);
+    }
+    println!("{} entries.", archive.len());
+    Ok(true)
+}
+
+fn extract(pak_path: &Path, directory: &Path) -> CommandResult {
+    let archive = PakArchive::open(pak_path)?;
+    for entry in archive.entries() {
+        //Normalized, the path of an entry cannot go out of the directory.
+        let output = directory.join(normalize_pak_path(entry.path.as_str()));
+        if let Some(parent) = output.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(output, archive.read_entry(entry.path.as_str())?)?;
+    }
+    println!("{} entries extracted in {}.", archive.len(), directory.display());
+    Ok(true)
+}
+
+fn verify(pak_path: &Path) -> CommandResult {
+    let archive = PakArchive::open(pak_path)?;
+    let mut corrupted = 0;
+    for entry in archive.entries() {
+        if let Err(error) = archive.verify_entry(entry.path.as_str()) {
+            eprintln!("corrupted   {}: {}", entry.path, error);
+            corrupted += 1;
+        }
+    }
+    println!("{} entries verified, {} corrupted.", archive.len(), corrupted);
+    Ok(corrupted == 0)
+}
+
+/// Prints the assets of the database whose GUID is already used : their references by GUID go to another asset.
+fn print_guid_collisions(database: &AssetDatabase) {
+    for collision in database.guid_collisions() {
+        eprintln!("guid collision {}: {} is already used by {}", collision.path.display(), collision.guid, collision.other_path.display());
+    }
+}
+
+fn deps(source_root: &Path, asset: &Path) -> CommandResult {
+    let database = AssetDatabase::scan(source_root)?;
+    print_guid_collisions(&database);
+    let record = match database.resolve(&asset.to_string_lossy()).or_else(|| database.resolve(&format!("{}{}", VFS_SCHEME, normalize_pak_path(asset)))) {
+        Some(record) => record,
+        None => {
+            eprintln!("The asset {} does not exist in {}.", asset.display(), source_root.display());
+            return Ok(false);
+        },
+    };
+
+    let pipeline = AssetPipeline::new();
+    let mut visited = HashSet::new();
+    println!("{}", record.path.display());
+    let missing = print_dependencies(&pipeline, source_root, &database, record, 1, &mut visited);
+    Ok(missing == 0)
+}
+
+/// Prints the references of an asset, and the references of the assets referenced, once.
+/// They are the references indexed by the asset database, and the dependencies found by the compiler of the asset, like the material library of an OBJ file.
+/// Returns the number of references to assets which are not in the project.
+fn print_dependencies(pipeline: &AssetPipeline, source_root: &Path, database: &AssetDatabase, record: &AssetRecord, depth: usize, visited: &mut HashSet<PathBuf>) -> usize {
+    visited.insert(record.path.clone());
+    let indent = "    ".repeat(depth);
+    let mut references: Vec<String> = record.references.iter().map(|reference| reference.reference.clone()).collect();
+    if let Some(asset) = virtual_path_relative(record.path.as_path()) {
+        match pipeline.dependencies_of(source_root, asset.as_str()) {
+            Ok(dependencies) => {
+                for dependency in dependencies {
+                    let dependency = dependency.to_string_lossy().into_owned();
+                    if !references.contains(&dependency) {
+                        references.push(dependency);
+                    }
+                }
+            },
+            Err(error) => eprintln!("The dependencies found by the compiler of {} are unknown: {}", record.path.display(), error),
+        }
+    }
+
+    let mut missing = 0;
+    for reference in references.iter() {
+        match database.resolve(reference.as_str()) {
+            Some(dependency) => {
+                println!("{}{}", indent, reference);
+                if !visited.contains(&dependency.path) {
+                    missing += print_dependencies(pipeline, source_root, database, dependency, depth + 1, visited);
+                }
+            },
+            None => {
+                println!("{}{} (missing)", indent, reference);
+                missing += 1;
+            },
+        }
+    }
+    missing
+}
+
+fn move_asset(source_root: &Path, from: &Path, to: &Path, dry_run: bool) -> CommandResult {
+    let mut database = AssetDatabase::scan(source_root)?;
+    print_guid_collisions(&database);
+    let (asset_move, moved, rewritten) = if dry_run {
+        (database.plan_move(from, to)?, "to move", "to rewrite")
+    } else {
+        (database.move_asset_files(from, to)?, "moved", "rewritten")
+    };
+
+    for &(ref source, ref destination) in asset_move.moved_files.iter() {
+        println!("{:<14}{} -> {}", moved, source.display(), destination.display());
+    }
+    for file in asset_move.rewritten_files.iter() {
+        println!("{:<14}{}", rewritten, file.display());
+    }
+    //Only a dry run lists them, the move itself is refused.
+    for input in asset_move.stranded_inputs.iter() {
+        eprintln!("{:<14}{}", "left behind", input.display());
+    }
+    println!("{} files {}, {} files {}.", asset_move.moved_files.len(), moved, asset_move.rewritten_files.len(), rewritten);
+    Ok(asset_move.stranded_inputs.is_empty())
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(EXIT_USAGE);
+}
+
+fn main() {
+    let arguments: Vec<String> = env::args().skip(1).collect();
+    let mut rebuild = false;
+    let mut dry_run = false;
+    let mut root = None;
+    let mut positional = Vec::new();
+    let mut iter = arguments.iter();
+    while let Some(argument) = iter.next() {
+        match argument.as_str() {
+            "--rebuild" => rebuild = true,
+            "--dry-run" => dry_run = true,
+            "--root" => match iter.next() {
+                Some(directory) => root = Some(PathBuf::from(directory)),
+                None => usage_error("--root needs a directory."),
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            },
+            _ => positional.push(argument.as_str()),
+        }
+    }
+
+    let result = match positional.as_slice() {
+        &["build", source_root, output_root] => build(Path::new(source_root), Path::new(output_root), rebuild),
+        &["pack", directory, pak_path] => pack(Path::new(directory), Path::new(pak_path)),
+        &["list", pak_path] => list(Path::new(pak_path)),
+        &["extract", pak_path, directory] => extract(Path::new(pak_path), Path::new(directory)),
+        &["verify", pak_path] => verify(Path::new(pak_path)),
+        &["deps", asset] => deps(root.as_ref().map(PathBuf::as_path).unwrap_or_else(|| Path::new(".")), Path::new(asset)),
+        &["move", from, to] => move_asset(root.as_ref().map(PathBuf::as_path).unwrap_or_else(|| Path::new(".")), Path::new(from), Path::new(to), dry_run),
+        &[] => usage_error("No command given."),
+        &[command, ..] => usage_error(&format!("Unknown command, or wrong arguments for the command: {}.", command)),
+    };
+
+    match result {
+        Ok(true) => {},
+        Ok(false) => process::exit(EXIT_FAILURE),
+        Err(error) => {
+            eprintln!("error: {}", error);
+            process::exit(EXIT_FAILURE);
+        },
+    }
+}
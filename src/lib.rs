@@ -5,13 +5,18 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-extern crate maskerad_object_pool;
 extern crate maskerad_memory_allocators;
 extern crate maskerad_data_parser;
 extern crate maskerad_gameobject_model;
 extern crate gltf;
 extern crate lewton;
 extern crate imagefmt;
+extern crate mikktspace;
+extern crate lz4_flex;
+extern crate zstd;
+extern crate flate2;
+extern crate memmap2;
+extern crate toml;
 #[macro_use]
 extern crate log;
 extern crate maskerad_filesystem;
@@ -20,6 +25,9 @@ pub mod resources;
 pub mod properties;
 pub mod resource_manager;
 pub mod property_manager;
+pub mod pipeline;
+#[cfg(test)]
+mod test_project;
 
 /*
     functionalities :
@@ -33,14 +41,15 @@ pub mod property_manager;
     - a unified interface, through the form of a memory manager. -> one structure               OK
     - handle streaming, if the fs support it.
 
-    resource file organization : a simple one, to begin with :
-    - no zip or pak files containing a lot of data like love2D and Unreal.
+    resource file organization :
     - just a file.
+    - or pak files containing a lot of data like love2D and Unreal (see resources::pak), mounted by the resource manager.
+    - both are mounted in a virtual filesystem, with res:// paths and overlays for patches and mods (see resources::vfs).
 
 
     file formats :
     - textures : tga.
-    - 3D models : glTF.
+    - 3D models : glTF, Wavefront OBJ (+ MTL materials).
     - Audio : ogg.
     - or maybe make an offline tool to "compile" data in bin form.
 
@@ -75,12 +84,14 @@ ________________________________________________________________________________
     Resource DB :
     - Assets need to pass a conditioning pipeline to be usable by a game engine.
     - need to convert the asset to a binary format usable by GA.
+      -> The native resources : a versioned header, the dependencies and an aligned payload. Stale cooked data is refused.
 
     Conditioning pipeline :
     - what compression algorithm is the most efficient for this type of bitmap ?
     - The frames to keep from a blender animation ?
 
     => We need metadata, to know how to process an asset.
+       -> A .meta toml file next to each asset (Untitled.tga.meta), read in a ResourceMeta : GUID, import settings, tags, dependencies.
 
     Form of a resource DB :
     - config files (xml, toml...) "linked" to assets, which encode the resource building metadata.
@@ -97,6 +108,9 @@ ________________________________________________________________________________
    - guarantee referential integrity of resources (if a mesh reference a material, and this one is deleted/moved, warn the user about it and remove/update the reference from the mesh)
    - revision history (who did what when)
    - search query
+   => The AssetDatabase of the pipeline module indexes a project directory : queries by type, tag, name and referrer,
+      and the references left dangling when an asset is moved or deleted. It moves an asset on disk too, rewriting the references
+      to its path in the levels, the game objects and the meta files (`maskerad-assets move`, with `--dry-run` to list them first).
 
    some references :
    -    Unreal engine browser (UnrealEd -> THE tool. the editor IS PART of the engine, not a separate software using the engine)
@@ -141,6 +155,8 @@ ________________________________________________________________________________
 
     FIRST DRAFT OF OUR OFFLINE RESOURCE MANAGER:
     - The naughty dog solution looks cool. maybe choose another config file.
+    - The resource compilers are in the pipeline module : tga -> texture, glTF/OBJ -> mesh, ogg -> audio clip,
+      with the import settings of the .meta files.
 _____________________________________________________________________________________________________
 
     PART TWO
@@ -185,7 +201,7 @@ ________________________________________________________________________________
    We may need custom file formats, if the open standards one (JPEG, COLLADA, OBJ...)
    doesn't give us all the data we need.
 
-   resources need GUIDs
+   resources need GUIDs -> see resources::guid, stored in the .meta files next to the assets.
 
    Registry of loaded resources -> ensure data loaded one time only, at any given time :
 
@@ -0,0 +1,702 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::BTreeMap;
+use std::collections::btree_map::Values;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use toml::{self, Value};
+use pipeline::asset_pipeline::{AssetPipeline, collect_assets};
+use pipeline::compiler::virtual_asset_path;
+use resources::guid::{GuidDatabase, ResourceGuid, GUID_SCHEME};
+use resources::resource_meta::{ResourceMeta, meta_path, META_EXTENSION};
+use resources::resources_registry::ResourceType;
+use resources::vfs::{VFS_SCHEME, virtual_path_relative};
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+
+const TOML_EXTENSION: &str = "toml";
+
+//The virtual path of an asset given by virtual path, or by path relative to the project directory.
+fn target_path(asset: &Path) -> PathBuf {
+    virtual_path_relative(asset).map(|relative| virtual_asset_path(relative.as_str()))
+        .unwrap_or_else(|| virtual_asset_path(asset))
+}
+
+/// Matches a text against a glob pattern, where `*` matches any sequence of characters, even `/`, and `?` any character.
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match (pattern.first(), text.first()) {
+        (None, None) => true,
+        (Some(&'*'), _) => glob_match(&pattern[1..], text) || (!text.is_empty() && glob_match(pattern, &text[1..])),
+        (Some(&'?'), Some(_)) => glob_match(&pattern[1..], &text[1..]),
+        (Some(expected), Some(found)) if expected == found => glob_match(&pattern[1..], &text[1..]),
+        _ => false,
+    }
+}
+
+/// Collects the references to resources in the strings of a toml value, once each.
+fn collect_references(value: &Value, own_path: &str, references: &mut Vec<String>) {
+    match value {
+        &Value::String(ref string) => {
+            let is_reference = string.starts_with(VFS_SCHEME) || string.starts_with(GUID_SCHEME);
+            if is_reference && string != own_path && !references.contains(string) {
+                references.push(string.clone());
+            }
+        },
+        &Value::Array(ref values) => {
+            for value in values.iter() {
+                collect_references(value, own_path, references);
+            }
+        },
+        &Value::Table(ref table) => {
+            for value in table.values() {
+                collect_references(value, own_path, references);
+            }
+        },
+        _ => {},
+    }
+}
+
+/// Where a reference to a resource is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReferenceOrigin {
+    /// In the dependencies of the meta file of the asset.
+    Meta,
+    /// In the asset itself, like the resources of the game objects of a level.
+    Content,
+}
+
+/// Replaces the references to a resource quoted in a toml file, like `path = "res://gltf_resource/untitled.gltf"`, with another reference.
+/// The name of a sub-resource, after a `#`, is kept. The rest of the file is left as it is, with its comments and its layout.
+fn rewrite_references(content: &str, references: &[String], new_reference: &str) -> String {
+    let mut content = String::from(content);
+    for reference in references.iter() {
+        for quote in ['"', '\''].iter() {
+            content = content.replace(&format!("{}{}{}", quote, reference, quote), &format!("{}{}{}", quote, new_reference, quote))
+                .replace(&format!("{}{}#", quote, reference), &format!("{}{}#", quote, new_reference));
+        }
+    }
+    content
+}
+
+//The file holding the rewritten content of a file, until the asset is moved.
+fn rewrite_path(file: &Path) -> PathBuf {
+    let mut name = file.file_name().map(|name| name.to_os_string()).unwrap_or_default();
+    name.push(".rewrite");
+    file.with_file_name(name)
+}
+
+//Moves the files back, in the reverse order.
+fn undo_moves(moved: &[(PathBuf, PathBuf)]) {
+    for &(ref source, ref destination) in moved.iter().rev() {
+        if let Err(error) = fs::rename(destination, source) {
+            error!("Could not move the file {} back to {}: {}", destination.display(), source.display(), error);
+        }
+    }
+}
+
+//Removes the files holding the rewritten contents, by (rewritten content, rewritten file, original content).
+fn remove_rewrites(rewrites: &[(PathBuf, PathBuf, Vec<u8>)]) {
+    for &(ref rewrite, _, _) in rewrites.iter() {
+        let _ = fs::remove_file(rewrite);
+    }
+}
+
+/// A reference from an asset to a resource.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AssetReference {
+    /// The reference as it is written, a virtual path or a GUID reference.
+    pub reference: String,
+    pub origin: ReferenceOrigin,
+}
+
+/// An asset of the project, with what the database knows about it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetRecord {
+    /// The virtual path of the asset, like `res://tga_resource/Untitled.tga`.
+    pub path: PathBuf,
+    pub guid: Option<ResourceGuid>,
+    /// The type of the resource, `None` for the assets the engine does not load directly, like the levels.
+    pub resource_type: Option<ResourceType>,
+    pub tags: Vec<String>,
+    pub references: Vec<AssetReference>,
+}
+
+impl AssetRecord {
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|asset_tag| asset_tag == tag)
+    }
+}
+
+/// A reference to a resource which is not in the project.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DanglingReference {
+    /// The virtual path of the asset holding the reference.
+    pub referrer: PathBuf,
+    pub reference: AssetReference,
+}
+
+/// An asset whose GUID is already used by another asset. The references by GUID resolve to the other asset.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GuidCollision {
+    pub guid: ResourceGuid,
+    /// The virtual path of the asset whose GUID is already used.
+    pub path: PathBuf,
+    /// The virtual path of the asset the GUID resolves to.
+    pub other_path: PathBuf,
+}
+
+/// The changes made on disk by moving an asset, or which would be made.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetMove {
+    /// The virtual path of the asset before the move.
+    pub from: PathBuf,
+    /// The virtual path of the asset after the move.
+    pub to: PathBuf,
+    /// The files moved, the asset and its meta file, relative to the project directory.
+    pub moved_files: Vec<(PathBuf, PathBuf)>,
+    /// The files whose references to the asset are rewritten, relative to the project directory, at their paths after the move.
+    pub rewritten_files: Vec<PathBuf>,
+    /// The files read by the compiler of the asset through paths relative to it, like the buffers of a glTF file or the material
+    /// libraries of an OBJ file, relative to the project directory. They would not be found from the new directory of the asset,
+    /// the move is refused when there are some.
+    pub stranded_inputs: Vec<PathBuf>,
+    //The new content of the rewritten files.
+    contents: Vec<String>,
+}
+
+/// The index of the assets of a project directory : their paths, GUIDs, types, tags and references,
+/// read from their meta files and, for the toml files like the levels and the game objects, from their content.
+///
+/// The assets are identified by their virtual paths, the project directory being the root of the virtual filesystem.
+#[derive(Debug, Clone)]
+pub struct AssetDatabase {
+    root: PathBuf,
+    assets: BTreeMap<PathBuf, AssetRecord>,
+    guids: GuidDatabase,
+    collisions: Vec<GuidCollision>,
+}
+
+impl AssetDatabase {
+    /// Indexes the assets of a project directory, and of its subdirectories.
+    pub fn scan<P: AsRef<Path>>(project_root: P) -> ResourceManagerResult<Self> {
+        debug!("Indexing the assets of the project directory {}.", project_root.as_ref().display());
+        let mut database = AssetDatabase {
+            root: project_root.as_ref().to_path_buf(),
+            assets: BTreeMap::new(),
+            guids: GuidDatabase::new(),
+            collisions: Vec::new(),
+        };
+        for asset in collect_assets(project_root.as_ref())? {
+            let record = database.read_record(asset.as_path())?;
+            if let Some(guid) = record.guid {
+                match database.guids.path_of(guid).map(Path::to_path_buf) {
+                    Some(other_path) => {
+                        error!("The asset {} has the GUID {}, already used by the asset {}.", record.path.display(), guid, other_path.display());
+                        database.collisions.push(GuidCollision {
+                            guid,
+                            path: record.path.clone(),
+                            other_path,
+                        });
+                    },
+                    None => database.guids.insert(guid, record.path.clone())?,
+                }
+            }
+            database.assets.insert(record.path.clone(), record);
+        }
+        Ok(database)
+    }
+
+    fn read_record(&self, asset: &Path) -> ResourceManagerResult<AssetRecord> {
+        let source_path = self.root.join(asset);
+        let virtual_path = virtual_asset_path(asset);
+        let meta = ResourceMeta::read(source_path.as_path())
+            .map_err(|error| ResourceManagerError::ResourceError(format!("Could not read the meta file of {}: {}", source_path.display(), error)))?
+            .unwrap_or_else(ResourceMeta::new);
+
+        let mut references: Vec<AssetReference> = meta.dependencies.iter()
+            .map(|dependency| AssetReference {
+                reference: dependency.to_string_lossy().into_owned(),
+                origin: ReferenceOrigin::Meta,
+            })
+            .collect();
+        if asset.extension().map(|extension| extension == TOML_EXTENSION).unwrap_or(false) {
+            let mut content = String::new();
+            File::open(source_path.as_path())?.read_to_string(&mut content)?;
+            let value: Value = toml::from_str(content.as_str())
+                .map_err(|error| ResourceManagerError::TomlError(format!("Could not parse the toml file {}", source_path.display()), error))?;
+            let mut content_references = Vec::new();
+            collect_references(&value, &virtual_path.to_string_lossy(), &mut content_references);
+            references.extend(content_references.into_iter().map(|reference| AssetReference {
+                reference,
+                origin: ReferenceOrigin::Content,
+            }));
+        }
+
+        Ok(AssetRecord {
+            resource_type: ResourceType::from_path(asset),
+            path: virtual_path,
+            guid: meta.guid,
+            tags: meta.tags,
+            references,
+        })
+    }
+
+    /// The project directory.
+    pub fn root(&self) -> &Path {
+        self.root.as_path()
+    }
+
+    pub fn len(&self) -> usize {
+        self.assets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+
+    /// The assets, sorted by virtual path.
+    pub fn assets(&self) -> Values<PathBuf, AssetRecord> {
+        self.assets.values()
+    }
+
+    pub fn guid_database(&self) -> &GuidDatabase {
+        &self.guids
+    }
+
+    /// The assets whose GUID is already used by another asset, found by the scan.
+    pub fn guid_collisions(&self) -> &[GuidCollision] {
+        self.collisions.as_slice()
+    }
+
+    /// The asset referenced by a virtual path, or by a GUID reference. The name of a sub-resource, after a `#`, is ignored.
+    pub fn resolve(&self, reference: &str) -> Option<&AssetRecord> {
+        let reference = reference.split('#').next().unwrap_or(reference);
+        let path = match ResourceGuid::from_reference(reference) {
+            Some(guid) => self.guids.path_of(guid.ok()?)?.to_path_buf(),
+            None => PathBuf::from(format!("{}{}", VFS_SCHEME, virtual_path_relative(reference)?)),
+        };
+        self.assets.get(path.as_path())
+    }
+
+    pub fn by_type(&self, resource_type: ResourceType) -> Vec<&AssetRecord> {
+        self.assets.values().filter(|record| record.resource_type == Some(resource_type)).collect()
+    }
+
+    pub fn by_tag(&self, tag: &str) -> Vec<&AssetRecord> {
+        self.assets.values().filter(|record| record.has_tag(tag)).collect()
+    }
+
+    /// The assets whose name matches a glob pattern, like `*.tga`. A pattern with a `/` is matched against
+    /// the path of the assets relative to the project directory instead, like `toml_resource/level*.toml`.
+    pub fn by_name(&self, pattern: &str) -> Vec<&AssetRecord> {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let match_path = pattern.contains(&'/');
+        self.assets.values()
+            .filter(|record| {
+                let relative_path = virtual_path_relative(record.path.as_path()).unwrap_or_default();
+                let text = if match_path {
+                    relative_path.as_str()
+                } else {
+                    relative_path.rsplit('/').next().unwrap_or("")
+                };
+                glob_match(pattern.as_slice(), text.chars().collect::<Vec<char>>().as_slice())
+            })
+            .collect()
+    }
+
+    /// The assets referencing a resource, by path or by GUID.
+    pub fn referencing(&self, reference: &str) -> Vec<&AssetRecord> {
+        let target = self.resolve(reference).map(|record| record.path.as_path());
+        self.assets.values()
+            .filter(|record| record.references.iter().any(|asset_reference| match target {
+                Some(target) => self.resolve(asset_reference.reference.as_str()).map(|referenced| referenced.path.as_path() == target).unwrap_or(false),
+                None => asset_reference.reference == reference,
+            }))
+            .collect()
+    }
+
+    /// The references to resources which are not in the project.
+    pub fn dangling_references(&self) -> Vec<DanglingReference> {
+        let mut dangling = Vec::new();
+        for record in self.assets.values() {
+            for reference in record.references.iter() {
+                if self.resolve(reference.reference.as_str()).is_none() {
+                    dangling.push(DanglingReference {
+                        referrer: record.path.clone(),
+                        reference: reference.clone(),
+                    });
+                }
+            }
+        }
+        dangling
+    }
+
+    //The virtual path of an asset given by reference, or by path relative to the project directory.
+    fn record_path(&self, asset: &Path) -> ResourceManagerResult<PathBuf> {
+        self.resolve(&asset.to_string_lossy()).map(|record| record.path.clone())
+            .or_else(|| self.assets.get(virtual_asset_path(asset).as_path()).map(|record| record.path.clone()))
+            .ok_or_else(|| ResourceManagerError::ResourceError(format!("The asset {} is not in the asset database !", asset.display())))
+    }
+
+    //The path of an asset on disk, relative to the project directory.
+    fn relative_path(asset: &Path) -> PathBuf {
+        virtual_path_relative(asset).map(PathBuf::from).unwrap_or_else(|| asset.to_path_buf())
+    }
+
+    //The references to an asset, the ones by GUID being kept only if `with_guids` is true.
+    fn references_to(&self, path: &Path, with_guids: bool) -> Vec<DanglingReference> {
+        let mut references = Vec::new();
+        for record in self.assets.values() {
+            for reference in record.references.iter() {
+                let is_guid = reference.reference.starts_with(GUID_SCHEME);
+                let resolved = self.resolve(reference.reference.as_str()).map(|referenced| referenced.path.as_path() == path).unwrap_or(false);
+                if resolved && (with_guids || !is_guid) {
+                    references.push(DanglingReference {
+                        referrer: record.path.clone(),
+                        reference: reference.clone(),
+                    });
+                }
+            }
+        }
+        references
+    }
+
+    /// Removes an asset from the index, after it has been deleted, and returns the references it leaves dangling.
+    pub fn remove_asset<P: AsRef<Path>>(&mut self, path: P) -> ResourceManagerResult<Vec<DanglingReference>> {
+        debug!("Removing the asset {} from the asset database.", path.as_ref().display());
+        let path = self.record_path(path.as_ref())?;
+        let dangling = self.references_to(path.as_path(), true);
+        let owned_guid = self.assets.remove(path.as_path())
+            .and_then(|record| record.guid)
+            .filter(|&guid| self.guids.path_of(guid) == Some(path.as_path()));
+        if let Some(guid) = owned_guid {
+            self.guids.remove(guid);
+            //The first asset colliding with the removed one gets its GUID.
+            if let Some(index) = self.collisions.iter().position(|collision| collision.other_path == path) {
+                let owner = self.collisions.remove(index).path;
+                self.guids.insert(guid, owner.clone())?;
+                for collision in self.collisions.iter_mut().filter(|collision| collision.other_path == path) {
+                    collision.other_path = owner.clone();
+                }
+            }
+        }
+        self.collisions.retain(|collision| collision.path != path);
+        Ok(dangling)
+    }
+
+    /// Moves an asset in the index, after it has been moved with its meta file, and returns the references it leaves dangling :
+    /// the references by path to its old path. The references by GUID follow it.
+    pub fn move_asset<P, Q>(&mut self, from: P, to: Q) -> ResourceManagerResult<Vec<DanglingReference>> where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        debug!("Moving the asset {} to {} in the asset database.", from.as_ref().display(), to.as_ref().display());
+        let from = self.record_path(from.as_ref())?;
+        let to = target_path(to.as_ref());
+        if from == to {
+            return Ok(Vec::new());
+        }
+        if self.assets.contains_key(to.as_path()) {
+            return Err(ResourceManagerError::ResourceError(format!("The asset {} already exists in the asset database !", to.display())));
+        }
+
+        let dangling = self.references_to(from.as_path(), false);
+        let mut record = self.assets.remove(from.as_path()).expect("The asset has been resolved.");
+        if let Some(guid) = record.guid {
+            if self.guids.path_of(guid) == Some(from.as_path()) {
+                self.guids.set_path(guid, to.clone())?;
+            }
+        }
+        for collision in self.collisions.iter_mut() {
+            if collision.path == from {
+                collision.path = to.clone();
+            }
+            if collision.other_path == from {
+                collision.other_path = to.clone();
+            }
+        }
+        record.path = to.clone();
+        self.assets.insert(to, record);
+        Ok(dangling)
+    }
+
+    //The files read by the compiler of an asset through paths relative to it, relative to the project directory.
+    fn relative_inputs(&self, asset: &Path) -> ResourceManagerResult<Vec<PathBuf>> {
+        match AssetPipeline::new().compile_asset(self.root.as_path(), asset) {
+            Ok(Some((compiled_asset, _))) => Ok(compiled_asset.inputs),
+            Ok(None) => Ok(Vec::new()),
+            Err(error) => Err(ResourceManagerError::ResourceError(format!("Could not find the files read by the compiler of the asset {}: {}", asset.display(), error))),
+        }
+    }
+
+    /// Lists what moving an asset on disk would change, without changing anything : the asset and its meta file to move,
+    /// and the files referencing it by path to rewrite, the levels, the game objects and the meta files.
+    ///
+    /// The references by GUID follow the asset, the files holding only those are left as they are.
+    /// The files read by the compiler of the asset through relative paths are listed in `stranded_inputs` when it changes of directory.
+    pub fn plan_move<P, Q>(&self, from: P, to: Q) -> ResourceManagerResult<AssetMove> where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        debug!("Planning the move of the asset {} to {}.", from.as_ref().display(), to.as_ref().display());
+        let from = self.record_path(from.as_ref())?;
+        let to = target_path(to.as_ref());
+        let mut asset_move = AssetMove {
+            from: from.clone(),
+            to: to.clone(),
+            moved_files: Vec::new(),
+            rewritten_files: Vec::new(),
+            stranded_inputs: Vec::new(),
+            contents: Vec::new(),
+        };
+        if from == to {
+            return Ok(asset_move);
+        }
+        let from_relative = AssetDatabase::relative_path(from.as_path());
+        let to_relative = AssetDatabase::relative_path(to.as_path());
+        if self.assets.contains_key(to.as_path()) || self.root.join(to_relative.as_path()).exists() {
+            return Err(ResourceManagerError::ResourceError(format!("Could not move the asset {}, the asset {} already exists !", from.display(), to.display())));
+        }
+
+        asset_move.moved_files.push((from_relative.clone(), to_relative.clone()));
+        if self.root.join(meta_path(from_relative.as_path())).is_file() {
+            asset_move.moved_files.push((meta_path(from_relative.as_path()), meta_path(to_relative.as_path())));
+        }
+        //The paths of the inputs are relative to the directory of the asset, they do not follow it to another directory.
+        if from_relative.parent() != to_relative.parent() {
+            asset_move.stranded_inputs = self.relative_inputs(from_relative.as_path())?;
+        }
+
+        //The references to rewrite, by file holding them.
+        let mut rewrites: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+        for reference in self.references_to(from.as_path(), false) {
+            let referrer = AssetDatabase::relative_path(reference.referrer.as_path());
+            let file = match reference.reference.origin {
+                ReferenceOrigin::Meta => meta_path(referrer),
+                ReferenceOrigin::Content => referrer,
+            };
+            let written = String::from(reference.reference.reference.split('#').next().unwrap_or(""));
+            let references = rewrites.entry(file).or_insert_with(Vec::new);
+            if !references.contains(&written) {
+                references.push(written);
+            }
+        }
+        //A level or a game object references itself by path, like the id of a game object.
+        if from_relative.extension().map(|extension| extension == TOML_EXTENSION).unwrap_or(false) {
+            rewrites.entry(from_relative.clone()).or_insert_with(Vec::new).push(from.to_string_lossy().into_owned());
+        }
+
+        let new_reference = to.to_string_lossy().into_owned();
+        for (file, references) in rewrites {
+            let mut content = String::new();
+            File::open(self.root.join(file.as_path()))?.read_to_string(&mut content)?;
+            let rewritten = rewrite_references(content.as_str(), references.as_slice(), new_reference.as_str());
+            if rewritten != content {
+                let file = asset_move.moved_files.iter()
+                    .find(|&&(ref source, _)| *source == file)
+                    .map(|&(_, ref destination)| destination.clone())
+                    .unwrap_or(file);
+                asset_move.rewritten_files.push(file);
+                asset_move.contents.push(rewritten);
+            }
+        }
+        Ok(asset_move)
+    }
+
+    /// Moves an asset and its meta file on disk, rewrites the references to its path in the levels, the game objects
+    /// and the meta files of the project, and updates the index. Returns what has been changed.
+    ///
+    /// An asset whose compiler reads other files through relative paths, like a glTF file and its buffers, cannot change of directory.
+    /// On failure, the files already moved are moved back and the files already rewritten get their content back.
+    pub fn move_asset_files<P, Q>(&mut self, from: P, to: Q) -> ResourceManagerResult<AssetMove> where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        debug!("Moving the asset {} to {} in the project directory.", from.as_ref().display(), to.as_ref().display());
+        let asset_move = self.plan_move(from, to)?;
+        if !asset_move.stranded_inputs.is_empty() {
+            let inputs: Vec<String> = asset_move.stranded_inputs.iter().map(|input| input.display().to_string()).collect();
+            return Err(ResourceManagerError::ResourceError(format!("Could not move the asset {} to {}, the files {} read through paths relative to it would not be found !", asset_move.from.display(), asset_move.to.display(), inputs.join(", "))));
+        }
+
+        //The rewritten contents are written beside the files before anything is moved, with the original contents kept to roll back.
+        let mut rewrites = Vec::new();
+        for (file, content) in asset_move.rewritten_files.iter().zip(asset_move.contents.iter()) {
+            let current_file = asset_move.moved_files.iter()
+                .find(|&&(_, ref destination)| destination == file)
+                .map(|&(ref source, _)| self.root.join(source))
+                .unwrap_or_else(|| self.root.join(file));
+            let rewrite = rewrite_path(current_file.as_path());
+            match fs::read(current_file.as_path()).and_then(|original| fs::write(rewrite.as_path(), content.as_bytes()).map(|_| original)) {
+                Ok(original) => rewrites.push((rewrite, self.root.join(file), original)),
+                Err(error) => {
+                    let _ = fs::remove_file(rewrite.as_path());
+                    remove_rewrites(rewrites.as_slice());
+                    return Err(ResourceManagerError::IoError(format!("Could not rewrite the references of the file {}", file.display()), error));
+                },
+            }
+        }
+
+        let mut moved = Vec::new();
+        for &(ref source, ref destination) in asset_move.moved_files.iter() {
+            let source_file = self.root.join(source);
+            let destination_file = self.root.join(destination);
+            let result = match destination_file.parent() {
+                Some(parent) => fs::create_dir_all(parent),
+                None => Ok(()),
+            }.and_then(|_| fs::rename(source_file.as_path(), destination_file.as_path()));
+            if let Err(error) = result {
+                undo_moves(moved.as_slice());
+                remove_rewrites(rewrites.as_slice());
+                return Err(ResourceManagerError::IoError(format!("Could not move the file {}", source.display()), error));
+            }
+            moved.push((source_file, destination_file));
+        }
+
+        for (index, &(ref rewrite, ref file, _)) in rewrites.iter().enumerate() {
+            if let Err(error) = fs::rename(rewrite, file) {
+                for &(_, ref file, ref original) in rewrites[..index].iter() {
+                    if let Err(error) = fs::write(file, original) {
+                        error!("Could not restore the content of the file {}: {}", file.display(), error);
+                    }
+                }
+                remove_rewrites(&rewrites[index..]);
+                undo_moves(moved.as_slice());
+                return Err(ResourceManagerError::IoError(format!("Could not rewrite the references of the file {}", file.display()), error));
+            }
+        }
+
+        self.move_asset(asset_move.from.as_path(), asset_move.to.as_path())?;
+        for file in asset_move.rewritten_files.iter() {
+            let asset = if file.extension().map(|extension| extension == META_EXTENSION).unwrap_or(false) {
+                file.with_extension("")
+            } else {
+                file.clone()
+            };
+            let record = self.read_record(asset.as_path())?;
+            self.assets.insert(record.path.clone(), record);
+        }
+        Ok(asset_move)
+    }
+}
+
+#[cfg(test)]
+mod asset_database_test {
+    use super::*;
+    use std::fs;
+    use test_project::TestProject;
+
+    fn paths(records: Vec<&AssetRecord>) -> Vec<PathBuf> {
+        records.into_iter().map(|record| record.path.clone()).collect()
+    }
+
+    #[test]
+    fn asset_database_query_and_check_references() {
+        let project = TestProject::new("asset_database", &["tga_resource/Untitled.tga", "gltf_resource/untitled.gltf", "gltf_resource/untitled.gltf.meta", "gltf_resource/untitled.bin", "toml_resource/level2.toml", "toml_resource/level3.toml", "toml_resource/gameobject4.toml", "toml_resource/gameobject5.toml"]);
+        let project_root = project.root();
+        fs::write(project_root.join("tga_resource/Untitled.tga.meta"), "tags = [\"wall\"]\ndependencies = [\"res://tga_resource/missing.tga\"]\n").expect("Could not write the meta file.");
+
+        let mut database = AssetDatabase::scan(project_root).expect("Could not index the project.");
+        assert_eq!(database.len(), 7);
+        assert_eq!(paths(database.by_type(ResourceType::Tga)), vec![PathBuf::from("res://tga_resource/Untitled.tga")]);
+        assert_eq!(paths(database.by_tag("wall")), vec![PathBuf::from("res://tga_resource/Untitled.tga")]);
+        assert_eq!(database.by_name("*.toml").len(), 4);
+        assert_eq!(paths(database.by_name("toml_resource/level?.toml")), vec![PathBuf::from("res://toml_resource/level2.toml"), PathBuf::from("res://toml_resource/level3.toml")]);
+        assert!(database.resolve("guid://5b0c7e2a-9d41-4c8e-a3f6-2e9b71d04c55").is_some());
+
+        //The levels reference the mesh by path or by GUID, the game object by path.
+        let referrers = vec![PathBuf::from("res://toml_resource/gameobject4.toml"), PathBuf::from("res://toml_resource/level2.toml"), PathBuf::from("res://toml_resource/level3.toml")];
+        assert_eq!(paths(database.referencing("res://gltf_resource/untitled.gltf")), referrers);
+        assert_eq!(paths(database.referencing("guid://5b0c7e2a-9d41-4c8e-a3f6-2e9b71d04c55")), referrers);
+
+        let dangling = database.dangling_references();
+        assert_eq!(dangling.len(), 1);
+        assert_eq!(dangling[0].referrer, PathBuf::from("res://tga_resource/Untitled.tga"));
+        assert_eq!(dangling[0].reference.origin, ReferenceOrigin::Meta);
+
+        //Moving the mesh breaks the references by path only.
+        let broken = database.move_asset("res://gltf_resource/untitled.gltf", "res://meshes/cube.gltf").expect("Could not move the mesh.");
+        assert_eq!(broken.iter().map(|reference| reference.referrer.clone()).collect::<Vec<PathBuf>>(), vec![PathBuf::from("res://toml_resource/gameobject4.toml"), PathBuf::from("res://toml_resource/level2.toml")]);
+        assert_eq!(database.dangling_references().len(), 3);
+        assert_eq!(database.guid_database().path_of(ResourceGuid::parse("5b0c7e2a-9d41-4c8e-a3f6-2e9b71d04c55").unwrap()), Some(Path::new("res://meshes/cube.gltf")));
+
+        //Deleting a game object breaks the levels using it.
+        let broken = database.remove_asset("toml_resource/gameobject5.toml").expect("Could not remove the game object.");
+        assert_eq!(broken.len(), 2);
+        assert!(database.remove_asset("res://toml_resource/gameobject5.toml").is_err());
+        assert!(database.guid_collisions().is_empty());
+
+        //A copied asset keeps the GUID of its meta file, the collision is reported instead of failing the scan.
+        project.copy("gltf_resource/untitled.gltf", "gltf_resource/copy.gltf");
+        project.copy("gltf_resource/untitled.gltf.meta", "gltf_resource/copy.gltf.meta");
+        let mut database = AssetDatabase::scan(project_root).expect("Could not index the project.");
+        let guid = ResourceGuid::parse("5b0c7e2a-9d41-4c8e-a3f6-2e9b71d04c55").unwrap();
+        assert_eq!(database.guid_collisions(), &[GuidCollision {
+            guid,
+            path: PathBuf::from("res://gltf_resource/untitled.gltf"),
+            other_path: PathBuf::from("res://gltf_resource/copy.gltf"),
+        }]);
+        assert_eq!(database.resolve("guid://5b0c7e2a-9d41-4c8e-a3f6-2e9b71d04c55").map(|record| record.path.clone()), Some(PathBuf::from("res://gltf_resource/copy.gltf")));
+        database.remove_asset("res://gltf_resource/copy.gltf").expect("Could not remove the copy.");
+        assert!(database.guid_collisions().is_empty());
+        assert_eq!(database.guid_database().path_of(guid), Some(Path::new("res://gltf_resource/untitled.gltf")));
+    }
+
+    #[test]
+    fn asset_database_move_and_rewrite_references() {
+        let project = TestProject::new("asset_database_move", &["tga_resource/Untitled.tga", "gltf_resource/untitled.gltf", "gltf_resource/untitled.gltf.meta", "gltf_resource/untitled.bin", "toml_resource/level2.toml", "toml_resource/level3.toml", "toml_resource/gameobject4.toml", "toml_resource/gameobject5.toml"]);
+        let project_root = project.root();
+        fs::write(project_root.join("tga_resource/Untitled.tga.meta"), "# The texture of the mesh.\ndependencies = [\"res://gltf_resource/untitled.gltf#Mesh0\"]\n").expect("Could not write the meta file.");
+        let level2 = fs::read_to_string(project_root.join("toml_resource/level2.toml")).expect("Could not read the level.");
+
+        //The buffer of the glTF file is read relative to it, the mesh cannot leave its directory.
+        let mut database = AssetDatabase::scan(project_root).expect("Could not index the project.");
+        let planned = database.plan_move("res://gltf_resource/untitled.gltf", "res://meshes/cube.gltf").expect("Could not plan the move.");
+        assert_eq!(planned.stranded_inputs, vec![PathBuf::from("gltf_resource/untitled.bin")]);
+        assert!(database.move_asset_files("res://gltf_resource/untitled.gltf", "res://meshes/cube.gltf").is_err());
+        assert!(project_root.join("gltf_resource/untitled.gltf").is_file());
+        assert!(!project_root.join("meshes").exists());
+
+        //A dry run lists the files to change, the level referencing the mesh by GUID is left as it is.
+        let planned = database.plan_move("res://gltf_resource/untitled.gltf", "res://gltf_resource/cube.gltf").expect("Could not plan the move.");
+        assert_eq!(planned.moved_files, vec![
+            (PathBuf::from("gltf_resource/untitled.gltf"), PathBuf::from("gltf_resource/cube.gltf")),
+            (PathBuf::from("gltf_resource/untitled.gltf.meta"), PathBuf::from("gltf_resource/cube.gltf.meta")),
+        ]);
+        assert_eq!(planned.rewritten_files, vec![PathBuf::from("tga_resource/Untitled.tga.meta"), PathBuf::from("toml_resource/gameobject4.toml"), PathBuf::from("toml_resource/level2.toml")]);
+        assert!(planned.stranded_inputs.is_empty());
+        assert_eq!(fs::read_to_string(project_root.join("toml_resource/level2.toml")).expect("Could not read the level."), level2);
+        assert!(project_root.join("gltf_resource/untitled.gltf").is_file());
+
+        let moved = database.move_asset_files("res://gltf_resource/untitled.gltf", "res://gltf_resource/cube.gltf").expect("Could not move the mesh.");
+        assert_eq!(moved, planned);
+        assert!(project_root.join("gltf_resource/cube.gltf.meta").is_file());
+        assert!(!project_root.join("gltf_resource/untitled.gltf").exists());
+        assert_eq!(fs::read_to_string(project_root.join("toml_resource/level2.toml")).expect("Could not read the level."), level2.replace("res://gltf_resource/untitled.gltf", "res://gltf_resource/cube.gltf"));
+        assert!(fs::read_to_string(project_root.join("tga_resource/Untitled.tga.meta")).expect("Could not read the meta file.").contains("\"res://gltf_resource/cube.gltf#Mesh0\""));
+        assert!(database.dangling_references().is_empty());
+        assert_eq!(database.referencing("res://gltf_resource/cube.gltf").len(), 4);
+
+        //A game object is moved with its own id, the levels using it follow.
+        let moved = database.move_asset_files("toml_resource/gameobject5.toml", "toml_resource/objects/gameobject5.toml").expect("Could not move the game object.");
+        assert_eq!(moved.rewritten_files, vec![PathBuf::from("toml_resource/objects/gameobject5.toml"), PathBuf::from("toml_resource/level2.toml"), PathBuf::from("toml_resource/level3.toml")]);
+        assert!(fs::read_to_string(project_root.join("toml_resource/objects/gameobject5.toml")).expect("Could not read the game object.").starts_with("id = \"res://toml_resource/objects/gameobject5.toml\""));
+        assert!(database.dangling_references().is_empty());
+        assert!(database.plan_move("res://toml_resource/level2.toml", "toml_resource/level3.toml").is_err());
+
+        //A move which fails leaves the files as they were.
+        let level2 = fs::read_to_string(project_root.join("toml_resource/level2.toml")).expect("Could not read the level.");
+        let gameobject5 = fs::read_to_string(project_root.join("toml_resource/objects/gameobject5.toml")).expect("Could not read the game object.");
+        assert!(database.move_asset_files("res://toml_resource/objects/gameobject5.toml", "res://tga_resource/Untitled.tga/gameobject5.toml").is_err());
+        assert_eq!(fs::read_to_string(project_root.join("toml_resource/objects/gameobject5.toml")).expect("Could not read the game object."), gameobject5);
+        assert_eq!(fs::read_to_string(project_root.join("toml_resource/level2.toml")).expect("Could not read the level."), level2);
+        assert!(!project_root.join("toml_resource/level2.toml.rewrite").exists());
+        assert!(!project_root.join("toml_resource/objects/gameobject5.toml.rewrite").exists());
+        assert!(database.resolve("res://toml_resource/objects/gameobject5.toml").is_some());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,495 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::collections::HashSet;
+use pipeline::build_cache::{BuildCache, BuildCacheEntry, BUILD_CACHE_FILE, file_hash};
+use pipeline::compiler::{ResourceCompiler, CompileContext};
+use pipeline::texture_compiler::TextureCompiler;
+use pipeline::mesh_compiler::MeshCompiler;
+use pipeline::audio_compiler::AudioCompiler;
+use resources::resource_meta::{ResourceMeta, META_EXTENSION, meta_path};
+use resources::guid::{GuidDatabase, ResourceGuid};
+use resources::native_resource::NativeResource;
+use resources::vfs::{VFS_SCHEME, virtual_path_relative};
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+
+/// Lists the assets of a source directory and of its subdirectories, relative to it and sorted. Meta files are not assets.
+pub fn collect_assets<P: AsRef<Path>>(source_root: P) -> ResourceManagerResult<Vec<PathBuf>> {
+    debug!("Collecting the assets of the directory {}.", source_root.as_ref().display());
+    let mut assets = Vec::new();
+    collect_directory(source_root.as_ref(), Path::new(""), &mut assets)?;
+    assets.sort();
+    Ok(assets)
+}
+
+fn collect_directory(source_root: &Path, directory: &Path, assets: &mut Vec<PathBuf>) -> ResourceManagerResult<()> {
+    for entry in fs::read_dir(source_root.join(directory))? {
+        let entry = entry?;
+        let asset = directory.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            collect_directory(source_root, asset.as_path(), assets)?;
+        } else if asset.extension().map(|extension| extension != META_EXTENSION).unwrap_or(true) {
+            assets.push(asset);
+        }
+    }
+    Ok(())
+}
+
+fn write_output(path: &Path, data: &[u8]) -> ResourceManagerResult<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    File::create(path)?.write_all(data)?;
+    Ok(())
+}
+
+fn source_hash(path: &Path) -> ResourceManagerResult<u64> {
+    file_hash(path)?.ok_or_else(|| ResourceManagerError::ResourceError(format!("The source file {} does not exist !", path.display())))
+}
+
+/// The asset needed by a dependency, a virtual path or a GUID reference, relative to the source directory.
+///
+/// Returns `None` if the dependency is not a virtual path, or if its GUID is not in the database.
+pub fn dependency_asset(dependency: &Path, guids: &GuidDatabase) -> Option<PathBuf> {
+    let reference = dependency.to_string_lossy();
+    let virtual_path = match ResourceGuid::from_reference(&reference) {
+        Some(guid) => guids.path_of(guid.ok()?)?.to_path_buf(),
+        None => dependency.to_path_buf(),
+    };
+    virtual_path_relative(virtual_path).map(PathBuf::from)
+}
+
+/// An asset compiled by the pipeline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledAsset {
+    /// The path of the asset, relative to the source directory.
+    pub source: PathBuf,
+    /// The path of the compiled asset, relative to the output directory.
+    pub output: PathBuf,
+    pub compiler: String,
+    /// The other source files read to compile the asset, relative to the source directory.
+    pub inputs: Vec<PathBuf>,
+    /// The resources needed by the asset, found by its compiler or listed in its meta file.
+    pub dependencies: Vec<PathBuf>,
+}
+
+/// What happened to the assets of a source directory during a build.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BuildReport {
+    pub compiled: Vec<CompiledAsset>,
+    /// The assets without compiler, like the levels, copied as they are.
+    pub copied: Vec<PathBuf>,
+    /// The assets which did not change since the last build, like their dependencies.
+    pub skipped: Vec<PathBuf>,
+    /// The assets which could not be compiled, with the description of the error.
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl BuildReport {
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// The asset conditioning pipeline : compiles the source assets of a directory to engine-ready data, with their meta files.
+///
+/// An asset is compiled by the compiler handling its extension, to a native resource named after it, like `Untitled.tga.tex`.
+pub struct AssetPipeline {
+    compilers: Vec<Box<ResourceCompiler>>,
+}
+
+impl fmt::Debug for AssetPipeline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<&str> = self.compilers.iter().map(|compiler| compiler.name()).collect();
+        write!(f, "AssetPipeline {{ compilers: {:?} }}", names)
+    }
+}
+
+impl Default for AssetPipeline {
+    /// A pipeline with the texture, mesh and audio compilers.
+    fn default() -> Self {
+        let mut pipeline = AssetPipeline::without_compilers();
+        pipeline.register_compiler(TextureCompiler);
+        pipeline.register_compiler(MeshCompiler);
+        pipeline.register_compiler(AudioCompiler);
+        pipeline
+    }
+}
+
+impl AssetPipeline {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// A pipeline copying every asset as it is, until compilers are registered.
+    pub fn without_compilers() -> Self {
+        debug!("Creating an AssetPipeline without compilers.");
+        AssetPipeline {
+            compilers: Vec::new(),
+        }
+    }
+
+    /// Registers a compiler. It takes precedence over the compilers registered before for the same extensions.
+    pub fn register_compiler<C: ResourceCompiler + 'static>(&mut self, compiler: C) {
+        debug!("Registering the compiler {} in the AssetPipeline.", compiler.name());
+        self.compilers.push(Box::new(compiler));
+    }
+
+    /// The compiler of an asset, according to its extension.
+    pub fn compiler_for<P: AsRef<Path>>(&self, asset: P) -> Option<&ResourceCompiler> {
+        let extension = asset.as_ref().extension().and_then(|extension| extension.to_str())?;
+        self.compilers.iter().rev()
+            .find(|compiler| compiler.extensions().contains(&extension))
+            .map(|compiler| &**compiler)
+    }
+
+    /// The path of the output of an asset : the path of the asset followed by the extension of its compiler, or the path of the asset without compiler.
+    pub fn output_path<P: AsRef<Path>>(&self, asset: P) -> PathBuf {
+        match self.compiler_for(asset.as_ref()) {
+            Some(compiler) => {
+                let mut output = asset.as_ref().as_os_str().to_os_string();
+                output.push(".");
+                output.push(compiler.output_extension());
+                PathBuf::from(output)
+            },
+            None => asset.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Compiles an asset of the source directory, with its meta file, and returns its native resource.
+    ///
+    /// Returns `None` if the asset has no compiler.
+    pub fn compile_asset<S, A>(&self, source_root: S, asset: A) -> ResourceManagerResult<Option<(CompiledAsset, Vec<u8>)>> where
+        S: AsRef<Path>,
+        A: AsRef<Path>,
+    {
+        let compiler = match self.compiler_for(asset.as_ref()) {
+            Some(compiler) => compiler,
+            None => return Ok(None),
+        };
+        debug!("Compiling the asset {} with the {} compiler.", asset.as_ref().display(), compiler.name());
+        let source_path = source_root.as_ref().join(asset.as_ref());
+        let meta = ResourceMeta::read(source_path.as_path())?.unwrap_or_else(ResourceMeta::new);
+        let mut source = Vec::new();
+        File::open(source_path.as_path())?.read_to_end(&mut source)?;
+
+        let context = CompileContext::new(source_root.as_ref(), asset.as_ref(), &meta);
+        let compiled = compiler.compile(source.as_slice(), &context)?;
+        let mut dependencies = compiled.dependencies;
+        for dependency in meta.dependencies.iter() {
+            if !dependencies.contains(dependency) {
+                dependencies.push(dependency.clone());
+            }
+        }
+
+        //The output is a native resource, the loaders check that it is not stale.
+        let native_resource = NativeResource::with_dependencies(compiler.resource_type(), compiler.version(), dependencies.clone(), compiled.data);
+        let mut data = Vec::new();
+        native_resource.write_to(&mut data)?;
+
+        let compiled_asset = CompiledAsset {
+            source: asset.as_ref().to_path_buf(),
+            output: self.output_path(asset.as_ref()),
+            compiler: String::from(compiler.name()),
+            inputs: context.inputs(),
+            dependencies,
+        };
+        Ok(Some((compiled_asset, data)))
+    }
+
+    /// The resources needed by an asset of the source directory : the ones found by its compiler, and the ones listed in its meta file.
+    pub fn dependencies_of<S, A>(&self, source_root: S, asset: A) -> ResourceManagerResult<Vec<PathBuf>> where
+        S: AsRef<Path>,
+        A: AsRef<Path>,
+    {
+        match self.compile_asset(source_root.as_ref(), asset.as_ref())? {
+            Some((compiled_asset, _)) => Ok(compiled_asset.dependencies),
+            None => Ok(ResourceMeta::read(source_root.as_ref().join(asset.as_ref()))?.map(|meta| meta.dependencies).unwrap_or_default()),
+        }
+    }
+
+    /// Tells if the output of an asset, built as described by its cache entry, is up to date :
+    /// the asset, its meta file, the other files read by its compiler and the version of its compiler did not change.
+    fn is_up_to_date(&self, source_root: &Path, output_root: &Path, asset: &Path, entry: &BuildCacheEntry) -> ResourceManagerResult<bool> {
+        let compiler = self.compiler_for(asset).map(|compiler| (String::from(compiler.name()), compiler.version()));
+        if entry.compiler != compiler || entry.output != self.output_path(asset) || !output_root.join(entry.output.as_path()).is_file() {
+            return Ok(false);
+        }
+        let source_path = source_root.join(asset);
+        if file_hash(source_path.as_path())? != Some(entry.source_hash) || file_hash(meta_path(source_path.as_path()))? != entry.meta_hash {
+            return Ok(false);
+        }
+        for &(ref input, hash) in entry.inputs.iter() {
+            if file_hash(source_root.join(input))? != Some(hash) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn cache_entry(&self, source_root: &Path, asset: &Path, inputs: &[PathBuf], dependencies: Vec<PathBuf>) -> ResourceManagerResult<BuildCacheEntry> {
+        let source_path = source_root.join(asset);
+        let mut input_hashes = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            input_hashes.push((input.clone(), source_hash(source_root.join(input).as_path())?));
+        }
+        Ok(BuildCacheEntry {
+            source_hash: source_hash(source_path.as_path())?,
+            meta_hash: file_hash(meta_path(source_path.as_path()))?,
+            compiler: self.compiler_for(asset).map(|compiler| (String::from(compiler.name()), compiler.version())),
+            inputs: input_hashes,
+            dependencies,
+            output: self.output_path(asset),
+        })
+    }
+
+    /// Compiles the assets of the source directory in the output directory, and copies the assets without compiler.
+    ///
+    /// The build is incremental : the assets which did not change since the last build are skipped, according to the build cache of the output directory.
+    /// An asset which cannot be compiled is reported, and does not stop the build.
+    pub fn build<S, O>(&self, source_root: S, output_root: O) -> ResourceManagerResult<BuildReport> where
+        S: AsRef<Path>,
+        O: AsRef<Path>,
+    {
+        let cache_path = output_root.as_ref().join(BUILD_CACHE_FILE);
+        let mut cache = match BuildCache::load(cache_path.as_path()) {
+            Ok(cache) => cache,
+            Err(error) => {
+                warn!("The build cache {} could not be read, every asset will be built: {}", cache_path.display(), error);
+                BuildCache::new()
+            },
+        };
+        let report = self.build_with_cache(source_root.as_ref(), output_root.as_ref(), &mut cache)?;
+        cache.save(cache_path.as_path())?;
+        Ok(report)
+    }
+
+    /// Builds every asset of the source directory in the output directory, even the ones which did not change.
+    pub fn rebuild<S, O>(&self, source_root: S, output_root: O) -> ResourceManagerResult<BuildReport> where
+        S: AsRef<Path>,
+        O: AsRef<Path>,
+    {
+        let mut cache = BuildCache::new();
+        let report = self.build_with_cache(source_root.as_ref(), output_root.as_ref(), &mut cache)?;
+        cache.save(output_root.as_ref().join(BUILD_CACHE_FILE))?;
+        Ok(report)
+    }
+
+    /// Builds the assets of the source directory in the output directory, skipping the assets which are up to date in the build cache, and updates it.
+    ///
+    /// An asset is built again when it changed, or when one of its dependencies is built again. The outputs of the deleted assets are removed.
+    pub fn build_with_cache<S, O>(&self, source_root: S, output_root: O, cache: &mut BuildCache) -> ResourceManagerResult<BuildReport> where
+        S: AsRef<Path>,
+        O: AsRef<Path>,
+    {
+        debug!("Building the assets of {} in {}.", source_root.as_ref().display(), output_root.as_ref().display());
+        let assets = collect_assets(source_root.as_ref())?;
+        for (asset, entry) in cache.iter().filter(|&(asset, _)| !assets.contains(asset)) {
+            debug!("The asset {} has been deleted, removing its output.", asset.display());
+            let _ = fs::remove_file(output_root.as_ref().join(entry.output.as_path()));
+        }
+        cache.retain_assets(assets.as_slice());
+
+        let mut outdated = HashSet::new();
+        for asset in assets.iter() {
+            let up_to_date = match cache.get(asset) {
+                Some(entry) => self.is_up_to_date(source_root.as_ref(), output_root.as_ref(), asset.as_path(), entry)?,
+                None => false,
+            };
+            if !up_to_date {
+                outdated.insert(asset.clone());
+            }
+        }
+
+        //The assets whose meta file cannot be read, or whose GUID is already used, fail on their own.
+        let (guids, mut meta_problems) = GuidDatabase::scan_directory_by_asset(source_root.as_ref(), VFS_SCHEME)?;
+        for &(ref asset, _) in meta_problems.iter() {
+            outdated.insert(asset.clone());
+        }
+
+        //The dependents of an outdated asset are outdated, until no more asset is found.
+        loop {
+            let dependents: Vec<PathBuf> = assets.iter()
+                .filter(|asset| !outdated.contains(*asset))
+                .filter(|asset| cache.get(asset).map(|entry| entry.dependencies.iter().any(|dependency| {
+                    dependency_asset(dependency.as_path(), &guids).map(|dependency| outdated.contains(&dependency)).unwrap_or(false)
+                })).unwrap_or(false))
+                .cloned()
+                .collect();
+            if dependents.is_empty() {
+                break;
+            }
+            for dependent in dependents {
+                debug!("The asset {} is outdated because of one of its dependencies.", dependent.display());
+                outdated.insert(dependent);
+            }
+        }
+
+        let mut report = BuildReport::default();
+        for asset in assets {
+            if !outdated.contains(&asset) {
+                debug!("The asset {} is up to date.", asset.display());
+                report.skipped.push(asset);
+                continue;
+            }
+            let result = match meta_problems.iter().position(|&(ref problem_asset, _)| *problem_asset == asset) {
+                Some(index) => Err(meta_problems.remove(index).1),
+                None => self.compile_asset(source_root.as_ref(), asset.as_path()),
+            };
+            match result {
+                Ok(Some((compiled_asset, data))) => {
+                    write_output(output_root.as_ref().join(compiled_asset.output.as_path()).as_path(), data.as_slice())?;
+                    let entry = self.cache_entry(source_root.as_ref(), asset.as_path(), compiled_asset.inputs.as_slice(), compiled_asset.dependencies.clone())?;
+                    cache.insert(asset, entry);
+                    report.compiled.push(compiled_asset);
+                },
+                Ok(None) => {
+                    let output = output_root.as_ref().join(asset.as_path());
+                    if let Some(parent) = output.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::copy(source_root.as_ref().join(asset.as_path()), output)?;
+                    let dependencies = ResourceMeta::read(source_root.as_ref().join(asset.as_path()))?.map(|meta| meta.dependencies).unwrap_or_default();
+                    let entry = self.cache_entry(source_root.as_ref(), asset.as_path(), &[], dependencies)?;
+                    cache.insert(asset.clone(), entry);
+                    report.copied.push(asset);
+                },
+                Err(error) => {
+                    error!("The asset {} could not be compiled: {}", asset.display(), error);
+                    if let Some(entry) = cache.remove(asset.as_path()) {
+                        let _ = fs::remove_file(output_root.as_ref().join(entry.output.as_path()));
+                    }
+                    report.failed.push((asset, error.to_string()));
+                },
+            }
+        }
+
+        //The meta files left have no asset beside them.
+        for (asset, error) in meta_problems {
+            error!("The meta file of {} is invalid: {}", asset.display(), error);
+            report.failed.push((asset, error.to_string()));
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod asset_pipeline_test {
+    use super::*;
+    use resources::native_texture::{read_texture, NATIVE_TEXTURE_VERSION};
+    use resources::native_mesh::{read_mesh, NATIVE_MESH_VERSION};
+    use resources::native_audio::{read_audio, NATIVE_AUDIO_VERSION};
+    use resources::resources_registry::ResourceType;
+    use test_project::TestProject;
+
+    fn read_payload(path: PathBuf, resource_type: ResourceType, payload_version: u32) -> NativeResource {
+        let mut file = File::open(path.as_path()).expect("Could not open the native resource.");
+        NativeResource::read_expected(&mut file, resource_type, payload_version).expect("Could not read the native resource.")
+    }
+
+    #[test]
+    fn asset_pipeline_build_directory() {
+        let source = TestProject::new("asset_pipeline_source", &["tga_resource/Untitled.tga", "gltf_resource/untitled.gltf", "gltf_resource/untitled.bin", "ogg_resource/untitled.ogg", "obj_resource/untitled.obj", "obj_resource/untitled.mtl", "toml_resource/level2.toml"]);
+        let output = TestProject::empty("asset_pipeline_output");
+        let (source_root, output_root) = (source.root(), output.root());
+        fs::write(source_root.join("tga_resource/Untitled.tga.meta"), "[texture]\nsrgb = true\ngenerate_mips = true\n").expect("Could not write the meta file.");
+        fs::write(source_root.join("ogg_resource/broken.ogg"), "not an ogg file").expect("Could not write the broken asset.");
+
+        let pipeline = AssetPipeline::new();
+        assert_eq!(pipeline.output_path("tga_resource/Untitled.tga"), PathBuf::from("tga_resource/Untitled.tga.tex"));
+        let report = pipeline.build(source_root, output_root).expect("Could not build the assets.");
+        assert_eq!(report.compiled.iter().map(|asset| asset.source.clone()).collect::<Vec<PathBuf>>(), vec![
+            PathBuf::from("gltf_resource/untitled.gltf"),
+            PathBuf::from("obj_resource/untitled.obj"),
+            PathBuf::from("ogg_resource/untitled.ogg"),
+            PathBuf::from("tga_resource/Untitled.tga"),
+        ]);
+        assert!(report.copied.contains(&PathBuf::from("toml_resource/level2.toml")));
+        assert!(!report.copied.contains(&PathBuf::from("tga_resource/Untitled.tga.meta")));
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, PathBuf::from("ogg_resource/broken.ogg"));
+        assert!(!report.is_success());
+
+        //The runtime reads the outputs.
+        let texture = read_payload(output_root.join("tga_resource/Untitled.tga.tex"), ResourceType::Tga, NATIVE_TEXTURE_VERSION);
+        assert!(read_texture(&mut texture.payload.as_slice()).expect("Could not read the texture.").is_srgb());
+        let mesh = read_payload(output_root.join("gltf_resource/untitled.gltf.mesh"), ResourceType::Mesh, NATIVE_MESH_VERSION);
+        assert_eq!(read_mesh(&mut mesh.payload.as_slice()).expect("Could not read the mesh.").vertex_count(), 24);
+        let obj = read_payload(output_root.join("obj_resource/untitled.obj.mesh"), ResourceType::Mesh, NATIVE_MESH_VERSION);
+        assert!(obj.dependencies.contains(&PathBuf::from("res://tga_resource/Untitled.tga")));
+        let audio = read_payload(output_root.join("ogg_resource/untitled.ogg.audio"), ResourceType::Ogg, NATIVE_AUDIO_VERSION);
+        assert!(read_audio(&mut audio.payload.as_slice()).is_ok());
+        assert!(output_root.join("toml_resource/level2.toml").is_file());
+
+        let dependencies = pipeline.dependencies_of(source_root, "obj_resource/untitled.obj").expect("Could not find the dependencies of the obj file.");
+        assert!(dependencies.contains(&PathBuf::from("res://tga_resource/Untitled.tga")));
+        assert_eq!(dependency_asset(dependencies[0].as_path(), &GuidDatabase::new()), Some(PathBuf::from("tga_resource/Untitled.tga")));
+    }
+
+    #[test]
+    fn asset_pipeline_incremental_build() {
+        let source = TestProject::new("asset_pipeline_incremental_source", &["tga_resource/Untitled.tga", "gltf_resource/untitled.gltf", "gltf_resource/untitled.bin", "ogg_resource/untitled.ogg", "obj_resource/untitled.obj", "obj_resource/untitled.mtl"]);
+        let output = TestProject::empty("asset_pipeline_incremental_output");
+        let (source_root, output_root) = (source.root(), output.root());
+
+        let pipeline = AssetPipeline::new();
+        let report = pipeline.build(source_root, output_root).expect("Could not build the assets.");
+        assert_eq!(report.compiled.len(), 4);
+        assert!(report.skipped.is_empty());
+        assert!(output_root.join(BUILD_CACHE_FILE).is_file());
+
+        //Nothing changed.
+        let report = pipeline.build(source_root, output_root).expect("Could not build the assets.");
+        assert!(report.compiled.is_empty());
+        assert!(report.copied.is_empty());
+        assert_eq!(report.skipped.len(), 6);
+
+        //The texture changed, the obj file using it is built again.
+        fs::write(source_root.join("tga_resource/Untitled.tga.meta"), "[texture]\nsrgb = true\n").expect("Could not write the meta file.");
+        let report = pipeline.build(source_root, output_root).expect("Could not build the assets.");
+        assert_eq!(report.compiled.iter().map(|asset| asset.source.clone()).collect::<Vec<PathBuf>>(), vec![
+            PathBuf::from("obj_resource/untitled.obj"),
+            PathBuf::from("tga_resource/Untitled.tga"),
+        ]);
+
+        //A file read by the gltf compiler changed.
+        let mut buffer = fs::read(source_root.join("gltf_resource/untitled.bin")).expect("Could not read the buffer.");
+        buffer.push(0);
+        fs::write(source_root.join("gltf_resource/untitled.bin"), buffer).expect("Could not write the buffer.");
+        let report = pipeline.build(source_root, output_root).expect("Could not build the assets.");
+        assert_eq!(report.compiled.iter().map(|asset| asset.source.clone()).collect::<Vec<PathBuf>>(), vec![PathBuf::from("gltf_resource/untitled.gltf")]);
+        assert_eq!(report.copied, vec![PathBuf::from("gltf_resource/untitled.bin")]);
+
+        //A deleted asset loses its output.
+        fs::remove_file(source_root.join("ogg_resource/untitled.ogg")).expect("Could not remove the ogg file.");
+        let report = pipeline.build(source_root, output_root).expect("Could not build the assets.");
+        assert!(report.compiled.is_empty());
+        assert!(!output_root.join("ogg_resource/untitled.ogg.audio").exists());
+
+        //A broken meta file, or a GUID used twice, only fails its asset, and removes its output.
+        fs::write(source_root.join("tga_resource/Untitled.tga.meta"), "[texture\n").expect("Could not write the meta file.");
+        fs::write(source_root.join("gltf_resource/untitled.gltf.meta"), "guid = \"5b0c7e2a-9d41-4c8e-a3f6-2e9b71d04c55\"\n").expect("Could not write the meta file.");
+        fs::write(source_root.join("obj_resource/untitled.obj.meta"), "guid = \"5b0c7e2a-9d41-4c8e-a3f6-2e9b71d04c55\"\n").expect("Could not write the meta file.");
+        let report = pipeline.build(source_root, output_root).expect("Could not build the assets.");
+        let failed: Vec<PathBuf> = report.failed.iter().map(|&(ref asset, _)| asset.clone()).collect();
+        //The gltf file is scanned first and keeps the GUID, the obj file reusing it fails.
+        assert_eq!(failed, vec![PathBuf::from("obj_resource/untitled.obj"), PathBuf::from("tga_resource/Untitled.tga")]);
+        assert!(report.failed[0].1.contains("is already used by res://gltf_resource/untitled.gltf"));
+        assert!(output_root.join("gltf_resource/untitled.gltf.mesh").exists());
+        assert!(!output_root.join("obj_resource/untitled.obj.mesh").exists());
+        assert!(!output_root.join("tga_resource/Untitled.tga.tex").exists());
+        fs::write(source_root.join("tga_resource/Untitled.tga.meta"), "[texture]\nsrgb = true\n").expect("Could not write the meta file.");
+        fs::remove_file(source_root.join("obj_resource/untitled.obj.meta")).expect("Could not remove the meta file.");
+
+        let report = pipeline.rebuild(source_root, output_root).expect("Could not rebuild the assets.");
+        assert_eq!(report.compiled.len(), 3);
+    }
+}
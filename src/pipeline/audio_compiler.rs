@@ -0,0 +1,121 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//The format of the compiled audio clips is described in resources::native_audio.
+
+use std::io::Cursor;
+use lewton::inside_ogg::OggStreamReader;
+use pipeline::compiler::{ResourceCompiler, CompileContext, CompiledResource};
+use resources::native_audio::{write_audio, NativeAudio, NATIVE_AUDIO_VERSION};
+use resources::ogg_registry::{OggResource, last_granule_position, decode_to_pcm};
+use resources::resources_registry::ResourceType;
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+
+pub const AUDIO_OUTPUT_EXTENSION: &str = "audio";
+
+/// Validates ogg files by decoding them completely.
+///
+/// The clips having a target format in their meta files are converted to this format, with their vorbis comments and their resampled loop markers.
+/// The other ones are kept as they are.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AudioCompiler;
+
+impl ResourceCompiler for AudioCompiler {
+    fn name(&self) -> &str {
+        "audio"
+    }
+
+    fn version(&self) -> u32 {
+        NATIVE_AUDIO_VERSION
+    }
+
+    fn resource_type(&self) -> ResourceType {
+        ResourceType::Ogg
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ogg"]
+    }
+
+    fn output_extension(&self) -> &str {
+        AUDIO_OUTPUT_EXTENSION
+    }
+
+    fn compile(&self, source: &[u8], context: &CompileContext) -> ResourceManagerResult<CompiledResource> {
+        debug!("Compiling the audio clip {}.", context.asset().display());
+        let mut reader = Cursor::new(source);
+        let frames = last_granule_position(&mut reader)?;
+        let mut ogg_reader = OggStreamReader::new(reader)?;
+        let pcm = decode_to_pcm(&mut ogg_reader)?;
+        if pcm.samples().is_empty() {
+            return Err(ResourceManagerError::ResourceError(format!("The audio clip {} has no samples !", context.asset().display())));
+        }
+        let ogg = OggResource::new(ogg_reader, frames);
+
+        let audio = match context.meta().audio_target_format {
+            Some(format) => {
+                let clip = pcm.convert_to(format);
+                let loop_points = match ogg.metadata().loop_points() {
+                    Some(loop_points) => Some(loop_points.resampled(ogg.metadata().sample_rate(), clip.sample_rate()).ok_or_else(|| {
+                        ResourceManagerError::ResourceError(format!("The loop markers of the audio clip {} do not fit in 64 bits at {} Hz !", context.asset().display(), clip.sample_rate()))
+                    })?),
+                    None => None,
+                };
+                NativeAudio::Pcm {
+                    clip,
+                    loop_points,
+                    comments: ogg.metadata().comments().to_vec(),
+                }
+            },
+            None => NativeAudio::Vorbis {
+                format: pcm.format(),
+                frames,
+                stream: source.to_vec(),
+            },
+        };
+        let mut data = Vec::new();
+        write_audio(&mut data, &audio)?;
+        Ok(CompiledResource::new(data))
+    }
+}
+
+#[cfg(test)]
+mod audio_compiler_test {
+    use super::*;
+    use std::path::Path;
+    use resources::audio_conversion::AudioFormat;
+    use resources::native_audio::read_audio;
+    use resources::resource_meta::ResourceMeta;
+
+    #[test]
+    fn audio_compiler_compile_ogg() {
+        let source = ::std::fs::read("ogg_resource/untitled.ogg").expect("Could not read the ogg file.");
+        let mut meta = ResourceMeta::new();
+        let compiled = AudioCompiler.compile(source.as_slice(), &CompileContext::new(Path::new("."), Path::new("ogg_resource/untitled.ogg"), &meta)).expect("Could not compile the ogg file.");
+        match read_audio(&mut compiled.data.as_slice()).expect("Could not read the compiled clip.") {
+            NativeAudio::Vorbis { stream, frames, .. } => {
+                assert_eq!(stream, source);
+                assert!(frames.is_some());
+            },
+            NativeAudio::Pcm { .. } => panic!("The clip has been decoded without target format."),
+        }
+
+        meta.audio_target_format = Some(AudioFormat::new(22050, 1));
+        let compiled = AudioCompiler.compile(source.as_slice(), &CompileContext::new(Path::new("."), Path::new("ogg_resource/untitled.ogg"), &meta)).expect("Could not compile the ogg file.");
+        let audio = read_audio(&mut compiled.data.as_slice()).expect("Could not read the compiled clip.");
+        assert_eq!(audio.format(), AudioFormat::new(22050, 1));
+        assert!(audio.frames().unwrap_or(0) > 0);
+        //The converted clip keeps the vorbis comments of its source.
+        let source_comments = OggStreamReader::new(Cursor::new(source.as_slice())).expect("Could not read the ogg file.").comment_hdr.comment_list;
+        match audio {
+            NativeAudio::Pcm { comments, .. } => assert_eq!(comments, source_comments),
+            NativeAudio::Vorbis { .. } => panic!("The clip has not been decoded with a target format."),
+        }
+
+        assert!(AudioCompiler.compile(b"not an ogg file", &CompileContext::new(Path::new("."), Path::new("broken.ogg"), &meta)).is_err());
+    }
+}
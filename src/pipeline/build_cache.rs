@@ -0,0 +1,256 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*
+    The build cache : what every asset was built from in the last build, to skip the assets which did not change.
+
+    It is a toml file in the output directory, with one table per asset :
+
+    [assets."gltf_resource/untitled.gltf"]
+    source = "9f3c2a4b1e6d8a07"             content hash of the asset.
+    meta = "1b2c3d4e5f607182"               content hash of its meta file, absent without meta file.
+    compiler = "mesh"                       absent for an asset copied as it is.
+    compiler_version = 1
+    output = "gltf_resource/untitled.gltf.mesh"
+    dependencies = []                       the resources needed by the asset.
+
+    [assets."gltf_resource/untitled.gltf".inputs]
+    "gltf_resource/untitled.bin" = "0a1b2c3d4e5f6071"      content hash of the other source files read.
+*/
+
+use std::collections::HashMap;
+use std::collections::hash_map::Iter;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use toml::{self, Value};
+use toml::value::Table;
+use resources::binary::fnv1a_hash;
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+
+/// The name of the build cache, in the output directory.
+pub const BUILD_CACHE_FILE: &str = "build_cache.toml";
+
+/// The content hash of a file, `None` if it does not exist.
+pub fn file_hash<P: AsRef<Path>>(path: P) -> ResourceManagerResult<Option<u64>> {
+    let mut data = Vec::new();
+    match File::open(path.as_ref()) {
+        Ok(mut file) => {
+            file.read_to_end(&mut data)?;
+            Ok(Some(fnv1a_hash(data.as_slice())))
+        },
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(ResourceManagerError::IoError(format!("Could not hash the file {}", path.as_ref().display()), error)),
+    }
+}
+
+fn format_hash(hash: u64) -> Value {
+    Value::String(format!("{:016x}", hash))
+}
+
+fn parse_hash(value: &Value, asset: &str) -> ResourceManagerResult<u64> {
+    value.as_str()
+        .and_then(|hash| u64::from_str_radix(hash, 16).ok())
+        .ok_or_else(|| ResourceManagerError::ResourceError(format!("A hash of the asset {} in the build cache is not valid !", asset)))
+}
+
+/// What an asset was built from, and what it was built to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildCacheEntry {
+    pub source_hash: u64,
+    pub meta_hash: Option<u64>,
+    /// The name and the version of the compiler, `None` for an asset copied as it is.
+    pub compiler: Option<(String, u32)>,
+    /// The other source files read by the compiler, relative to the source directory, with their content hashes.
+    pub inputs: Vec<(PathBuf, u64)>,
+    pub dependencies: Vec<PathBuf>,
+    /// The path of the output, relative to the output directory.
+    pub output: PathBuf,
+}
+
+impl BuildCacheEntry {
+    fn to_table(&self) -> Table {
+        let mut table = Table::new();
+        table.insert(String::from("source"), format_hash(self.source_hash));
+        if let Some(meta_hash) = self.meta_hash {
+            table.insert(String::from("meta"), format_hash(meta_hash));
+        }
+        if let Some((ref name, version)) = self.compiler {
+            table.insert(String::from("compiler"), Value::String(name.clone()));
+            table.insert(String::from("compiler_version"), Value::Integer(i64::from(version)));
+        }
+        table.insert(String::from("output"), Value::String(self.output.to_string_lossy().into_owned()));
+        table.insert(String::from("dependencies"), Value::Array(self.dependencies.iter().map(|dependency| Value::String(dependency.to_string_lossy().into_owned())).collect()));
+        table.insert(String::from("inputs"), Value::Table(self.inputs.iter().map(|&(ref input, hash)| (input.to_string_lossy().into_owned(), format_hash(hash))).collect()));
+        table
+    }
+
+    fn from_table(table: &Table, asset: &str) -> ResourceManagerResult<Self> {
+        let invalid = || ResourceManagerError::ResourceError(format!("The asset {} of the build cache is not valid !", asset));
+        let source_hash = parse_hash(table.get("source").ok_or_else(invalid)?, asset)?;
+        let meta_hash = match table.get("meta") {
+            Some(meta) => Some(parse_hash(meta, asset)?),
+            None => None,
+        };
+        let compiler = match (table.get("compiler"), table.get("compiler_version")) {
+            (Some(&Value::String(ref name)), Some(&Value::Integer(version))) if version >= 0 && version <= i64::from(u32::max_value()) => Some((name.clone(), version as u32)),
+            (None, None) => None,
+            _ => return Err(invalid()),
+        };
+        let output = table.get("output").and_then(Value::as_str).map(PathBuf::from).ok_or_else(invalid)?;
+        let dependencies = match table.get("dependencies") {
+            Some(&Value::Array(ref dependencies)) => dependencies.iter().map(|dependency| dependency.as_str().map(PathBuf::from).ok_or_else(invalid)).collect::<ResourceManagerResult<Vec<PathBuf>>>()?,
+            None => Vec::new(),
+            Some(_) => return Err(invalid()),
+        };
+        let inputs = match table.get("inputs") {
+            Some(&Value::Table(ref inputs)) => inputs.iter().map(|(input, hash)| parse_hash(hash, asset).map(|hash| (PathBuf::from(input), hash))).collect::<ResourceManagerResult<Vec<(PathBuf, u64)>>>()?,
+            None => Vec::new(),
+            Some(_) => return Err(invalid()),
+        };
+
+        Ok(BuildCacheEntry {
+            source_hash,
+            meta_hash,
+            compiler,
+            inputs,
+            dependencies,
+            output,
+        })
+    }
+}
+
+/// The entries of the assets of the last build, by path relative to the source directory.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BuildCache {
+    entries: HashMap<PathBuf, BuildCacheEntry>,
+}
+
+impl BuildCache {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get<P: AsRef<Path>>(&self, asset: P) -> Option<&BuildCacheEntry> {
+        self.entries.get(asset.as_ref())
+    }
+
+    pub fn insert<P: Into<PathBuf>>(&mut self, asset: P, entry: BuildCacheEntry) -> Option<BuildCacheEntry> {
+        self.entries.insert(asset.into(), entry)
+    }
+
+    pub fn remove<P: AsRef<Path>>(&mut self, asset: P) -> Option<BuildCacheEntry> {
+        self.entries.remove(asset.as_ref())
+    }
+
+    /// Removes the entries of the assets which are not in the list, because they have been deleted.
+    pub fn retain_assets(&mut self, assets: &[PathBuf]) {
+        self.entries.retain(|asset, _| assets.contains(asset));
+    }
+
+    pub fn iter(&self) -> Iter<PathBuf, BuildCacheEntry> {
+        self.entries.iter()
+    }
+
+    pub fn clear(&mut self) {
+        debug!("Clearing the build cache.");
+        self.entries.clear();
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R) -> ResourceManagerResult<Self> {
+        debug!("Reading a build cache.");
+        let mut content = String::new();
+        reader.read_to_string(&mut content)?;
+        let value: Value = toml::from_str(content.as_str())?;
+        let mut cache = BuildCache::new();
+        match value.get("assets") {
+            Some(&Value::Table(ref assets)) => {
+                for (asset, entry) in assets.iter() {
+                    match entry {
+                        &Value::Table(ref entry) => {
+                            cache.insert(asset.as_str(), BuildCacheEntry::from_table(entry, asset.as_str())?);
+                        },
+                        _ => return Err(ResourceManagerError::ResourceError(format!("The asset {} of the build cache is not a toml table !", asset))),
+                    }
+                }
+            },
+            Some(_) => return Err(ResourceManagerError::ResourceError(String::from("The assets of the build cache are not a toml table !"))),
+            None => {},
+        }
+        Ok(cache)
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> ResourceManagerResult<()> {
+        debug!("Writing a build cache.");
+        let assets: Table = self.entries.iter().map(|(asset, entry)| (asset.to_string_lossy().into_owned(), Value::Table(entry.to_table()))).collect();
+        let mut cache = Table::new();
+        cache.insert(String::from("assets"), Value::Table(assets));
+        let content = toml::to_string(&Value::Table(cache)).map_err(|error| ResourceManagerError::ResourceError(format!("Could not write the build cache: {}", error)))?;
+        writer.write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    /// Reads the build cache at the given path, empty if there is none yet.
+    pub fn load<P: AsRef<Path>>(path: P) -> ResourceManagerResult<Self> {
+        debug!("Loading the build cache at path {}.", path.as_ref().display());
+        match File::open(path.as_ref()) {
+            Ok(mut file) => BuildCache::read_from(&mut file),
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(BuildCache::new()),
+            Err(error) => Err(ResourceManagerError::IoError(format!("Could not open the build cache {}", path.as_ref().display()), error)),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> ResourceManagerResult<()> {
+        debug!("Saving the build cache at path {}.", path.as_ref().display());
+        self.write_to(&mut File::create(path.as_ref())?)
+    }
+}
+
+#[cfg(test)]
+mod build_cache_test {
+    use super::*;
+
+    #[test]
+    fn build_cache_write_and_read() {
+        assert_eq!(fnv1a_hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert!(fnv1a_hash(b"a") != fnv1a_hash(b"b"));
+
+        let mut cache = BuildCache::new();
+        cache.insert("gltf_resource/untitled.gltf", BuildCacheEntry {
+            source_hash: fnv1a_hash(b"gltf"),
+            meta_hash: Some(u64::max_value()),
+            compiler: Some((String::from("mesh"), 3)),
+            inputs: vec![(PathBuf::from("gltf_resource/untitled.bin"), 0)],
+            dependencies: vec![PathBuf::from("res://tga_resource/Untitled.tga")],
+            output: PathBuf::from("gltf_resource/untitled.gltf.mesh"),
+        });
+        cache.insert("toml_resource/level2.toml", BuildCacheEntry {
+            source_hash: 42,
+            meta_hash: None,
+            compiler: None,
+            inputs: Vec::new(),
+            dependencies: Vec::new(),
+            output: PathBuf::from("toml_resource/level2.toml"),
+        });
+
+        let mut bytes = Vec::new();
+        cache.write_to(&mut bytes).expect("Could not write the build cache.");
+        assert_eq!(BuildCache::read_from(&mut bytes.as_slice()).expect("Could not read the build cache."), cache);
+
+        cache.retain_assets(&[PathBuf::from("toml_resource/level2.toml")]);
+        assert_eq!(cache.len(), 1);
+        assert!(BuildCache::load(::std::env::temp_dir().join("maskerad_missing_build_cache.toml")).expect("Could not load a missing build cache.").is_empty());
+    }
+}
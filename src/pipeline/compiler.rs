@@ -0,0 +1,126 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use resources::pak::normalize_pak_path;
+use resources::resource_meta::ResourceMeta;
+use resources::resources_registry::ResourceType;
+use resources::vfs::VFS_SCHEME;
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+
+/// The virtual path of an asset of the source directory, like `res://tga_resource/Untitled.tga`.
+pub fn virtual_asset_path<P: AsRef<Path>>(asset: P) -> PathBuf {
+    PathBuf::from(format!("{}{}", VFS_SCHEME, normalize_pak_path(asset)))
+}
+
+/// The output of a compiler.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CompiledResource {
+    /// The engine-ready data of the resource.
+    pub data: Vec<u8>,
+    /// The resources which must be loaded with this one, as virtual paths.
+    pub dependencies: Vec<PathBuf>,
+}
+
+impl CompiledResource {
+    pub fn new(data: Vec<u8>) -> Self {
+        CompiledResource {
+            data,
+            dependencies: Vec::new(),
+        }
+    }
+
+    pub fn with_dependencies(data: Vec<u8>, dependencies: Vec<PathBuf>) -> Self {
+        CompiledResource {
+            data,
+            dependencies,
+        }
+    }
+}
+
+/// What a compiler knows about the asset it compiles.
+#[derive(Debug)]
+pub struct CompileContext<'c> {
+    source_root: &'c Path,
+    asset: &'c Path,
+    meta: &'c ResourceMeta,
+    inputs: RefCell<Vec<PathBuf>>,
+}
+
+impl<'c> CompileContext<'c> {
+    /// The context of the asset at the path `asset`, relative to the source directory `source_root`.
+    pub fn new(source_root: &'c Path, asset: &'c Path, meta: &'c ResourceMeta) -> Self {
+        CompileContext {
+            source_root,
+            asset,
+            meta,
+            inputs: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// The path of the asset, relative to the source directory.
+    pub fn asset(&self) -> &Path {
+        self.asset
+    }
+
+    /// The path the runtime finds the compiled asset at.
+    pub fn virtual_path(&self) -> PathBuf {
+        virtual_asset_path(self.asset)
+    }
+
+    /// The metadata of the asset, empty if it has no meta file.
+    pub fn meta(&self) -> &ResourceMeta {
+        self.meta
+    }
+
+    /// Reads another source file needed by the asset, like the buffers of a glTF file, relative to the directory of the asset.
+    ///
+    /// The file is recorded as an input of the asset.
+    pub fn read_input<P: AsRef<Path>>(&self, path: P) -> ResourceManagerResult<Vec<u8>> {
+        let directory = self.asset.parent().map(Path::to_path_buf).unwrap_or_default();
+        let input = PathBuf::from(normalize_pak_path(directory.join(path.as_ref())));
+        debug!("Reading the input {} of the asset {}.", input.display(), self.asset.display());
+        let mut data = Vec::new();
+        File::open(self.source_root.join(input.as_path()))
+            .and_then(|mut file| file.read_to_end(&mut data))
+            .map_err(|error| ResourceManagerError::IoError(format!("Could not read the input {} of the asset {}", input.display(), self.asset.display()), error))?;
+        let mut inputs = self.inputs.borrow_mut();
+        if !inputs.contains(&input) {
+            inputs.push(input);
+        }
+        Ok(data)
+    }
+
+    /// The other source files read by the compiler, relative to the source directory.
+    pub fn inputs(&self) -> Vec<PathBuf> {
+        self.inputs.borrow().clone()
+    }
+}
+
+/// Turns a type of source asset into engine-ready data.
+pub trait ResourceCompiler {
+    /// The name of the compiler, for the logs and the build reports.
+    fn name(&self) -> &str;
+
+    /// The version of the compiler, to bump when its output changes. It is the payload version of its native resources.
+    fn version(&self) -> u32;
+
+    /// The type of the resource compiled, recorded in the header of its native resources.
+    fn resource_type(&self) -> ResourceType;
+
+    /// The extensions of the source assets handled by the compiler, without the dot.
+    fn extensions(&self) -> &[&str];
+
+    /// The extension appended to the path of the asset to name its output, without the dot.
+    fn output_extension(&self) -> &str;
+
+    /// Compiles the data of a source asset.
+    fn compile(&self, source: &[u8], context: &CompileContext) -> ResourceManagerResult<CompiledResource>;
+}
@@ -0,0 +1,127 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//The format of the compiled meshes is described in resources::native_mesh.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use gltf::Gltf;
+use pipeline::compiler::{ResourceCompiler, CompileContext, CompiledResource, virtual_asset_path};
+use resources::native_mesh::{write_mesh, NATIVE_MESH_VERSION};
+use resources::obj_loader::{parse_obj, parse_mtl, material_path};
+use resources::gltf_loader::gltf_mesh;
+use resources::mesh_registry::MeshResource;
+use resources::resources_registry::ResourceType;
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+
+pub const MESH_OUTPUT_EXTENSION: &str = "mesh";
+
+//Reads an OBJ file and resolves its materials. The textures of the materials are dependencies of the mesh.
+fn read_obj(source: &[u8], context: &CompileContext) -> ResourceManagerResult<(MeshResource, Vec<PathBuf>)> {
+    let mut obj_data = parse_obj(source)?;
+    let directory = context.asset().parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut material_paths = HashMap::new();
+    let mut dependencies = Vec::new();
+    for library in obj_data.material_libraries.iter() {
+        let library_data = context.read_input(library)?;
+        let library_path = directory.join(library);
+        let library_directory = library_path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let library_virtual_path = virtual_asset_path(library_path.as_path());
+        for material in parse_mtl(library_data.as_slice(), library_directory)? {
+            for texture in material.textures() {
+                let texture = virtual_asset_path(texture);
+                if !dependencies.contains(&texture) {
+                    dependencies.push(texture);
+                }
+            }
+            material_paths.insert(material.name.clone(), material_path(library_virtual_path.as_path(), material.name.as_str()));
+        }
+        dependencies.push(library_virtual_path);
+    }
+    obj_data.resolve_materials(&material_paths);
+    Ok((obj_data.mesh, dependencies))
+}
+
+/// Compiles glTF and OBJ files to meshes, processed with the mesh flags of their meta files.
+///
+/// The meshes of a glTF file are merged, without their node transforms and their materials.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MeshCompiler;
+
+impl ResourceCompiler for MeshCompiler {
+    fn name(&self) -> &str {
+        "mesh"
+    }
+
+    fn version(&self) -> u32 {
+        NATIVE_MESH_VERSION
+    }
+
+    fn resource_type(&self) -> ResourceType {
+        ResourceType::Mesh
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["gltf", "obj"]
+    }
+
+    fn output_extension(&self) -> &str {
+        MESH_OUTPUT_EXTENSION
+    }
+
+    fn compile(&self, source: &[u8], context: &CompileContext) -> ResourceManagerResult<CompiledResource> {
+        debug!("Compiling the mesh {}.", context.asset().display());
+        let (mut mesh, dependencies) = match context.asset().extension().and_then(|extension| extension.to_str()) {
+            Some("gltf") => {
+                let gltf = Gltf::from_reader(source)?.validate_completely()?;
+                (gltf_mesh(&gltf, context.asset(), |uri| context.read_input(uri))?, Vec::new())
+            },
+            Some("obj") => read_obj(source, context)?,
+            _ => return Err(ResourceManagerError::ResourceError(format!("The mesh {} is neither a glTF file nor an OBJ file !", context.asset().display()))),
+        };
+        context.meta().mesh_processing.unwrap_or_default().apply(&mut mesh);
+
+        let mut data = Vec::new();
+        write_mesh(&mut data, &mesh)?;
+        Ok(CompiledResource::with_dependencies(data, dependencies))
+    }
+}
+
+#[cfg(test)]
+mod mesh_compiler_test {
+    use super::*;
+    use resources::native_mesh::read_mesh;
+    use resources::resource_meta::ResourceMeta;
+    use resources::mesh_processing::MeshProcessing;
+
+    #[test]
+    fn mesh_compiler_compile_gltf_and_obj() {
+        let mut meta = ResourceMeta::new();
+        meta.mesh_processing = Some(MeshProcessing { compute_bounds: true, ..MeshProcessing::none() });
+
+        //The buffer of the glTF file is an input of the mesh.
+        let gltf_source = ::std::fs::read("gltf_resource/untitled.gltf").expect("Could not read the gltf file.");
+        let context = CompileContext::new(Path::new("."), Path::new("gltf_resource/untitled.gltf"), &meta);
+        let compiled = MeshCompiler.compile(gltf_source.as_slice(), &context).expect("Could not compile the gltf mesh.");
+        assert_eq!(context.inputs(), vec![PathBuf::from("gltf_resource/untitled.bin")]);
+        let mesh = read_mesh(&mut compiled.data.as_slice()).expect("Could not read the compiled mesh.");
+        assert_eq!(mesh.vertex_count(), 24);
+        assert_eq!(mesh.triangle_count(), 12);
+        assert!(mesh.has_normals());
+        assert!(mesh.bounds.is_some());
+
+        //The material library of the OBJ file is an input, its textures are dependencies.
+        let obj_source = ::std::fs::read("obj_resource/untitled.obj").expect("Could not read the obj file.");
+        let context = CompileContext::new(Path::new("."), Path::new("obj_resource/untitled.obj"), &meta);
+        let compiled = MeshCompiler.compile(obj_source.as_slice(), &context).expect("Could not compile the obj mesh.");
+        assert_eq!(context.inputs(), vec![PathBuf::from("obj_resource/untitled.mtl")]);
+        assert_eq!(compiled.dependencies, vec![PathBuf::from("res://tga_resource/Untitled.tga"), PathBuf::from("res://obj_resource/untitled.mtl")]);
+        let mesh = read_mesh(&mut compiled.data.as_slice()).expect("Could not read the compiled mesh.");
+        assert_eq!(mesh.triangle_count(), 2);
+        assert_eq!(mesh.primitives[0].material, Some(material_path("res://obj_resource/untitled.mtl", "Material")));
+    }
+}
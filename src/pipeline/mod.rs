@@ -0,0 +1,14 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+pub mod compiler;
+pub mod build_cache;
+pub mod texture_compiler;
+pub mod mesh_compiler;
+pub mod audio_compiler;
+pub mod asset_pipeline;
+pub mod asset_database;
\ No newline at end of file
@@ -0,0 +1,84 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//The format of the compiled textures is described in resources::native_texture.
+
+use std::io::Cursor;
+use imagefmt::{tga, ColFmt};
+use pipeline::compiler::{ResourceCompiler, CompileContext, CompiledResource};
+use resources::native_texture::{write_texture, NATIVE_TEXTURE_VERSION};
+use resources::texture_processing::generate_mips;
+use resources::resources_registry::ResourceType;
+use resources::resource_manager_errors::ResourceManagerResult;
+
+pub const TEXTURE_OUTPUT_EXTENSION: &str = "tex";
+
+/// Compiles tga images to RGBA8 textures, with the import settings of their meta files.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TextureCompiler;
+
+impl ResourceCompiler for TextureCompiler {
+    fn name(&self) -> &str {
+        "texture"
+    }
+
+    fn version(&self) -> u32 {
+        NATIVE_TEXTURE_VERSION
+    }
+
+    fn resource_type(&self) -> ResourceType {
+        ResourceType::Tga
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tga"]
+    }
+
+    fn output_extension(&self) -> &str {
+        TEXTURE_OUTPUT_EXTENSION
+    }
+
+    fn compile(&self, source: &[u8], context: &CompileContext) -> ResourceManagerResult<CompiledResource> {
+        debug!("Compiling the texture {}.", context.asset().display());
+        let image = tga::read(&mut Cursor::new(source), ColFmt::RGBA)?;
+        let settings = context.meta().texture.unwrap_or_default();
+        let mips = if settings.generate_mips { generate_mips(&image) } else { Vec::new() };
+
+        let mut data = Vec::new();
+        write_texture(&mut data, &image, settings.srgb, mips.as_slice())?;
+        Ok(CompiledResource::new(data))
+    }
+}
+
+#[cfg(test)]
+mod texture_compiler_test {
+    use super::*;
+    use imagefmt::Image;
+    use resources::native_texture::read_texture;
+    use std::path::Path;
+    use resources::resource_meta::ResourceMeta;
+    use resources::texture_processing::TextureImportSettings;
+
+    #[test]
+    fn texture_compiler_compile_tga() {
+        let source = ::std::fs::read("tga_resource/Untitled.tga").expect("Could not read the tga file.");
+        let mut meta = ResourceMeta::new();
+        meta.texture = Some(TextureImportSettings { srgb: true, generate_mips: true });
+        let context = CompileContext::new(Path::new("."), Path::new("tga_resource/Untitled.tga"), &meta);
+
+        let compiled = TextureCompiler.compile(source.as_slice(), &context).expect("Could not compile the texture.");
+        assert!(compiled.dependencies.is_empty());
+        let texture = read_texture(&mut compiled.data.as_slice()).expect("Could not read the compiled texture.");
+        let image: &Image<u8> = texture.as_ref();
+        assert_eq!((image.w, image.h), (640, 400));
+        assert_eq!(image.buf.len(), 640 * 400 * 4);
+        assert!(texture.is_srgb());
+        assert_eq!(texture.mip_levels().len(), 9);
+        assert_eq!((texture.mip_levels()[8].w, texture.mip_levels()[8].h), (1, 1));
+        assert!(read_texture(&mut &compiled.data[..20]).is_err());
+    }
+}
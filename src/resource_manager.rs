@@ -9,21 +9,48 @@ use std::path::{PathBuf, Path};
 use gltf::Gltf;
 use lewton::inside_ogg::OggStreamReader;
 use imagefmt::tga;
-use imagefmt::ColFmt;
+use imagefmt::{ColFmt, Image};
 use maskerad_data_parser::level_description::LevelDescription;
-use resources::resources_registry::ResourceRegistry;
+use resources::resources_registry::{ResourceRegistry, ResourceType};
 //use resources::refcount_registry::RefCountRegistry;
-use resources::ogg_registry::OggResource;
+use resources::ogg_registry::{OggResource, last_granule_position, decode_to_pcm};
+use resources::audio_conversion::AudioFormat;
 use resources::gltf_registry::GltfResource;
-use resources::tga_registry::TgaResource;
+use resources::tga_registry::{TgaResource, HeapTgaResource};
+use resources::heap_region::{HeapRegion, HeapHandle};
+use resources::lru_cache::{LruCache, CacheWatermarks};
+use resources::pak::PakArchive;
+use resources::native_resource::NativeResource;
+use resources::mapped_pak::MappedPakArchive;
+use resources::vfs::{VirtualFilesystem, is_virtual_path};
+use resources::hot_reload::{HotReloadWatcher, ReloadEvent};
+use resources::guid::{GuidDatabase, ResourceGuid};
+use resources::resource_meta::{ResourceMeta, meta_path};
+use resources::texture_processing::TextureImportSettings;
+use resources::resource_events::{ResourceEventQueue, ResourceEvent, ResourceEventKind, ResourceEventFilter, SubscriptionId};
+use resources::resource_reader::{ResourceReader, ResourceRead};
+use resources::obj_loader::{ObjData, parse_obj, parse_mtl, material_path};
+use resources::gltf_loader::gltf_mesh;
+use resources::mesh_processing::MeshProcessing;
+use resources::mesh_simplification::{MeshLodSettings, LodLevel, generate_lods, lod_path};
+use resources::mesh_registry::{MeshResource, MeshLod};
+use resources::memory_budget::{MemoryBudget, MemorySize, ResourceCategory, ResourceScope};
+use resources::memory_report::{MemoryReport, StackAllocatorStats, HeapRegionStats, ResourceMemoryUsage};
+use resources::scratch::{ScratchContext, ScratchBuffer};
+use resources::resource_pool::{ResourcePool, PoolHandle, DEFAULT_POOL_CAPACITY};
+use resources::material_registry::MaterialInstanceResource;
+use resources::ogg_registry::SoundCueResource;
 
 use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+use resources::native_texture::{read_texture, NATIVE_TEXTURE_VERSION};
+use resources::native_mesh::{read_mesh, NATIVE_MESH_VERSION};
+use resources::native_audio::{read_audio, NativeAudio, NATIVE_AUDIO_VERSION};
 use maskerad_memory_allocators::StackAllocator;
 use maskerad_filesystem::filesystem::Filesystem;
 
-use std::cell::{RefCell, Ref};
-use std::io::BufReader;
-use std::fs::File;
+use std::cell::{Cell, RefCell, Ref, RefMut};
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
 //TODO: We must get rid of the filesystem dependency injection, and rework the shit out of this goddamn resource manager.
 
 pub struct ResourceManager<'a> {
@@ -32,39 +59,981 @@ pub struct ResourceManager<'a> {
     double_ended_allocator: (StackAllocator, StackAllocator),
     global_resource_registry: RefCell<ResourceRegistry<'a>>,
     level_resource_registry: RefCell<ResourceRegistry<'a>>,
-    marker_global_resource: usize,
-    marker_global_resource_copy: usize,
+    marker_global_resource: Cell<usize>,
+    marker_global_resource_copy: Cell<usize>,
+    audio_target_format: Option<AudioFormat>,
+    audio_target_format_overrides: HashMap<PathBuf, Option<AudioFormat>>,
+    mesh_processing: MeshProcessing,
+    mesh_processing_overrides: HashMap<PathBuf, MeshProcessing>,
+    mesh_lod_settings: MeshLodSettings,
+    mesh_lod_settings_overrides: HashMap<PathBuf, MeshLodSettings>,
+    memory_budget: RefCell<MemoryBudget>,
+    scratch_open: Cell<bool>,
+    allocator_stats: RefCell<(StackAllocatorStats, StackAllocatorStats)>,
+    material_instances: RefCell<ResourcePool<MaterialInstanceResource>>,
+    sound_cues: RefCell<ResourcePool<SoundCueResource>>,
+    heap_region: RefCell<HeapRegion>,
+    heap_tga_resources: RefCell<HashMap<PathBuf, HeapTgaResource>>,
+    cache_watermarks: Option<CacheWatermarks>,
+    resource_cache: RefCell<LruCache>,
+    mounted_paks: Vec<PakArchive>,
+    mapped_paks: Vec<&'a MappedPakArchive>,
+    vfs: VirtualFilesystem,
+    hot_reload: bool,
+    hot_reload_watcher: RefCell<HotReloadWatcher>,
+    events: RefCell<ResourceEventQueue>,
+    guid_database: GuidDatabase,
+    metas: RefCell<HashMap<PathBuf, ResourceMeta>>,
 }
 
 impl<'a> ResourceManager<'a> {
     pub fn with_capacity(capacity: usize, capacity_copy: usize) -> Self {
+        ResourceManager::with_pool_capacity(capacity, capacity_copy, DEFAULT_POOL_CAPACITY)
+    }
+
+    /// Creates a ResourceManager whose pools of small resources hold `pool_capacity` resources each.
+    pub fn with_pool_capacity(capacity: usize, capacity_copy: usize, pool_capacity: usize) -> Self {
         ResourceManager {
             double_ended_allocator: (StackAllocator::with_capacity(capacity/2, capacity_copy/2), StackAllocator::with_capacity(capacity/2, capacity_copy/2)),
             global_resource_registry: RefCell::new(ResourceRegistry::new()),
             level_resource_registry: RefCell::new(ResourceRegistry::new()),
-            marker_global_resource: 0,
-            marker_global_resource_copy: 0,
+            marker_global_resource: Cell::new(0),
+            marker_global_resource_copy: Cell::new(0),
+            audio_target_format: None,
+            audio_target_format_overrides: HashMap::new(),
+            mesh_processing: MeshProcessing::none(),
+            mesh_processing_overrides: HashMap::new(),
+            mesh_lod_settings: MeshLodSettings::default(),
+            mesh_lod_settings_overrides: HashMap::new(),
+            memory_budget: RefCell::new(MemoryBudget::new()),
+            scratch_open: Cell::new(false),
+            allocator_stats: RefCell::new((
+                StackAllocatorStats { capacity: capacity/2, capacity_copy: capacity_copy/2, ..Default::default() },
+                StackAllocatorStats { capacity: capacity/2, capacity_copy: capacity_copy/2, ..Default::default() },
+            )),
+            material_instances: RefCell::new(ResourcePool::with_capacity(pool_capacity)),
+            sound_cues: RefCell::new(ResourcePool::with_capacity(pool_capacity)),
+            heap_region: RefCell::new(HeapRegion::with_capacity(0)),
+            heap_tga_resources: RefCell::new(HashMap::new()),
+            cache_watermarks: None,
+            resource_cache: RefCell::new(LruCache::new()),
+            mounted_paks: Vec::new(),
+            mapped_paks: Vec::new(),
+            vfs: VirtualFilesystem::new(),
+            hot_reload: false,
+            hot_reload_watcher: RefCell::new(HotReloadWatcher::new()),
+            events: RefCell::new(ResourceEventQueue::new()),
+            guid_database: GuidDatabase::new(),
+            metas: RefCell::new(HashMap::new()),
         }
     }
 
     pub fn set_marker_global_resources(&mut self, marker: usize) {
         debug!("Setting the marker to the end of the global resources.");
-        self.marker_global_resource = marker;
+        self.marker_global_resource.set(marker);
     }
 
     pub fn marker_global_resources(&self) -> usize {
         debug!("Getting the marker to the end of the global resources.");
-        self.marker_global_resource
+        self.marker_global_resource.get()
     }
 
     pub fn set_marker_global_resources_copy(&mut self, marker: usize) {
         debug!("Setting the marker to the end of the global resources (copy).");
-        self.marker_global_resource_copy = marker;
+        self.marker_global_resource_copy.set(marker);
     }
 
     pub fn marker_global_resources_copy(&self) -> usize {
         debug!("Getting the marker to the end of the global resources (copy).");
-        self.marker_global_resource_copy
+        self.marker_global_resource_copy.get()
+    }
+
+    /// Sets the format every decoded audio clip is converted to at load time.
+    ///
+    /// With `None`, audio resources are kept as ogg streams and are not decoded.
+    pub fn set_audio_target_format(&mut self, format: Option<AudioFormat>) {
+        debug!("Setting the default target format of audio resources.");
+        self.audio_target_format = format;
+    }
+
+    pub fn audio_target_format(&self) -> Option<AudioFormat> {
+        debug!("Getting the default target format of audio resources.");
+        self.audio_target_format
+    }
+
+    /// Overrides the default target format for the audio resource at the given path.
+    ///
+    /// `None` keeps this resource as an ogg stream, whatever the default target format is.
+    pub fn set_audio_target_format_of<P: Into<PathBuf>>(&mut self, path: P, format: Option<AudioFormat>) {
+        debug!("Overriding the target format of an audio resource.");
+        self.audio_target_format_overrides.insert(path.into(), format);
+    }
+
+    pub fn remove_audio_target_format_of<P: AsRef<Path>>(&mut self, path: P) {
+        debug!("Removing the target format override of the audio resource at path {}.", path.as_ref().display());
+        self.audio_target_format_overrides.remove(path.as_ref());
+    }
+
+    /// The target format of an audio resource : its override, or else the format of its meta file, or else the default one.
+    pub fn audio_target_format_of<P: AsRef<Path>>(&self, path: P) -> Option<AudioFormat> {
+        debug!("Getting the target format of the audio resource at path {}.", path.as_ref().display());
+        if let Some(format) = self.audio_target_format_overrides.get(path.as_ref()) {
+            return *format;
+        }
+        match self.metas.borrow().get(path.as_ref()).and_then(|meta| meta.audio_target_format) {
+            Some(format) => Some(format),
+            None => self.audio_target_format,
+        }
+    }
+
+    /// Sets the post-processing steps applied to every mesh at load time.
+    pub fn set_mesh_processing(&mut self, mesh_processing: MeshProcessing) {
+        debug!("Setting the default post-processing steps of mesh resources.");
+        self.mesh_processing = mesh_processing;
+    }
+
+    pub fn mesh_processing(&self) -> MeshProcessing {
+        debug!("Getting the default post-processing steps of mesh resources.");
+        self.mesh_processing
+    }
+
+    /// Overrides the default post-processing steps for the mesh resource at the given path.
+    pub fn set_mesh_processing_of<P: Into<PathBuf>>(&mut self, path: P, mesh_processing: MeshProcessing) {
+        debug!("Overriding the post-processing steps of a mesh resource.");
+        self.mesh_processing_overrides.insert(path.into(), mesh_processing);
+    }
+
+    pub fn remove_mesh_processing_of<P: AsRef<Path>>(&mut self, path: P) {
+        debug!("Removing the post-processing steps override of the mesh resource at path {}.", path.as_ref().display());
+        self.mesh_processing_overrides.remove(path.as_ref());
+    }
+
+    /// The post-processing steps of a mesh resource : its override, or else the steps of its meta file, or else the default ones.
+    pub fn mesh_processing_of<P: AsRef<Path>>(&self, path: P) -> MeshProcessing {
+        debug!("Getting the post-processing steps of the mesh resource at path {}.", path.as_ref().display());
+        if let Some(mesh_processing) = self.mesh_processing_overrides.get(path.as_ref()) {
+            return *mesh_processing;
+        }
+        match self.metas.borrow().get(path.as_ref()).and_then(|meta| meta.mesh_processing) {
+            Some(mesh_processing) => mesh_processing,
+            None => self.mesh_processing,
+        }
+    }
+
+    /// The import settings of a texture resource, given by its meta file. Without meta file, the texture is imported as is.
+    pub fn texture_import_settings_of<P: AsRef<Path>>(&self, path: P) -> TextureImportSettings {
+        debug!("Getting the import settings of the texture resource at path {}.", path.as_ref().display());
+        self.metas.borrow().get(path.as_ref()).and_then(|meta| meta.texture).unwrap_or_default()
+    }
+
+    /// Sets the levels of detail generated for every mesh at load time.
+    pub fn set_mesh_lod_settings(&mut self, mesh_lod_settings: MeshLodSettings) {
+        debug!("Setting the default levels of detail of mesh resources.");
+        self.mesh_lod_settings = mesh_lod_settings;
+    }
+
+    pub fn mesh_lod_settings(&self) -> &MeshLodSettings {
+        debug!("Getting the default levels of detail of mesh resources.");
+        &self.mesh_lod_settings
+    }
+
+    /// Overrides the default levels of detail for the mesh resource at the given path.
+    pub fn set_mesh_lod_settings_of<P: Into<PathBuf>>(&mut self, path: P, mesh_lod_settings: MeshLodSettings) {
+        debug!("Overriding the levels of detail of a mesh resource.");
+        self.mesh_lod_settings_overrides.insert(path.into(), mesh_lod_settings);
+    }
+
+    pub fn remove_mesh_lod_settings_of<P: AsRef<Path>>(&mut self, path: P) {
+        debug!("Removing the levels of detail override of the mesh resource at path {}.", path.as_ref().display());
+        self.mesh_lod_settings_overrides.remove(path.as_ref());
+    }
+
+    /// The levels of detail of a mesh resource : its override, or else the levels of its meta file, or else the default ones.
+    pub fn mesh_lod_settings_of<P: AsRef<Path>>(&self, path: P) -> MeshLodSettings {
+        debug!("Getting the levels of detail of the mesh resource at path {}.", path.as_ref().display());
+        if let Some(mesh_lod_settings) = self.mesh_lod_settings_overrides.get(path.as_ref()) {
+            return mesh_lod_settings.clone();
+        }
+        match self.metas.borrow().get(path.as_ref()).and_then(|meta| meta.mesh_lods.clone()) {
+            Some(mesh_lod_settings) => mesh_lod_settings,
+            None => self.mesh_lod_settings.clone(),
+        }
+    }
+
+    /// Sets the memory budget of a category of resources, in a scope. `None` means unlimited.
+    pub fn set_memory_budget(&mut self, scope: ResourceScope, category: ResourceCategory, budget: Option<usize>) {
+        debug!("Setting a memory budget.");
+        self.memory_budget.borrow_mut().set_budget(scope, category, budget);
+    }
+
+    pub fn memory_budget(&self) -> Ref<MemoryBudget> {
+        debug!("Borrowing an immutable reference to the memory budget.");
+        self.memory_budget.borrow()
+    }
+
+    fn reserve_memory(&self, scope: ResourceScope, category: ResourceCategory, bytes: usize) -> ResourceManagerResult<()> {
+        self.memory_budget.borrow_mut().reserve(scope, category, bytes)
+    }
+
+    //Allocates a resource in the resource stack, and keeps track of the high-water marks.
+    fn alloc_resource<T, F>(&'a self, op: F) -> ResourceManagerResult<&'a mut T> where
+        F: FnOnce() -> T,
+    {
+        let resource = self.double_ended_allocator.0.alloc(op)?;
+        self.update_allocator_stats();
+        Ok(resource)
+    }
+
+    //Reserves the memory of a resource in the budget of its scope, and allocates it in the resource stack.
+    //The reservation is released if the allocation fails.
+    fn alloc_budgeted<T: MemorySize>(&'a self, scope: ResourceScope, category: ResourceCategory, resource: T) -> ResourceManagerResult<&'a mut T> {
+        let size = resource.memory_size();
+        self.reserve_memory(scope, category, size)?;
+        self.alloc_resource(|| resource).map_err(|error| {
+            self.memory_budget.borrow_mut().release(scope, category, size);
+            error
+        })
+    }
+
+    fn registry(&self, scope: ResourceScope) -> &RefCell<ResourceRegistry<'a>> {
+        match scope {
+            ResourceScope::Global => &self.global_resource_registry,
+            ResourceScope::Level => &self.level_resource_registry,
+        }
+    }
+
+    fn update_allocator_stats(&self) {
+        let mut allocator_stats = self.allocator_stats.borrow_mut();
+        allocator_stats.0.update(self.double_ended_allocator.0.marker(), self.double_ended_allocator.0.marker_copy());
+        allocator_stats.1.update(self.double_ended_allocator.1.marker(), self.double_ended_allocator.1.marker_copy());
+    }
+
+    /// Creates a context for the temporary allocations of the loaders, in the temporary data stack.
+    ///
+    /// Only one context can be open at a time, the loading methods fail while another one is open.
+    pub fn scratch_context(&self) -> ResourceManagerResult<ScratchContext> {
+        debug!("Creating a scratch context in the temporary data stack allocator.");
+        match ScratchContext::exclusive(&self.double_ended_allocator.1, &self.scratch_open) {
+            Some(scratch) => Ok(scratch),
+            None => {
+                error!("A scratch context is already open in the temporary data stack allocator.");
+                Err(ResourceManagerError::ResourceError(String::from("A scratch context is already open in the temporary data stack !")))
+            },
+        }
+    }
+
+    /// Returns the memory used by every loaded resource, and the state of the allocators.
+    pub fn memory_report(&self) -> MemoryReport {
+        debug!("Creating a memory report.");
+        self.update_allocator_stats();
+        let global_registry = self.global_resource_registry.borrow();
+        let level_registry = self.level_resource_registry.borrow();
+        let mut resources = global_registry.memory_usage(ResourceScope::Global);
+        resources.extend(level_registry.memory_usage(ResourceScope::Level));
+        let heap_region = self.heap_region.borrow();
+        resources.extend(self.heap_tga_resources.borrow().iter().map(|(path, tga)| {
+            ResourceMemoryUsage::new(path.as_path(), ResourceType::Tga, ResourceScope::Level, heap_region.get(tga.handle).map(|pixels| pixels.len()).unwrap_or(0))
+        }));
+        let allocator_stats = self.allocator_stats.borrow();
+
+        MemoryReport {
+            resources,
+            resource_objects_bytes: global_registry.resource_objects_size() + level_registry.resource_objects_size(),
+            resource_stack: allocator_stats.0,
+            temporary_stack: allocator_stats.1,
+            heap_region: HeapRegionStats {
+                capacity: heap_region.capacity(),
+                used: heap_region.used(),
+                free: heap_region.free(),
+                largest_free_block: heap_region.largest_free_block(),
+                block_count: heap_region.block_count(),
+            },
+        }
+    }
+
+    //____Pooled resources____
+    //Small resources are stored in pools, and can be removed one by one, whatever their scope.
+
+    fn has_loaded_material<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.level_resource_registry.borrow().has_material(path.as_ref()) || self.global_resource_registry.borrow().has_material(path.as_ref())
+    }
+
+    fn has_loaded_ogg<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.level_resource_registry.borrow().has_ogg(path.as_ref()) || self.global_resource_registry.borrow().has_ogg(path.as_ref())
+    }
+
+    /// Stores a material instance in its pool. Its material must be loaded.
+    pub fn add_material_instance(&self, instance: MaterialInstanceResource) -> ResourceManagerResult<PoolHandle<MaterialInstanceResource>> {
+        debug!("Adding a material instance to the resource manager.");
+        if !self.has_loaded_material(instance.material.as_path()) {
+            error!("The material of a material instance is not loaded.");
+            return Err(ResourceManagerError::ResourceError(format!("The material {} of the material instance is not loaded !", instance.material.display())));
+        }
+        self.material_instances.borrow_mut().insert(instance)
+    }
+
+    pub fn material_instance(&self, handle: PoolHandle<MaterialInstanceResource>) -> Option<Ref<MaterialInstanceResource>> {
+        debug!("Getting a material instance from the resource manager.");
+        let pool = self.material_instances.borrow();
+        if !pool.contains(handle) {
+            return None;
+        }
+        Some(Ref::map(pool, |pool| pool.get(handle).expect("The material instance has just been found.")))
+    }
+
+    pub fn material_instance_mut(&self, handle: PoolHandle<MaterialInstanceResource>) -> Option<RefMut<MaterialInstanceResource>> {
+        debug!("Getting a mutable material instance from the resource manager.");
+        let pool = self.material_instances.borrow_mut();
+        if !pool.contains(handle) {
+            return None;
+        }
+        Some(RefMut::map(pool, |pool| pool.get_mut(handle).expect("The material instance has just been found.")))
+    }
+
+    /// Removes a material instance. Its slot is reused by the next material instance.
+    pub fn remove_material_instance(&self, handle: PoolHandle<MaterialInstanceResource>) -> Option<MaterialInstanceResource> {
+        debug!("Removing a material instance from the resource manager.");
+        self.material_instances.borrow_mut().remove(handle)
+    }
+
+    pub fn material_instances(&self) -> Ref<ResourcePool<MaterialInstanceResource>> {
+        debug!("Borrowing an immutable reference to the material instance pool.");
+        self.material_instances.borrow()
+    }
+
+    /// Stores a sound cue in its pool. Its ogg file must be loaded.
+    pub fn add_sound_cue(&self, sound_cue: SoundCueResource) -> ResourceManagerResult<PoolHandle<SoundCueResource>> {
+        debug!("Adding a sound cue to the resource manager.");
+        if !self.has_loaded_ogg(sound_cue.clip.as_path()) {
+            error!("The ogg file of a sound cue is not loaded.");
+            return Err(ResourceManagerError::ResourceError(format!("The ogg file {} of the sound cue is not loaded !", sound_cue.clip.display())));
+        }
+        self.sound_cues.borrow_mut().insert(sound_cue)
+    }
+
+    pub fn sound_cue(&self, handle: PoolHandle<SoundCueResource>) -> Option<Ref<SoundCueResource>> {
+        debug!("Getting a sound cue from the resource manager.");
+        let pool = self.sound_cues.borrow();
+        if !pool.contains(handle) {
+            return None;
+        }
+        Some(Ref::map(pool, |pool| pool.get(handle).expect("The sound cue has just been found.")))
+    }
+
+    pub fn sound_cue_mut(&self, handle: PoolHandle<SoundCueResource>) -> Option<RefMut<SoundCueResource>> {
+        debug!("Getting a mutable sound cue from the resource manager.");
+        let pool = self.sound_cues.borrow_mut();
+        if !pool.contains(handle) {
+            return None;
+        }
+        Some(RefMut::map(pool, |pool| pool.get_mut(handle).expect("The sound cue has just been found.")))
+    }
+
+    /// Removes a sound cue. Its slot is reused by the next sound cue.
+    pub fn remove_sound_cue(&self, handle: PoolHandle<SoundCueResource>) -> Option<SoundCueResource> {
+        debug!("Removing a sound cue from the resource manager.");
+        self.sound_cues.borrow_mut().remove(handle)
+    }
+
+    pub fn sound_cues(&self) -> Ref<ResourcePool<SoundCueResource>> {
+        debug!("Borrowing an immutable reference to the sound cue pool.");
+        self.sound_cues.borrow()
+    }
+
+    //____Heap region____
+    //Level resources which must be unloaded on their own live in the heap region, instead of the resource stack.
+
+    /// Sets the capacity of the heap region. The resources already in the heap region are unloaded.
+    pub fn set_heap_capacity(&mut self, capacity: usize) {
+        debug!("Setting the capacity of the heap region to {} bytes.", capacity);
+        self.unload_heap_resources();
+        self.heap_region = RefCell::new(HeapRegion::with_capacity(capacity));
+    }
+
+    pub fn heap_region(&self) -> Ref<HeapRegion> {
+        debug!("Borrowing an immutable reference to the heap region.");
+        self.heap_region.borrow()
+    }
+
+    /// Loads a tga image in the heap region, so it can be unloaded on its own with `unload_heap_tga`.
+    pub fn load_tga_in_heap<P>(&'a self, path: P, filesystem: &Filesystem) -> ResourceManagerResult<()> where
+        P: AsRef<Path> + Into<PathBuf>,
+    {
+        debug!("Loading tga data with path {} in the heap region.", path.as_ref().display());
+        if self.heap_tga_resources.borrow().contains_key(path.as_ref()) {
+            return Ok(());
+        }
+
+        let result = self.scratch_context().and_then(|scratch| self.load_heap_tga(path.as_ref(), filesystem, &scratch));
+        self.emit_result(path.as_ref(), ResourceEventKind::Loaded, result)
+    }
+
+    fn load_heap_tga<P>(&'a self, path: P, filesystem: &Filesystem, scratch: &ScratchContext) -> ResourceManagerResult<()> where
+        P: AsRef<Path> + Into<PathBuf>,
+    {
+        let image = self.read_heap_tga(path.as_ref(), filesystem, scratch)?;
+        let handle = self.alloc_heap_pixels(image.buf.as_slice())?;
+        self.resource_cache.borrow_mut().insert(path.as_ref(), image.buf.len());
+        self.watch_resource(path.as_ref(), path.as_ref());
+        self.heap_tga_resources.borrow_mut().insert(path.into(), HeapTgaResource {
+            width: image.w,
+            height: image.h,
+            fmt: image.fmt,
+            handle,
+        });
+        self.evict_cached_resources();
+        Ok(())
+    }
+
+    //Reads and decodes a tga image meant for the heap region.
+    fn read_heap_tga(&self, path: &Path, filesystem: &Filesystem, scratch: &ScratchContext) -> ResourceManagerResult<Image<u8>> {
+        self.load_meta(path, filesystem, ResourceScope::Level)?;
+        //A change of the import settings reloads the image.
+        if self.resource_exists(meta_path(path)) {
+            self.watch_resource(path, meta_path(path));
+        }
+        let mut reader = self.open_resource(path, filesystem)?;
+        let mut tga_bytes = ResourceManager::read_resource_data(&mut reader, scratch)?;
+        Ok(tga::read(&mut tga_bytes, ColFmt::Auto)?)
+    }
+
+    //Copies pixels in the heap region, within the texture budget of the level.
+    fn alloc_heap_pixels(&self, pixels: &[u8]) -> ResourceManagerResult<HeapHandle> {
+        //In cache mode, unreferenced resources are evicted until the pixels fit in the budget and the heap region.
+        self.evicting_if_needed(0, || self.reserve_memory(ResourceScope::Level, ResourceCategory::Texture, pixels.len()))?;
+        self.evicting_if_needed(pixels.len(), || self.heap_region.borrow_mut().alloc_bytes(pixels)).map_err(|error| {
+            self.memory_budget.borrow_mut().release(ResourceScope::Level, ResourceCategory::Texture, pixels.len());
+            error
+        })
+    }
+
+    //Replaces the pixels of a tga image of the heap region. The old pixels are freed only once the new ones are allocated.
+    fn reload_heap_tga(&self, path: &Path, filesystem: &Filesystem, scratch: &ScratchContext) -> ResourceManagerResult<()> {
+        let image = self.read_heap_tga(path, filesystem, scratch)?;
+        //The old pixels cannot be evicted to make room for the new ones, the resource must survive a failed reload.
+        self.resource_cache.borrow_mut().add_reference(path);
+        let handle = self.alloc_heap_pixels(image.buf.as_slice());
+        self.resource_cache.borrow_mut().remove_reference(path);
+        let handle = handle?;
+
+        let old_tga = self.heap_tga_resources.borrow_mut().insert(path.to_path_buf(), HeapTgaResource {
+            width: image.w,
+            height: image.h,
+            fmt: image.fmt,
+            handle,
+        });
+        if let Some(old_tga) = old_tga {
+            let mut heap_region = self.heap_region.borrow_mut();
+            let size = heap_region.get(old_tga.handle).map(|pixels| pixels.len()).unwrap_or(0);
+            heap_region.free_block(old_tga.handle);
+            self.memory_budget.borrow_mut().release(ResourceScope::Level, ResourceCategory::Texture, size);
+        }
+        //The references taken on the resource survive the reload.
+        self.resource_cache.borrow_mut().resize(path, image.buf.len());
+        self.evict_cached_resources();
+        Ok(())
+    }
+
+    pub fn get_heap_tga<P: AsRef<Path>>(&self, path: P) -> ResourceManagerResult<HeapTgaResource> {
+        debug!("Trying to get a tga resource with path {} in the heap region.", path.as_ref().display());
+        match self.heap_tga_resources.borrow().get(path.as_ref()) {
+            Some(tga) => {
+                self.resource_cache.borrow_mut().touch(path.as_ref());
+                Ok(*tga)
+            },
+            None => {
+                error!("The tga resource could not be found in the heap region.");
+                Err(ResourceManagerError::ResourceError(format!("Could not find the tga data at path {} in the heap region !", path.as_ref().display())))
+            },
+        }
+    }
+
+    /// Borrows the pixels of a tga image of the heap region. No defragmentation can happen while they are borrowed.
+    pub fn heap_tga_pixels<P: AsRef<Path>>(&self, path: P) -> ResourceManagerResult<Ref<[u8]>> {
+        debug!("Borrowing the pixels of the tga resource with path {} in the heap region.", path.as_ref().display());
+        let tga = self.get_heap_tga(path)?;
+        let heap_region = self.heap_region.borrow();
+        if !heap_region.contains(tga.handle) {
+            return Err(ResourceManagerError::ResourceError(String::from("The pixels of the tga resource are not in the heap region !")));
+        }
+        Ok(Ref::map(heap_region, |heap_region| heap_region.get(tga.handle).expect("The pixels have just been found.")))
+    }
+
+    pub fn has_heap_tga<P: AsRef<Path>>(&self, path: P) -> bool {
+        debug!("Checking if the heap region has a tga resource with path {}.", path.as_ref().display());
+        self.heap_tga_resources.borrow().contains_key(path.as_ref())
+    }
+
+    /// Unloads a tga image of the heap region, its memory can be reused right away.
+    pub fn unload_heap_tga<P: AsRef<Path>>(&self, path: P) -> ResourceManagerResult<()> {
+        debug!("Unloading the tga resource with path {} from the heap region.", path.as_ref().display());
+        self.free_heap_tga(path.as_ref())?;
+        self.metas.borrow_mut().remove(path.as_ref());
+        self.emit(ResourceEvent::new(ResourceEventKind::Unloaded, path.as_ref(), Some(ResourceType::Tga)));
+        Ok(())
+    }
+
+    fn free_heap_tga<P: AsRef<Path>>(&self, path: P) -> ResourceManagerResult<()> {
+        let tga = match self.heap_tga_resources.borrow_mut().remove(path.as_ref()) {
+            Some(tga) => tga,
+            None => {
+                error!("The tga resource could not be found in the heap region.");
+                return Err(ResourceManagerError::ResourceError(format!("Could not find the tga data at path {} in the heap region !", path.as_ref().display())));
+            },
+        };
+
+        let mut heap_region = self.heap_region.borrow_mut();
+        let size = heap_region.get(tga.handle).map(|pixels| pixels.len()).unwrap_or(0);
+        heap_region.free_block(tga.handle);
+        self.resource_cache.borrow_mut().remove(path.as_ref());
+        self.memory_budget.borrow_mut().release(ResourceScope::Level, ResourceCategory::Texture, size);
+        Ok(())
+    }
+
+    /// Marks a tga image of the heap region as used, it will not be evicted until it is released.
+    pub fn acquire_heap_tga<P: AsRef<Path>>(&self, path: P) -> ResourceManagerResult<()> {
+        debug!("Acquiring the tga resource with path {} in the heap region.", path.as_ref().display());
+        if self.resource_cache.borrow_mut().add_reference(path.as_ref()) {
+            Ok(())
+        } else {
+            error!("The tga resource could not be found in the heap region.");
+            Err(ResourceManagerError::ResourceError(format!("Could not find the tga data at path {} in the heap region !", path.as_ref().display())))
+        }
+    }
+
+    /// Releases a tga image of the heap region. Once unreferenced, it stays resident until it is evicted or unloaded.
+    pub fn release_heap_tga<P: AsRef<Path>>(&self, path: P) -> ResourceManagerResult<()> {
+        debug!("Releasing the tga resource with path {} in the heap region.", path.as_ref().display());
+        match self.resource_cache.borrow_mut().remove_reference(path.as_ref()) {
+            Some(_) => Ok(()),
+            None => {
+                error!("The tga resource could not be found in the heap region.");
+                Err(ResourceManagerError::ResourceError(format!("Could not find the tga data at path {} in the heap region !", path.as_ref().display())))
+            },
+        }
+    }
+
+    //____Cache mode____
+
+    /// Enables the cache mode with the given watermarks, in bytes of heap region resources, or disables it with `None`.
+    ///
+    /// In cache mode, the unreferenced resources of the heap region are evicted least recently used first,
+    /// when the high watermark is exceeded or when a new resource does not fit.
+    pub fn set_cache_mode(&mut self, watermarks: Option<CacheWatermarks>) {
+        debug!("Setting the cache mode of the resource manager.");
+        self.cache_watermarks = watermarks;
+    }
+
+    pub fn cache_mode(&self) -> Option<CacheWatermarks> {
+        self.cache_watermarks
+    }
+
+    pub fn resource_cache(&self) -> Ref<LruCache> {
+        debug!("Borrowing an immutable reference to the resource cache.");
+        self.resource_cache.borrow()
+    }
+
+    /// In cache mode, evicts the least recently used unreferenced resources if the high watermark is exceeded.
+    ///
+    /// Returns the paths of the evicted resources.
+    pub fn evict_cached_resources(&self) -> Vec<PathBuf> {
+        debug!("Evicting the least recently used resources.");
+        let evicted = match self.cache_watermarks {
+            Some(watermarks) => self.resource_cache.borrow().eviction_candidates(watermarks),
+            None => Vec::new(),
+        };
+        evicted.into_iter().filter(|path| {
+            match self.unload_heap_tga(path) {
+                Ok(()) => true,
+                Err(error) => {
+                    error!("The resource at path {} could not be evicted: {}", path.display(), error);
+                    false
+                },
+            }
+        }).collect()
+    }
+
+    //Retries the operation after evicting the least recently used resource, as long as it fails in cache mode.
+    //The heap region is defragmented when the `heap_bytes` the operation allocates in it fit only in its whole free memory.
+    fn evicting_if_needed<T, F>(&self, heap_bytes: usize, mut op: F) -> ResourceManagerResult<T> where
+        F: FnMut() -> ResourceManagerResult<T>,
+    {
+        loop {
+            let error = match op() {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+            if self.cache_watermarks.is_none() {
+                return Err(error);
+            }
+            let least_recently_used = match self.resource_cache.borrow().unreferenced().into_iter().next() {
+                Some(path) => path,
+                None => return Err(error),
+            };
+            debug!("Evicting {} to make room for a new resource.", least_recently_used.display());
+            self.unload_heap_tga(least_recently_used.as_path())?;
+            let mut heap_region = self.heap_region.borrow_mut();
+            if heap_bytes > 0 && heap_region.needs_defragmentation(heap_bytes) {
+                heap_region.defragment();
+            }
+        }
+    }
+
+    /// Moves the resources of the heap region to gather its free memory, until about `max_bytes` bytes have been moved.
+    ///
+    /// Meant to be called once per frame. The handles of the resources stay valid. Returns the number of bytes moved.
+    pub fn defragment_heap(&self, max_bytes: usize) -> usize {
+        debug!("Defragmenting the heap region.");
+        self.heap_region.borrow_mut().defragment_step(max_bytes)
+    }
+
+    fn unload_heap_resources(&self) {
+        debug!("Unloading the resources of the heap region.");
+        let unloaded: Vec<PathBuf> = self.heap_tga_resources.borrow_mut().drain().map(|(path, _)| path).collect();
+        for path in unloaded {
+            self.emit(ResourceEvent::new(ResourceEventKind::Unloaded, path, Some(ResourceType::Tga)));
+        }
+        self.resource_cache.borrow_mut().clear();
+        self.heap_region.borrow_mut().clear();
+    }
+
+    //____GUIDs____
+
+    /// Sets the database giving the paths of the resources referenced by GUID.
+    pub fn set_guid_database(&mut self, guid_database: GuidDatabase) {
+        debug!("Setting the GUID database of the resource manager.");
+        self.guid_database = guid_database;
+    }
+
+    /// Reads the GUID database, written by `GuidDatabase::write_to`, at the given path. Meant to be called at startup.
+    pub fn load_guid_database<P: AsRef<Path>>(&mut self, path: P, filesystem: &Filesystem) -> ResourceManagerResult<()> {
+        debug!("Loading the GUID database at path {}.", path.as_ref().display());
+        let mut reader = self.open_resource(path.as_ref(), filesystem)?;
+        self.guid_database = GuidDatabase::read_from(&mut reader)?;
+        Ok(())
+    }
+
+    pub fn guid_database(&self) -> &GuidDatabase {
+        &self.guid_database
+    }
+
+    pub fn guid_of<P: AsRef<Path>>(&self, path: P) -> Option<ResourceGuid> {
+        self.guid_database.guid_of(path)
+    }
+
+    /// The path of a referenced resource : the path of its GUID for a `guid://` reference, or else the reference itself.
+    ///
+    /// The resources are registered with their paths, this path finds them in the registries.
+    pub fn resource_path(&self, reference: &str) -> ResourceManagerResult<PathBuf> {
+        match ResourceGuid::from_reference(reference) {
+            Some(guid) => {
+                let guid = guid?;
+                match self.guid_database.path_of(guid) {
+                    Some(path) => Ok(path.to_path_buf()),
+                    None => {
+                        error!("The GUID {} could not be found in the GUID database.", guid);
+                        Err(ResourceManagerError::ResourceError(format!("Could not find the GUID {} in the GUID database !", guid)))
+                    },
+                }
+            },
+            None => Ok(PathBuf::from(reference)),
+        }
+    }
+
+    //____Meta files____
+
+    //Whether the data of a resource can be opened, without opening it.
+    fn resource_exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        if is_virtual_path(path.as_ref()) {
+            return self.vfs.exists(path.as_ref());
+        }
+        self.mounted_paks.iter().any(|pak| pak.contains(path.as_ref()))
+            || self.mapped_paks.iter().any(|pak| pak.contains(path.as_ref()))
+            || path.as_ref().is_file()
+    }
+
+    //Reads the sidecar meta file of a resource about to be loaded, if it has one, and records the dependencies it declares.
+    fn load_meta<P: AsRef<Path>>(&self, path: P, filesystem: &Filesystem, scope: ResourceScope) -> ResourceManagerResult<()> {
+        let meta_path = meta_path(path.as_ref());
+        if !self.resource_exists(meta_path.as_path()) {
+            self.metas.borrow_mut().remove(path.as_ref());
+            return Ok(());
+        }
+
+        debug!("Reading the meta file of the resource with path {}.", path.as_ref().display());
+        let mut reader = self.open_resource(meta_path.as_path(), filesystem)?;
+        let meta = ResourceMeta::read_from(&mut reader)?;
+        for dependency in meta.dependencies.iter() {
+            let dependency = self.resource_path(&dependency.to_string_lossy())?;
+            self.registry(scope).borrow_mut().add_dependency(path.as_ref(), dependency);
+        }
+        self.metas.borrow_mut().insert(path.as_ref().to_path_buf(), meta);
+        Ok(())
+    }
+
+    /// The metadata of a loaded resource, read from its sidecar meta file. `None` if the resource has no meta file.
+    pub fn meta_of<P: AsRef<Path>>(&self, path: P) -> Option<Ref<ResourceMeta>> {
+        debug!("Getting the metadata of the resource with path {}.", path.as_ref().display());
+        let metas = self.metas.borrow();
+        if !metas.contains_key(path.as_ref()) {
+            return None;
+        }
+        Some(Ref::map(metas, |metas| metas.get(path.as_ref()).expect("The metadata has just been found.")))
+    }
+
+    //____Events____
+
+    fn emit(&self, event: ResourceEvent) {
+        //The queue is not borrowed while the callbacks are called, they can use the resource manager.
+        let mut dispatch = self.events.borrow_mut().begin_dispatch(event);
+        dispatch.run();
+        self.events.borrow_mut().end_dispatch(dispatch);
+    }
+
+    //Emits the event of an operation on a resource, or a Failed event if the operation failed.
+    fn emit_result<P: AsRef<Path>, T>(&self, path: P, kind: ResourceEventKind, result: ResourceManagerResult<T>) -> ResourceManagerResult<T> {
+        let kind = match result {
+            Ok(_) => kind,
+            Err(ref error) => ResourceEventKind::Failed(error.to_string()),
+        };
+        self.emit(ResourceEvent::from_path(kind, path.as_ref()));
+        result
+    }
+
+    //Emits an Unloaded event for every resource of a registry.
+    fn emit_unloaded(&self, registry: &ResourceRegistry, scope: ResourceScope) {
+        for usage in registry.memory_usage(scope) {
+            self.emit(ResourceEvent::new(ResourceEventKind::Unloaded, usage.path, Some(usage.resource_type)));
+        }
+    }
+
+    /// Sets the number of events kept until they are drained.
+    pub fn set_event_queue_capacity(&mut self, capacity: usize) {
+        debug!("Setting the capacity of the resource event queue to {} events.", capacity);
+        self.events.borrow_mut().set_capacity(capacity);
+    }
+
+    pub fn events(&self) -> Ref<ResourceEventQueue> {
+        debug!("Borrowing an immutable reference to the resource event queue.");
+        self.events.borrow()
+    }
+
+    /// Removes and returns the events which happened since the last drain, oldest first. Meant to be called once per frame.
+    pub fn drain_events(&self) -> Vec<ResourceEvent> {
+        debug!("Draining the resource events.");
+        self.events.borrow_mut().drain()
+    }
+
+    /// Removes and returns the events matching the filter, the other events stay queued for the other consumers.
+    pub fn drain_events_matching(&self, filter: &ResourceEventFilter) -> Vec<ResourceEvent> {
+        debug!("Draining the matching resource events.");
+        self.events.borrow_mut().drain_matching(filter)
+    }
+
+    /// Calls `callback` with every matching event, as soon as it happens.
+    ///
+    /// The callback is called while the resource manager is working. It can use the resource manager,
+    /// but the events emitted meanwhile are not sent to the subscriptions being called.
+    pub fn subscribe<F>(&self, filter: ResourceEventFilter, callback: F) -> SubscriptionId where
+        F: FnMut(&ResourceEvent) + 'static,
+    {
+        debug!("Subscribing to the resource events.");
+        self.events.borrow_mut().subscribe(filter, callback)
+    }
+
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        debug!("Unsubscribing from the resource events.");
+        self.events.borrow_mut().unsubscribe(id)
+    }
+
+    //____Hot reload____
+    //Meant for development : the source files of the loaded resources are polled, and the changed resources are reloaded.
+    //Only the resources of the heap region are reloaded : their data is reached through a handle, so it can be swapped
+    //and freed. The resources of the resource stack are borrowed for the lifetime of the level, their old data could not be.
+
+    /// Enables or disables the hot reload of the resources of the heap region loaded from now on.
+    ///
+    /// Only the resources read from files can be reloaded, not the ones read from pak files.
+    /// The resources of the resource stack are not watched, they are reloaded with their level.
+    pub fn set_hot_reload(&mut self, enabled: bool) {
+        debug!("Setting the hot reload of the resource manager.");
+        self.hot_reload = enabled;
+        if !enabled {
+            self.hot_reload_watcher.borrow_mut().clear();
+        }
+    }
+
+    pub fn hot_reload(&self) -> bool {
+        self.hot_reload
+    }
+
+    pub fn hot_reload_watcher(&self) -> Ref<HotReloadWatcher> {
+        debug!("Borrowing an immutable reference to the hot reload watcher.");
+        self.hot_reload_watcher.borrow()
+    }
+
+    //The file of the OS filesystem a resource is read from, None if it is read from a pak file.
+    fn source_file<P: AsRef<Path>>(&self, path: P) -> Option<PathBuf> {
+        if is_virtual_path(path.as_ref()) {
+            return self.vfs.resolve(path.as_ref()).and_then(|mount| mount.real_path(path.as_ref()));
+        }
+        if self.mounted_paks.iter().any(|pak| pak.contains(path.as_ref())) || self.mapped_paks.iter().any(|pak| pak.contains(path.as_ref())) {
+            return None;
+        }
+        Some(path.as_ref().to_path_buf())
+    }
+
+    //Watches a source file of a resource, when the hot reload is enabled.
+    fn watch_resource<P, S>(&self, path: P, source: S) where
+        P: AsRef<Path>,
+        S: AsRef<Path>,
+    {
+        if !self.hot_reload {
+            return;
+        }
+        if let Some(source_file) = self.source_file(source.as_ref()) {
+            self.hot_reload_watcher.borrow_mut().watch(path.as_ref(), source_file);
+        }
+    }
+
+    /// Reloads the resources whose source files changed since the last poll. Meant to be called once per frame.
+    ///
+    /// The reloaded data replaces the old one behind the handle of the resource, and the old data is freed.
+    /// A resource which cannot be reloaded keeps its old data.
+    pub fn poll_hot_reload(&'a self, filesystem: &Filesystem) -> Vec<ReloadEvent> {
+        trace!("Polling the resources to hot reload.");
+        if !self.hot_reload {
+            return Vec::new();
+        }
+
+        let changed = self.hot_reload_watcher.borrow_mut().poll();
+        let mut events = Vec::new();
+        for path in changed {
+            match self.reload_resource(path.as_path(), filesystem) {
+                Ok(()) => {
+                    let dependents = self.level_resource_registry.borrow().dependents_of(path.as_path());
+                    events.push(ReloadEvent {
+                        path,
+                        dependents,
+                    });
+                },
+                Err(error) => error!("The resource at path {} could not be reloaded: {}", path.display(), error),
+            }
+        }
+        events
+    }
+
+    /// Reloads a resource of the heap region from its source file, and swaps the new data behind its handle.
+    pub fn reload_resource<P: AsRef<Path>>(&'a self, path: P, filesystem: &Filesystem) -> ResourceManagerResult<()> {
+        debug!("Reloading the resource with path {}.", path.as_ref().display());
+        if !self.has_heap_tga(path.as_ref()) {
+            error!("Only the resources of the heap region can be reloaded.");
+            return self.emit_result(path.as_ref(), ResourceEventKind::Reloaded, Err(ResourceManagerError::ResourceError(format!("The resource at path {} is not in the heap region, it cannot be reloaded !", path.as_ref().display()))));
+        }
+        let result = self.scratch_context().and_then(|scratch| self.reload_heap_tga(path.as_ref(), filesystem, &scratch));
+        self.emit_result(path.as_ref(), ResourceEventKind::Reloaded, result)
+    }
+
+    //____Pak files____
+
+    /// Mounts a pak file. The resources it contains are read from it instead of the filesystem.
+    ///
+    /// The pak files mounted last are searched first.
+    pub fn mount_pak(&mut self, pak: PakArchive) {
+        debug!("Mounting the pak file at path {}.", pak.path().display());
+        self.mounted_paks.push(pak);
+    }
+
+    /// Unmounts the pak file at the given path, and gives it back.
+    pub fn unmount_pak<P: AsRef<Path>>(&mut self, path: P) -> Option<PakArchive> {
+        debug!("Unmounting the pak file at path {}.", path.as_ref().display());
+        match self.mounted_paks.iter().position(|pak| pak.path() == path.as_ref()) {
+            Some(index) => Some(self.mounted_paks.remove(index)),
+            None => None,
+        }
+    }
+
+    pub fn mounted_paks(&self) -> &[PakArchive] {
+        self.mounted_paks.as_slice()
+    }
+
+    /// Mounts a pak file mapped in memory. Its uncompressed entries can be loaded without copy with `load_raw`.
+    ///
+    /// The mapped pak files mounted last are searched first, after the pak files mounted with `mount_pak`.
+    pub fn mount_mapped_pak(&mut self, pak: &'a MappedPakArchive) {
+        debug!("Mounting the mapped pak file at path {}.", pak.path().display());
+        self.mapped_paks.push(pak);
+    }
+
+    pub fn unmount_mapped_pak<P: AsRef<Path>>(&mut self, path: P) -> Option<&'a MappedPakArchive> {
+        debug!("Unmounting the mapped pak file at path {}.", path.as_ref().display());
+        match self.mapped_paks.iter().position(|pak| pak.path() == path.as_ref()) {
+            Some(index) => Some(self.mapped_paks.remove(index)),
+            None => None,
+        }
+    }
+
+    pub fn mapped_paks(&self) -> &[&'a MappedPakArchive] {
+        self.mapped_paks.as_slice()
+    }
+
+    //____Virtual filesystem____
+
+    /// The virtual filesystem the resources with a virtual path, like `res://meshes/foo.gltf`, are read from.
+    pub fn vfs(&self) -> &VirtualFilesystem {
+        &self.vfs
+    }
+
+    /// Mounts directories, pak files or files in memory in the virtual filesystem.
+    pub fn vfs_mut(&mut self) -> &mut VirtualFilesystem {
+        &mut self.vfs
+    }
+
+    //Opens the data of a resource, in the virtual filesystem for a virtual path,
+    //in the mounted pak files or else in the filesystem for the other paths.
+    fn open_resource<P: AsRef<Path>>(&self, path: P, filesystem: &Filesystem) -> ResourceManagerResult<ResourceReader> {
+        if is_virtual_path(path.as_ref()) {
+            return self.vfs.open(path.as_ref());
+        }
+        if let Some(pak) = self.mounted_paks.iter().rev().find(|pak| pak.contains(path.as_ref())) {
+            trace!("The resource at path {} is read from the pak file at path {}.", path.as_ref().display(), pak.path().display());
+            return pak.open_entry(path.as_ref());
+        }
+        if let Some(pak) = self.mapped_paks.iter().rev().find(|pak| pak.contains(path.as_ref())) {
+            trace!("The resource at path {} is read from the mapped pak file at path {}.", path.as_ref().display(), pak.path().display());
+            return pak.archive().open_entry(path.as_ref());
+        }
+        Ok(ResourceReader::from(filesystem.open(path.as_ref())?))
+    }
+
+    /// Publishes an uncompressed entry of a mapped pak file as a raw resource of the level, without copying it.
+    ///
+    /// The data stays in the mapping, and is borrowed for the lifetime of the mount.
+    pub fn load_raw<P>(&self, path: P) -> ResourceManagerResult<&'a [u8]> where
+        P: AsRef<Path> + Into<PathBuf>,
+    {
+        debug!("Loading raw data with path {} in resource manager.", path.as_ref().display());
+        let result = self.borrow_raw(path.as_ref());
+        let kind = match result {
+            Ok(raw_data) => {
+                self.level_resource_registry.borrow_mut().add_raw(path.as_ref(), raw_data);
+                ResourceEventKind::Loaded
+            },
+            Err(ref error) => ResourceEventKind::Failed(error.to_string()),
+        };
+        self.emit(ResourceEvent::new(kind, path.as_ref(), Some(ResourceType::Raw)));
+        result
+    }
+
+    fn borrow_raw<P: AsRef<Path>>(&self, path: P) -> ResourceManagerResult<&'a [u8]> {
+        let pak = match self.mapped_paks.iter().rev().find(|pak| pak.contains(path.as_ref())) {
+            Some(pak) => *pak,
+            None => {
+                error!("The raw data could not be found in the mapped pak files.");
+                return Err(ResourceManagerError::ResourceError(format!("Could not find the raw data at path {} in the mapped pak files !", path.as_ref().display())));
+            },
+        };
+        pak.entry_bytes(path.as_ref())
     }
 
     pub fn level_resource_registry(&self) -> Ref<ResourceRegistry> {
@@ -98,79 +1067,379 @@ impl<'a> ResourceManager<'a> {
         vec
     }
 
-    fn load_tga<P>(&'a self, path: P, reader: &mut BufReader<File>) -> ResourceManagerResult<()> where
+    //Reads the data of a resource in the temporary data stack, preallocated when the size of the data is known.
+    fn read_resource_data<'s, R: ResourceRead>(reader: &mut R, scratch: &'s ScratchContext) -> ResourceManagerResult<ScratchBuffer<'s>> {
+        let data_size = reader.data_size().unwrap_or(0) as usize;
+        scratch.read_all_with_capacity(reader, data_size)
+    }
+
+    fn load_tga<P, R>(&'a self, path: P, reader: &mut R, scratch: &ScratchContext, scope: ResourceScope) -> ResourceManagerResult<()> where
         P: AsRef<Path> + Into<PathBuf>,
+        R: ResourceRead,
     {
         debug!("Loading tga data with path {} in resource manager.", path.as_ref().display());
         //create tga.
-        let tga_data = self.double_ended_allocator.0.alloc(|| {
-            TgaResource::from(tga::read(reader, ColFmt::Auto).unwrap())
-        })?;
+        let mut tga_bytes = ResourceManager::read_resource_data(reader, scratch)?;
+        let tga = TgaResource::with_import_settings(tga::read(&mut tga_bytes, ColFmt::Auto)?, self.texture_import_settings_of(path.as_ref()));
+        let tga_data = self.alloc_budgeted(scope, ResourceCategory::Texture, tga)?;
         //Add in registry.
-        self.level_resource_registry.borrow_mut().add_tga(path.as_ref(), tga_data);
+        self.registry(scope).borrow_mut().add_tga(path.as_ref(), tga_data);
         Ok(())
     }
 
-    fn load_gltf<P>(&'a self, path: P, reader: &mut BufReader<File>) -> ResourceManagerResult<()> where
+    fn load_gltf<P, R>(&'a self, path: P, reader: &mut R, filesystem: &Filesystem, scratch: &ScratchContext, scope: ResourceScope) -> ResourceManagerResult<()> where
         P: AsRef<Path> + Into<PathBuf>,
+        R: ResourceRead,
     {
         debug!("Loading gltf data with path {} in resource manager.", path.as_ref().display());
-        //create gltf.
-        let gltf_data = self.double_ended_allocator.0.alloc(|| {
-            GltfResource::from(Gltf::from_reader(reader).unwrap().validate_completely().unwrap())
+        let gltf_bytes = ResourceManager::read_resource_data(reader, scratch)?;
+        let source_size = gltf_bytes.len();
+        let gltf = Gltf::from_reader(gltf_bytes)?.validate_completely()?;
+
+        //Extract the mesh, from the buffers next to the gltf file.
+        let directory = path.as_ref().parent().map(Path::to_path_buf).unwrap_or_default();
+        let mut mesh = gltf_mesh(&gltf, path.as_ref(), |uri| {
+            let buffer_path = directory.join(uri);
+            let mut buffer = Vec::new();
+            self.open_resource(buffer_path.as_path(), filesystem)?.read_to_end(&mut buffer)?;
+            Ok(buffer)
         })?;
+        self.mesh_processing_of(path.as_ref()).apply(&mut mesh);
+
+        //create gltf.
+        let gltf = GltfResource::with_source_size(gltf, source_size);
+        let gltf_size = gltf.memory_size();
+        let gltf_data = self.alloc_budgeted(scope, ResourceCategory::Mesh, gltf)?;
         //Add in registry.
-        self.level_resource_registry.borrow_mut().add_gltf(path.as_ref(), gltf_data);
-        Ok(())
+        self.registry(scope).borrow_mut().add_gltf(path.as_ref(), gltf_data);
+        //A document without triangles, only lines or points, has no mesh.
+        if mesh.primitives.is_empty() {
+            warn!("The gltf file at path {} has no triangle primitive, it has been loaded without mesh.", path.as_ref().display());
+            return Ok(());
+        }
+        //The document of a mesh which could not be loaded is not kept.
+        self.load_mesh(path.as_ref(), mesh, scope).map_err(|error| {
+            self.registry(scope).borrow_mut().remove_gltf(path.as_ref());
+            self.memory_budget.borrow_mut().release(scope, ResourceCategory::Mesh, gltf_size);
+            error
+        })
     }
 
-    fn load_ogg<P>(&'a self, path: P, reader: BufReader<File>) -> ResourceManagerResult<()> where
+    fn load_ogg<P, R>(&'a self, path: P, reader: R, scope: ResourceScope) -> ResourceManagerResult<()> where
         P: AsRef<Path> + Into<PathBuf>,
+        R: Into<ResourceReader>,
     {
         debug!("Loading ogg data with path {} in resource manager.", path.as_ref().display());
+        let mut reader = reader.into();
+        //The last page of the stream gives us its duration.
+        let total_samples = last_granule_position(&mut reader)?;
+        let mut ogg_reader = OggStreamReader::new(reader)?;
+        //Decode and convert the clip if the mixer needs it.
+        let pcm = match self.audio_target_format_of(path.as_ref()) {
+            Some(format) => Some(decode_to_pcm(&mut ogg_reader)?.convert_to(format)),
+            None => None,
+        };
         //create ogg.
-        let ogg_data = self.double_ended_allocator.0.alloc(|| {
-            OggResource::from(OggStreamReader::new(reader).unwrap())
-        })?;
+        let ogg = match pcm {
+            Some(pcm) => OggResource::with_pcm(ogg_reader, total_samples, pcm),
+            None => OggResource::new(ogg_reader, total_samples),
+        };
+        let ogg_data = self.alloc_budgeted(scope, ResourceCategory::Audio, ogg)?;
+        //Add in registry
+        self.registry(scope).borrow_mut().add_ogg(path.as_ref(), ogg_data);
+        Ok(())
+    }
+
+    fn load_obj<P, R>(&'a self, path: P, reader: &mut R, filesystem: &Filesystem, scratch: &ScratchContext, scope: ResourceScope) -> ResourceManagerResult<()> where
+        P: AsRef<Path> + Into<PathBuf>,
+        R: ResourceRead,
+    {
+        debug!("Loading obj data with path {} in resource manager.", path.as_ref().display());
+        let mut obj_data = parse_obj(ResourceManager::read_resource_data(reader, scratch)?)?;
+
+        let mut materials = Vec::new();
+        let result = self.load_obj_materials(path.as_ref(), &mut obj_data, filesystem, scratch, scope, &mut materials).and_then(|()| {
+            //create mesh.
+            let mut mesh = obj_data.mesh;
+            self.mesh_processing_of(path.as_ref()).apply(&mut mesh);
+            self.load_mesh(path.as_ref(), mesh, scope)
+        });
+
+        //The materials of a mesh which could not be loaded are not kept, the textures they reference may be used by other resources.
+        if result.is_err() {
+            let mut registry = self.registry(scope).borrow_mut();
+            for (resource_path, size) in materials {
+                registry.remove_material(resource_path.as_path());
+                registry.remove_dependencies(resource_path.as_path());
+                self.memory_budget.borrow_mut().release(scope, ResourceCategory::Other, size);
+            }
+            registry.remove_dependencies(path.as_ref());
+        }
+        result
+    }
+
+    //Loads the materials of an obj file, and the textures they reference. The registered materials are recorded with their size.
+    fn load_obj_materials(&'a self, path: &Path, obj_data: &mut ObjData, filesystem: &Filesystem, scratch: &ScratchContext, scope: ResourceScope, materials: &mut Vec<(PathBuf, usize)>) -> ResourceManagerResult<()> {
+        let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let mut material_paths = HashMap::new();
+        for library in obj_data.material_libraries.iter() {
+            let library_path = directory.join(library);
+            let library_directory = library_path.parent().map(Path::to_path_buf).unwrap_or_default();
+            let mut mtl_reader = self.open_resource(library_path.as_path(), filesystem)?;
+
+            for material in parse_mtl(ResourceManager::read_resource_data(&mut mtl_reader, scratch)?, library_directory)? {
+                let resource_path = material_path(library_path.as_path(), material.name.as_str());
+                let textures: Vec<PathBuf> = material.textures().iter().map(|texture| texture.to_path_buf()).collect();
+                for texture in textures {
+                    self.load_texture_dependency(texture.as_path(), filesystem, scratch, scope)?;
+                    self.registry(scope).borrow_mut().add_dependency(resource_path.as_path(), texture);
+                }
+
+                material_paths.insert(material.name.clone(), resource_path.clone());
+                let size = material.memory_size();
+                let material_data = self.alloc_budgeted(scope, ResourceCategory::Other, material)?;
+                self.registry(scope).borrow_mut().add_material(resource_path.as_path(), material_data);
+                self.registry(scope).borrow_mut().add_dependency(path, resource_path.as_path());
+                materials.push((resource_path, size));
+            }
+        }
+        obj_data.resolve_materials(&material_paths);
+        Ok(())
+    }
+
+    fn load_mesh<P>(&'a self, path: P, mut mesh: MeshResource, scope: ResourceScope) -> ResourceManagerResult<()> where
+        P: AsRef<Path> + Into<PathBuf>,
+    {
+        debug!("Loading mesh data with path {} in resource manager.", path.as_ref().display());
+        //Generate the levels of detail, stored as sub-resources of the mesh.
+        let mesh_lod_settings = self.mesh_lod_settings_of(path.as_ref());
+        let lods = generate_lods(&mesh, &mesh_lod_settings);
+        for (index, level) in mesh_lod_settings.levels.iter().enumerate().take(lods.len()) {
+            mesh.lods.push(MeshLod {
+                resource: lod_path(path.as_ref(), index + 1),
+                screen_size: level.screen_size,
+            });
+        }
+        let lod_paths: Vec<PathBuf> = mesh.lods.iter().map(|lod| lod.resource.clone()).collect();
+
+        //The budget is checked for the mesh and its levels of detail at once, nothing is registered if it is exceeded.
+        let size = mesh.memory_size() + lods.iter().map(MemorySize::memory_size).sum::<usize>();
+        self.reserve_memory(scope, ResourceCategory::Mesh, size)?;
+        let mut registered = Vec::with_capacity(lods.len());
+        let mut result = Ok(());
+        for (lod, resource_path) in lods.into_iter().zip(lod_paths.into_iter()) {
+            match self.alloc_resource(|| lod) {
+                Ok(lod_data) => {
+                    self.registry(scope).borrow_mut().add_mesh(resource_path.as_path(), lod_data);
+                    registered.push(resource_path);
+                },
+                Err(error) => {
+                    result = Err(error);
+                    break;
+                },
+            }
+        }
+        if result.is_ok() {
+            //create mesh.
+            match self.alloc_resource(|| mesh) {
+                Ok(mesh_data) => {
+                    //Add in registry.
+                    self.registry(scope).borrow_mut().add_mesh(path.as_ref(), mesh_data);
+                    return Ok(());
+                },
+                Err(error) => result = Err(error),
+            }
+        }
+
+        //The levels of detail of a mesh which could not be loaded are not kept.
+        for resource_path in registered {
+            self.registry(scope).borrow_mut().remove_mesh(resource_path);
+        }
+        self.memory_budget.borrow_mut().release(scope, ResourceCategory::Mesh, size);
+        result
+    }
+
+    //Reads the native resource cooked by the asset pipeline, refusing stale data, and records its dependencies in the registry of the scope.
+    fn read_native<P, R>(&self, path: P, reader: &mut R, resource_type: ResourceType, payload_version: u32, scope: ResourceScope) -> ResourceManagerResult<NativeResource> where
+        P: AsRef<Path>,
+        R: ResourceRead,
+    {
+        let native_resource = NativeResource::read_expected(reader, resource_type, payload_version)?;
+        for dependency in native_resource.dependencies.iter() {
+            self.registry(scope).borrow_mut().add_dependency(path.as_ref(), dependency.as_path());
+        }
+        Ok(native_resource)
+    }
+
+    fn load_native_texture<P, R>(&'a self, path: P, reader: &mut R, scope: ResourceScope) -> ResourceManagerResult<()> where
+        P: AsRef<Path> + Into<PathBuf>,
+        R: ResourceRead,
+    {
+        debug!("Loading native texture data with path {} in resource manager.", path.as_ref().display());
+        let native_resource = self.read_native(path.as_ref(), reader, ResourceType::Tga, NATIVE_TEXTURE_VERSION, scope)?;
+        //create tga.
+        let tga = read_texture(&mut native_resource.payload.as_slice())?;
+        let tga_data = self.alloc_budgeted(scope, ResourceCategory::Texture, tga)?;
+        //Add in registry.
+        self.registry(scope).borrow_mut().add_tga(path.as_ref(), tga_data);
+        Ok(())
+    }
+
+    fn load_native_mesh<P, R>(&'a self, path: P, reader: &mut R, scope: ResourceScope) -> ResourceManagerResult<()> where
+        P: AsRef<Path> + Into<PathBuf>,
+        R: ResourceRead,
+    {
+        debug!("Loading native mesh data with path {} in resource manager.", path.as_ref().display());
+        let native_resource = self.read_native(path.as_ref(), reader, ResourceType::Mesh, NATIVE_MESH_VERSION, scope)?;
+        //The mesh has been processed by the asset pipeline, only its levels of detail are generated.
+        let mesh = read_mesh(&mut native_resource.payload.as_slice())?;
+        self.load_mesh(path, mesh, scope)
+    }
+
+    fn load_native_audio<P, R>(&'a self, path: P, reader: &mut R, scope: ResourceScope) -> ResourceManagerResult<()> where
+        P: AsRef<Path> + Into<PathBuf>,
+        R: ResourceRead,
+    {
+        debug!("Loading native audio data with path {} in resource manager.", path.as_ref().display());
+        let native_resource = self.read_native(path.as_ref(), reader, ResourceType::Ogg, NATIVE_AUDIO_VERSION, scope)?;
+        //create ogg.
+        let ogg = match read_audio(&mut native_resource.payload.as_slice())? {
+            NativeAudio::Vorbis { frames, stream, .. } => OggResource::new(OggStreamReader::new(ResourceReader::from(Cursor::new(stream)))?, frames),
+            NativeAudio::Pcm { clip, loop_points, comments } => OggResource::from_pcm(clip, comments, loop_points),
+        };
+        let ogg_data = self.alloc_budgeted(scope, ResourceCategory::Audio, ogg)?;
         //Add in registry
-        self.level_resource_registry.borrow_mut().add_ogg(path.as_ref(), ogg_data);
+        self.registry(scope).borrow_mut().add_ogg(path.as_ref(), ogg_data);
         Ok(())
     }
 
+    fn load_texture_dependency<P>(&'a self, path: P, filesystem: &Filesystem, scratch: &ScratchContext, scope: ResourceScope) -> ResourceManagerResult<()> where
+        P: AsRef<Path> + Into<PathBuf>,
+    {
+        //A texture of the heap region is not copied in the resource stack.
+        if self.registry(scope).borrow().has_tga(path.as_ref()) || self.has_heap_tga(path.as_ref()) {
+            return Ok(());
+        }
+
+        match path.as_ref().extension().and_then(|extension| extension.to_str()) {
+            Some("tga") => {
+                self.load_meta(path.as_ref(), filesystem, scope)?;
+                let mut reader = self.open_resource(path.as_ref(), filesystem)?;
+                self.load_tga(path.as_ref(), &mut reader, scratch, scope)?;
+                self.emit(ResourceEvent::new(ResourceEventKind::Loaded, path.as_ref(), Some(ResourceType::Tga)));
+                Ok(())
+            },
+            _ => {
+                warn!("The texture at path {} cannot be loaded by the engine, it has only been recorded as a dependency.", path.as_ref().display());
+                Ok(())
+            },
+        }
+    }
+
     fn load_global_resources(&self) {
         unimplemented!()
     }
 
-    fn load_global_resource<P>(&self, path: P, filesystem: &Filesystem) where
+    /// Loads a resource kept until the game is closed, in the global registry and the global budgets.
+    ///
+    /// The global resources live below the resources of the level in the resource stack, they must be loaded before them.
+    pub fn load_global_resource<P>(&'a self, path: P, filesystem: &Filesystem) -> ResourceManagerResult<()> where
         P: AsRef<Path> + Into<PathBuf>,
     {
-        unimplemented!()
+        debug!("Loading the global resource with path {}.", path.as_ref().display());
+        if !self.level_resource_registry.borrow().is_empty() {
+            error!("A global resource is loaded after the resources of the level.");
+            return Err(ResourceManagerError::ResourceError(format!("The global resource at path {} must be loaded before the resources of the level !", path.as_ref().display())));
+        }
+
+        let result = self.scratch_context().and_then(|scratch| self.load_resource(path.as_ref(), filesystem, &scratch, ResourceScope::Global));
+        //Unloading the level rolls the resource stack back to the end of the global resources.
+        self.marker_global_resource.set(self.double_ended_allocator.0.marker());
+        self.marker_global_resource_copy.set(self.double_ended_allocator.0.marker_copy());
+        self.emit_result(path.as_ref(), ResourceEventKind::Loaded, result)
     }
 
     fn clear(&self) {
         debug!("unloading global resources from the resource manager.");
         //The game has been closed if the global resources must be unloaded. Clear everything.
+        self.emit_unloaded(&self.level_resource_registry.borrow(), ResourceScope::Level);
+        self.emit_unloaded(&self.global_resource_registry.borrow(), ResourceScope::Global);
         self.level_resource_registry.borrow_mut().clear();
         self.global_resource_registry.borrow_mut().clear();
+        self.material_instances.borrow_mut().clear();
+        self.sound_cues.borrow_mut().clear();
+        self.unload_heap_resources();
+        self.hot_reload_watcher.borrow_mut().clear();
+        self.metas.borrow_mut().clear();
         self.double_ended_allocator.0.reset();
         self.double_ended_allocator.0.reset_copy();
         self.double_ended_allocator.1.reset();
         self.double_ended_allocator.1.reset_copy();
+        self.memory_budget.borrow_mut().release_all();
     }
 
     fn unload_level_resources(&self)
     {
         debug!("Unloading level resources from the resource manager.");
+        self.emit_unloaded(&self.level_resource_registry.borrow(), ResourceScope::Level);
         self.level_resource_registry.borrow_mut().clear();
+        self.unload_heap_resources();
+        //The metadata and the watched source files of the global resources outlive the level.
+        let global_resource_registry = self.global_resource_registry.borrow();
+        self.hot_reload_watcher.borrow_mut().retain(|path| global_resource_registry.contains(path));
+        self.metas.borrow_mut().retain(|path, _| global_resource_registry.contains(path));
+        self.memory_budget.borrow_mut().release_scope(ResourceScope::Level);
         self.double_ended_allocator.0.reset_to_marker(self.marker_global_resources());
         self.double_ended_allocator.0.reset_to_marker_copy(self.marker_global_resources_copy());
     }
 
-    //TODO: needed ?
-    fn unload_temporary_data(&self) {
-        debug!("Unloading temporary data.");
-        self.double_ended_allocator.1.reset();
-        self.double_ended_allocator.1.reset_copy();
+    //Loads a resource in the registry of its scope, with the loader of its type.
+    fn load_resource<P: AsRef<Path>>(&'a self, path: P, filesystem: &Filesystem, scratch: &ScratchContext, scope: ResourceScope) -> ResourceManagerResult<()> {
+        let path = path.as_ref();
+        self.load_meta(path, filesystem, scope)?;
+        let mut reader = self.open_resource(path, filesystem)?;
+
+        match path.extension() {
+            Some(osstr_ext) => {
+                match osstr_ext.to_str() {
+                    Some(str_ext) => {
+                        match str_ext {
+                            "ogg" => {
+                                self.load_ogg(path, reader, scope)?;
+                            },
+                            "tga" => {
+                                self.load_tga(path, &mut reader, scratch, scope)?;
+                            },
+                            "gltf" => {
+                                self.load_gltf(path, &mut reader, filesystem, scratch, scope)?;
+                            },
+                            "obj" => {
+                                self.load_obj(path, &mut reader, filesystem, scratch, scope)?;
+                            },
+                            "tex" => {
+                                self.load_native_texture(path, &mut reader, scope)?;
+                            },
+                            "mesh" => {
+                                self.load_native_mesh(path, &mut reader, scope)?;
+                            },
+                            "audio" => {
+                                self.load_native_audio(path, &mut reader, scope)?;
+                            },
+                            _ => {
+                                return Err(ResourceManagerError::ResourceError(format!("The data at path {} cannot be loaded by the engine !", path.display())));
+                            }
+                        }
+                    },
+                    None => {
+                        return Err(ResourceManagerError::ResourceError(format!("The path {} is not valid unicode !", path.display())));
+                    }
+                }
+            },
+            None => {
+                return Err(ResourceManagerError::ResourceError(format!("The path {} is not valid !", path.display())));
+            }
+        }
+        Ok(())
     }
 
     pub fn load_level_resources<L>(&'a self, level_description: L, filesystem: &Filesystem) -> ResourceManagerResult<()> where
@@ -185,42 +1454,18 @@ impl<'a> ResourceManager<'a> {
         */
 
         let needed_resources = self.read_needed_resources(level_description.as_ref());
+        //The intermediate buffers of the loaders live in the temporary data stack, until the whole level is loaded or has failed.
+        let scratch = self.scratch_context()?;
         self.unload_level_resources();
-        self.unload_temporary_data();
         for resource_str in needed_resources {
-            let path: &Path = resource_str.as_ref();
-            let mut reader = filesystem.open(path)?;
-
-            match path.extension() {
-                Some(osstr_ext) => {
-                    match osstr_ext.to_str() {
-                        Some(str_ext) => {
-                            match str_ext {
-                                "ogg" => {
-                                    self.load_ogg(path, reader)?;
-                                },
-                                "tga" => {
-                                    self.load_tga(path, &mut reader)?;
-                                },
-                                "gltf" => {
-                                    self.load_gltf(path, &mut reader)?;
-                                },
-                                _ => {
-                                    return Err(ResourceManagerError::ResourceError(format!("The data at path {} cannot be loaded by the engine !", path.display())));
-                                }
-                            }
-                        },
-                        None => {
-                            return Err(ResourceManagerError::ResourceError(format!("The path {} is not valid unicode !", path.display())));
-                        }
-                    }
-                },
-                None => {
-                    return Err(ResourceManagerError::ResourceError(format!("The path {} is not valid !", path.display())));
-                }
-            }
+            //Levels reference their resources by path, or by GUID to survive the reorganisation of the files.
+            let path = self.resource_path(resource_str.as_str())?;
+            let result = self.load_resource(path.as_path(), filesystem, &scratch, ResourceScope::Level);
+            self.emit_result(path.as_path(), ResourceEventKind::Loaded, result)?;
         }
 
+        self.update_allocator_stats();
+        scratch.release();
         Ok(())
     }
 }
@@ -229,6 +1474,17 @@ impl<'a> ResourceManager<'a> {
 mod resource_manager_test {
     use super::*;
     use maskerad_filesystem::game_directories::RootDir;
+    use std::io::{Read, Write};
+    use std::rc::Rc;
+    use imagefmt::Image;
+    use resources::heap_region::HEAP_ALIGNMENT;
+    use resources::pak::{PakWriter, PakCompression, PAK_DEFAULT_ALIGNMENT};
+    use resources::vfs::{VfsSource, VfsMount, MemoryMount, VFS_SCHEME};
+    use pipeline::asset_pipeline::AssetPipeline;
+    use resources::native_audio::write_audio;
+    use resources::ogg_registry::LoopPoints;
+    use test_project::TestProject;
+    use resources::audio_conversion::PcmClip;
     #[test]
     fn resource_manager_creation() {
         let resource_manager = ResourceManager::with_capacity(100, 100);
@@ -244,28 +1500,42 @@ mod resource_manager_test {
     fn resource_manager_load_unload_get_resource() {
         //Filesystem, StackAlloc, ResourceManager.
         let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
-        let resource_man = ResourceManager::with_capacity(10000000, 10000000); //10 mb
+        let mut resource_man = ResourceManager::with_capacity(10000000, 10000000); //10 mb
+        //The resources of the levels have paths relative to the root of the crate.
+        let working_directory = fs.construct_path_from_root(RootDir::WorkingDirectory, "").expect("Could not create the working directory path.");
+        resource_man.vfs_mut().mount("working_directory", VFS_SCHEME, VfsSource::Directory(working_directory), 0);
 
         //Load tga
         let tga_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "tga_resource/Untitled.tga").expect("Could not create tga path.");
         let mut tga_reader = fs.open(tga_path.as_path()).expect("Could not creater tga reader.");
-        resource_man.load_tga(tga_path.as_path(), &mut tga_reader).expect("Could not load tga image.");
+        resource_man.load_tga(tga_path.as_path(), &mut tga_reader, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level).expect("Could not load tga image.");
         assert!(!resource_man.level_resource_registry.borrow().is_tga_empty());
         assert!(resource_man.level_resource_registry().get_tga(tga_path.as_path()).is_ok());
 
         //Load gltf
         let gltf_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "gltf_resource/untitled.gltf").expect("Could not create gltf path.");
         let mut gltf_reader = fs.open(gltf_path.as_path()).expect("Could not create gltf reader.");
-        resource_man.load_gltf(gltf_path.as_path(), &mut gltf_reader).expect("Could not load gltf data.");
+        resource_man.set_mesh_processing_of(gltf_path.as_path(), MeshProcessing { compute_bounds: true, ..MeshProcessing::none() });
+        resource_man.load_gltf(gltf_path.as_path(), &mut gltf_reader, &fs, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level).expect("Could not load gltf data.");
         assert!(!resource_man.level_resource_registry.borrow().is_gltf_empty());
         assert!(resource_man.level_resource_registry().get_gltf(gltf_path.as_path()).is_ok());
+        //The glTF file gives the same mesh resource as an OBJ file.
+        assert_eq!(resource_man.level_resource_registry().get_mesh(gltf_path.as_path()).expect("Could not find the gltf mesh.").triangle_count(), 12);
+        assert!(resource_man.level_resource_registry().get_mesh(gltf_path.as_path()).expect("Could not find the gltf mesh.").bounds.is_some());
 
         //Load ogg
         let ogg_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "ogg_resource/untitled.ogg").expect("Could not create ogg path.");
         let mut ogg_reader = fs.open(ogg_path.as_path()).expect("Could not create ogg reader.");
-        resource_man.load_ogg(ogg_path.as_path(), ogg_reader).expect("Could not load ogg data.");
+        resource_man.load_ogg(ogg_path.as_path(), ogg_reader, ResourceScope::Level).expect("Could not load ogg data.");
         assert!(!resource_man.level_resource_registry.borrow().is_ogg_empty());
         assert!(resource_man.level_resource_registry().get_ogg(ogg_path.as_path()).is_ok());
+        {
+            let registry = resource_man.level_resource_registry();
+            let ogg_metadata = registry.get_ogg(ogg_path.as_path()).unwrap().metadata();
+            assert!(ogg_metadata.sample_rate() > 0);
+            assert!(ogg_metadata.channels() > 0);
+            assert!(ogg_metadata.total_samples().is_some());
+        }
         //unload
         resource_man.clear();
         assert!(resource_man.level_resource_registry.borrow().is_tga_empty());
@@ -290,6 +1560,483 @@ mod resource_manager_test {
         resource_man.clear();
     }
 
+    #[test]
+    fn resource_manager_load_level_resources_by_guid() {
+        let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
+        let mut resource_man = ResourceManager::with_capacity(10000000, 10000000);
+        let working_directory = fs.construct_path_from_root(RootDir::WorkingDirectory, "").expect("Could not create the working directory path.");
+        let guid_database = GuidDatabase::scan_directory(working_directory.join("gltf_resource"), "res://gltf_resource").expect("Could not scan the gltf directory.");
+        let level_path = working_directory.join("toml_resource/level3.toml");
+        resource_man.vfs_mut().mount("working_directory", VFS_SCHEME, VfsSource::Directory(working_directory), 0);
+        resource_man.set_guid_database(guid_database);
+
+        let gltf_guid = ResourceGuid::parse("5b0c7e2a-9d41-4c8e-a3f6-2e9b71d04c55").expect("Could not parse the GUID.");
+        assert_eq!(resource_man.guid_of("res://gltf_resource/untitled.gltf"), Some(gltf_guid));
+        assert_eq!(resource_man.resource_path(gltf_guid.reference().as_str()).expect("Could not resolve the GUID."), PathBuf::from("res://gltf_resource/untitled.gltf"));
+        assert!(resource_man.resource_path(ResourceGuid::generate().reference().as_str()).is_err());
+
+        let mut level_reader = fs.open(level_path.as_path()).expect("Could not create level reader.");
+        let level_desc = LevelDescription::load_from_toml(&mut level_reader).expect("Could not create level description.");
+        resource_man.load_level_resources(&level_desc, &fs).expect("Could not load all level resources");
+        let gltf_path = resource_man.resource_path(gltf_guid.reference().as_str()).expect("Could not resolve the GUID.");
+        assert!(resource_man.level_resource_registry().has_gltf(gltf_path));
+        resource_man.clear();
+    }
+
+    #[test]
+    fn resource_manager_load_obj_with_materials() {
+        let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
+        let resource_man = ResourceManager::with_capacity(10000000, 10000000);
+        let obj_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "obj_resource/untitled.obj").expect("Could not create obj path.");
+        let mtl_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "obj_resource/untitled.mtl").expect("Could not create mtl path.");
+        let tga_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "obj_resource/../tga_resource/Untitled.tga").expect("Could not create tga path.");
+        let mut obj_reader = fs.open(obj_path.as_path()).expect("Could not create obj reader.");
+        resource_man.load_obj(obj_path.as_path(), &mut obj_reader, &fs, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level).expect("Could not load obj data.");
+
+        let registry = resource_man.level_resource_registry();
+        let mesh = registry.get_mesh(obj_path.as_path()).expect("Could not find the mesh.");
+        assert_eq!(mesh.triangle_count(), 2);
+        let material = material_path(mtl_path.as_path(), "Material");
+        assert_eq!(mesh.primitives[0].material, Some(material.clone()));
+        assert!(registry.get_material(material.as_path()).is_ok());
+        assert!(registry.has_tga(tga_path.as_path()));
+        assert_eq!(registry.dependencies_of(material.as_path()), &[tga_path.clone()]);
+        assert_eq!(registry.dependencies_of(obj_path.as_path()), &[material]);
+    }
+
+    #[test]
+    fn resource_manager_load_resources_from_virtual_paths() {
+        let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
+        let mut resource_man = ResourceManager::with_capacity(10000000, 10000000);
+        let working_directory = fs.construct_path_from_root(RootDir::WorkingDirectory, "").expect("Could not create the working directory path.");
+        resource_man.vfs_mut().mount("base", VFS_SCHEME, VfsSource::Directory(working_directory), 0);
+        //A patch overrides the material library of the mesh, with a material without texture.
+        let mut patch = MemoryMount::new();
+        patch.insert("untitled.mtl", b"newmtl Material\nKd 1.0 0.0 0.0\n".to_vec());
+        resource_man.vfs_mut().mount("patch", "res://obj_resource", VfsSource::Memory(patch), 10);
+
+        assert_eq!(resource_man.vfs().resolve("res://obj_resource/untitled.obj").map(VfsMount::name), Some("base"));
+        assert_eq!(resource_man.vfs().resolve("res://obj_resource/untitled.mtl").map(VfsMount::name), Some("patch"));
+        let mut obj_reader = resource_man.open_resource("res://obj_resource/untitled.obj", &fs).expect("Could not create obj reader.");
+        resource_man.load_obj("res://obj_resource/untitled.obj", &mut obj_reader, &fs, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level).expect("Could not load obj data.");
+
+        let registry = resource_man.level_resource_registry();
+        assert!(registry.has_mesh("res://obj_resource/untitled.obj"));
+        let material = material_path("res://obj_resource/untitled.mtl", "Material");
+        assert!(registry.get_material(material.as_path()).is_ok());
+        assert!(registry.is_tga_empty());
+        assert!(resource_man.open_resource("res://obj_resource/missing.obj", &fs).is_err());
+    }
+
+    #[test]
+    fn resource_manager_hot_reload_changed_resources() {
+        let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
+        //The artist works on copies of the resources.
+        let project = TestProject::new("resource_manager_hot_reload", &["obj_resource/untitled.obj", "obj_resource/untitled.mtl", "tga_resource/Untitled.tga"]);
+
+        let mut resource_man = ResourceManager::with_capacity(10000000, 10000000);
+        resource_man.set_heap_capacity(10000000);
+        resource_man.vfs_mut().mount("work", VFS_SCHEME, VfsSource::Directory(project.root().to_path_buf()), 0);
+        resource_man.set_hot_reload(true);
+        let texture = PathBuf::from("res://obj_resource/../tga_resource/Untitled.tga");
+        let material = material_path("res://obj_resource/untitled.mtl", "Material");
+        resource_man.load_tga_in_heap(texture.as_path(), &fs).expect("Could not load the tga in the heap region.");
+        resource_man.load_resource("res://obj_resource/untitled.obj", &fs, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level).expect("Could not load obj data.");
+        //The texture of the heap region is not copied in the resource stack, and the resources of the resource stack are not watched.
+        assert!(!resource_man.level_resource_registry().has_tga(texture.as_path()));
+        assert!(resource_man.hot_reload_watcher().is_watched(texture.as_path()));
+        assert!(!resource_man.hot_reload_watcher().is_watched("res://obj_resource/untitled.obj"));
+        assert!(resource_man.poll_hot_reload(&fs).is_empty());
+
+        //The texture is saved again, trailing data is ignored by the tga decoder.
+        let old_handle = resource_man.get_heap_tga(texture.as_path()).expect("Could not find the texture.").handle;
+        let usage = resource_man.memory_budget().usage(ResourceScope::Level, ResourceCategory::Texture);
+        ::std::fs::OpenOptions::new().append(true).open(project.path("tga_resource/Untitled.tga")).expect("Could not open the tga file.").write_all(&[0; 16]).expect("Could not write the tga file.");
+        let events = resource_man.poll_hot_reload(&fs);
+        assert_eq!(events, vec![ReloadEvent { path: texture.clone(), dependents: vec![material] }]);
+        assert!(resource_man.get_heap_tga(texture.as_path()).expect("Could not find the texture.").handle != old_handle);
+        //The old pixels are freed, and their budget released.
+        assert_eq!(resource_man.heap_region().block_count(), 1);
+        assert_eq!(resource_man.memory_budget().usage(ResourceScope::Level, ResourceCategory::Texture), usage);
+        assert!(resource_man.poll_hot_reload(&fs).is_empty());
+
+        //A broken file keeps the old data.
+        ::std::fs::File::create(project.path("tga_resource/Untitled.tga")).expect("Could not create the tga file.").write_all(b"not a tga").expect("Could not write the tga file.");
+        assert!(resource_man.poll_hot_reload(&fs).is_empty());
+        assert!(resource_man.has_heap_tga(texture.as_path()));
+        assert!(resource_man.reload_resource("res://obj_resource/untitled.obj", &fs).is_err());
+        resource_man.clear();
+        assert!(resource_man.hot_reload_watcher().is_empty());
+    }
+
+    #[test]
+    fn resource_manager_pooled_resources_unloaded_individually() {
+        let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
+        let resource_man = ResourceManager::with_pool_capacity(10000000, 10000000, 2);
+        let obj_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "obj_resource/untitled.obj").expect("Could not create obj path.");
+        let mtl_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "obj_resource/untitled.mtl").expect("Could not create mtl path.");
+        let material = material_path(mtl_path.as_path(), "Material");
+
+        //The material is not loaded yet.
+        assert!(resource_man.add_material_instance(MaterialInstanceResource::new(material.as_path())).is_err());
+        let mut obj_reader = fs.open(obj_path.as_path()).expect("Could not create obj reader.");
+        resource_man.load_obj(obj_path.as_path(), &mut obj_reader, &fs, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level).expect("Could not load obj data.");
+
+        let red = resource_man.add_material_instance(MaterialInstanceResource { diffuse: Some([1.0, 0.0, 0.0]), ..MaterialInstanceResource::new(material.as_path()) }).expect("Could not add a material instance.");
+        let transparent = resource_man.add_material_instance(MaterialInstanceResource { opacity: Some(0.5), ..MaterialInstanceResource::new(material.as_path()) }).expect("Could not add a material instance.");
+        assert!(resource_man.add_material_instance(MaterialInstanceResource::new(material.as_path())).is_err());
+
+        assert!(resource_man.remove_material_instance(red).is_some());
+        assert!(resource_man.material_instance(red).is_none());
+        assert_eq!(resource_man.material_instance(transparent).expect("Could not find the material instance.").opacity, Some(0.5));
+        let green = resource_man.add_material_instance(MaterialInstanceResource { diffuse: Some([0.0, 1.0, 0.0]), ..MaterialInstanceResource::new(material.as_path()) }).expect("Could not reuse the slot of a material instance.");
+        resource_man.material_instance_mut(green).expect("Could not find the material instance.").opacity = Some(0.25);
+        assert_eq!(resource_man.material_instance(green).expect("Could not find the material instance.").opacity, Some(0.25));
+        assert!(resource_man.material_instance(red).is_none());
+        assert_eq!(resource_man.material_instances().len(), 2);
+
+        //Sound cues need their ogg file.
+        assert!(resource_man.add_sound_cue(SoundCueResource::new("not_loaded.ogg")).is_err());
+        assert!(resource_man.sound_cues().is_empty());
+
+        resource_man.clear();
+        assert!(resource_man.material_instances().is_empty());
+    }
+
+    #[test]
+    fn resource_manager_unload_heap_resources_individually() {
+        let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
+        let mut resource_man = ResourceManager::with_capacity(10000000, 10000000);
+        resource_man.set_heap_capacity(10000000);
+        let tga_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "tga_resource/Untitled.tga").expect("Could not create tga path.");
+        let stack_marker = resource_man.double_ended_allocator.0.marker();
+
+        resource_man.load_tga_in_heap(tga_path.as_path(), &fs).expect("Could not load the tga in the heap region.");
+        //Nothing is allocated in the resource stack.
+        assert_eq!(resource_man.double_ended_allocator.0.marker(), stack_marker);
+        let tga = resource_man.get_heap_tga(tga_path.as_path()).expect("Could not find the tga.");
+        let pixel_count = resource_man.heap_tga_pixels(tga_path.as_path()).expect("Could not borrow the pixels.").len();
+        assert!(pixel_count >= tga.width * tga.height);
+        assert_eq!(resource_man.memory_budget().usage(ResourceScope::Level, ResourceCategory::Texture), pixel_count);
+
+        resource_man.unload_heap_tga(tga_path.as_path()).expect("Could not unload the tga.");
+        assert!(!resource_man.has_heap_tga(tga_path.as_path()));
+        assert!(resource_man.heap_region().is_empty());
+        assert_eq!(resource_man.memory_budget().usage(ResourceScope::Level, ResourceCategory::Texture), 0);
+        assert!(resource_man.unload_heap_tga(tga_path.as_path()).is_err());
+        assert_eq!(resource_man.defragment_heap(1024), 0);
+    }
+
+    #[test]
+    fn resource_manager_reload_heap_resources() {
+        let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
+        let project = TestProject::empty("resource_manager_heap_reload");
+        let work_path = project.copy("tga_resource/Untitled.tga", "Untitled.tga");
+
+        let mut resource_man = ResourceManager::with_capacity(10000000, 10000000);
+        resource_man.set_heap_capacity(10000000);
+        resource_man.load_tga_in_heap(work_path.as_path(), &fs).expect("Could not load the tga in the heap region.");
+        resource_man.acquire_heap_tga(work_path.as_path()).expect("Could not acquire the tga.");
+        let pixel_count = resource_man.heap_tga_pixels(work_path.as_path()).expect("Could not borrow the pixels.").len();
+
+        //The new pixels replace the old ones, the references survive the reload.
+        resource_man.reload_resource(work_path.as_path(), &fs).expect("Could not reload the tga.");
+        assert_eq!(resource_man.resource_cache().references(work_path.as_path()), Some(1));
+        assert_eq!(resource_man.memory_budget().usage(ResourceScope::Level, ResourceCategory::Texture), pixel_count);
+        assert_eq!(resource_man.heap_region().block_count(), 1);
+
+        //A broken file keeps the old pixels.
+        ::std::fs::File::create(work_path.as_path()).expect("Could not create the tga file.").write_all(b"not a tga").expect("Could not write the tga file.");
+        assert!(resource_man.reload_resource(work_path.as_path(), &fs).is_err());
+        assert_eq!(resource_man.heap_tga_pixels(work_path.as_path()).expect("Could not borrow the pixels.").len(), pixel_count);
+        assert_eq!(resource_man.resource_cache().references(work_path.as_path()), Some(1));
+        assert_eq!(resource_man.memory_budget().usage(ResourceScope::Level, ResourceCategory::Texture), pixel_count);
+    }
+
+    #[test]
+    fn resource_manager_emit_resource_events() {
+        let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
+        let mut resource_man = ResourceManager::with_capacity(10000000, 10000000);
+        resource_man.set_heap_capacity(10000000);
+        let working_directory = fs.construct_path_from_root(RootDir::WorkingDirectory, "").expect("Could not create the working directory path.");
+        resource_man.vfs_mut().mount("working_directory", VFS_SCHEME, VfsSource::Directory(working_directory), 0);
+        let textures = Rc::new(RefCell::new(Vec::new()));
+        let textures_clone = textures.clone();
+        resource_man.subscribe(ResourceEventFilter::all().with_type(ResourceType::Tga), move |event| {
+            textures_clone.borrow_mut().push(event.kind.clone());
+        });
+
+        resource_man.load_tga_in_heap("res://tga_resource/Untitled.tga", &fs).expect("Could not load the tga in the heap region.");
+        //A resource already loaded is not loaded again.
+        resource_man.load_tga_in_heap("res://tga_resource/Untitled.tga", &fs).expect("Could not load the tga in the heap region.");
+        assert!(resource_man.load_tga_in_heap("res://tga_resource/missing.tga", &fs).is_err());
+        resource_man.unload_heap_tga("res://tga_resource/Untitled.tga").expect("Could not unload the tga.");
+        assert_eq!(textures.borrow().len(), 3);
+
+        let events = resource_man.drain_events();
+        assert_eq!(events[0], ResourceEvent::new(ResourceEventKind::Loaded, "res://tga_resource/Untitled.tga", Some(ResourceType::Tga)));
+        match events[1].kind {
+            ResourceEventKind::Failed(_) => assert_eq!(events[1].path, PathBuf::from("res://tga_resource/missing.tga")),
+            ref kind => panic!("Unexpected event {:?}", kind),
+        }
+        assert_eq!(events[2], ResourceEvent::new(ResourceEventKind::Unloaded, "res://tga_resource/Untitled.tga", Some(ResourceType::Tga)));
+        assert!(resource_man.drain_events().is_empty());
+
+        //The events of other consumers stay queued.
+        let mut gltf_reader = resource_man.open_resource("res://gltf_resource/untitled.gltf", &fs).expect("Could not create gltf reader.");
+        resource_man.load_gltf("res://gltf_resource/untitled.gltf", &mut gltf_reader, &fs, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level).expect("Could not load gltf data.");
+        resource_man.unload_level_resources();
+        assert!(resource_man.drain_events_matching(&ResourceEventFilter::all().with_path_prefix("res://tga_resource")).is_empty());
+        assert_eq!(resource_man.drain_events(), vec![
+            ResourceEvent::new(ResourceEventKind::Unloaded, "res://gltf_resource/untitled.gltf", Some(ResourceType::Gltf)),
+            ResourceEvent::new(ResourceEventKind::Unloaded, "res://gltf_resource/untitled.gltf", Some(ResourceType::Mesh)),
+        ]);
+    }
+
+    #[test]
+    fn resource_manager_evict_least_recently_used_in_cache_mode() {
+        let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
+        let tga_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "tga_resource/Untitled.tga").expect("Could not create tga path.");
+        let other_tga_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "obj_resource/../tga_resource/Untitled.tga").expect("Could not create tga path.");
+        let tga_size = {
+            let resource_man = ResourceManager::with_capacity(10000000, 10000000);
+            resource_man.load_tga(tga_path.as_path(), &mut fs.open(tga_path.as_path()).expect("Could not create tga reader."), &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level).expect("Could not load tga data.");
+            let registry = resource_man.level_resource_registry();
+            let tga: &TgaResource = registry.get_tga(tga_path.as_path()).expect("Could not find the tga.");
+            let image: &Image<u8> = tga.as_ref();
+            image.buf.len()
+        };
+
+        //Room for one image in the heap region.
+        let mut resource_man = ResourceManager::with_capacity(10000000, 10000000);
+        resource_man.set_heap_capacity(tga_size + tga_size / 2 + HEAP_ALIGNMENT);
+        resource_man.set_cache_mode(Some(CacheWatermarks::new(tga_size * 2, tga_size)));
+
+        resource_man.load_tga_in_heap(tga_path.as_path(), &fs).expect("Could not load the tga in the heap region.");
+        resource_man.acquire_heap_tga(tga_path.as_path()).expect("Could not acquire the tga.");
+        //The first image is referenced, it cannot be evicted.
+        assert!(resource_man.load_tga_in_heap(other_tga_path.as_path(), &fs).is_err());
+
+        resource_man.release_heap_tga(tga_path.as_path()).expect("Could not release the tga.");
+        //Unreferenced, but resident until memory pressure.
+        assert!(resource_man.has_heap_tga(tga_path.as_path()));
+        resource_man.load_tga_in_heap(other_tga_path.as_path(), &fs).expect("Could not load the tga in the heap region.");
+        assert!(!resource_man.has_heap_tga(tga_path.as_path()));
+        assert!(resource_man.has_heap_tga(other_tga_path.as_path()));
+        assert_eq!(resource_man.resource_cache().resident_bytes(), tga_size);
+    }
+
+    #[test]
+    fn resource_manager_post_process_mesh_on_load() {
+        let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
+        let mut resource_man = ResourceManager::with_capacity(10000000, 10000000);
+        let obj_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "obj_resource/untitled.obj").expect("Could not create obj path.");
+        resource_man.set_mesh_processing_of(obj_path.as_path(), MeshProcessing::all());
+        assert_eq!(resource_man.mesh_processing(), MeshProcessing::none());
+
+        let mut obj_reader = fs.open(obj_path.as_path()).expect("Could not create obj reader.");
+        resource_man.load_obj(obj_path.as_path(), &mut obj_reader, &fs, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level).expect("Could not load obj data.");
+        let registry = resource_man.level_resource_registry();
+        let mesh = registry.get_mesh(obj_path.as_path()).expect("Could not find the mesh.");
+        assert_eq!(mesh.tangents.len(), mesh.vertex_count());
+        let bounds = mesh.bounds.expect("The bounds of the mesh have not been computed.");
+        assert_eq!(bounds.min, [-1.0, 0.0, -1.0]);
+        assert_eq!(bounds.max, [1.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn resource_manager_generate_mesh_lods_on_load() {
+        let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
+        let obj_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "obj_resource/untitled.obj").expect("Could not create obj path.");
+        let mut resource_man = ResourceManager::with_capacity(10000000, 10000000);
+        let working_directory = fs.construct_path_from_root(RootDir::WorkingDirectory, "").expect("Could not create the working directory path.");
+        resource_man.vfs_mut().mount("base", VFS_SCHEME, VfsSource::Directory(working_directory), 0);
+        //The levels of detail of the cube, with its hard edges, come from its meta file.
+        let mut patch = MemoryMount::new();
+        patch.insert("untitled.gltf.meta", b"[[lods]]\ntriangle_ratio = 0.5\nscreen_size = 0.3\n\n[[lods]]\ntriangle_ratio = 0.25\nscreen_size = 0.1\n".to_vec());
+        resource_man.vfs_mut().mount("patch", "res://gltf_resource", VfsSource::Memory(patch), 10);
+        assert!(resource_man.mesh_lod_settings().is_empty());
+
+        resource_man.load_resource("res://gltf_resource/untitled.gltf", &fs, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level).expect("Could not load the gltf file.");
+        assert_eq!(resource_man.mesh_lod_settings_of("res://gltf_resource/untitled.gltf"), MeshLodSettings::new(vec![LodLevel::new(0.5, 0.3), LodLevel::new(0.25, 0.1)]));
+        let registry = resource_man.level_resource_registry();
+        let mesh = registry.get_mesh("res://gltf_resource/untitled.gltf").expect("Could not find the mesh.");
+        assert_eq!(mesh.triangle_count(), 12);
+        assert_eq!(mesh.lods.len(), 2);
+        assert_eq!(mesh.lods[0].resource, lod_path("res://gltf_resource/untitled.gltf", 1));
+        assert_eq!(mesh.lods[0].screen_size, 0.3);
+        assert_eq!(mesh.lods[1].screen_size, 0.1);
+        let lod1 = registry.get_mesh(lod_path("res://gltf_resource/untitled.gltf", 1)).expect("Could not find the first level of detail.");
+        let lod2 = registry.get_mesh(lod_path("res://gltf_resource/untitled.gltf", 2)).expect("Could not find the second level of detail.");
+        assert!(lod1.triangle_count() < mesh.triangle_count());
+        assert!(lod2.triangle_count() <= lod1.triangle_count());
+
+        //A mesh exceeding its budget leaves none of its levels of detail behind.
+        let mut budget_man = ResourceManager::with_capacity(10000000, 10000000);
+        budget_man.set_mesh_lod_settings(MeshLodSettings::new(vec![LodLevel::new(0.5, 0.3)]));
+        budget_man.set_memory_budget(ResourceScope::Level, ResourceCategory::Mesh, Some(64));
+        let mut obj_reader = fs.open(obj_path.as_path()).expect("Could not create obj reader.");
+        assert!(budget_man.load_obj(obj_path.as_path(), &mut obj_reader, &fs, &budget_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level).is_err());
+        assert!(!budget_man.level_resource_registry().has_mesh(lod_path(obj_path.as_path(), 1)));
+        assert_eq!(budget_man.memory_budget().usage(ResourceScope::Level, ResourceCategory::Mesh), 0);
+        //Nor its materials.
+        assert!(budget_man.level_resource_registry().is_material_empty());
+        assert!(budget_man.level_resource_registry().dependencies_of(obj_path.as_path()).is_empty());
+        assert_eq!(budget_man.memory_budget().usage(ResourceScope::Level, ResourceCategory::Other), 0);
+    }
+
+    #[test]
+    fn resource_manager_enforce_memory_budgets() {
+        let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
+        let mut resource_man = ResourceManager::with_capacity(10000000, 10000000);
+        resource_man.set_memory_budget(ResourceScope::Level, ResourceCategory::Texture, Some(16));
+        let tga_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "tga_resource/Untitled.tga").expect("Could not create tga path.");
+        let mut tga_reader = fs.open(tga_path.as_path()).expect("Could not creater tga reader.");
+        match resource_man.load_tga(tga_path.as_path(), &mut tga_reader, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level) {
+            Err(ResourceManagerError::BudgetExceeded(_, category, requested, remaining)) => {
+                assert_eq!(category, ResourceCategory::Texture);
+                assert!(requested > 16);
+                assert_eq!(remaining, 16);
+            },
+            _ => panic!("The texture budget should have been exceeded."),
+        }
+        assert!(resource_man.level_resource_registry().is_tga_empty());
+        assert_eq!(resource_man.memory_budget().usage(ResourceScope::Level, ResourceCategory::Texture), 0);
+
+        let ogg_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "ogg_resource/untitled.ogg").expect("Could not create ogg path.");
+        let ogg_reader = fs.open(ogg_path.as_path()).expect("Could not create ogg reader.");
+        resource_man.load_ogg(ogg_path.as_path(), ogg_reader, ResourceScope::Level).expect("Could not load ogg data.");
+        assert!(resource_man.memory_budget().usage(ResourceScope::Level, ResourceCategory::Audio) > 0);
+        resource_man.unload_level_resources();
+        assert_eq!(resource_man.memory_budget().usage(ResourceScope::Level, ResourceCategory::Audio), 0);
+    }
+
+    #[test]
+    fn resource_manager_release_the_reservation_of_a_failed_allocation() {
+        let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
+        let resource_man = ResourceManager::with_capacity(64, 64);
+        let ogg_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "ogg_resource/untitled.ogg").expect("Could not create ogg path.");
+        let ogg_reader = fs.open(ogg_path.as_path()).expect("Could not create ogg reader.");
+        assert!(resource_man.load_ogg(ogg_path.as_path(), ogg_reader, ResourceScope::Level).is_err());
+        assert!(resource_man.level_resource_registry().is_ogg_empty());
+        assert_eq!(resource_man.memory_budget().usage(ResourceScope::Level, ResourceCategory::Audio), 0);
+    }
+
+    #[test]
+    fn resource_manager_load_global_resources() {
+        let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
+        let mut resource_man = ResourceManager::with_capacity(10000000, 10000000);
+        resource_man.set_memory_budget(ResourceScope::Global, ResourceCategory::Texture, Some(16));
+        let tga_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "tga_resource/Untitled.tga").expect("Could not create tga path.");
+        let ogg_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "ogg_resource/untitled.ogg").expect("Could not create ogg path.");
+
+        //The global budgets are enforced.
+        assert!(resource_man.load_global_resource(tga_path.as_path(), &fs).is_err());
+        assert!(resource_man.global_resource_registry().is_tga_empty());
+        assert_eq!(resource_man.memory_budget().usage(ResourceScope::Global, ResourceCategory::Texture), 0);
+
+        resource_man.load_global_resource(ogg_path.as_path(), &fs).expect("Could not load the global ogg data.");
+        assert!(resource_man.global_resource_registry().has_ogg(ogg_path.as_path()));
+        assert!(resource_man.level_resource_registry().is_ogg_empty());
+        assert!(resource_man.memory_budget().usage(ResourceScope::Global, ResourceCategory::Audio) > 0);
+        assert_eq!(resource_man.memory_budget().usage(ResourceScope::Level, ResourceCategory::Audio), 0);
+        assert!(resource_man.marker_global_resources() > 0);
+
+        //The global resources cannot be loaded above the resources of the level, nor reloaded.
+        resource_man.load_resource(tga_path.as_path(), &fs, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level).expect("Could not load tga image.");
+        assert!(resource_man.load_global_resource(ogg_path.as_path(), &fs).is_err());
+        assert!(resource_man.reload_resource(ogg_path.as_path(), &fs).is_err());
+
+        resource_man.unload_level_resources();
+        assert!(resource_man.level_resource_registry().is_tga_empty());
+        assert!(resource_man.global_resource_registry().has_ogg(ogg_path.as_path()));
+    }
+
+    #[test]
+    fn resource_manager_memory_report() {
+        let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
+        let mut resource_man = ResourceManager::with_capacity(10000000, 10000000);
+        resource_man.set_heap_capacity(10000000);
+        resource_man.set_memory_budget(ResourceScope::Level, ResourceCategory::Mesh, Some(64));
+        let tga_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "tga_resource/Untitled.tga").expect("Could not create tga path.");
+        let mut tga_reader = fs.open(tga_path.as_path()).expect("Could not creater tga reader.");
+        resource_man.load_tga(tga_path.as_path(), &mut tga_reader, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level).expect("Could not load tga image.");
+
+        let report = resource_man.memory_report();
+        assert_eq!(report.resources.len(), 1);
+        assert!(report.total_of_type(ResourceType::Tga) > 0);
+        assert_eq!(report.total_of_scope(ResourceScope::Level), report.total());
+        assert_eq!(report.total_of_scope(ResourceScope::Global), 0);
+        assert!(report.resource_stack.high_water_mark > 0);
+        assert!(report.to_csv().lines().count() == 2);
+        assert_eq!(report.heap_region.capacity, 10000000);
+        assert_eq!(report.fragmentation(), 0.0);
+
+        //The object of a load which has been rolled back is orphaned in the resource stack.
+        let orphaned_bytes = report.orphaned_stack_bytes();
+        let obj_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "obj_resource/untitled.obj").expect("Could not create obj path.");
+        let mut obj_reader = fs.open(obj_path.as_path()).expect("Could not create obj reader.");
+        assert!(resource_man.load_obj(obj_path.as_path(), &mut obj_reader, &fs, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level).is_err());
+        assert!(resource_man.memory_report().orphaned_stack_bytes() > orphaned_bytes);
+
+        resource_man.clear();
+        let report = resource_man.memory_report();
+        assert!(report.resources.is_empty());
+        assert_eq!(report.resource_stack.used, 0);
+        assert!(report.resource_stack.high_water_mark > 0);
+    }
+
+    #[test]
+    fn resource_manager_scratch_context_released_wholesale() {
+        let resource_man = ResourceManager::with_capacity(10000000, 10000000);
+        let scratch = resource_man.scratch_context().expect("Could not open a scratch context.");
+        //The contexts do not overlap.
+        assert!(resource_man.scratch_context().is_err());
+        let mut data: &[u8] = &[42; 40000];
+        let mut buffer = scratch.read_all(&mut data).expect("Could not read data in a scratch buffer.");
+        assert_eq!(buffer.len(), 40000);
+        assert!(scratch.used() >= 40000);
+        let mut first_bytes = [0; 4];
+        buffer.read_exact(&mut first_bytes).expect("Could not read the scratch buffer.");
+        assert_eq!(first_bytes, [42; 4]);
+        assert!(resource_man.memory_report().temporary_stack.used >= 40000);
+        assert!(scratch.buffer_with_capacity(usize::max_value()).is_err());
+
+        scratch.release();
+        assert_eq!(resource_man.memory_report().temporary_stack.used, 0);
+
+        //A context is released when it is dropped, on the error paths of the loaders as well.
+        {
+            let scratch = resource_man.scratch_context().expect("Could not open a scratch context.");
+            let mut data: &[u8] = &[42; 40000];
+            scratch.read_all(&mut data).expect("Could not read data in a scratch buffer.");
+        }
+        assert_eq!(resource_man.memory_report().temporary_stack.used, 0);
+        resource_man.scratch_context().expect("Could not open a scratch context.");
+    }
+
+    #[test]
+    fn resource_manager_convert_audio_on_load() {
+        let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
+        let mut resource_man = ResourceManager::with_capacity(10000000, 10000000);
+        let ogg_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "ogg_resource/untitled.ogg").expect("Could not create ogg path.");
+        resource_man.set_audio_target_format(Some(AudioFormat::new(48000, 2)));
+        assert_eq!(resource_man.audio_target_format_of(ogg_path.as_path()), Some(AudioFormat::new(48000, 2)));
+        resource_man.set_audio_target_format_of(ogg_path.as_path(), Some(AudioFormat::new(22050, 1)));
+        assert_eq!(resource_man.audio_target_format_of(ogg_path.as_path()), Some(AudioFormat::new(22050, 1)));
+
+        let ogg_reader = fs.open(ogg_path.as_path()).expect("Could not create ogg reader.");
+        resource_man.load_ogg(ogg_path.as_path(), ogg_reader, ResourceScope::Level).expect("Could not load ogg data.");
+        {
+            let registry = resource_man.level_resource_registry();
+            let ogg = registry.get_ogg(ogg_path.as_path()).unwrap();
+            let pcm = ogg.pcm().expect("The ogg data has not been decoded.");
+            assert_eq!(pcm.format(), AudioFormat::new(22050, 1));
+            assert!(pcm.frames() > 0);
+        }
+        resource_man.clear();
+    }
+
     #[test]
     fn resource_manager_load_unload_asynchronously_resource() {
 
@@ -306,8 +2053,193 @@ mod resource_manager_test {
         //Referential integrity -> Model has a mesh, which has a skeletons and anims. Skeleton must be loaded before anims...
     }
 
+    #[test]
+    fn resource_manager_borrow_raw_resources_from_mapped_pak() {
+        let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
+        let tga_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "tga_resource/Untitled.tga").expect("Could not create tga path.");
+        let gltf_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "gltf_resource/untitled.gltf").expect("Could not create gltf path.");
+        let pak_path = ::std::env::temp_dir().join(format!("maskerad_resource_manager_mapped_test_{}.pak", ::std::process::id()));
+
+        let mut pak_writer = PakWriter::create(pak_path.as_path()).expect("Could not create the pak file.");
+        pak_writer.set_compression(ResourceType::Tga, PakCompression::None);
+        pak_writer.add_file("raw/Untitled.tga", tga_path.as_path()).expect("Could not pack the tga file.");
+        pak_writer.add_file("raw/untitled.gltf", gltf_path.as_path()).expect("Could not pack the gltf file.");
+        pak_writer.finish().expect("Could not finish the pak file.");
+
+        let mapped_pak = MappedPakArchive::open(pak_path.as_path()).expect("Could not map the pak file.");
+        let mut resource_man = ResourceManager::with_capacity(10000000, 10000000);
+        resource_man.mount_mapped_pak(&mapped_pak);
+        let stack_marker = resource_man.double_ended_allocator.0.marker();
+
+        let raw_tga = resource_man.load_raw("raw/Untitled.tga").expect("Could not load the raw tga data.");
+        //The data is borrowed from the mapping, nothing is copied in the resource stack.
+        assert_eq!(raw_tga.as_ptr(), mapped_pak.entry_bytes("raw/Untitled.tga").expect("Could not borrow the tga entry.").as_ptr());
+        assert_eq!(resource_man.double_ended_allocator.0.marker(), stack_marker);
+        let mut tga_bytes = Vec::new();
+        fs.open(tga_path.as_path()).expect("Could not create tga reader.").read_to_end(&mut tga_bytes).expect("Could not read the tga file.");
+        assert_eq!(raw_tga, tga_bytes.as_slice());
+        assert!(resource_man.level_resource_registry().has_raw("raw/Untitled.tga"));
+
+        //Compressed entries must be read through a reader.
+        assert!(resource_man.load_raw("raw/untitled.gltf").is_err());
+        assert!(resource_man.open_resource("raw/untitled.gltf", &fs).is_ok());
+        resource_man.clear();
+    }
+
     #[test]
     fn resource_manager_package_resources_in_one_big_file() {
-        //Optional
+        let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
+        let mut resource_man = ResourceManager::with_capacity(10000000, 10000000);
+        let obj_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "obj_resource/untitled.obj").expect("Could not create obj path.");
+        let mtl_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "obj_resource/untitled.mtl").expect("Could not create mtl path.");
+        let tga_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "tga_resource/Untitled.tga").expect("Could not create tga path.");
+        let ogg_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "ogg_resource/untitled.ogg").expect("Could not create ogg path.");
+        let pak_path = ::std::env::temp_dir().join(format!("maskerad_resource_manager_test_{}.pak", ::std::process::id()));
+
+        //The resources are packed under paths which do not exist in the filesystem.
+        let mut pak_writer = PakWriter::create(pak_path.as_path()).expect("Could not create the pak file.");
+        pak_writer.add_file("packed/untitled.obj", obj_path.as_path()).expect("Could not pack the obj file.");
+        pak_writer.add_file("packed/untitled.mtl", mtl_path.as_path()).expect("Could not pack the mtl file.");
+        //The mtl file references ../tga_resource/Untitled.tga.
+        pak_writer.add_file("tga_resource/Untitled.tga", tga_path.as_path()).expect("Could not pack the tga file.");
+        //The ogg file is read backward by its loader, it must work with compressed data too.
+        pak_writer.set_compression(ResourceType::Ogg, PakCompression::Deflate);
+        pak_writer.add_file("packed/untitled.ogg", ogg_path.as_path()).expect("Could not pack the ogg file.");
+        assert!(pak_writer.add_file("packed/untitled.ogg", ogg_path.as_path()).is_err());
+        pak_writer.finish().expect("Could not finish the pak file.");
+
+        let pak = PakArchive::open(pak_path.as_path()).expect("Could not open the pak file.");
+        assert_eq!(pak.len(), 4);
+        assert!(pak.entries().iter().all(|entry| entry.offset % PAK_DEFAULT_ALIGNMENT as u64 == 0));
+        let mut tga_bytes = Vec::new();
+        fs.open(tga_path.as_path()).expect("Could not create tga reader.").read_to_end(&mut tga_bytes).expect("Could not read the tga file.");
+        assert_eq!(pak.read_entry("packed/../tga_resource/Untitled.tga").expect("Could not read the tga entry."), tga_bytes);
+        let tga_entry = pak.entry("tga_resource/Untitled.tga").expect("Could not find the tga entry.");
+        assert_eq!(tga_entry.compression().expect("Unknown compression."), PakCompression::Lz4);
+        assert_eq!(tga_entry.uncompressed_size, tga_bytes.len() as u64);
+        assert!(pak.entry("packed/untitled.obj").expect("Could not find the obj entry.").is_compressed());
+        resource_man.mount_pak(pak);
+
+        //A pak file whose table of contents has a wrong hash is refused.
+        let mut corrupted = ::std::fs::read(pak_path.as_path()).expect("Could not read the pak file.");
+        let mut toc_offset = [0; 8];
+        toc_offset.copy_from_slice(&corrupted[16..24]);
+        corrupted[u64::from_le_bytes(toc_offset) as usize] ^= 0xff;
+        let corrupted_path = pak_path.with_extension("corrupted.pak");
+        ::std::fs::write(corrupted_path.as_path(), corrupted).expect("Could not write the corrupted pak file.");
+        assert!(PakArchive::open(corrupted_path.as_path()).is_err());
+        ::std::fs::remove_file(corrupted_path).expect("Could not remove the corrupted pak file.");
+
+        let mut obj_reader = resource_man.open_resource("packed/untitled.obj", &fs).expect("Could not open the packed obj file.");
+        resource_man.load_obj("packed/untitled.obj", &mut obj_reader, &fs, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level).expect("Could not load the packed obj data.");
+        let ogg_reader = resource_man.open_resource("packed/untitled.ogg", &fs).expect("Could not open the packed ogg file.");
+        resource_man.load_ogg("packed/untitled.ogg", ogg_reader, ResourceScope::Level).expect("Could not load the packed ogg data.");
+
+        {
+            let registry = resource_man.level_resource_registry();
+            assert_eq!(registry.get_mesh("packed/untitled.obj").expect("Could not find the mesh.").triangle_count(), 2);
+            assert!(registry.has_tga("packed/../tga_resource/Untitled.tga"));
+            assert!(registry.has_ogg("packed/untitled.ogg"));
+        }
+        assert!(resource_man.open_resource("packed/missing.ogg", &fs).is_err());
+        resource_man.clear();
+    }
+
+    #[test]
+    fn resource_manager_apply_meta_files() {
+        let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
+        let tga_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "tga_resource/Untitled.tga").expect("Could not create tga path.");
+        let ogg_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "ogg_resource/untitled.ogg").expect("Could not create ogg path.");
+        let mut assets = MemoryMount::new();
+        assets.insert("wall.tga", ::std::fs::read(tga_path.as_path()).expect("Could not read the tga file."));
+        assets.insert("wall.tga.meta", b"tags = [\"wall\"]\ndependencies = [\"res://assets/music.ogg\"]\n\n[texture]\nsrgb = true\ngenerate_mips = true\n".to_vec());
+        assets.insert("music.ogg", ::std::fs::read(ogg_path.as_path()).expect("Could not read the ogg file."));
+        assets.insert("music.ogg.meta", b"dependencies = [\"res://assets/wall.tga\"]\n\n[audio]\nsample_rate = 22050\nchannels = 1\n".to_vec());
+        let mut resource_man = ResourceManager::with_capacity(10000000, 10000000);
+        resource_man.vfs_mut().mount("assets", "res://assets", VfsSource::Memory(assets), 0);
+
+        resource_man.load_global_resource("res://assets/music.ogg", &fs).expect("Could not load ogg data.");
+        resource_man.load_resource("res://assets/wall.tga", &fs, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level).expect("Could not load tga image.");
+        assert!(resource_man.meta_of("res://assets/wall.tga").expect("Could not find the metadata of the tga.").has_tag("wall"));
+        assert_eq!(resource_man.level_resource_registry().dependencies_of("res://assets/wall.tga"), &[PathBuf::from("res://assets/music.ogg")]);
+        //The dependencies of a meta file are recorded in the registry of the scope of the resource.
+        assert_eq!(resource_man.global_resource_registry().dependencies_of("res://assets/music.ogg"), &[PathBuf::from("res://assets/wall.tga")]);
+        assert!(resource_man.level_resource_registry().dependencies_of("res://assets/music.ogg").is_empty());
+
+        //The import settings of the meta files are applied by the loaders.
+        let registry = resource_man.level_resource_registry();
+        let tga = registry.get_tga("res://assets/wall.tga").expect("Could not find the tga.");
+        assert!(tga.is_srgb());
+        assert_eq!(tga.mip_levels().len(), 9);
+        assert_eq!((tga.mip_levels()[0].w, tga.mip_levels()[0].h), (320, 200));
+        let global_registry = resource_man.global_resource_registry();
+        let ogg = global_registry.get_ogg("res://assets/music.ogg").expect("Could not find the ogg.");
+        assert_eq!(ogg.pcm().expect("The ogg has not been decoded.").format(), AudioFormat::new(22050, 1));
+        assert_eq!(resource_man.audio_target_format(), None);
+        assert!(resource_man.meta_of("res://assets/missing.tga").is_none());
+        drop(registry);
+        drop(global_registry);
+
+        //The metadata of the global resources outlives the level.
+        resource_man.unload_level_resources();
+        assert!(resource_man.meta_of("res://assets/wall.tga").is_none());
+        assert!(resource_man.meta_of("res://assets/music.ogg").is_some());
+        assert_eq!(resource_man.audio_target_format_of("res://assets/music.ogg"), Some(AudioFormat::new(22050, 1)));
+    }
+
+    #[test]
+    fn resource_manager_load_native_resources() {
+        let fs = Filesystem::new("test_resource_man", "Malkaviel").expect("Could not create fs.");
+        let pipeline = AssetPipeline::new();
+        let (_, texture) = pipeline.compile_asset(".", "tga_resource/Untitled.tga").expect("Could not compile the tga.").expect("The tga has no compiler.");
+        let (_, mesh) = pipeline.compile_asset(".", "obj_resource/untitled.obj").expect("Could not compile the obj.").expect("The obj has no compiler.");
+        let (_, audio) = pipeline.compile_asset(".", "ogg_resource/untitled.ogg").expect("Could not compile the ogg.").expect("The ogg has no compiler.");
+        let mut pcm_audio = Vec::new();
+        let voice_clip = NativeAudio::Pcm {
+            clip: PcmClip::new(AudioFormat::new(22050, 2), vec![0, 1, 2, 3, 4, 5]),
+            loop_points: Some(LoopPoints { start: 1, length: 2 }),
+            comments: vec![(String::from("LOOPSTART"), String::from("2"))],
+        };
+        write_audio(&mut pcm_audio, &voice_clip).expect("Could not write the pcm audio clip.");
+        let mut pcm = Vec::new();
+        NativeResource::new(ResourceType::Ogg, NATIVE_AUDIO_VERSION, pcm_audio).write_to(&mut pcm).expect("Could not write the pcm audio clip.");
+        let mut stale = Vec::new();
+        NativeResource::new(ResourceType::Tga, NATIVE_TEXTURE_VERSION - 1, Vec::new()).write_to(&mut stale).expect("Could not write the stale texture.");
+        let mut assets = MemoryMount::new();
+        assets.insert("wall.tga.tex", texture);
+        assets.insert("cube.obj.mesh", mesh.clone());
+        assets.insert("global_cube.obj.mesh", mesh);
+        assets.insert("music.ogg.audio", audio);
+        assets.insert("voice.ogg.audio", pcm);
+        assets.insert("stale.tga.tex", stale);
+        let mut resource_man = ResourceManager::with_capacity(10000000, 10000000);
+        resource_man.vfs_mut().mount("cooked", "res://cooked", VfsSource::Memory(assets), 0);
+
+        resource_man.load_resource("res://cooked/wall.tga.tex", &fs, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level).expect("Could not load the native texture.");
+        resource_man.load_resource("res://cooked/cube.obj.mesh", &fs, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level).expect("Could not load the native mesh.");
+        resource_man.load_resource("res://cooked/global_cube.obj.mesh", &fs, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Global).expect("Could not load the global native mesh.");
+        resource_man.load_resource("res://cooked/music.ogg.audio", &fs, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level).expect("Could not load the native audio clip.");
+        resource_man.load_resource("res://cooked/voice.ogg.audio", &fs, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level).expect("Could not load the pcm audio clip.");
+        //Data cooked by another version of the texture compiler must be cooked again.
+        match resource_man.load_resource("res://cooked/stale.tga.tex", &fs, &resource_man.scratch_context().expect("Could not open a scratch context."), ResourceScope::Level) {
+            Err(ResourceManagerError::VersionMismatch(_, found, supported)) => assert_eq!((found, supported), (NATIVE_TEXTURE_VERSION - 1, NATIVE_TEXTURE_VERSION)),
+            _ => panic!("Stale cooked data has been loaded."),
+        }
+
+        let registry = resource_man.level_resource_registry();
+        let tga: &Image<u8> = registry.get_tga("res://cooked/wall.tga.tex").expect("Could not find the texture.").as_ref();
+        assert_eq!((tga.w, tga.h), (640, 400));
+        assert!(registry.get_mesh("res://cooked/cube.obj.mesh").is_ok());
+        assert!(registry.dependencies_of("res://cooked/cube.obj.mesh").contains(&PathBuf::from("res://tga_resource/Untitled.tga")));
+        assert!(registry.get_ogg("res://cooked/music.ogg.audio").is_ok());
+        let voice = registry.get_ogg("res://cooked/voice.ogg.audio").expect("Could not find the pcm audio clip.");
+        assert!(voice.reader().is_none());
+        assert_eq!(voice.pcm().expect("The pcm audio clip has no samples.").frames(), 3);
+        assert_eq!(voice.metadata().total_samples(), Some(3));
+        assert_eq!(voice.loop_points(), Some(LoopPoints { start: 1, length: 2 }));
+        assert!(!registry.has_tga("res://cooked/stale.tga.tex"));
+        //The dependencies of a native resource are recorded in the registry of its scope.
+        assert!(registry.dependencies_of("res://cooked/global_cube.obj.mesh").is_empty());
+        assert!(resource_man.global_resource_registry().dependencies_of("res://cooked/global_cube.obj.mesh").contains(&PathBuf::from("res://tga_resource/Untitled.tga")));
     }
 }
\ No newline at end of file
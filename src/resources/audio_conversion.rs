@@ -0,0 +1,341 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::f64::consts::PI;
+use std::f32::consts::FRAC_1_SQRT_2;
+
+//Number of input samples on each side of the output sample, used by the windowed-sinc filter.
+const SINC_HALF_WIDTH: usize = 16;
+//Number of precomputed sub-sample positions of the filter kernel (the "phases" of the polyphase filter).
+const SINC_PHASES: usize = 256;
+
+/// The sample rate and channel count audio clips must have to be mixed directly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AudioFormat {
+    pub sample_rate: u32,
+    pub channels: u8,
+}
+
+impl AudioFormat {
+    pub fn new(sample_rate: u32, channels: u8) -> Self {
+        AudioFormat {
+            sample_rate,
+            channels,
+        }
+    }
+}
+
+/// Decoded PCM data, with interleaved samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PcmClip {
+    format: AudioFormat,
+    samples: Vec<i16>,
+}
+
+impl PcmClip {
+    pub fn new(format: AudioFormat, samples: Vec<i16>) -> Self {
+        PcmClip {
+            format,
+            samples,
+        }
+    }
+
+    pub fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.format.sample_rate
+    }
+
+    pub fn channels(&self) -> u8 {
+        self.format.channels
+    }
+
+    pub fn samples(&self) -> &[i16] {
+        self.samples.as_slice()
+    }
+
+    /// The number of samples per channel.
+    pub fn frames(&self) -> usize {
+        if self.format.channels == 0 {
+            return 0;
+        }
+        self.samples.len() / self.format.channels as usize
+    }
+
+    /// Remixes and resamples the clip to the given format.
+    pub fn convert_to(self, target: AudioFormat) -> Self {
+        if self.format == target {
+            return self;
+        }
+
+        debug!("Converting a pcm clip from {} Hz, {} channel(s) to {} Hz, {} channel(s).", self.format.sample_rate, self.format.channels, target.sample_rate, target.channels);
+        let remixed = remix_channels(&self.samples, self.format.channels, target.channels);
+        let resampled = resample(&remixed, target.channels, self.format.sample_rate, target.sample_rate);
+        PcmClip::new(target, resampled)
+    }
+}
+
+//The speakers of a standard channel layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Speaker {
+    FrontLeft,
+    FrontRight,
+    Center,
+    Lfe,
+    SideLeft,
+    SideRight,
+    RearLeft,
+    RearRight,
+    RearCenter,
+}
+
+//The speakers of the channels of a vorbis stream, in the order of its interleaved samples.
+fn speaker_layout(channels: usize) -> Option<&'static [Speaker]> {
+    use self::Speaker::*;
+    match channels {
+        2 => Some(&[FrontLeft, FrontRight]),
+        3 => Some(&[FrontLeft, Center, FrontRight]),
+        4 => Some(&[FrontLeft, FrontRight, RearLeft, RearRight]),
+        5 => Some(&[FrontLeft, Center, FrontRight, RearLeft, RearRight]),
+        6 => Some(&[FrontLeft, Center, FrontRight, RearLeft, RearRight, Lfe]),
+        7 => Some(&[FrontLeft, Center, FrontRight, SideLeft, SideRight, RearCenter, Lfe]),
+        8 => Some(&[FrontLeft, Center, FrontRight, SideLeft, SideRight, RearLeft, RearRight, Lfe]),
+        _ => None,
+    }
+}
+
+//Where a speaker missing from the output layout is mixed, by order of preference. The LFE channel is dropped.
+fn speaker_fallbacks(speaker: Speaker) -> &'static [&'static [(Speaker, f32)]] {
+    use self::Speaker::*;
+    const HALF_POWER: f32 = FRAC_1_SQRT_2;
+    match speaker {
+        FrontLeft | FrontRight | Lfe => &[],
+        Center => &[&[(FrontLeft, HALF_POWER), (FrontRight, HALF_POWER)]],
+        SideLeft => &[&[(RearLeft, 1.0)], &[(FrontLeft, HALF_POWER)]],
+        SideRight => &[&[(RearRight, 1.0)], &[(FrontRight, HALF_POWER)]],
+        RearLeft => &[&[(SideLeft, 1.0)], &[(FrontLeft, HALF_POWER)]],
+        RearRight => &[&[(SideRight, 1.0)], &[(FrontRight, HALF_POWER)]],
+        RearCenter => &[
+            &[(RearLeft, HALF_POWER), (RearRight, HALF_POWER)],
+            &[(SideLeft, HALF_POWER), (SideRight, HALF_POWER)],
+            &[(FrontLeft, HALF_POWER), (FrontRight, HALF_POWER)],
+        ],
+    }
+}
+
+//The gains of the input channels in every output channel, `matrix[output][input]`.
+fn mix_matrix(from: usize, to: usize) -> Vec<Vec<f32>> {
+    let mut matrix = vec![vec![0.0f32; from]; to];
+    match (from, to, speaker_layout(from), speaker_layout(to)) {
+        //Mono goes to the center speaker, or else to the front speakers, or else to every speaker.
+        (1, _, _, layout) => {
+            let front = layout.map(|layout| {
+                if layout.contains(&Speaker::Center) { vec![Speaker::Center] } else { vec![Speaker::FrontLeft, Speaker::FrontRight] }
+            });
+            for (output, gains) in matrix.iter_mut().enumerate() {
+                let is_front = match (layout, front.as_ref()) {
+                    (Some(layout), Some(front)) => front.contains(&layout[output]),
+                    _ => true,
+                };
+                gains[0] = if is_front { 1.0 } else { 0.0 };
+            }
+        },
+        //Mono is the average of the stereo downmix.
+        (_, 1, Some(_), _) => {
+            let stereo = mix_matrix(from, 2);
+            for input in 0..from {
+                matrix[0][input] = (stereo[0][input] + stereo[1][input]) / 2.0;
+            }
+        },
+        (_, _, Some(from_layout), Some(to_layout)) => {
+            for (input, speaker) in from_layout.iter().enumerate() {
+                match to_layout.iter().position(|output_speaker| output_speaker == speaker) {
+                    Some(output) => matrix[output][input] += 1.0,
+                    None => {
+                        let fallback = speaker_fallbacks(*speaker).iter()
+                            .find(|fallback| fallback.iter().all(|&(output_speaker, _)| to_layout.contains(&output_speaker)));
+                        for &(output_speaker, gain) in fallback.map(|fallback| fallback.iter()).into_iter().flatten() {
+                            let output = to_layout.iter().position(|speaker| *speaker == output_speaker).expect("The speaker is in the layout.");
+                            matrix[output][input] += gain;
+                        }
+                    },
+                }
+            }
+            //The output channels receiving several inputs are attenuated, so they cannot clip.
+            for gains in matrix.iter_mut() {
+                let total: f32 = gains.iter().sum();
+                if total > 1.0 {
+                    for gain in gains.iter_mut() {
+                        *gain /= total;
+                    }
+                }
+            }
+        },
+        //Without known layout, input channel `i` is averaged in output channel `i % to`.
+        _ => {
+            for input in 0..from {
+                matrix[input % to][input] = 1.0;
+            }
+            for gains in matrix.iter_mut() {
+                let count = gains.iter().filter(|&&gain| gain > 0.0).count();
+                for gain in gains.iter_mut() {
+                    if count > 0 {
+                        *gain /= count as f32;
+                    }
+                }
+            }
+        },
+    }
+    matrix
+}
+
+/// Up-mixes or down-mixes interleaved samples, whose channels are in the vorbis order.
+///
+/// The standard layouts, up to 7.1, are mixed with a downmix matrix : the center and the surround channels missing
+/// from the output are mixed at -3 dB in the front left and right channels, and the LFE channel is dropped.
+/// Mono goes to the center channel, or to the front channels, and mixing down to mono averages the stereo downmix.
+/// Without a known layout, input channel `i` is mixed in output channel `i % to`.
+pub fn remix_channels(samples: &[i16], from: u8, to: u8) -> Vec<i16> {
+    if from == to || from == 0 || to == 0 {
+        return samples.to_vec();
+    }
+
+    let from = from as usize;
+    let to = to as usize;
+    let frames = samples.len() / from;
+    let matrix = mix_matrix(from, to);
+    let mut output = Vec::with_capacity(frames * to);
+
+    for frame in samples.chunks(from).take(frames) {
+        for gains in matrix.iter() {
+            let mix: f32 = frame.iter().zip(gains.iter()).map(|(&sample, &gain)| f32::from(sample) * gain).sum();
+            output.push(mix.round().max(i16::min_value() as f32).min(i16::max_value() as f32) as i16);
+        }
+    }
+
+    output
+}
+
+fn blackman_window(x: f64) -> f64 {
+    //x in [-1, 1]
+    let n = (x + 1.0) / 2.0;
+    0.42 - 0.5 * (2.0 * PI * n).cos() + 0.08 * (4.0 * PI * n).cos()
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+//Precomputes the windowed-sinc kernel for every phase, with a cutoff relative to the input sample rate.
+fn build_kernel(cutoff: f64) -> Vec<f32> {
+    let taps = SINC_HALF_WIDTH * 2;
+    let mut kernel = Vec::with_capacity((SINC_PHASES + 1) * taps);
+    for phase in 0..SINC_PHASES + 1 {
+        let fraction = phase as f64 / SINC_PHASES as f64;
+        for tap in 0..taps {
+            //Distance between the output sample and the input sample of this tap.
+            let distance = tap as f64 - (SINC_HALF_WIDTH as f64 - 1.0) - fraction;
+            let window = blackman_window(distance / SINC_HALF_WIDTH as f64);
+            kernel.push((cutoff * sinc(cutoff * distance) * window) as f32);
+        }
+    }
+    kernel
+}
+
+/// Resamples interleaved samples with a polyphase windowed-sinc filter.
+///
+/// When down-sampling, the cutoff frequency is lowered to the output Nyquist frequency to avoid aliasing.
+pub fn resample(samples: &[i16], channels: u8, from_rate: u32, to_rate: u32) -> Vec<i16> {
+    if from_rate == to_rate || from_rate == 0 || to_rate == 0 || channels == 0 {
+        return samples.to_vec();
+    }
+
+    let channels = channels as usize;
+    let input_frames = samples.len() / channels;
+    let output_frames = (input_frames as u64 * u64::from(to_rate) / u64::from(from_rate)) as usize;
+    let step = f64::from(from_rate) / f64::from(to_rate);
+    let cutoff = if to_rate < from_rate { f64::from(to_rate) / f64::from(from_rate) } else { 1.0 };
+    let kernel = build_kernel(cutoff);
+    let taps = SINC_HALF_WIDTH * 2;
+
+    let mut output = Vec::with_capacity(output_frames * channels);
+    for output_frame in 0..output_frames {
+        let position = output_frame as f64 * step;
+        let base = position.floor() as isize;
+        let fraction = position - base as f64;
+        let phase_position = fraction * SINC_PHASES as f64;
+        let phase = phase_position.floor() as usize;
+        let phase_fraction = (phase_position - phase as f64) as f32;
+        let low = &kernel[phase * taps..(phase + 1) * taps];
+        let high = &kernel[(phase + 1) * taps..(phase + 2) * taps];
+
+        for channel in 0..channels {
+            let mut accumulator = 0.0f32;
+            for tap in 0..taps {
+                let input_frame = base + tap as isize - (SINC_HALF_WIDTH as isize - 1);
+                if input_frame < 0 || input_frame >= input_frames as isize {
+                    continue;
+                }
+                let coefficient = low[tap] + (high[tap] - low[tap]) * phase_fraction;
+                accumulator += f32::from(samples[input_frame as usize * channels + channel]) * coefficient;
+            }
+            output.push(accumulator.round().max(i16::min_value() as f32).min(i16::max_value() as f32) as i16);
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod audio_conversion_test {
+    use super::*;
+
+    #[test]
+    fn audio_conversion_remix_mono_to_stereo_and_back() {
+        let mono = vec![1, 2, 3, 4];
+        let stereo = remix_channels(&mono, 1, 2);
+        assert_eq!(stereo, vec![1, 1, 2, 2, 3, 3, 4, 4]);
+        assert_eq!(remix_channels(&stereo, 2, 1), mono);
+    }
+
+    #[test]
+    fn audio_conversion_downmix_5_1_to_stereo() {
+        //Front left, center, front right, rear left, rear right, LFE.
+        let center = remix_channels(&[0, 1000, 0, 0, 0, 1000], 6, 2);
+        assert_eq!(center[0], center[1]);
+        assert!(center[0] > 0);
+        let front_left = remix_channels(&[1000, 0, 0, 0, 0, 0], 6, 2);
+        assert!(front_left[0] > 0);
+        assert_eq!(front_left[1], 0);
+        let rear_right = remix_channels(&[0, 0, 0, 0, 1000, 0], 6, 2);
+        assert_eq!(rear_right[0], 0);
+        assert!(rear_right[1] > 0);
+        assert_eq!(remix_channels(&[0, 0, 0, 0, 0, 1000], 6, 2), vec![0, 0]);
+        //Mono goes to the center channel only.
+        assert_eq!(remix_channels(&[1000], 1, 6), vec![0, 1000, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn audio_conversion_resample_length_and_dc_level() {
+        let input = vec![1000i16; 22_050];
+        let output = resample(&input, 1, 22_050, 48_000);
+        assert_eq!(output.len(), 48_000);
+        //Far from the edges, a constant signal must stay constant.
+        assert!((output[24_000] - 1000).abs() <= 2);
+
+        let clip = PcmClip::new(AudioFormat::new(44_100, 1), vec![0; 44_100]);
+        let converted = clip.convert_to(AudioFormat::new(48_000, 2));
+        assert_eq!(converted.frames(), 48_000);
+        assert_eq!(converted.channels(), 2);
+    }
+}
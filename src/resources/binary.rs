@@ -0,0 +1,97 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//Little-endian encoding of the pak files and of the native resources.
+
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// FNV-1a hash of some bytes. It is stable across builds and platforms, unlike the hashers of the standard library.
+pub fn fnv1a_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+pub fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+pub fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+pub fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+pub fn read_f32<R: Read>(reader: &mut R) -> io::Result<f32> {
+    read_u32(reader).map(f32::from_bits)
+}
+
+//The length is read from the data, the buffer grows with what is actually read instead of being preallocated.
+pub fn read_bytes<R: Read>(reader: &mut R, len: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "The data is truncated."));
+    }
+    Ok(bytes)
+}
+
+/// Reads a string written by `write_string`.
+pub fn read_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    let len = read_u32(reader)? as usize;
+    String::from_utf8(read_bytes(reader, len)?)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "A string is not valid utf-8."))
+}
+
+/// Reads a path written by `write_path`.
+pub fn read_path<R: Read>(reader: &mut R) -> io::Result<PathBuf> {
+    read_string(reader).map(PathBuf::from)
+}
+
+pub fn write_u8<W: Write>(writer: &mut W, value: u8) -> io::Result<()> {
+    writer.write_all(&[value])
+}
+
+pub fn write_u16<W: Write>(writer: &mut W, value: u16) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub fn write_u32<W: Write>(writer: &mut W, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub fn write_u64<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub fn write_f32<W: Write>(writer: &mut W, value: f32) -> io::Result<()> {
+    write_u32(writer, value.to_bits())
+}
+
+/// Writes a string as its length, in bytes, and its utf-8 bytes.
+pub fn write_string<W: Write>(writer: &mut W, string: &str) -> io::Result<()> {
+    write_u32(writer, string.len() as u32)?;
+    writer.write_all(string.as_bytes())
+}
+
+/// Writes a path as its length, in bytes, and its utf-8 bytes.
+pub fn write_path<W: Write, P: AsRef<Path>>(writer: &mut W, path: P) -> io::Result<()> {
+    write_string(writer, path.as_ref().to_string_lossy().as_ref())
+}
@@ -0,0 +1,240 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::path::Path;
+use gltf::Gltf;
+use gltf::accessor::{Accessor, DataType, Dimensions};
+use gltf::mesh::{Mode, Semantic};
+use resources::mesh_registry::{MeshResource, MeshPrimitive};
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+
+//Reads the components of the elements of an accessor. An f64 holds every component type exactly.
+fn read_accessor(accessor: &Accessor, buffers: &[Vec<u8>]) -> ResourceManagerResult<Vec<f64>> {
+    let data_type = accessor.data_type();
+    let component_size = match data_type {
+        DataType::I8 | DataType::U8 => 1,
+        DataType::I16 | DataType::U16 => 2,
+        DataType::U32 | DataType::F32 => 4,
+    };
+    let components = match accessor.dimensions() {
+        Dimensions::Scalar => 1,
+        Dimensions::Vec2 => 2,
+        Dimensions::Vec3 => 3,
+        Dimensions::Vec4 | Dimensions::Mat2 => 4,
+        Dimensions::Mat3 => 9,
+        Dimensions::Mat4 => 16,
+    };
+
+    let view = accessor.view();
+    let buffer = &buffers[view.buffer().index()];
+    let stride = view.stride().unwrap_or(component_size * components);
+    let start = view.offset() + accessor.offset();
+    //The counts come from the file, the last element is checked before anything is allocated.
+    let end = match accessor.count() {
+        0 => Some(start),
+        count => (count - 1).checked_mul(stride)
+            .and_then(|last| last.checked_add(start))
+            .and_then(|last| last.checked_add(component_size * components)),
+    };
+    if end.map(|end| end > buffer.len()).unwrap_or(true) {
+        return Err(ResourceManagerError::ResourceError(format!("The accessor {} of a glTF file is out of the bounds of its buffer !", accessor.index())));
+    }
+    let mut values = Vec::with_capacity(accessor.count() * components);
+    for element in 0..accessor.count() {
+        for component in 0..components {
+            let offset = start + element * stride + component * component_size;
+            let bytes = match buffer.get(offset..offset + component_size) {
+                Some(bytes) => bytes,
+                None => return Err(ResourceManagerError::ResourceError(format!("The accessor {} of a glTF file is out of the bounds of its buffer !", accessor.index()))),
+            };
+            let value = match data_type {
+                DataType::I8 => f64::from(bytes[0] as i8),
+                DataType::U8 => f64::from(bytes[0]),
+                DataType::I16 => f64::from(i16::from_le_bytes([bytes[0], bytes[1]])),
+                DataType::U16 => f64::from(u16::from_le_bytes([bytes[0], bytes[1]])),
+                DataType::U32 => f64::from(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+                DataType::F32 => f64::from(f32::from_bits(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))),
+            };
+            values.push(value);
+        }
+    }
+    Ok(values)
+}
+
+//The vertex attributes of a glTF primitive, None if it does not have it. Every vertex must have `components` values.
+fn read_attribute(primitive: &::gltf::mesh::Primitive, semantic: Semantic, components: usize, vertex_count: usize, buffers: &[Vec<u8>]) -> ResourceManagerResult<Option<Vec<f32>>> {
+    match primitive.get(&semantic) {
+        Some(accessor) => {
+            let values = read_accessor(&accessor, buffers)?;
+            if values.len() != vertex_count * components {
+                return Err(ResourceManagerError::ResourceError(format!("The accessor {} of a glTF file does not have {} values for each of the {} vertices of its primitive !", accessor.index(), components, vertex_count)));
+            }
+            Ok(Some(values.into_iter().map(|value| value as f32).collect()))
+        },
+        None => Ok(None),
+    }
+}
+
+//Decodes the data of a base64 `data:` URI, like `data:application/octet-stream;base64,AAABAA==`.
+fn decode_data_uri(uri: &str) -> Option<Vec<u8>> {
+    let data = &uri[uri.find(";base64,")? + ";base64,".len()..];
+    let mut bytes = Vec::with_capacity(data.len() / 4 * 3);
+    let (mut accumulator, mut bits) = (0u32, 0);
+    for byte in data.bytes().take_while(|&byte| byte != b'=') {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        accumulator = (accumulator << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((accumulator >> bits) as u8);
+            accumulator &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+/// Merges the triangle primitives of every mesh of a glTF document in one mesh, in the space of the meshes,
+/// without their node transforms and their materials. An attribute is kept only if every primitive has it.
+/// The other primitives, like lines or points, are skipped : the mesh has no primitive if the document has no triangles.
+///
+/// The buffers are read with `read_buffer`, from their URIs relative to the glTF file at `path`, or decoded from their `data:` URIs.
+pub fn gltf_mesh<P, F>(gltf: &Gltf, path: P, mut read_buffer: F) -> ResourceManagerResult<MeshResource> where
+    P: AsRef<Path>,
+    F: FnMut(&str) -> ResourceManagerResult<Vec<u8>>,
+{
+    debug!("Extracting the mesh of the glTF file {}.", path.as_ref().display());
+    let mut buffers = Vec::new();
+    for buffer in gltf.buffers() {
+        if buffer.uri().starts_with("data:") {
+            match decode_data_uri(buffer.uri()) {
+                Some(data) => buffers.push(data),
+                None => return Err(ResourceManagerError::ResourceError(format!("The glTF file {} has a buffer whose data URI is not base64 !", path.as_ref().display()))),
+            }
+        } else {
+            buffers.push(read_buffer(buffer.uri())?);
+        }
+    }
+
+    let mut mesh = MeshResource::new();
+    let (mut has_normals, mut has_tex_coords, mut has_tangents) = (true, true, true);
+    for gltf_mesh in gltf.meshes() {
+        for primitive in gltf_mesh.primitives() {
+            match primitive.mode() {
+                Mode::Triangles => {},
+                mode => {
+                    warn!("The glTF file {} has a primitive which is not a triangle list ({:?}), it is skipped.", path.as_ref().display(), mode);
+                    continue;
+                },
+            }
+
+            let vertex_count = match primitive.get(&Semantic::Positions) {
+                Some(accessor) => accessor.count(),
+                None => return Err(ResourceManagerError::ResourceError(format!("The glTF file {} has a primitive without positions !", path.as_ref().display()))),
+            };
+            let base = mesh.positions.len();
+            if base + vertex_count > u32::max_value() as usize {
+                return Err(ResourceManagerError::ResourceError(format!("The glTF file {} has more vertices than a mesh can index !", path.as_ref().display())));
+            }
+            let positions = read_attribute(&primitive, Semantic::Positions, 3, vertex_count, buffers.as_slice())?.expect("The primitive has positions.");
+            mesh.positions.extend(positions.chunks(3).map(|position| [position[0], position[1], position[2]]));
+
+            match read_attribute(&primitive, Semantic::Normals, 3, vertex_count, buffers.as_slice())? {
+                Some(ref normals) if has_normals => mesh.normals.extend(normals.chunks(3).map(|normal| [normal[0], normal[1], normal[2]])),
+                _ => has_normals = false,
+            }
+            match read_attribute(&primitive, Semantic::TexCoords(0), 2, vertex_count, buffers.as_slice())? {
+                Some(ref tex_coords) if has_tex_coords => mesh.tex_coords.extend(tex_coords.chunks(2).map(|tex_coord| [tex_coord[0], tex_coord[1]])),
+                _ => has_tex_coords = false,
+            }
+            match read_attribute(&primitive, Semantic::Tangents, 4, vertex_count, buffers.as_slice())? {
+                Some(ref tangents) if has_tangents => mesh.tangents.extend(tangents.chunks(4).map(|tangent| [tangent[0], tangent[1], tangent[2], tangent[3]])),
+                _ => has_tangents = false,
+            }
+
+            //The indices come from the file, they must refer to the vertices of their primitive.
+            let indices: Vec<u32> = match primitive.indices() {
+                Some(accessor) => read_accessor(&accessor, buffers.as_slice())?.into_iter()
+                    .map(|index| if index >= 0.0 && index < vertex_count as f64 {
+                        Ok((base + index as usize) as u32)
+                    } else {
+                        Err(ResourceManagerError::ResourceError(format!("The glTF file {} has an index out of the {} vertices of its primitive !", path.as_ref().display(), vertex_count)))
+                    })
+                    .collect::<ResourceManagerResult<Vec<u32>>>()?,
+                None => (base as u32..(base + vertex_count) as u32).collect(),
+            };
+            if indices.len() % 3 != 0 {
+                return Err(ResourceManagerError::ResourceError(format!("The glTF file {} has a triangle list whose index count is not a multiple of 3 !", path.as_ref().display())));
+            }
+            mesh.primitives.push(MeshPrimitive {
+                indices,
+                material: None,
+            });
+        }
+    }
+
+    if !has_normals { mesh.normals.clear(); }
+    if !has_tex_coords { mesh.tex_coords.clear(); }
+    if !has_tangents { mesh.tangents.clear(); }
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod gltf_loader_test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn gltf_loader_extract_mesh() {
+        let gltf = Gltf::from_reader(fs::File::open("gltf_resource/untitled.gltf").expect("Could not open the gltf file.")).expect("Could not parse the gltf file.")
+            .validate_completely().expect("Could not validate the gltf file.");
+        let mut read_buffers = Vec::new();
+        let mesh = gltf_mesh(&gltf, "gltf_resource/untitled.gltf", |uri| {
+            read_buffers.push(String::from(uri));
+            Ok(fs::read(Path::new("gltf_resource").join(uri))?)
+        }).expect("Could not extract the mesh.");
+        assert_eq!(read_buffers, vec![String::from("untitled.bin")]);
+        assert_eq!(mesh.vertex_count(), 24);
+        assert_eq!(mesh.triangle_count(), 12);
+        assert!(mesh.has_normals());
+        assert!(gltf_mesh(&gltf, "gltf_resource/untitled.gltf", |_| Ok(Vec::new())).is_err());
+    }
+
+    //A triangle and a line, with their buffer embedded in a data URI.
+    fn embedded_gltf(buffer: &str) -> Gltf {
+        let document = format!(r#"{{
+            "asset": {{"version": "2.0"}},
+            "buffers": [{{"byteLength": 44, "uri": "data:application/octet-stream;base64,{}"}}],
+            "bufferViews": [{{"buffer": 0, "byteOffset": 0, "byteLength": 36}}, {{"buffer": 0, "byteOffset": 36, "byteLength": 6}}],
+            "accessors": [
+                {{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0]}},
+                {{"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}}
+            ],
+            "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0}}, "indices": 1, "mode": 4}}, {{"attributes": {{"POSITION": 0}}, "mode": 1}}]}}]
+        }}"#, buffer);
+        Gltf::from_reader(document.as_bytes()).expect("Could not parse the gltf file.").validate_completely().expect("Could not validate the gltf file.")
+    }
+
+    #[test]
+    fn gltf_loader_extract_embedded_mesh() {
+        let gltf = embedded_gltf("AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIAAAA=");
+        let mesh = gltf_mesh(&gltf, "embedded.gltf", |uri| panic!("The buffer {} should not be read.", uri)).expect("Could not extract the mesh.");
+        assert_eq!(mesh.vertex_count(), 3);
+        assert_eq!(mesh.triangle_count(), 1);
+        assert_eq!(mesh.positions[1], [1.0, 0.0, 0.0]);
+
+        //The third index, 7, is out of the vertices of the triangle.
+        let gltf = embedded_gltf("AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAcAAAA=");
+        assert!(gltf_mesh(&gltf, "embedded.gltf", |_| Ok(Vec::new())).is_err());
+    }
+}
@@ -6,15 +6,31 @@
 // copied, modified, or distributed except according to those terms.
 
 use std::collections::HashMap;
+use std::collections::hash_map::Iter;
 use std::path::{PathBuf, Path};
+use std::mem;
 use gltf::Gltf;
+use resources::memory_budget::MemorySize;
 
+//The size of the source document is kept to estimate the memory used by the parsed document.
 #[derive(Debug)]
-pub struct GltfResource(Gltf);
+pub struct GltfResource(Gltf, usize);
+
+impl GltfResource {
+    pub fn with_source_size(gltf: Gltf, source_size: usize) -> Self {
+        GltfResource(gltf, source_size)
+    }
+}
 
 impl From<Gltf> for GltfResource {
     fn from(gltf: Gltf) -> Self {
-        GltfResource(gltf)
+        GltfResource(gltf, 0)
+    }
+}
+
+impl MemorySize for GltfResource {
+    fn memory_size(&self) -> usize {
+        mem::size_of::<GltfResource>() + self.1
     }
 }
 
@@ -67,6 +83,11 @@ impl<'a> GltfRegistry<'a> {
         self.0.insert(path.into(),gltf_res)
     }
 
+    pub fn iter(&self) -> Iter<PathBuf, &'a GltfResource> {
+        debug!("Getting an iterator of the GltfRegistry.");
+        self.0.iter()
+    }
+
     pub fn clear(&mut self) {
         debug!("Clearing the gltf registry.");
         self.0.clear();
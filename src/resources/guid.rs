@@ -0,0 +1,348 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::fs::{self, File};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use toml::{self, Value};
+use toml::value::Table;
+use resources::pak::normalize_pak_path;
+use resources::vfs::{VFS_SCHEME, virtual_path_relative};
+use resources::resource_meta::{ResourceMeta, META_EXTENSION};
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+
+/// The scheme of the references to a resource by GUID, like `guid://3f2a5c1e-8b4d-4f6a-9c2e-1d7b8a9f0e3c`.
+pub const GUID_SCHEME: &str = "guid://";
+
+static GUID_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A globally unique identifier of an asset, which stays the same when the asset is moved.
+///
+/// It is formatted like a random UUID (version 4).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ResourceGuid(u64, u64);
+
+impl ResourceGuid {
+    /// Generates a new random GUID.
+    pub fn generate() -> Self {
+        //The hasher of a RandomState is randomly keyed, the time and the counter make every call different.
+        let random_u64 = |salt: u64| {
+            let mut hasher = RandomState::new().build_hasher();
+            salt.hash(&mut hasher);
+            GUID_COUNTER.fetch_add(1, Ordering::SeqCst).hash(&mut hasher);
+            if let Ok(duration) = SystemTime::now().duration_since(UNIX_EPOCH) {
+                duration.as_secs().hash(&mut hasher);
+                duration.subsec_nanos().hash(&mut hasher);
+            }
+            hasher.finish()
+        };
+
+        //Version 4 and variant 1, like a random UUID.
+        let high = (random_u64(0) & 0xffff_ffff_ffff_0fff) | 0x0000_0000_0000_4000;
+        let low = (random_u64(1) & 0x3fff_ffff_ffff_ffff) | 0x8000_0000_0000_0000;
+        ResourceGuid(high, low)
+    }
+
+    pub fn from_parts(high: u64, low: u64) -> Self {
+        ResourceGuid(high, low)
+    }
+
+    /// Parses a GUID made of 32 hexadecimal digits, hyphens being ignored.
+    pub fn parse(guid: &str) -> ResourceManagerResult<Self> {
+        let digits: String = guid.chars().filter(|&character| character != '-').collect();
+        if digits.len() != 32 || !digits.chars().all(|character| character.is_digit(16)) {
+            return Err(ResourceManagerError::ResourceError(format!("{} is not a valid GUID !", guid)));
+        }
+        let high = u64::from_str_radix(&digits[..16], 16).expect("The digits have been checked.");
+        let low = u64::from_str_radix(&digits[16..], 16).expect("The digits have been checked.");
+        Ok(ResourceGuid(high, low))
+    }
+
+    /// The reference to the resource by GUID, like `guid://3f2a5c1e-8b4d-4f6a-9c2e-1d7b8a9f0e3c`.
+    pub fn reference(&self) -> String {
+        format!("{}{}", GUID_SCHEME, self)
+    }
+
+    /// Parses a reference to a resource by GUID. Returns `None` if the reference does not start with `guid://`.
+    pub fn from_reference(reference: &str) -> Option<ResourceManagerResult<Self>> {
+        if reference.starts_with(GUID_SCHEME) {
+            Some(ResourceGuid::parse(&reference[GUID_SCHEME.len()..]))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for ResourceGuid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}", self.0 >> 32, (self.0 >> 16) & 0xffff, self.0 & 0xffff, self.1 >> 48, self.1 & 0xffff_ffff_ffff)
+    }
+}
+
+impl fmt::Debug for ResourceGuid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ResourceGuid({})", self)
+    }
+}
+
+/// Reads the GUID in the sidecar meta file of an asset. Returns `None` if the asset has no meta file, or no GUID.
+pub fn read_meta_guid<P: AsRef<Path>>(asset: P) -> ResourceManagerResult<Option<ResourceGuid>> {
+    debug!("Reading the GUID of the asset at path {}.", asset.as_ref().display());
+    Ok(ResourceMeta::read(asset)?.and_then(|meta| meta.guid))
+}
+
+/// Returns the GUID of an asset, after generating it and writing it in its sidecar meta file if it had none.
+///
+/// The other entries of the meta file are kept, but not its comments.
+pub fn assign_guid<P: AsRef<Path>>(asset: P) -> ResourceManagerResult<ResourceGuid> {
+    debug!("Assigning a GUID to the asset at path {}.", asset.as_ref().display());
+    let mut meta = ResourceMeta::read(asset.as_ref())?.unwrap_or_else(ResourceMeta::new);
+    if let Some(guid) = meta.guid {
+        return Ok(guid);
+    }
+
+    let guid = ResourceGuid::generate();
+    meta.guid = Some(guid);
+    meta.write(asset)?;
+    Ok(guid)
+}
+
+/// The two-way mapping between the GUIDs of the assets and their paths.
+///
+/// Levels reference their resources by GUID, the database gives their current paths.
+#[derive(Debug, Default, Clone)]
+pub struct GuidDatabase {
+    paths: HashMap<ResourceGuid, PathBuf>,
+    guids: HashMap<PathBuf, ResourceGuid>,
+}
+
+impl GuidDatabase {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Maps a GUID to a path. A GUID and a path can only be mapped once.
+    pub fn insert<P: Into<PathBuf>>(&mut self, guid: ResourceGuid, path: P) -> ResourceManagerResult<()> {
+        let path = path.into();
+        debug!("Inserting the GUID {} of the path {} in the GUID database.", guid, path.display());
+        if let Some(other_path) = self.paths.get(&guid) {
+            if *other_path != path {
+                return Err(ResourceManagerError::ResourceError(format!("The GUID {} of {} is already used by {} !", guid, path.display(), other_path.display())));
+            }
+        }
+        if let Some(other_guid) = self.guids.get(path.as_path()) {
+            if *other_guid != guid {
+                return Err(ResourceManagerError::ResourceError(format!("The path {} already has the GUID {}, it cannot have the GUID {} !", path.display(), other_guid, guid)));
+            }
+        }
+        self.paths.insert(guid, path.clone());
+        self.guids.insert(path, guid);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, guid: ResourceGuid) -> Option<PathBuf> {
+        debug!("Removing the GUID {} from the GUID database.", guid);
+        let path = self.paths.remove(&guid)?;
+        self.guids.remove(path.as_path());
+        Some(path)
+    }
+
+    /// Maps a GUID to the new path of its asset, after it has been moved.
+    pub fn set_path<P: Into<PathBuf>>(&mut self, guid: ResourceGuid, path: P) -> ResourceManagerResult<()> {
+        let path = path.into();
+        debug!("Moving the asset with the GUID {} to the path {} in the GUID database.", guid, path.display());
+        if let Some(other_guid) = self.guids.get(path.as_path()) {
+            if *other_guid != guid {
+                return Err(ResourceManagerError::ResourceError(format!("The path {} already has the GUID {} !", path.display(), other_guid)));
+            }
+        }
+        if let Some(old_path) = self.paths.insert(guid, path.clone()) {
+            self.guids.remove(old_path.as_path());
+        }
+        self.guids.insert(path, guid);
+        Ok(())
+    }
+
+    pub fn path_of(&self, guid: ResourceGuid) -> Option<&Path> {
+        self.paths.get(&guid).map(PathBuf::as_path)
+    }
+
+    pub fn guid_of<P: AsRef<Path>>(&self, path: P) -> Option<ResourceGuid> {
+        self.guids.get(path.as_ref()).cloned()
+    }
+
+    pub fn contains(&self, guid: ResourceGuid) -> bool {
+        self.paths.contains_key(&guid)
+    }
+
+    pub fn iter<'b>(&'b self) -> Box<Iterator<Item = (ResourceGuid, &'b Path)> + 'b> {
+        Box::new(self.paths.iter().map(|(guid, path)| (*guid, path.as_path())))
+    }
+
+    /// Adds the mappings of another database, failing on the first conflicting mapping.
+    pub fn merge(&mut self, other: &GuidDatabase) -> ResourceManagerResult<()> {
+        debug!("Merging two GUID databases.");
+        for (guid, path) in other.iter() {
+            self.insert(guid, path)?;
+        }
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        debug!("Clearing the GUID database.");
+        self.paths.clear();
+        self.guids.clear();
+    }
+
+    /// Builds the database from the meta files of the assets of a directory, and of its subdirectories.
+    ///
+    /// The assets get virtual paths, under `virtual_root`, like `res://textures`.
+    pub fn scan_directory<P, V>(directory: P, virtual_root: V) -> ResourceManagerResult<GuidDatabase> where
+        P: AsRef<Path>,
+        V: AsRef<Path>,
+    {
+        let (database, problems) = GuidDatabase::scan_directory_by_asset(directory, virtual_root)?;
+        match problems.into_iter().next() {
+            Some((_, error)) => Err(error),
+            None => Ok(database),
+        }
+    }
+
+    /// Builds the database like `scan_directory`, skipping the meta files which cannot be read or whose GUID is already used.
+    ///
+    /// Their assets are returned with the error, by path relative to the directory. Only the errors reading the directory stop the scan.
+    pub fn scan_directory_by_asset<P, V>(directory: P, virtual_root: V) -> ResourceManagerResult<(GuidDatabase, Vec<(PathBuf, ResourceManagerError)>)> where
+        P: AsRef<Path>,
+        V: AsRef<Path>,
+    {
+        debug!("Scanning the meta files of the directory {}.", directory.as_ref().display());
+        let mut database = GuidDatabase::new();
+        let mut problems = Vec::new();
+        let virtual_root = virtual_path_relative(virtual_root.as_ref()).unwrap_or_else(|| normalize_pak_path(virtual_root.as_ref()));
+        database.scan(directory.as_ref(), directory.as_ref(), virtual_root.as_str(), &mut problems)?;
+        Ok((database, problems))
+    }
+
+    fn scan(&mut self, root: &Path, directory: &Path, virtual_directory: &str, problems: &mut Vec<(PathBuf, ResourceManagerError)>) -> ResourceManagerResult<()> {
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            let name = match path.file_name().and_then(|name| name.to_str()) {
+                Some(name) => name.to_owned(),
+                None => continue,
+            };
+            let virtual_path = if virtual_directory.is_empty() { name.clone() } else { format!("{}/{}", virtual_directory, name) };
+
+            if path.is_dir() {
+                self.scan(root, path.as_path(), virtual_path.as_str(), problems)?;
+            } else if path.extension().map(|extension| extension == META_EXTENSION).unwrap_or(false) {
+                let asset_virtual_path = &virtual_path[..virtual_path.len() - META_EXTENSION.len() - 1];
+                let result = File::open(path.as_path())
+                    .map_err(ResourceManagerError::from)
+                    .and_then(|mut file| ResourceMeta::read_from(&mut file))
+                    .map_err(|error| ResourceManagerError::ResourceError(format!("Could not read the meta file {}: {}", path.display(), error)))
+                    .and_then(|meta| match meta.guid {
+                        Some(guid) => self.insert(guid, format!("{}{}", VFS_SCHEME, asset_virtual_path)),
+                        None => Ok(()),
+                    });
+                if let Err(error) = result {
+                    let asset = path.with_extension("");
+                    problems.push((asset.strip_prefix(root).map(Path::to_path_buf).unwrap_or(asset), error));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a database written by `write_to`.
+    pub fn read_from<R: Read>(reader: &mut R) -> ResourceManagerResult<GuidDatabase> {
+        debug!("Reading a GUID database.");
+        let mut content = String::new();
+        reader.read_to_string(&mut content)?;
+        let mut database = GuidDatabase::new();
+        let value: Value = toml::from_str(content.as_str())?;
+        let guids = match value.get("guids") {
+            Some(&Value::Table(ref guids)) => guids,
+            Some(_) => return Err(ResourceManagerError::ResourceError(String::from("The guids of the GUID database are not a toml table !"))),
+            None => return Ok(database),
+        };
+        for (guid, path) in guids.iter() {
+            match path.as_str() {
+                Some(path) => database.insert(ResourceGuid::parse(guid.as_str())?, path)?,
+                None => return Err(ResourceManagerError::ResourceError(format!("The path of the GUID {} is not a string !", guid))),
+            }
+        }
+        Ok(database)
+    }
+
+    /// Writes the database as a toml file, with a `guids` table mapping the GUIDs to the paths.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> ResourceManagerResult<()> {
+        debug!("Writing a GUID database.");
+        let mut guids = Table::new();
+        for (guid, path) in self.iter() {
+            guids.insert(guid.to_string(), Value::String(path.to_string_lossy().into_owned()));
+        }
+        let mut database = Table::new();
+        database.insert(String::from("guids"), Value::Table(guids));
+        let content = toml::to_string(&Value::Table(database)).map_err(|error| ResourceManagerError::ResourceError(format!("Could not write the GUID database: {}", error)))?;
+        writer.write_all(content.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod guid_test {
+    use super::*;
+    use resources::resource_meta::meta_path;
+
+    #[test]
+    fn guid_database_map_guids_and_paths() {
+        let guid = ResourceGuid::generate();
+        assert!(guid != ResourceGuid::generate());
+        assert_eq!(ResourceGuid::parse(guid.to_string().as_str()).expect("Could not parse the GUID."), guid);
+        assert_eq!(ResourceGuid::from_reference(guid.reference().as_str()).expect("Not a GUID reference.").expect("Could not parse the GUID."), guid);
+        assert!(ResourceGuid::from_reference("res://a.tga").is_none());
+        assert!(ResourceGuid::parse("3f2a5c1e-8b4d").is_err());
+        assert_eq!(ResourceGuid::from_parts(0x3f2a_5c1e_8b4d_4f6a, 0x9c2e_1d7b_8a9f_0e3c).to_string(), "3f2a5c1e-8b4d-4f6a-9c2e-1d7b8a9f0e3c");
+
+        let directory = ::std::env::temp_dir().join("maskerad_guid_test");
+        fs::create_dir_all(directory.join("textures")).expect("Could not create the test directory.");
+        let texture = directory.join("textures/wall.tga");
+        let _ = fs::remove_file(meta_path(texture.as_path()));
+        assert_eq!(read_meta_guid(texture.as_path()).expect("Could not read the meta file."), None);
+        let texture_guid = assign_guid(texture.as_path()).expect("Could not assign a GUID.");
+        assert_eq!(assign_guid(texture.as_path()).expect("Could not assign a GUID."), texture_guid);
+
+        let mut database = GuidDatabase::scan_directory(directory.as_path(), "res://").expect("Could not scan the directory.");
+        assert_eq!(database.path_of(texture_guid), Some(Path::new("res://textures/wall.tga")));
+        assert!(database.insert(texture_guid, "res://textures/floor.tga").is_err());
+
+        //The asset is moved, its GUID stays the same.
+        database.set_path(texture_guid, "res://textures/walls/wall.tga").expect("Could not move the asset.");
+        assert_eq!(database.guid_of("res://textures/walls/wall.tga"), Some(texture_guid));
+        assert_eq!(database.guid_of("res://textures/wall.tga"), None);
+
+        let mut bytes = Vec::new();
+        database.write_to(&mut bytes).expect("Could not write the database.");
+        let read_database = GuidDatabase::read_from(&mut bytes.as_slice()).expect("Could not read the database.");
+        assert_eq!(read_database.path_of(texture_guid), Some(Path::new("res://textures/walls/wall.tga")));
+        assert_eq!(read_database.len(), 1);
+    }
+}
@@ -0,0 +1,340 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+
+/// Alignment of the blocks of a heap region, relative to its start.
+pub const HEAP_ALIGNMENT: usize = 16;
+
+fn align(size: usize) -> usize {
+    (size + HEAP_ALIGNMENT - 1) / HEAP_ALIGNMENT * HEAP_ALIGNMENT
+}
+
+/// A handle to a block of a HeapRegion.
+///
+/// The block may be moved by a defragmentation, the handle stays valid until the block is freed.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HeapHandle {
+    index: u32,
+    generation: u32,
+}
+
+impl fmt::Debug for HeapHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HeapHandle({}, generation {})", self.index, self.generation)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct HeapBlock {
+    offset: usize,
+    size: usize,
+    aligned_size: usize,
+}
+
+#[derive(Debug)]
+struct HeapEntry {
+    generation: u32,
+    block: Option<HeapBlock>,
+}
+
+/// A general-purpose memory region, whose blocks can be freed one by one.
+///
+/// Blocks are allocated in the smallest free block big enough (best fit), and freed blocks are coalesced with
+/// their free neighbours. The blocks are only accessed through their handles, which lets `defragment_step`
+/// move them toward the start of the region to gather the free memory in one block.
+pub struct HeapRegion {
+    data: Vec<u8>,
+    entries: Vec<HeapEntry>,
+    free_entries: Vec<u32>,
+    //offset -> size of the free blocks.
+    free_blocks: BTreeMap<usize, usize>,
+    //offset -> entry of the allocated blocks.
+    allocated_blocks: BTreeMap<usize, u32>,
+}
+
+impl fmt::Debug for HeapRegion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HeapRegion {{ capacity: {}, used: {}, blocks: {}, free blocks: {} }}", self.capacity(), self.used(), self.allocated_blocks.len(), self.free_blocks.len())
+    }
+}
+
+impl HeapRegion {
+    pub fn with_capacity(capacity: usize) -> Self {
+        debug!("Creating a HeapRegion with a capacity of {} bytes.", capacity);
+        let capacity = capacity / HEAP_ALIGNMENT * HEAP_ALIGNMENT;
+        let mut free_blocks = BTreeMap::new();
+        if capacity > 0 {
+            free_blocks.insert(0, capacity);
+        }
+
+        HeapRegion {
+            data: vec![0; capacity],
+            entries: Vec::new(),
+            free_entries: Vec::new(),
+            free_blocks,
+            allocated_blocks: BTreeMap::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Bytes taken by the allocated blocks, alignment padding included.
+    pub fn used(&self) -> usize {
+        self.capacity() - self.free()
+    }
+
+    pub fn free(&self) -> usize {
+        self.free_blocks.values().sum()
+    }
+
+    pub fn largest_free_block(&self) -> usize {
+        self.free_blocks.values().cloned().max().unwrap_or(0)
+    }
+
+    /// The fraction of the free memory which is not in the largest free block.
+    pub fn fragmentation(&self) -> f32 {
+        let free = self.free();
+        if free == 0 {
+            0.0
+        } else {
+            1.0 - self.largest_free_block() as f32 / free as f32
+        }
+    }
+
+    /// Whether a block of `size` bytes fits in the free memory but in none of the free blocks,
+    /// a defragmentation would make room for it.
+    pub fn needs_defragmentation(&self, size: usize) -> bool {
+        let aligned_size = align(size.max(1));
+        self.free() >= aligned_size && self.largest_free_block() < aligned_size
+    }
+
+    pub fn block_count(&self) -> usize {
+        self.allocated_blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allocated_blocks.is_empty()
+    }
+
+    /// Allocates a zeroed block of `size` bytes.
+    pub fn alloc(&mut self, size: usize) -> ResourceManagerResult<HeapHandle> {
+        debug!("Allocating {} bytes in a HeapRegion.", size);
+        let aligned_size = align(size.max(1));
+        let best_fit = self.free_blocks.iter()
+            .filter(|&(_, &free_size)| free_size >= aligned_size)
+            .min_by_key(|&(_, &free_size)| free_size)
+            .map(|(&offset, &free_size)| (offset, free_size));
+
+        let (offset, free_size) = match best_fit {
+            Some(best_fit) => best_fit,
+            None => {
+                error!("The HeapRegion has no free block big enough.");
+                return Err(ResourceManagerError::ResourceError(format!("The heap region cannot allocate {} bytes, its largest free block has {} bytes ({} bytes free) !", size, self.largest_free_block(), self.free())));
+            },
+        };
+
+        self.free_blocks.remove(&offset);
+        if free_size > aligned_size {
+            self.free_blocks.insert(offset + aligned_size, free_size - aligned_size);
+        }
+        for byte in self.data[offset..offset + aligned_size].iter_mut() {
+            *byte = 0;
+        }
+
+        let block = HeapBlock {
+            offset,
+            size,
+            aligned_size,
+        };
+        let index = match self.free_entries.pop() {
+            Some(index) => {
+                self.entries[index as usize].block = Some(block);
+                index
+            },
+            None => {
+                self.entries.push(HeapEntry {
+                    generation: 0,
+                    block: Some(block),
+                });
+                (self.entries.len() - 1) as u32
+            },
+        };
+        self.allocated_blocks.insert(offset, index);
+
+        Ok(HeapHandle {
+            index,
+            generation: self.entries[index as usize].generation,
+        })
+    }
+
+    /// Allocates a block holding a copy of `bytes`.
+    pub fn alloc_bytes(&mut self, bytes: &[u8]) -> ResourceManagerResult<HeapHandle> {
+        let handle = self.alloc(bytes.len())?;
+        self.get_mut(handle).expect("The block has just been allocated.").copy_from_slice(bytes);
+        Ok(handle)
+    }
+
+    fn block(&self, handle: HeapHandle) -> Option<HeapBlock> {
+        match self.entries.get(handle.index as usize) {
+            Some(entry) if entry.generation == handle.generation => entry.block,
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, handle: HeapHandle) -> bool {
+        self.block(handle).is_some()
+    }
+
+    pub fn get(&self, handle: HeapHandle) -> Option<&[u8]> {
+        self.block(handle).map(move |block| &self.data[block.offset..block.offset + block.size])
+    }
+
+    pub fn get_mut(&mut self, handle: HeapHandle) -> Option<&mut [u8]> {
+        match self.block(handle) {
+            Some(block) => Some(&mut self.data[block.offset..block.offset + block.size]),
+            None => None,
+        }
+    }
+
+    /// Frees a block, and returns `false` if the handle was not valid.
+    pub fn free_block(&mut self, handle: HeapHandle) -> bool {
+        debug!("Freeing a block of a HeapRegion.");
+        let block = match self.block(handle) {
+            Some(block) => block,
+            None => return false,
+        };
+
+        {
+            let entry = &mut self.entries[handle.index as usize];
+            entry.block = None;
+            entry.generation = entry.generation.wrapping_add(1);
+        }
+        self.free_entries.push(handle.index);
+        self.allocated_blocks.remove(&block.offset);
+        self.insert_free_block(block.offset, block.aligned_size);
+        true
+    }
+
+    //Inserts a free block, coalesced with the free blocks around it.
+    fn insert_free_block(&mut self, mut offset: usize, mut size: usize) {
+        let previous = self.free_blocks.range(..offset).next_back().map(|(&previous_offset, &previous_size)| (previous_offset, previous_size));
+        if let Some((previous_offset, previous_size)) = previous {
+            if previous_offset + previous_size == offset {
+                self.free_blocks.remove(&previous_offset);
+                offset = previous_offset;
+                size += previous_size;
+            }
+        }
+        if let Some(next_size) = self.free_blocks.remove(&(offset + size)) {
+            size += next_size;
+        }
+        self.free_blocks.insert(offset, size);
+    }
+
+    /// Frees every block. The handles given until now become invalid.
+    pub fn clear(&mut self) {
+        debug!("Clearing a HeapRegion.");
+        for (index, entry) in self.entries.iter_mut().enumerate() {
+            if entry.block.take().is_some() {
+                entry.generation = entry.generation.wrapping_add(1);
+                self.free_entries.push(index as u32);
+            }
+        }
+        self.allocated_blocks.clear();
+        self.free_blocks.clear();
+        if self.capacity() > 0 {
+            self.free_blocks.insert(0, self.capacity());
+        }
+    }
+
+    /// Moves blocks toward the start of the region, until about `max_bytes` bytes have been moved.
+    ///
+    /// At least one block is moved if the region is fragmented, so that repeated calls always make progress.
+    /// The handles of the moved blocks stay valid. Returns the number of bytes moved, 0 when the region is compact.
+    pub fn defragment_step(&mut self, max_bytes: usize) -> usize {
+        debug!("Defragmenting a HeapRegion, moving at most {} bytes.", max_bytes);
+        let mut moved = 0;
+        loop {
+            let (free_offset, free_size) = match self.free_blocks.iter().next() {
+                Some((&free_offset, &free_size)) => (free_offset, free_size),
+                None => break,
+            };
+            //Free blocks are coalesced, the first free block is followed by a block to move, or by the end of the region.
+            let index = match self.allocated_blocks.get(&(free_offset + free_size)) {
+                Some(&index) => index,
+                None => break,
+            };
+            let mut block = self.entries[index as usize].block.expect("An allocated block has no entry.");
+            if moved > 0 && moved + block.aligned_size > max_bytes {
+                break;
+            }
+
+            self.data.copy_within(block.offset..block.offset + block.aligned_size, free_offset);
+            self.allocated_blocks.remove(&block.offset);
+            self.allocated_blocks.insert(free_offset, index);
+            self.free_blocks.remove(&free_offset);
+            block.offset = free_offset;
+            self.entries[index as usize].block = Some(block);
+            self.insert_free_block(free_offset + block.aligned_size, free_size);
+            moved += block.aligned_size;
+        }
+        moved
+    }
+
+    /// Moves every block toward the start of the region, leaving a single free block at its end.
+    pub fn defragment(&mut self) -> usize {
+        self.defragment_step(::std::usize::MAX)
+    }
+}
+
+#[cfg(test)]
+mod heap_region_test {
+    use super::*;
+
+    #[test]
+    fn heap_region_free_individually_and_defragment() {
+        let mut heap = HeapRegion::with_capacity(80);
+        let first = heap.alloc_bytes(&[1; 16]).expect("Could not allocate.");
+        let second = heap.alloc_bytes(&[2; 20]).expect("Could not allocate.");
+        let third = heap.alloc_bytes(&[3; 10]).expect("Could not allocate.");
+        assert_eq!(heap.used(), 64);
+
+        assert!(heap.free_block(first));
+        assert!(!heap.free_block(first));
+        assert_eq!(heap.get(first), None);
+        assert_eq!(heap.free(), 32);
+        assert!(heap.alloc(32).is_err());
+        assert_eq!(heap.fragmentation(), 0.5);
+        assert!(heap.needs_defragmentation(32));
+        assert!(!heap.needs_defragmentation(16));
+        assert!(!heap.needs_defragmentation(48));
+
+        //The second and third blocks are moved to the start, they can still be used through their handles.
+        assert_eq!(heap.defragment_step(1), 32);
+        assert_eq!(heap.defragment_step(1), 16);
+        assert_eq!(heap.defragment_step(1), 0);
+        assert_eq!(heap.largest_free_block(), 32);
+        assert_eq!(heap.fragmentation(), 0.0);
+        assert_eq!(heap.get(second), Some(&[2; 20][..]));
+        assert_eq!(heap.get(third), Some(&[3; 10][..]));
+
+        //Freed blocks are coalesced with their free neighbours.
+        let fourth = heap.alloc_bytes(&[4; 32]).expect("Could not allocate.");
+        assert!(heap.free_block(second));
+        assert!(heap.free_block(third));
+        assert_eq!(heap.largest_free_block(), 48);
+        assert_eq!(heap.get(fourth), Some(&[4; 32][..]));
+        heap.clear();
+        assert_eq!(heap.largest_free_block(), 80);
+        assert!(heap.is_empty());
+    }
+}
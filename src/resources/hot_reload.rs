@@ -0,0 +1,152 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+//The modification time and the size of a file. The size catches the changes made within the resolution of the clock.
+type FileStamp = (Option<SystemTime>, u64);
+
+fn file_stamp<P: AsRef<Path>>(path: P) -> Option<FileStamp> {
+    fs::metadata(path.as_ref()).ok().map(|metadata| (metadata.modified().ok(), metadata.len()))
+}
+
+/// A resource which has been reloaded, with the resources depending on it which may need to refresh.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReloadEvent {
+    pub path: PathBuf,
+    pub dependents: Vec<PathBuf>,
+}
+
+/// Polls the source files of loaded resources, to find the resources which must be reloaded.
+///
+/// A resource can have several source files, like a mesh and its material libraries.
+#[derive(Debug, Default)]
+pub struct HotReloadWatcher {
+    //resource path -> source file -> stamp of the source file when it was last loaded.
+    watched: HashMap<PathBuf, HashMap<PathBuf, Option<FileStamp>>>,
+}
+
+impl HotReloadWatcher {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Watches a source file of a resource, from its current state.
+    pub fn watch<P, S>(&mut self, path: P, source: S) where
+        P: Into<PathBuf>,
+        S: Into<PathBuf>,
+    {
+        let source = source.into();
+        debug!("Watching the source file {} of a resource.", source.display());
+        let stamp = file_stamp(source.as_path());
+        self.watched.entry(path.into()).or_insert_with(HashMap::new).insert(source, stamp);
+    }
+
+    pub fn unwatch<P: AsRef<Path>>(&mut self, path: P) -> bool {
+        debug!("Unwatching the resource with path {}.", path.as_ref().display());
+        self.watched.remove(path.as_ref()).is_some()
+    }
+
+    /// Keeps watching only the resources for which the predicate returns true.
+    pub fn retain<F>(&mut self, mut predicate: F) where
+        F: FnMut(&Path) -> bool,
+    {
+        debug!("Unwatching the resources which are not retained.");
+        self.watched.retain(|path, _| predicate(path.as_path()));
+    }
+
+    pub fn is_watched<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.watched.contains_key(path.as_ref())
+    }
+
+    /// The source files of a watched resource.
+    pub fn sources_of<P: AsRef<Path>>(&self, path: P) -> Vec<PathBuf> {
+        match self.watched.get(path.as_ref()) {
+            Some(sources) => sources.keys().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.watched.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watched.is_empty()
+    }
+
+    /// Returns the resources having a source file changed since the last poll, and records the new state of their source files.
+    ///
+    /// A source file which cannot be read, while it is being saved for example, is not considered changed.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        trace!("Polling the source files of the watched resources.");
+        let mut changed = Vec::new();
+        for (path, sources) in self.watched.iter_mut() {
+            let mut is_changed = false;
+            for (source, stamp) in sources.iter_mut() {
+                let new_stamp = match file_stamp(source.as_path()) {
+                    Some(new_stamp) => Some(new_stamp),
+                    None => continue,
+                };
+                if *stamp != new_stamp {
+                    *stamp = new_stamp;
+                    is_changed = true;
+                }
+            }
+            if is_changed {
+                changed.push(path.clone());
+            }
+        }
+        changed.sort();
+        changed
+    }
+
+    pub fn clear(&mut self) {
+        debug!("Clearing the HotReloadWatcher.");
+        self.watched.clear();
+    }
+}
+
+#[cfg(test)]
+mod hot_reload_test {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+
+    #[test]
+    fn hot_reload_watcher_poll_changed_sources() {
+        let directory = ::std::env::temp_dir().join(format!("maskerad_hot_reload_watcher_test_{}", ::std::process::id()));
+        fs::create_dir_all(directory.as_path()).expect("Could not create the test directory.");
+        let mesh = directory.join("mesh.obj");
+        let library = directory.join("mesh.mtl");
+        File::create(mesh.as_path()).expect("Could not create the mesh.").write_all(b"v 0 0 0\n").expect("Could not write the mesh.");
+        File::create(library.as_path()).expect("Could not create the library.").write_all(b"newmtl a\n").expect("Could not write the library.");
+
+        let mut watcher = HotReloadWatcher::new();
+        watcher.watch("res://mesh.obj", mesh.as_path());
+        watcher.watch("res://mesh.obj", library.as_path());
+        assert_eq!(watcher.len(), 1);
+        assert!(watcher.poll().is_empty());
+
+        //A change of any source file reloads the resource, once.
+        File::create(library.as_path()).expect("Could not create the library.").write_all(b"newmtl a\nKd 1 0 0\n").expect("Could not write the library.");
+        assert_eq!(watcher.poll(), vec![PathBuf::from("res://mesh.obj")]);
+        assert!(watcher.poll().is_empty());
+
+        //A missing source file is not a change.
+        fs::remove_file(mesh.as_path()).expect("Could not remove the mesh.");
+        assert!(watcher.poll().is_empty());
+        watcher.watch("res://mesh.mtl", library.as_path());
+        watcher.retain(|path| path == Path::new("res://mesh.obj"));
+        assert!(!watcher.is_watched("res://mesh.mtl"));
+        assert!(watcher.unwatch("res://mesh.obj"));
+        assert!(watcher.is_empty());
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// When the resident resources take more than `high` bytes, the unreferenced ones are evicted
+/// until they take `low` bytes or less.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheWatermarks {
+    pub high: usize,
+    pub low: usize,
+}
+
+impl CacheWatermarks {
+    pub fn new(high: usize, low: usize) -> Self {
+        CacheWatermarks {
+            high,
+            low: low.min(high),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CacheEntry {
+    last_access: u64,
+    references: u32,
+    bytes: usize,
+}
+
+/// Keeps track of the accesses and references of resident resources, to evict the least recently used ones first.
+///
+/// The timestamps are logical : every access increments a counter.
+#[derive(Debug, Default)]
+pub struct LruCache {
+    entries: HashMap<PathBuf, CacheEntry>,
+    clock: u64,
+    resident_bytes: usize,
+}
+
+impl LruCache {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// Starts tracking a resource taking `bytes` bytes, as just accessed and unreferenced.
+    pub fn insert<P: Into<PathBuf>>(&mut self, path: P, bytes: usize) {
+        debug!("Inserting a resource in the LruCache.");
+        let last_access = self.tick();
+        if let Some(old_entry) = self.entries.insert(path.into(), CacheEntry { last_access, references: 0, bytes }) {
+            self.resident_bytes -= old_entry.bytes;
+        }
+        self.resident_bytes += bytes;
+    }
+
+    /// Changes the size of a tracked resource, keeping its references. Returns false if the resource is not tracked.
+    pub fn resize<P: AsRef<Path>>(&mut self, path: P, bytes: usize) -> bool {
+        match self.entries.get_mut(path.as_ref()) {
+            Some(entry) => {
+                self.resident_bytes = self.resident_bytes - entry.bytes + bytes;
+                entry.bytes = bytes;
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn remove<P: AsRef<Path>>(&mut self, path: P) -> bool {
+        debug!("Removing a resource from the LruCache.");
+        match self.entries.remove(path.as_ref()) {
+            Some(entry) => {
+                self.resident_bytes -= entry.bytes;
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn contains<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.entries.contains_key(path.as_ref())
+    }
+
+    /// Updates the access timestamp of a resource.
+    pub fn touch<P: AsRef<Path>>(&mut self, path: P) {
+        let now = self.tick();
+        if let Some(entry) = self.entries.get_mut(path.as_ref()) {
+            entry.last_access = now;
+        }
+    }
+
+    pub fn last_access<P: AsRef<Path>>(&self, path: P) -> Option<u64> {
+        self.entries.get(path.as_ref()).map(|entry| entry.last_access)
+    }
+
+    /// A referenced resource is never evicted.
+    pub fn add_reference<P: AsRef<Path>>(&mut self, path: P) -> bool {
+        let now = self.tick();
+        match self.entries.get_mut(path.as_ref()) {
+            Some(entry) => {
+                entry.references += 1;
+                entry.last_access = now;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Returns the number of references left, `None` if the resource is not tracked.
+    pub fn remove_reference<P: AsRef<Path>>(&mut self, path: P) -> Option<u32> {
+        self.entries.get_mut(path.as_ref()).map(|entry| {
+            entry.references = entry.references.saturating_sub(1);
+            entry.references
+        })
+    }
+
+    pub fn references<P: AsRef<Path>>(&self, path: P) -> Option<u32> {
+        self.entries.get(path.as_ref()).map(|entry| entry.references)
+    }
+
+    pub fn resident_bytes(&self) -> usize {
+        self.resident_bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The unreferenced resources, least recently used first.
+    pub fn unreferenced(&self) -> Vec<PathBuf> {
+        let mut unreferenced: Vec<(&PathBuf, &CacheEntry)> = self.entries.iter()
+            .filter(|&(_, entry)| entry.references == 0)
+            .collect();
+        unreferenced.sort_by_key(|&(_, entry)| entry.last_access);
+        unreferenced.into_iter().map(|(path, _)| path.clone()).collect()
+    }
+
+    /// The resources to evict, least recently used first, if the resident resources exceed the high watermark.
+    pub fn eviction_candidates(&self, watermarks: CacheWatermarks) -> Vec<PathBuf> {
+        if self.resident_bytes <= watermarks.high {
+            return Vec::new();
+        }
+
+        let mut resident_bytes = self.resident_bytes;
+        self.unreferenced().into_iter()
+            .take_while(|path| {
+                if resident_bytes <= watermarks.low {
+                    return false;
+                }
+                resident_bytes -= self.entries[path].bytes;
+                true
+            })
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        debug!("Clearing the LruCache.");
+        self.entries.clear();
+        self.resident_bytes = 0;
+    }
+}
+
+#[cfg(test)]
+mod lru_cache_test {
+    use super::*;
+
+    #[test]
+    fn lru_cache_evict_least_recently_used_first() {
+        let mut cache = LruCache::new();
+        cache.insert("a", 10);
+        cache.insert("b", 10);
+        cache.insert("c", 10);
+        cache.insert("d", 10);
+        let watermarks = CacheWatermarks::new(35, 20);
+
+        cache.touch("a");
+        assert!(cache.add_reference("b"));
+        assert_eq!(cache.eviction_candidates(watermarks), vec![PathBuf::from("c"), PathBuf::from("d")]);
+        assert_eq!(cache.eviction_candidates(CacheWatermarks::new(40, 0)), Vec::<PathBuf>::new());
+
+        //Unreferenced resources stay resident until the high watermark is exceeded.
+        assert_eq!(cache.remove_reference("b"), Some(0));
+        assert!(cache.remove("c"));
+        assert!(cache.remove("d"));
+        assert_eq!(cache.resident_bytes(), 20);
+        assert!(cache.eviction_candidates(watermarks).is_empty());
+        assert_eq!(cache.unreferenced(), vec![PathBuf::from("a"), PathBuf::from("b")]);
+
+        //A reloaded resource keeps its references.
+        assert!(cache.add_reference("a"));
+        assert!(cache.resize("a", 30));
+        assert_eq!(cache.references("a"), Some(1));
+        assert_eq!(cache.resident_bytes(), 40);
+        assert!(!cache.resize("c", 10));
+    }
+}
@@ -0,0 +1,88 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+use memmap2::Mmap;
+use resources::pak::PakArchive;
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+
+/// A pak file mapped in memory.
+///
+/// The uncompressed entries are borrowed straight from the mapping, without being read into a buffer.
+/// The pak file must not be modified while it is mapped.
+pub struct MappedPakArchive {
+    archive: PakArchive,
+    mmap: Mmap,
+}
+
+impl fmt::Debug for MappedPakArchive {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MappedPakArchive {{ archive: {:?}, mapped bytes: {} }}", self.archive, self.mmap.len())
+    }
+}
+
+impl MappedPakArchive {
+    pub fn open<P: AsRef<Path>>(path: P) -> ResourceManagerResult<Self> {
+        debug!("Mapping the pak file at path {} in memory.", path.as_ref().display());
+        let archive = PakArchive::open(path.as_ref())?;
+        let file = File::open(path.as_ref())?;
+        //The mapping is only read, and pak files are not modified once built.
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        Ok(MappedPakArchive {
+            archive,
+            mmap,
+        })
+    }
+
+    /// The table of contents of the pak file. Its entries can also be read, decompressed, through it.
+    pub fn archive(&self) -> &PakArchive {
+        &self.archive
+    }
+
+    pub fn path(&self) -> &Path {
+        self.archive.path()
+    }
+
+    pub fn contains<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.archive.contains(path)
+    }
+
+    /// Borrows the data of an entry from the mapping.
+    ///
+    /// The entry must be uncompressed, and aligned on the alignment of the pak file.
+    pub fn entry_bytes<P: AsRef<Path>>(&self, path: P) -> ResourceManagerResult<&[u8]> {
+        debug!("Borrowing the entry {} of the mapped pak file at path {}.", path.as_ref().display(), self.path().display());
+        let entry = match self.archive.entry(path.as_ref()) {
+            Some(entry) => entry,
+            None => {
+                error!("The entry {} could not be found in the mapped pak file.", path.as_ref().display());
+                return Err(ResourceManagerError::ResourceError(format!("Could not find the entry {} in the pak file at path {} !", path.as_ref().display(), self.path().display())));
+            },
+        };
+
+        if entry.is_compressed() {
+            error!("The entry {} of the mapped pak file is compressed.", path.as_ref().display());
+            return Err(ResourceManagerError::ResourceError(format!("The entry {} of the pak file at path {} is compressed, it cannot be borrowed !", path.as_ref().display(), self.path().display())));
+        }
+        if self.archive.alignment() == 0 || entry.offset % self.archive.alignment() as u64 != 0 {
+            error!("The entry {} of the mapped pak file is not aligned.", path.as_ref().display());
+            return Err(ResourceManagerError::ResourceError(format!("The entry {} of the pak file at path {} is not aligned on {} bytes !", path.as_ref().display(), self.path().display(), self.archive.alignment())));
+        }
+        let end = match entry.offset.checked_add(entry.size) {
+            Some(end) if end <= self.mmap.len() as u64 => end,
+            _ => {
+                error!("The entry {} of the mapped pak file is truncated.", path.as_ref().display());
+                return Err(ResourceManagerError::ResourceError(format!("The entry {} of the pak file at path {} ends after the end of the file !", path.as_ref().display(), self.path().display())));
+            },
+        };
+
+        Ok(&self.mmap[entry.offset as usize..end as usize])
+    }
+}
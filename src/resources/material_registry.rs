@@ -0,0 +1,152 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Iter;
+use std::path::{PathBuf, Path};
+use std::mem;
+use resources::memory_budget::MemorySize;
+
+/// A material, as described by a Wavefront MTL file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialResource {
+    pub name: String,
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub emissive: [f32; 3],
+    pub shininess: f32,
+    pub opacity: f32,
+    pub ambient_texture: Option<PathBuf>,
+    pub diffuse_texture: Option<PathBuf>,
+    pub specular_texture: Option<PathBuf>,
+    pub normal_texture: Option<PathBuf>,
+}
+
+impl MaterialResource {
+    pub fn new<I: Into<String>>(name: I) -> Self {
+        MaterialResource {
+            name: name.into(),
+            ambient: [0.0, 0.0, 0.0],
+            diffuse: [1.0, 1.0, 1.0],
+            specular: [0.0, 0.0, 0.0],
+            emissive: [0.0, 0.0, 0.0],
+            shininess: 0.0,
+            opacity: 1.0,
+            ambient_texture: None,
+            diffuse_texture: None,
+            specular_texture: None,
+            normal_texture: None,
+        }
+    }
+
+    /// Returns the paths of all the textures used by this material.
+    pub fn textures(&self) -> Vec<&Path> {
+        [&self.ambient_texture, &self.diffuse_texture, &self.specular_texture, &self.normal_texture].iter()
+            .filter_map(|texture| texture.as_ref().map(|path| path.as_path()))
+            .collect()
+    }
+}
+
+impl MemorySize for MaterialResource {
+    fn memory_size(&self) -> usize {
+        let textures: usize = self.textures().iter().map(|texture| texture.as_os_str().len()).sum();
+        mem::size_of::<MaterialResource>() + self.name.len() + textures
+    }
+}
+
+impl AsRef<MaterialResource> for MaterialResource {
+    fn as_ref(&self) -> &MaterialResource {
+        self
+    }
+}
+
+/// An instance of a material, overriding some of its parameters.
+///
+/// Material instances are small and often created and destroyed, they are stored in a pool of the ResourceManager.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialInstanceResource {
+    /// The path of the material, `library#name`.
+    pub material: PathBuf,
+    pub diffuse: Option<[f32; 3]>,
+    pub opacity: Option<f32>,
+    pub diffuse_texture: Option<PathBuf>,
+}
+
+impl MaterialInstanceResource {
+    pub fn new<P: Into<PathBuf>>(material: P) -> Self {
+        MaterialInstanceResource {
+            material: material.into(),
+            diffuse: None,
+            opacity: None,
+            diffuse_texture: None,
+        }
+    }
+
+    /// Returns the diffuse color of this instance, or the one of its material.
+    pub fn diffuse_of(&self, material: &MaterialResource) -> [f32; 3] {
+        self.diffuse.unwrap_or(material.diffuse)
+    }
+
+    /// Returns the opacity of this instance, or the one of its material.
+    pub fn opacity_of(&self, material: &MaterialResource) -> f32 {
+        self.opacity.unwrap_or(material.opacity)
+    }
+
+    /// Returns the diffuse texture of this instance, or the one of its material.
+    pub fn diffuse_texture_of<'b>(&'b self, material: &'b MaterialResource) -> Option<&'b Path> {
+        self.diffuse_texture.as_ref().or(material.diffuse_texture.as_ref()).map(|path| path.as_path())
+    }
+}
+
+#[derive(Debug)]
+pub struct MaterialRegistry<'a>(HashMap<PathBuf, &'a MaterialResource>);
+
+impl<'a> Default for MaterialRegistry<'a> {
+    fn default() -> Self {
+        debug!("Creating a default MaterialRegistry.");
+        MaterialRegistry(HashMap::default())
+    }
+}
+
+impl<'a> MaterialRegistry<'a> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        debug!("Checking if the MaterialRegistry is empty.");
+        self.0.is_empty()
+    }
+
+    pub fn get<I: AsRef<Path>>(&self, path: I) -> Option<&&MaterialResource> {
+        debug!("Trying to get a material resource with path {}.", path.as_ref().display());
+        self.0.get(path.as_ref())
+    }
+
+    pub fn remove<I: AsRef<Path>>(&mut self, path: I) -> Option<&MaterialResource> {
+        debug!("Removing a material resource with path {}.", path.as_ref().display());
+        self.0.remove(path.as_ref())
+    }
+
+    pub fn insert<I>(&mut self, path: I, material_res: &'a MaterialResource) -> Option<&MaterialResource> where
+        I: Into<PathBuf>,
+    {
+        debug!("Inserting a material resource.");
+        self.0.insert(path.into(), material_res)
+    }
+
+    pub fn iter(&self) -> Iter<PathBuf, &'a MaterialResource> {
+        debug!("Getting an iterator of the MaterialRegistry.");
+        self.0.iter()
+    }
+
+    pub fn clear(&mut self) {
+        debug!("Clearing the material registry.");
+        self.0.clear();
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+use std::fmt;
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+
+/// The memory footprint of a resource : the resource itself and the heap data it owns.
+pub trait MemorySize {
+    fn memory_size(&self) -> usize;
+}
+
+/// The kind of memory consumer a budget applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceCategory {
+    Texture,
+    Mesh,
+    Audio,
+    Other,
+}
+
+impl ResourceCategory {
+    pub fn all() -> [ResourceCategory; 4] {
+        [ResourceCategory::Texture, ResourceCategory::Mesh, ResourceCategory::Audio, ResourceCategory::Other]
+    }
+}
+
+impl fmt::Display for ResourceCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &ResourceCategory::Texture => write!(f, "texture"),
+            &ResourceCategory::Mesh => write!(f, "mesh"),
+            &ResourceCategory::Audio => write!(f, "audio"),
+            &ResourceCategory::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// The lifetime of a resource : loaded for the whole game, or for the current level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceScope {
+    Global,
+    Level,
+}
+
+impl ResourceScope {
+    pub fn all() -> [ResourceScope; 2] {
+        [ResourceScope::Global, ResourceScope::Level]
+    }
+}
+
+impl fmt::Display for ResourceScope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &ResourceScope::Global => write!(f, "global"),
+            &ResourceScope::Level => write!(f, "level"),
+        }
+    }
+}
+
+/// Memory budgets per scope and category, and the memory used in each of them.
+///
+/// A scope and category without budget is unlimited.
+#[derive(Debug, Default)]
+pub struct MemoryBudget {
+    budgets: HashMap<(ResourceScope, ResourceCategory), usize>,
+    usage: HashMap<(ResourceScope, ResourceCategory), usize>,
+}
+
+impl MemoryBudget {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn set_budget(&mut self, scope: ResourceScope, category: ResourceCategory, budget: Option<usize>) {
+        debug!("Setting the {} budget of the {} scope.", category, scope);
+        match budget {
+            Some(budget) => {
+                self.budgets.insert((scope, category), budget);
+            },
+            None => {
+                self.budgets.remove(&(scope, category));
+            },
+        }
+    }
+
+    pub fn budget(&self, scope: ResourceScope, category: ResourceCategory) -> Option<usize> {
+        self.budgets.get(&(scope, category)).cloned()
+    }
+
+    pub fn usage(&self, scope: ResourceScope, category: ResourceCategory) -> usize {
+        self.usage.get(&(scope, category)).cloned().unwrap_or(0)
+    }
+
+    /// The number of bytes which can still be reserved, `None` if the budget is unlimited.
+    pub fn remaining(&self, scope: ResourceScope, category: ResourceCategory) -> Option<usize> {
+        self.budget(scope, category).map(|budget| budget.saturating_sub(self.usage(scope, category)))
+    }
+
+    /// Accounts for `bytes` in the given scope and category.
+    ///
+    /// Returns a `BudgetExceeded` error, and reserves nothing, if the budget would be exceeded.
+    pub fn reserve(&mut self, scope: ResourceScope, category: ResourceCategory, bytes: usize) -> ResourceManagerResult<()> {
+        debug!("Reserving {} bytes in the {} budget of the {} scope.", bytes, category, scope);
+        if let Some(remaining) = self.remaining(scope, category) {
+            if bytes > remaining {
+                error!("The {} budget of the {} scope is exceeded.", category, scope);
+                return Err(ResourceManagerError::BudgetExceeded(format!("The {} budget of the {} scope cannot hold {} more bytes, only {} bytes remain !", category, scope, bytes, remaining), category, bytes, remaining));
+            }
+        }
+
+        *self.usage.entry((scope, category)).or_insert(0) += bytes;
+        Ok(())
+    }
+
+    /// Gives back `bytes` to the given scope and category, when a resource is unloaded on its own.
+    pub fn release(&mut self, scope: ResourceScope, category: ResourceCategory, bytes: usize) {
+        debug!("Releasing {} bytes in the {} budget of the {} scope.", bytes, category, scope);
+        if let Some(usage) = self.usage.get_mut(&(scope, category)) {
+            *usage = usage.saturating_sub(bytes);
+        }
+    }
+
+    /// Forgets the memory used in a scope, when its resources are unloaded.
+    pub fn release_scope(&mut self, scope: ResourceScope) {
+        debug!("Releasing the memory used in the {} scope.", scope);
+        self.usage.retain(|&(usage_scope, _), _| usage_scope != scope);
+    }
+
+    pub fn release_all(&mut self) {
+        debug!("Releasing the memory used in every scope.");
+        self.usage.clear();
+    }
+}
@@ -0,0 +1,167 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use resources::memory_budget::{ResourceCategory, ResourceScope};
+use resources::resources_registry::ResourceType;
+
+/// The memory used by one resource.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceMemoryUsage {
+    pub path: PathBuf,
+    pub resource_type: ResourceType,
+    pub scope: ResourceScope,
+    /// The resource and the heap data it owns.
+    pub bytes: usize,
+}
+
+impl ResourceMemoryUsage {
+    pub fn new<P: AsRef<Path>>(path: P, resource_type: ResourceType, scope: ResourceScope, bytes: usize) -> Self {
+        ResourceMemoryUsage {
+            path: path.as_ref().to_path_buf(),
+            resource_type,
+            scope,
+            bytes,
+        }
+    }
+
+    pub fn category(&self) -> ResourceCategory {
+        self.resource_type.category()
+    }
+}
+
+/// The state of a stack allocator. Its two stacks hold the data needing to be dropped, and the `Copy` data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StackAllocatorStats {
+    pub capacity: usize,
+    pub used: usize,
+    pub high_water_mark: usize,
+    pub capacity_copy: usize,
+    pub used_copy: usize,
+    pub high_water_mark_copy: usize,
+}
+
+impl StackAllocatorStats {
+    /// Records the current markers of the allocator, and raises the high-water marks if needed.
+    pub fn update(&mut self, used: usize, used_copy: usize) {
+        self.used = used;
+        self.used_copy = used_copy;
+        self.high_water_mark = self.high_water_mark.max(used);
+        self.high_water_mark_copy = self.high_water_mark_copy.max(used_copy);
+    }
+
+    pub fn free(&self) -> usize {
+        self.capacity.saturating_sub(self.used)
+    }
+
+    pub fn free_copy(&self) -> usize {
+        self.capacity_copy.saturating_sub(self.used_copy)
+    }
+}
+
+/// The state of the heap region, holding the resources which can be unloaded one by one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HeapRegionStats {
+    pub capacity: usize,
+    pub used: usize,
+    pub free: usize,
+    pub largest_free_block: usize,
+    pub block_count: usize,
+}
+
+impl HeapRegionStats {
+    /// The fraction of the free memory which is not in the largest free block.
+    pub fn fragmentation(&self) -> f32 {
+        if self.free == 0 {
+            0.0
+        } else {
+            1.0 - self.largest_free_block as f32 / self.free as f32
+        }
+    }
+}
+
+/// A snapshot of the memory used by the resources and the allocators of a ResourceManager.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MemoryReport {
+    pub resources: Vec<ResourceMemoryUsage>,
+    /// Bytes of the resource stack allocator taken by the resource objects themselves.
+    pub resource_objects_bytes: usize,
+    /// The stack allocator holding the resources.
+    pub resource_stack: StackAllocatorStats,
+    /// The stack allocator holding the temporary data.
+    pub temporary_stack: StackAllocatorStats,
+    pub heap_region: HeapRegionStats,
+}
+
+impl MemoryReport {
+    pub fn total(&self) -> usize {
+        self.resources.iter().map(|usage| usage.bytes).sum()
+    }
+
+    pub fn total_of_type(&self, resource_type: ResourceType) -> usize {
+        self.resources.iter().filter(|usage| usage.resource_type == resource_type).map(|usage| usage.bytes).sum()
+    }
+
+    pub fn total_of_category(&self, category: ResourceCategory) -> usize {
+        self.resources.iter().filter(|usage| usage.category() == category).map(|usage| usage.bytes).sum()
+    }
+
+    pub fn total_of_scope(&self, scope: ResourceScope) -> usize {
+        self.resources.iter().filter(|usage| usage.scope == scope).map(|usage| usage.bytes).sum()
+    }
+
+    /// Bytes of the resource stack which are not taken by a registered resource object.
+    ///
+    /// They are the objects of the loads which have been rolled back or replaced, which stay allocated until
+    /// their level is unloaded, and the alignment padding and allocation headers.
+    pub fn orphaned_stack_bytes(&self) -> usize {
+        (self.resource_stack.used + self.resource_stack.used_copy).saturating_sub(self.resource_objects_bytes)
+    }
+
+    /// The fragmentation of the heap region. A stack allocator has no holes between its allocations.
+    pub fn fragmentation(&self) -> f32 {
+        self.heap_region.fragmentation()
+    }
+
+    /// Writes one line per resource : `path,type,category,scope,bytes`.
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "path,type,category,scope,bytes")?;
+        let mut resources: Vec<&ResourceMemoryUsage> = self.resources.iter().collect();
+        resources.sort_by(|a, b| a.path.cmp(&b.path));
+        for usage in resources {
+            writeln!(writer, "\"{}\",{},{},{},{}", usage.path.display().to_string().replace('"', "\"\""), usage.resource_type, usage.category(), usage.scope, usage.bytes)?;
+        }
+        Ok(())
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = Vec::new();
+        self.write_csv(&mut csv).expect("Writing in a vector cannot fail.");
+        String::from_utf8_lossy(&csv).into_owned()
+    }
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Resources: {} bytes in {} resources", self.total(), self.resources.len())?;
+        for scope in ResourceScope::all().iter() {
+            writeln!(f, "  {}: {} bytes", scope, self.total_of_scope(*scope))?;
+        }
+        for resource_type in ResourceType::all().iter() {
+            writeln!(f, "  {}: {} bytes", resource_type, self.total_of_type(*resource_type))?;
+        }
+        for &(name, stack) in [("Resource stack", &self.resource_stack), ("Temporary stack", &self.temporary_stack)].iter() {
+            writeln!(f, "{}: {}/{} bytes used (high-water mark {}), copy: {}/{} bytes used (high-water mark {})",
+                     name, stack.used, stack.capacity, stack.high_water_mark, stack.used_copy, stack.capacity_copy, stack.high_water_mark_copy)?;
+        }
+        writeln!(f, "Heap region: {}/{} bytes used in {} blocks, largest free block: {} bytes, fragmentation: {:.1}%",
+                 self.heap_region.used, self.heap_region.capacity, self.heap_region.block_count, self.heap_region.largest_free_block, self.fragmentation() * 100.0)?;
+        writeln!(f, "Orphaned resource stack: {} bytes", self.orphaned_stack_bytes())
+    }
+}
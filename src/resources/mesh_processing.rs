@@ -0,0 +1,384 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+use mikktspace::{self, Geometry};
+use resources::mesh_registry::{MeshResource, MeshBounds};
+
+//Size of the simulated post-transform vertex cache.
+const VERTEX_CACHE_SIZE: usize = 32;
+//Tuning constants of the vertex cache optimizer (Tom Forsyth, "Linear-Speed Vertex Cache Optimisation").
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+/// The post-processing steps applied to a mesh after it has been loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct MeshProcessing {
+    /// Compute the axis-aligned bounding box and the bounding sphere.
+    pub compute_bounds: bool,
+    /// Generate MikkTSpace tangents, if the mesh has normals and texture coordinates but no tangents.
+    pub generate_tangents: bool,
+    /// Merge the vertices having exactly the same attributes.
+    pub deduplicate_vertices: bool,
+    /// Reorder the triangles to make a better use of the post-transform vertex cache.
+    pub optimize_vertex_cache: bool,
+}
+
+impl MeshProcessing {
+    pub fn none() -> Self {
+        Default::default()
+    }
+
+    pub fn all() -> Self {
+        MeshProcessing {
+            compute_bounds: true,
+            generate_tangents: true,
+            deduplicate_vertices: true,
+            optimize_vertex_cache: true,
+        }
+    }
+
+    /// Applies the enabled steps on the mesh.
+    pub fn apply(&self, mesh: &mut MeshResource) {
+        if self.generate_tangents && !mesh.has_tangents() {
+            generate_tangents(mesh);
+        }
+        if self.deduplicate_vertices {
+            deduplicate_vertices(mesh);
+        }
+        if self.optimize_vertex_cache {
+            optimize_vertex_cache(mesh);
+        }
+        if self.compute_bounds {
+            mesh.bounds = compute_bounds(mesh);
+        }
+    }
+}
+
+/// Computes the axis-aligned bounding box and a bounding sphere centered on the box.
+pub fn compute_bounds(mesh: &MeshResource) -> Option<MeshBounds> {
+    debug!("Computing the bounds of a mesh.");
+    let first = match mesh.positions.first() {
+        Some(position) => *position,
+        None => return None,
+    };
+
+    let mut min = first;
+    let mut max = first;
+    for position in mesh.positions.iter() {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(position[axis]);
+            max[axis] = max[axis].max(position[axis]);
+        }
+    }
+
+    let center = [(min[0] + max[0]) * 0.5, (min[1] + max[1]) * 0.5, (min[2] + max[2]) * 0.5];
+    let radius = mesh.positions.iter()
+        .map(|position| {
+            let (x, y, z) = (position[0] - center[0], position[1] - center[1], position[2] - center[2]);
+            x * x + y * y + z * z
+        })
+        .fold(0.0f32, f32::max)
+        .sqrt();
+
+    Some(MeshBounds {
+        min,
+        max,
+        center,
+        radius,
+    })
+}
+
+//Every triangle corner of the mesh, as seen by the MikkTSpace generator.
+struct TangentSpaceGeometry<'a> {
+    mesh: &'a MeshResource,
+    corners: Vec<u32>,
+    tangents: Vec<[f32; 4]>,
+}
+
+impl<'a> Geometry for TangentSpaceGeometry<'a> {
+    fn num_faces(&self) -> usize {
+        self.corners.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.mesh.positions[self.corners[face * 3 + vert] as usize]
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.mesh.normals[self.corners[face * 3 + vert] as usize]
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.mesh.tex_coords[self.corners[face * 3 + vert] as usize]
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[face * 3 + vert] = tangent;
+    }
+}
+
+/// Generates MikkTSpace tangents. The mesh needs normals and texture coordinates.
+///
+/// A vertex shared by triangles may get different tangents, so the mesh is un-indexed before the generation,
+/// and re-indexed afterward. Returns false if the tangents could not be generated.
+pub fn generate_tangents(mesh: &mut MeshResource) -> bool {
+    debug!("Generating the tangents of a mesh.");
+    if !mesh.has_normals() || !mesh.has_tex_coords() {
+        warn!("Tangents cannot be generated without normals and texture coordinates.");
+        return false;
+    }
+
+    let corners: Vec<u32> = mesh.primitives.iter().flat_map(|primitive| primitive.indices.iter().cloned()).collect();
+    let tangents = {
+        let mut geometry = TangentSpaceGeometry {
+            mesh,
+            tangents: vec![[0.0; 4]; corners.len()],
+            corners: corners.clone(),
+        };
+        if !mikktspace::generate_tangents(&mut geometry) {
+            warn!("The MikkTSpace tangent generation failed.");
+            return false;
+        }
+        geometry.tangents
+    };
+
+    //Un-index the mesh, every corner gets its own vertex.
+    let positions = corners.iter().map(|&index| mesh.positions[index as usize]).collect();
+    let normals = corners.iter().map(|&index| mesh.normals[index as usize]).collect();
+    let tex_coords = corners.iter().map(|&index| mesh.tex_coords[index as usize]).collect();
+    mesh.positions = positions;
+    mesh.normals = normals;
+    mesh.tex_coords = tex_coords;
+    mesh.tangents = tangents;
+    let mut corner = 0;
+    for primitive in mesh.primitives.iter_mut() {
+        for index in primitive.indices.iter_mut() {
+            *index = corner;
+            corner += 1;
+        }
+    }
+
+    deduplicate_vertices(mesh);
+    true
+}
+
+/// Merges the vertices whose attributes are bit-for-bit identical, and removes the unused vertices.
+pub fn deduplicate_vertices(mesh: &mut MeshResource) {
+    debug!("Deduplicating the vertices of a mesh.");
+    let mut unique: HashMap<Vec<u32>, u32> = HashMap::new();
+    let mut remap: Vec<Option<u32>> = vec![None; mesh.positions.len()];
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut tex_coords = Vec::new();
+    let mut tangents = Vec::new();
+
+    for primitive in mesh.primitives.iter_mut() {
+        for index in primitive.indices.iter_mut() {
+            let old_index = *index as usize;
+            if let Some(new_index) = remap[old_index] {
+                *index = new_index;
+                continue;
+            }
+
+            let mut key: Vec<u32> = mesh.positions[old_index].iter().map(|value| value.to_bits()).collect();
+            if let Some(normal) = mesh.normals.get(old_index) {
+                key.extend(normal.iter().map(|value| value.to_bits()));
+            }
+            if let Some(tex_coord) = mesh.tex_coords.get(old_index) {
+                key.extend(tex_coord.iter().map(|value| value.to_bits()));
+            }
+            if let Some(tangent) = mesh.tangents.get(old_index) {
+                key.extend(tangent.iter().map(|value| value.to_bits()));
+            }
+
+            let new_index = match unique.get(&key) {
+                Some(&new_index) => new_index,
+                None => {
+                    let new_index = positions.len() as u32;
+                    positions.push(mesh.positions[old_index]);
+                    if let Some(normal) = mesh.normals.get(old_index) {
+                        normals.push(*normal);
+                    }
+                    if let Some(tex_coord) = mesh.tex_coords.get(old_index) {
+                        tex_coords.push(*tex_coord);
+                    }
+                    if let Some(tangent) = mesh.tangents.get(old_index) {
+                        tangents.push(*tangent);
+                    }
+                    unique.insert(key, new_index);
+                    new_index
+                },
+            };
+            remap[old_index] = Some(new_index);
+            *index = new_index;
+        }
+    }
+
+    mesh.positions = positions;
+    mesh.normals = normals;
+    mesh.tex_coords = tex_coords;
+    mesh.tangents = tangents;
+}
+
+fn vertex_score(cache_position: Option<usize>, remaining_triangles: usize) -> f32 {
+    if remaining_triangles == 0 {
+        return -1.0;
+    }
+
+    let cache_score = match cache_position {
+        //The vertices of the last triangle get a fixed score, to avoid favoring one of them.
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => {
+            let scale = 1.0 / (VERTEX_CACHE_SIZE - 3) as f32;
+            (1.0 - (position - 3) as f32 * scale).powf(CACHE_DECAY_POWER)
+        },
+        None => 0.0,
+    };
+    //Favor the vertices with few remaining triangles, to get rid of them quickly.
+    let valence_boost = VALENCE_BOOST_SCALE * (remaining_triangles as f32).powf(-VALENCE_BOOST_POWER);
+    cache_score + valence_boost
+}
+
+/// Reorders the triangles of a triangle list to reduce the post-transform vertex cache misses.
+pub fn optimize_triangle_order(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return indices.to_vec();
+    }
+
+    let mut vertex_triangles: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+    for triangle in 0..triangle_count {
+        for corner in 0..3 {
+            vertex_triangles[indices[triangle * 3 + corner] as usize].push(triangle);
+        }
+    }
+
+    let mut remaining: Vec<usize> = vertex_triangles.iter().map(|triangles| triangles.len()).collect();
+    let mut cache_positions: Vec<Option<usize>> = vec![None; vertex_count];
+    let mut scores: Vec<f32> = (0..vertex_count).map(|vertex| vertex_score(None, remaining[vertex])).collect();
+    let mut emitted = vec![false; triangle_count];
+    let triangle_score = |triangle: usize, scores: &[f32]| -> f32 {
+        (0..3).map(|corner| scores[indices[triangle * 3 + corner] as usize]).sum()
+    };
+
+    let mut cache: Vec<u32> = Vec::with_capacity(VERTEX_CACHE_SIZE + 3);
+    let mut output = Vec::with_capacity(indices.len());
+    let mut next_unemitted = 0;
+
+    for _ in 0..triangle_count {
+        //Look for the best triangle among the triangles of the cached vertices.
+        let mut best: Option<(usize, f32)> = None;
+        for &vertex in cache.iter() {
+            for &triangle in vertex_triangles[vertex as usize].iter() {
+                if emitted[triangle] {
+                    continue;
+                }
+                let score = triangle_score(triangle, &scores);
+                if best.map_or(true, |(_, best_score)| score > best_score) {
+                    best = Some((triangle, score));
+                }
+            }
+        }
+
+        //Nothing usable in the cache, take the next triangle which has not been emitted yet.
+        let best_triangle = match best {
+            Some((triangle, _)) => triangle,
+            None => {
+                while emitted[next_unemitted] {
+                    next_unemitted += 1;
+                }
+                next_unemitted
+            },
+        };
+
+        emitted[best_triangle] = true;
+        let triangle_vertices = [indices[best_triangle * 3], indices[best_triangle * 3 + 1], indices[best_triangle * 3 + 2]];
+        output.extend_from_slice(&triangle_vertices);
+
+        //Move the vertices of the triangle at the front of the cache.
+        for &vertex in triangle_vertices.iter() {
+            remaining[vertex as usize] -= 1;
+            cache.retain(|&cached| cached != vertex);
+        }
+        for &vertex in triangle_vertices.iter().rev() {
+            cache.insert(0, vertex);
+        }
+
+        //Update the scores of the cached vertices, and of the evicted ones.
+        for (position, &vertex) in cache.iter().enumerate() {
+            let vertex = vertex as usize;
+            cache_positions[vertex] = if position < VERTEX_CACHE_SIZE { Some(position) } else { None };
+            scores[vertex] = vertex_score(cache_positions[vertex], remaining[vertex]);
+        }
+        cache.truncate(VERTEX_CACHE_SIZE);
+    }
+
+    output
+}
+
+/// Reorders the triangles of every primitive of the mesh, see `optimize_triangle_order`.
+pub fn optimize_vertex_cache(mesh: &mut MeshResource) {
+    debug!("Optimizing the triangle order of a mesh for the post-transform vertex cache.");
+    let vertex_count = mesh.vertex_count();
+    for primitive in mesh.primitives.iter_mut() {
+        primitive.indices = optimize_triangle_order(&primitive.indices, vertex_count);
+    }
+}
+
+#[cfg(test)]
+mod mesh_processing_test {
+    use super::*;
+    use resources::mesh_registry::MeshPrimitive;
+
+    fn quad() -> MeshResource {
+        let mut mesh = MeshResource::new();
+        mesh.positions = vec![[0.0, 0.0, 0.0], [2.0, 0.0, 0.0], [2.0, 2.0, 0.0], [0.0, 0.0, 0.0], [2.0, 2.0, 0.0], [0.0, 2.0, 0.0]];
+        mesh.primitives = vec![MeshPrimitive {
+            indices: vec![0, 1, 2, 3, 4, 5],
+            material: None,
+        }];
+        mesh
+    }
+
+    #[test]
+    fn mesh_processing_bounds_and_deduplication() {
+        let mut mesh = quad();
+        let bounds = compute_bounds(&mesh).expect("The mesh has no bounds.");
+        assert_eq!(bounds.min, [0.0, 0.0, 0.0]);
+        assert_eq!(bounds.max, [2.0, 2.0, 0.0]);
+        assert_eq!(bounds.center, [1.0, 1.0, 0.0]);
+        assert!((bounds.radius - 2.0f32.sqrt()).abs() < 1e-6);
+
+        deduplicate_vertices(&mut mesh);
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.primitives[0].indices, vec![0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn mesh_processing_optimize_triangle_order_keeps_every_triangle() {
+        //A strip of 8 triangles, given in a scrambled order.
+        let mut indices = Vec::new();
+        for triangle in [5u32, 0, 7, 2, 4, 1, 6, 3].iter() {
+            indices.extend_from_slice(&[*triangle, triangle + 1, triangle + 2]);
+        }
+        let optimized = optimize_triangle_order(&indices, 10);
+        assert_eq!(optimized.len(), indices.len());
+
+        let mut triangles: Vec<&[u32]> = optimized.chunks(3).collect();
+        triangles.sort();
+        let mut expected: Vec<&[u32]> = indices.chunks(3).collect();
+        expected.sort();
+        assert_eq!(triangles, expected);
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Iter;
+use std::path::{PathBuf, Path};
+use std::mem;
+use resources::memory_budget::MemorySize;
+
+/// A set of triangles sharing the same material.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MeshPrimitive {
+    pub indices: Vec<u32>,
+    pub material: Option<PathBuf>,
+}
+
+/// The axis-aligned bounding box and the bounding sphere of a mesh.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshBounds {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+    pub center: [f32; 3],
+    pub radius: f32,
+}
+
+/// A simplified version of a mesh, stored as a sub-resource of the mesh.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshLod {
+    pub resource: PathBuf,
+    /// The mesh is replaced by this level of detail when its bounds cover less than this fraction of the screen height.
+    pub screen_size: f32,
+}
+
+/// The engine representation of a mesh : indexed vertex attributes and triangle lists.
+///
+/// `normals`, `tex_coords` and `tangents` are either empty, or have the same length as `positions`.
+/// The `w` component of a tangent is the handedness of the bitangent.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MeshResource {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub tex_coords: Vec<[f32; 2]>,
+    pub tangents: Vec<[f32; 4]>,
+    pub primitives: Vec<MeshPrimitive>,
+    pub bounds: Option<MeshBounds>,
+    pub lods: Vec<MeshLod>,
+}
+
+impl MeshResource {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.primitives.iter().map(|primitive| primitive.indices.len() / 3).sum()
+    }
+
+    pub fn has_normals(&self) -> bool {
+        !self.normals.is_empty()
+    }
+
+    pub fn has_tex_coords(&self) -> bool {
+        !self.tex_coords.is_empty()
+    }
+
+    pub fn has_tangents(&self) -> bool {
+        !self.tangents.is_empty()
+    }
+}
+
+impl MemorySize for MeshResource {
+    fn memory_size(&self) -> usize {
+        let indices: usize = self.primitives.iter().map(|primitive| primitive.indices.len() * mem::size_of::<u32>()).sum();
+        mem::size_of::<MeshResource>()
+            + self.positions.len() * mem::size_of::<[f32; 3]>()
+            + self.normals.len() * mem::size_of::<[f32; 3]>()
+            + self.tex_coords.len() * mem::size_of::<[f32; 2]>()
+            + self.tangents.len() * mem::size_of::<[f32; 4]>()
+            + self.primitives.len() * mem::size_of::<MeshPrimitive>()
+            + indices
+    }
+}
+
+impl AsRef<MeshResource> for MeshResource {
+    fn as_ref(&self) -> &MeshResource {
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct MeshRegistry<'a>(HashMap<PathBuf, &'a MeshResource>);
+
+impl<'a> Default for MeshRegistry<'a> {
+    fn default() -> Self {
+        debug!("Creating a default MeshRegistry.");
+        MeshRegistry(HashMap::default())
+    }
+}
+
+impl<'a> MeshRegistry<'a> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        debug!("Checking if the MeshRegistry is empty.");
+        self.0.is_empty()
+    }
+
+    pub fn get<I: AsRef<Path>>(&self, path: I) -> Option<&&MeshResource> {
+        debug!("Trying to get a mesh resource with path {}.", path.as_ref().display());
+        self.0.get(path.as_ref())
+    }
+
+    pub fn remove<I: AsRef<Path>>(&mut self, path: I) -> Option<&MeshResource> {
+        debug!("Removing a mesh resource with path {}.", path.as_ref().display());
+        self.0.remove(path.as_ref())
+    }
+
+    pub fn insert<I>(&mut self, path: I, mesh_res: &'a MeshResource) -> Option<&MeshResource> where
+        I: Into<PathBuf>,
+    {
+        debug!("Inserting a mesh resource.");
+        self.0.insert(path.into(), mesh_res)
+    }
+
+    pub fn iter(&self) -> Iter<PathBuf, &'a MeshResource> {
+        debug!("Getting an iterator of the MeshRegistry.");
+        self.0.iter()
+    }
+
+    pub fn clear(&mut self) {
+        debug!("Clearing the mesh registry.");
+        self.0.clear();
+    }
+}
@@ -0,0 +1,548 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::path::{Path, PathBuf};
+use resources::mesh_registry::{MeshResource, MeshPrimitive};
+use resources::mesh_processing::deduplicate_vertices;
+
+//Weight of the planes keeping the open borders of a mesh in place.
+const BORDER_WEIGHT: f64 = 1000.0;
+
+/// A level of detail : the fraction of the triangles of the source mesh to keep,
+/// and the screen size (fraction of the screen height covered by the bounds) under which it is used.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LodLevel {
+    pub triangle_ratio: f32,
+    pub screen_size: f32,
+}
+
+impl LodLevel {
+    pub fn new(triangle_ratio: f32, screen_size: f32) -> Self {
+        LodLevel {
+            triangle_ratio,
+            screen_size,
+        }
+    }
+}
+
+/// The levels of detail to generate when a mesh is loaded, from the most detailed to the least detailed.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MeshLodSettings {
+    pub levels: Vec<LodLevel>,
+}
+
+impl MeshLodSettings {
+    pub fn new(levels: Vec<LodLevel>) -> Self {
+        MeshLodSettings {
+            levels,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+}
+
+/// The resource path of a level of detail of a mesh : `mesh.obj#lod1`, `mesh.obj#lod2`...
+pub fn lod_path<P: AsRef<Path>>(mesh_path: P, level: usize) -> PathBuf {
+    PathBuf::from(format!("{}#lod{}", mesh_path.as_ref().display(), level))
+}
+
+//A symmetric 4x4 matrix, measuring the squared distance of a point to a set of planes.
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn from_plane(a: f64, b: f64, c: f64, d: f64, weight: f64) -> Self {
+        Quadric([
+            a * a * weight, a * b * weight, a * c * weight, a * d * weight,
+            b * b * weight, b * c * weight, b * d * weight,
+            c * c * weight, c * d * weight,
+            d * d * weight,
+        ])
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (value, other_value) in self.0.iter_mut().zip(other.0.iter()) {
+            *value += *other_value;
+        }
+    }
+
+    fn error(&self, point: [f32; 3]) -> f64 {
+        let q = &self.0;
+        let (x, y, z) = (f64::from(point[0]), f64::from(point[1]), f64::from(point[2]));
+        q[0] * x * x + 2.0 * q[1] * x * y + 2.0 * q[2] * x * z + 2.0 * q[3] * x
+            + q[4] * y * y + 2.0 * q[5] * y * z + 2.0 * q[6] * y
+            + q[7] * z * z + 2.0 * q[8] * z
+            + q[9]
+    }
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f64; 3] {
+    [f64::from(a[0] - b[0]), f64::from(a[1] - b[1]), f64::from(a[2] - b[2])]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(a: [f64; 3]) -> Option<[f64; 3]> {
+    let length = dot(a, a).sqrt();
+    if length < 1e-12 {
+        None
+    } else {
+        Some([a[0] / length, a[1] / length, a[2] / length])
+    }
+}
+
+fn triangle_normal(positions: &[[f32; 3]], triangle: &[u32; 3]) -> [f64; 3] {
+    let p0 = positions[triangle[0] as usize];
+    cross(sub(positions[triangle[1] as usize], p0), sub(positions[triangle[2] as usize], p0))
+}
+
+//The collapse of the vertex `from` on the vertex `to`. Ordered by increasing cost in the binary heap.
+#[derive(Debug, Clone, Copy)]
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+    from_version: u32,
+    to_version: u32,
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Collapse) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Collapse) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Collapse) -> Ordering {
+        other.cost.partial_cmp(&self.cost).unwrap_or(Ordering::Equal)
+    }
+}
+
+//The bits of a position, vertices with the same bits are at the same place.
+fn position_key(position: [f32; 3]) -> [u32; 3] {
+    [position[0].to_bits(), position[1].to_bits(), position[2].to_bits()]
+}
+
+//The vertices sharing a position are the wedges of a corner of the surface, with different attributes on each side of a seam.
+//The simplifier collapses corners, with all their wedges, along the edges between corners.
+struct Simplifier {
+    positions: Vec<[f32; 3]>,
+    //The corner of every vertex.
+    corners: Vec<u32>,
+    //The vertices of every corner.
+    wedges: Vec<Vec<u32>>,
+    triangles: Vec<[u32; 3]>,
+    triangle_primitives: Vec<usize>,
+    alive_triangles: Vec<bool>,
+    corner_triangles: Vec<Vec<usize>>,
+    quadrics: Vec<Quadric>,
+    removed: Vec<bool>,
+    versions: Vec<u32>,
+    heap: BinaryHeap<Collapse>,
+}
+
+impl Simplifier {
+    fn new(mesh: &MeshResource) -> Self {
+        let mut corners = Vec::with_capacity(mesh.vertex_count());
+        let mut wedges: Vec<Vec<u32>> = Vec::new();
+        let mut corner_of_position: HashMap<[u32; 3], u32> = HashMap::new();
+        for (vertex, position) in mesh.positions.iter().enumerate() {
+            let corner = *corner_of_position.entry(position_key(*position)).or_insert(wedges.len() as u32);
+            if corner as usize == wedges.len() {
+                wedges.push(Vec::new());
+            }
+            wedges[corner as usize].push(vertex as u32);
+            corners.push(corner);
+        }
+
+        let mut triangles = Vec::new();
+        let mut triangle_primitives = Vec::new();
+        for (primitive_index, primitive) in mesh.primitives.iter().enumerate() {
+            for triangle in primitive.indices.chunks(3).filter(|triangle| triangle.len() == 3) {
+                triangles.push([triangle[0], triangle[1], triangle[2]]);
+                triangle_primitives.push(primitive_index);
+            }
+        }
+
+        let corner_count = wedges.len();
+        let mut corner_triangles = vec![Vec::new(); corner_count];
+        let mut quadrics = vec![Quadric::default(); corner_count];
+        let mut edges: HashMap<(u32, u32), (usize, usize)> = HashMap::new();
+        for (triangle_index, triangle) in triangles.iter().enumerate() {
+            let normal = triangle_normal(&mesh.positions, triangle);
+            //The quadric of a face is weighted by its area.
+            let area = dot(normal, normal).sqrt() * 0.5;
+            if let Some(unit) = normalize(normal) {
+                let d = -dot(unit, sub(mesh.positions[triangle[0] as usize], [0.0; 3]));
+                let quadric = Quadric::from_plane(unit[0], unit[1], unit[2], d, area);
+                for &vertex in triangle.iter() {
+                    quadrics[corners[vertex as usize] as usize].add(&quadric);
+                }
+            }
+
+            for index in 0..3 {
+                let (a, b) = (corners[triangle[index] as usize], corners[triangle[(index + 1) % 3] as usize]);
+                corner_triangles[a as usize].push(triangle_index);
+                let entry = edges.entry((a.min(b), a.max(b))).or_insert((0, triangle_index));
+                entry.0 += 1;
+            }
+        }
+
+        //Keep the open borders in place, with planes perpendicular to the border faces.
+        //The edges of a seam are shared by the triangles on each side, they are not borders.
+        for (&(a, b), &(count, triangle_index)) in edges.iter() {
+            if count != 1 {
+                continue;
+            }
+            let position_a = mesh.positions[wedges[a as usize][0] as usize];
+            let position_b = mesh.positions[wedges[b as usize][0] as usize];
+            let normal = triangle_normal(&mesh.positions, &triangles[triangle_index]);
+            let edge = sub(position_b, position_a);
+            if let Some(unit) = normalize(cross(edge, normal)) {
+                let d = -dot(unit, sub(position_a, [0.0; 3]));
+                let quadric = Quadric::from_plane(unit[0], unit[1], unit[2], d, BORDER_WEIGHT * dot(edge, edge));
+                quadrics[a as usize].add(&quadric);
+                quadrics[b as usize].add(&quadric);
+            }
+        }
+
+        let mut simplifier = Simplifier {
+            positions: mesh.positions.clone(),
+            corners,
+            wedges,
+            alive_triangles: vec![true; triangles.len()],
+            triangles,
+            triangle_primitives,
+            corner_triangles,
+            quadrics,
+            removed: vec![false; corner_count],
+            versions: vec![0; corner_count],
+            heap: BinaryHeap::new(),
+        };
+        for &(a, b) in edges.keys() {
+            if a != b {
+                simplifier.push_edge(a, b);
+            }
+        }
+        simplifier
+    }
+
+    fn corner_position(&self, corner: u32) -> [f32; 3] {
+        self.positions[self.wedges[corner as usize][0] as usize]
+    }
+
+    //Pushes the cheapest collapse of the edge between the corners a and b.
+    fn push_edge(&mut self, a: u32, b: u32) {
+        let mut quadric = self.quadrics[a as usize];
+        quadric.add(&self.quadrics[b as usize]);
+
+        let mut best: Option<Collapse> = None;
+        for &(from, to) in [(a, b), (b, a)].iter() {
+            let cost = quadric.error(self.corner_position(to));
+            if best.map_or(true, |collapse| cost < collapse.cost) {
+                best = Some(Collapse {
+                    cost,
+                    from,
+                    to,
+                    from_version: self.versions[from as usize],
+                    to_version: self.versions[to as usize],
+                });
+            }
+        }
+
+        if let Some(collapse) = best {
+            self.heap.push(collapse);
+        }
+    }
+
+    fn has_corner(&self, triangle: usize, corner: u32) -> bool {
+        self.triangles[triangle].iter().any(|&vertex| self.corners[vertex as usize] == corner)
+    }
+
+    //A collapse must not flip the orientation of the remaining triangles.
+    fn flips_triangles(&self, from: u32, to: u32) -> bool {
+        let to_position = self.corner_position(to);
+        self.corner_triangles[from as usize].iter()
+            .filter(|&&triangle| self.alive_triangles[triangle] && !self.has_corner(triangle, to))
+            .any(|&triangle| {
+                let before: Vec<[f32; 3]> = self.triangles[triangle].iter().map(|&vertex| self.positions[vertex as usize]).collect();
+                let after: Vec<[f32; 3]> = self.triangles[triangle].iter()
+                    .map(|&vertex| if self.corners[vertex as usize] == from { to_position } else { self.positions[vertex as usize] })
+                    .collect();
+                dot(triangle_normal(&before, &[0, 1, 2]), triangle_normal(&after, &[0, 1, 2])) <= 0.0
+            })
+    }
+
+    //The wedge of the corner `to` a wedge of the corner `from` becomes : the one it shares a triangle with, on the same side of the seams.
+    fn target_wedge(&self, wedge: u32, to: u32) -> Option<u32> {
+        self.corner_triangles[self.corners[wedge as usize] as usize].iter()
+            .filter(|&&triangle| self.alive_triangles[triangle] && self.triangles[triangle].contains(&wedge))
+            .filter_map(|&triangle| self.triangles[triangle].iter().cloned().find(|&vertex| self.corners[vertex as usize] == to))
+            .next()
+    }
+
+    fn run(&mut self, target_triangles: usize) {
+        let mut alive = self.triangles.len();
+        while alive > target_triangles {
+            let collapse = match self.heap.pop() {
+                Some(collapse) => collapse,
+                None => break,
+            };
+            let (from, to) = (collapse.from as usize, collapse.to as usize);
+            if self.removed[from] || self.removed[to]
+                || self.versions[from] != collapse.from_version
+                || self.versions[to] != collapse.to_version
+                || self.flips_triangles(collapse.from, collapse.to) {
+                continue;
+            }
+
+            //Every wedge of the corner follows the collapse : it is merged with the wedge of the other corner on its side of the seams,
+            //or moved to the other corner, with its attributes, if it does not touch it.
+            let to_position = self.corner_position(collapse.to);
+            let targets: Vec<(u32, Option<u32>)> = self.wedges[from].iter().map(|&wedge| (wedge, self.target_wedge(wedge, collapse.to))).collect();
+
+            let from_triangles = ::std::mem::replace(&mut self.corner_triangles[from], Vec::new());
+            for triangle in from_triangles {
+                if !self.alive_triangles[triangle] {
+                    continue;
+                }
+                if self.has_corner(triangle, collapse.to) {
+                    self.alive_triangles[triangle] = false;
+                    alive -= 1;
+                } else {
+                    for vertex in self.triangles[triangle].iter_mut() {
+                        if let Some(&(_, Some(target))) = targets.iter().find(|&&(wedge, _)| wedge == *vertex) {
+                            *vertex = target;
+                        }
+                    }
+                    self.corner_triangles[to].push(triangle);
+                }
+            }
+            for (wedge, target) in targets {
+                if target.is_none() {
+                    self.positions[wedge as usize] = to_position;
+                    self.corners[wedge as usize] = collapse.to;
+                    self.wedges[to].push(wedge);
+                }
+            }
+
+            let from_quadric = self.quadrics[from];
+            self.quadrics[to].add(&from_quadric);
+            self.removed[from] = true;
+            self.versions[to] += 1;
+
+            //The costs of the edges around the remaining corner changed.
+            let mut neighbors: Vec<u32> = Vec::new();
+            for &triangle in self.corner_triangles[to].iter() {
+                if !self.alive_triangles[triangle] {
+                    continue;
+                }
+                for &vertex in self.triangles[triangle].iter() {
+                    let corner = self.corners[vertex as usize];
+                    if corner != collapse.to && !neighbors.contains(&corner) {
+                        neighbors.push(corner);
+                    }
+                }
+            }
+            for neighbor in neighbors {
+                self.push_edge(collapse.to, neighbor);
+            }
+        }
+    }
+}
+
+/// Simplifies a mesh with quadric error metrics, until it has at most `target_triangles` triangles,
+/// or until no more edge can be collapsed.
+///
+/// The vertices sharing a position, on each side of an attribute seam, are collapsed together : a vertex is merged
+/// with the vertex on its side of the seam, so the seams do not crack. The open borders of the mesh are kept in place.
+pub fn simplify(mesh: &MeshResource, target_triangles: usize) -> MeshResource {
+    debug!("Simplifying a mesh of {} triangles to {} triangles.", mesh.triangle_count(), target_triangles);
+    let mut simplifier = Simplifier::new(mesh);
+    simplifier.run(target_triangles);
+
+    let mut simplified = mesh.clone();
+    simplified.lods.clear();
+    simplified.positions = simplifier.positions.clone();
+    simplified.primitives = mesh.primitives.iter()
+        .map(|primitive| MeshPrimitive {
+            indices: Vec::new(),
+            material: primitive.material.clone(),
+        })
+        .collect();
+    for (triangle_index, triangle) in simplifier.triangles.iter().enumerate() {
+        if simplifier.alive_triangles[triangle_index] {
+            let primitive = simplifier.triangle_primitives[triangle_index];
+            simplified.primitives[primitive].indices.extend_from_slice(triangle);
+        }
+    }
+    //Remove the vertices which are not used anymore.
+    deduplicate_vertices(&mut simplified);
+    simplified
+}
+
+/// Generates a simplified mesh for every level of detail.
+///
+/// Each level is simplified from the previous one, the ratios are relative to the source mesh.
+pub fn generate_lods(mesh: &MeshResource, settings: &MeshLodSettings) -> Vec<MeshResource> {
+    debug!("Generating {} levels of detail.", settings.levels.len());
+    let source_triangles = mesh.triangle_count() as f32;
+    let mut lods: Vec<MeshResource> = Vec::with_capacity(settings.levels.len());
+    for level in settings.levels.iter() {
+        let target = (source_triangles * level.triangle_ratio.max(0.0).min(1.0)) as usize;
+        let lod = match lods.last() {
+            Some(previous) => simplify(previous, target),
+            None => simplify(mesh, target),
+        };
+        lods.push(lod);
+    }
+    lods
+}
+
+#[cfg(test)]
+mod mesh_simplification_test {
+    use super::*;
+
+    //A flat grid of size x size quads.
+    fn grid(size: u32) -> MeshResource {
+        let mut mesh = MeshResource::new();
+        for y in 0..size + 1 {
+            for x in 0..size + 1 {
+                mesh.positions.push([x as f32, y as f32, 0.0]);
+            }
+        }
+        let mut indices = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                let i = y * (size + 1) + x;
+                indices.extend_from_slice(&[i, i + 1, i + size + 2, i, i + size + 2, i + size + 1]);
+            }
+        }
+        mesh.primitives.push(MeshPrimitive {
+            indices,
+            material: None,
+        });
+        mesh
+    }
+
+    #[test]
+    fn mesh_simplification_reduce_flat_grid() {
+        let mesh = grid(8);
+        assert_eq!(mesh.triangle_count(), 128);
+        let simplified = simplify(&mesh, 32);
+        assert!(simplified.triangle_count() <= 32);
+        assert!(simplified.triangle_count() > 0);
+        assert!(simplified.vertex_count() < mesh.vertex_count());
+        //The borders of the grid must stay in place.
+        let bounds = ::resources::mesh_processing::compute_bounds(&simplified).unwrap();
+        assert_eq!(bounds.min, [0.0, 0.0, 0.0]);
+        assert_eq!(bounds.max, [8.0, 8.0, 0.0]);
+
+        let lods = generate_lods(&mesh, &MeshLodSettings::new(vec![LodLevel::new(0.5, 0.5), LodLevel::new(0.25, 0.25)]));
+        assert_eq!(lods.len(), 2);
+        assert!(lods[0].triangle_count() <= 64);
+        assert!(lods[1].triangle_count() <= 32);
+        assert_eq!(lod_path("meshes/grid.obj", 1), PathBuf::from("meshes/grid.obj#lod1"));
+    }
+
+    //The edges used by a single triangle, between positions, which are not on the border of the grid.
+    fn inner_open_edges(mesh: &MeshResource, size: f32) -> usize {
+        let mut edges: HashMap<([u32; 3], [u32; 3]), usize> = HashMap::new();
+        for triangle in mesh.primitives[0].indices.chunks(3) {
+            for index in 0..3 {
+                let (a, b) = (mesh.positions[triangle[index] as usize], mesh.positions[triangle[(index + 1) % 3] as usize]);
+                let (a, b) = (position_key(a), position_key(b));
+                *edges.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+        }
+        let on_border = |key: [u32; 3]| {
+            let (x, y) = (f32::from_bits(key[0]), f32::from_bits(key[1]));
+            x == 0.0 || y == 0.0 || x == size || y == size
+        };
+        edges.iter().filter(|&(&(a, b), &count)| count == 1 && !(on_border(a) && on_border(b))).count()
+    }
+
+    #[test]
+    fn mesh_simplification_collapse_seams() {
+        //The texture coordinates of the grid are split in two halves, the vertices of the middle column are doubled.
+        let mut mesh = grid(8);
+        mesh.tex_coords = mesh.positions.iter().map(|position| [position[0] / 8.0, position[1] / 8.0]).collect();
+        for y in 0..9 {
+            let vertex = y * 9 + 4;
+            mesh.positions.push([4.0, y as f32, 0.0]);
+            mesh.tex_coords.push([0.0, y as f32 / 8.0]);
+            let duplicate = mesh.positions.len() as u32 - 1;
+            let mut indices = mesh.primitives[0].indices.clone();
+            for triangle in indices.chunks_mut(3) {
+                if triangle.iter().any(|&index| mesh.positions[index as usize][0] > 4.0) {
+                    for index in triangle.iter_mut().filter(|index| **index == vertex) {
+                        *index = duplicate;
+                    }
+                }
+            }
+            mesh.primitives[0].indices = indices;
+        }
+        assert_eq!(mesh.vertex_count(), 90);
+
+        let simplified = simplify(&mesh, 32);
+        assert!(simplified.triangle_count() <= 32);
+        assert_eq!(simplified.tex_coords.len(), simplified.vertex_count());
+        assert_eq!(inner_open_edges(&simplified, 8.0), 0);
+        let bounds = ::resources::mesh_processing::compute_bounds(&simplified).unwrap();
+        assert_eq!((bounds.min, bounds.max), ([0.0, 0.0, 0.0], [8.0, 8.0, 0.0]));
+    }
+
+    #[test]
+    fn mesh_simplification_reduce_hard_edged_cube() {
+        //Each face of the cube has its own vertices, with the normal of the face.
+        let mut mesh = MeshResource::new();
+        let mut indices = Vec::new();
+        for &(axis, sign) in [(0, 1.0), (0, -1.0), (1, 1.0), (1, -1.0), (2, 1.0), (2, -1.0)].iter() {
+            let base = mesh.positions.len() as u32;
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+            for &(a, b) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].iter() {
+                let mut position = [0.0; 3];
+                position[axis] = sign;
+                position[u] = a;
+                position[v] = b * sign;
+                mesh.positions.push(position);
+                let mut normal = [0.0; 3];
+                normal[axis] = sign;
+                mesh.normals.push(normal);
+            }
+            indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+        mesh.primitives.push(MeshPrimitive {
+            indices,
+            material: None,
+        });
+        assert_eq!(mesh.triangle_count(), 12);
+
+        let simplified = simplify(&mesh, 6);
+        assert!(simplified.triangle_count() < 12);
+        assert!(simplified.triangle_count() > 0);
+        assert_eq!(simplified.normals.len(), simplified.vertex_count());
+    }
+}
@@ -11,3 +11,31 @@ pub mod tga_registry;
 pub mod refcount_registry;
 pub mod resource_manager_errors;
 pub mod resources_registry;
+pub mod audio_conversion;
+pub mod mesh_registry;
+pub mod material_registry;
+pub mod obj_loader;
+pub mod gltf_loader;
+pub mod mesh_processing;
+pub mod texture_processing;
+pub mod mesh_simplification;
+pub mod memory_budget;
+pub mod memory_report;
+pub mod scratch;
+pub mod resource_pool;
+pub mod heap_region;
+pub mod lru_cache;
+pub mod binary;
+pub mod pak;
+pub mod native_resource;
+pub mod native_texture;
+pub mod native_mesh;
+pub mod native_audio;
+pub mod resource_reader;
+pub mod raw_registry;
+pub mod mapped_pak;
+pub mod vfs;
+pub mod hot_reload;
+pub mod resource_events;
+pub mod guid;
+pub mod resource_meta;
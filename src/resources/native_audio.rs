@@ -0,0 +1,186 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*
+    Native audio clips : pcm samples to be mixed directly, or a vorbis stream to be streamed.
+
+    Every integer is little-endian.
+
+    - encoding      u32     0 : pcm, 1 : vorbis.
+    - sample rate   u32
+    - channels      u32
+    - frames        u64     number of samples per channel, 0 when unknown.
+    - size          u64     size of the data.
+    - data          pcm : the interleaved samples, as i16. vorbis : the ogg stream.
+
+    Pcm clips only, after the data :
+    - loop flags    u32     bit 0 : the clip has loop markers. The other bits are reserved, 0.
+    - loop start    u64     in samples per channel, at the sample rate of the clip. 0 without loop markers.
+    - loop length   u64     in samples per channel, at the sample rate of the clip. 0 without loop markers.
+    - comment count u32
+    - comments      the vorbis comments of the source stream, as the length (u32) and utf-8 bytes of the tag,
+                    then of the value. Their loop markers are superseded by the loop fields.
+*/
+
+use std::io::{Read, Write};
+use resources::binary::{read_u32, read_u64, read_bytes, read_string, write_u16, write_u32, write_u64, write_string};
+use resources::audio_conversion::{AudioFormat, PcmClip};
+use resources::ogg_registry::LoopPoints;
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+
+pub const NATIVE_AUDIO_VERSION: u32 = 2;
+const AUDIO_ENCODING_PCM: u32 = 0;
+const AUDIO_ENCODING_VORBIS: u32 = 1;
+const AUDIO_FLAG_LOOP: u32 = 1;
+
+/// A native audio clip.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NativeAudio {
+    /// Decoded and converted to the target format of the meta file of the clip.
+    Pcm {
+        clip: PcmClip,
+        /// The loop markers of the source stream, resampled to the sample rate of the clip.
+        loop_points: Option<LoopPoints>,
+        /// The vorbis comments of the source stream.
+        comments: Vec<(String, String)>,
+    },
+    /// A validated ogg stream, kept compressed to be streamed.
+    Vorbis {
+        format: AudioFormat,
+        frames: Option<u64>,
+        stream: Vec<u8>,
+    },
+}
+
+impl NativeAudio {
+    pub fn format(&self) -> AudioFormat {
+        match self {
+            &NativeAudio::Pcm { ref clip, .. } => clip.format(),
+            &NativeAudio::Vorbis { format, .. } => format,
+        }
+    }
+
+    /// The number of samples per channel, if it is known.
+    pub fn frames(&self) -> Option<u64> {
+        match self {
+            &NativeAudio::Pcm { ref clip, .. } => Some(clip.frames() as u64),
+            &NativeAudio::Vorbis { frames, .. } => frames,
+        }
+    }
+}
+
+/// Writes a native audio clip.
+pub fn write_audio<W: Write>(writer: &mut W, audio: &NativeAudio) -> ResourceManagerResult<()> {
+    debug!("Writing a native audio clip.");
+    let format = audio.format();
+    let encoding = match audio {
+        &NativeAudio::Pcm { .. } => AUDIO_ENCODING_PCM,
+        &NativeAudio::Vorbis { .. } => AUDIO_ENCODING_VORBIS,
+    };
+    write_u32(writer, encoding)?;
+    write_u32(writer, format.sample_rate)?;
+    write_u32(writer, u32::from(format.channels))?;
+    write_u64(writer, audio.frames().unwrap_or(0))?;
+    match audio {
+        &NativeAudio::Pcm { ref clip, loop_points, ref comments } => {
+            write_u64(writer, (clip.samples().len() * 2) as u64)?;
+            for sample in clip.samples() {
+                write_u16(writer, *sample as u16)?;
+            }
+            write_u32(writer, if loop_points.is_some() { AUDIO_FLAG_LOOP } else { 0 })?;
+            write_u64(writer, loop_points.map_or(0, |loop_points| loop_points.start))?;
+            write_u64(writer, loop_points.map_or(0, |loop_points| loop_points.length))?;
+            write_u32(writer, comments.len() as u32)?;
+            for &(ref tag, ref value) in comments.iter() {
+                write_string(writer, tag.as_str())?;
+                write_string(writer, value.as_str())?;
+            }
+        },
+        &NativeAudio::Vorbis { ref stream, .. } => {
+            write_u64(writer, stream.len() as u64)?;
+            writer.write_all(stream.as_slice())?;
+        },
+    }
+    Ok(())
+}
+
+/// Reads a native audio clip.
+pub fn read_audio<R: Read>(reader: &mut R) -> ResourceManagerResult<NativeAudio> {
+    debug!("Reading a native audio clip.");
+    let encoding = read_u32(reader)?;
+    let sample_rate = read_u32(reader)?;
+    let channels = read_u32(reader)?;
+    let frames = read_u64(reader)?;
+    let size = read_u64(reader)? as usize;
+    if sample_rate == 0 || channels == 0 || channels > u32::from(u8::max_value()) {
+        return Err(ResourceManagerError::ResourceError(format!("The format of a native audio clip, {} Hz and {} channel(s), is not valid !", sample_rate, channels)));
+    }
+    if encoding == AUDIO_ENCODING_PCM && size % 2 != 0 {
+        return Err(ResourceManagerError::ResourceError(format!("The pcm samples of a native audio clip take {} bytes, which is not a whole number of 16-bit samples !", size)));
+    }
+    let format = AudioFormat::new(sample_rate, channels as u8);
+    let data = read_bytes(reader, size)?;
+
+    match encoding {
+        AUDIO_ENCODING_PCM => {
+            let samples = data.chunks(2).map(|sample| i16::from_le_bytes([sample[0], sample[1]])).collect();
+            let loop_flags = read_u32(reader)?;
+            let loop_start = read_u64(reader)?;
+            let loop_length = read_u64(reader)?;
+            let loop_points = if loop_flags & AUDIO_FLAG_LOOP != 0 {
+                if loop_start.checked_add(loop_length).is_none() {
+                    return Err(ResourceManagerError::ResourceError(format!("The loop markers of a native audio clip, {} + {} samples, do not fit in 64 bits !", loop_start, loop_length)));
+                }
+                Some(LoopPoints { start: loop_start, length: loop_length })
+            } else {
+                None
+            };
+            let comment_count = read_u32(reader)?;
+            let mut comments = Vec::new();
+            for _ in 0..comment_count {
+                let tag = read_string(reader)?;
+                let value = read_string(reader)?;
+                comments.push((tag, value));
+            }
+            Ok(NativeAudio::Pcm {
+                clip: PcmClip::new(format, samples),
+                loop_points,
+                comments,
+            })
+        },
+        AUDIO_ENCODING_VORBIS => Ok(NativeAudio::Vorbis {
+            format,
+            frames: if frames == 0 { None } else { Some(frames) },
+            stream: data,
+        }),
+        _ => Err(ResourceManagerError::ResourceError(format!("The encoding {} of a native audio clip is not supported !", encoding))),
+    }
+}
+
+#[cfg(test)]
+mod native_audio_test {
+    use super::*;
+
+    #[test]
+    fn native_audio_write_and_read_pcm() {
+        let audio = NativeAudio::Pcm {
+            clip: PcmClip::new(AudioFormat::new(22050, 2), vec![0, -1, 2, -3, 4, -5]),
+            loop_points: Some(LoopPoints { start: 1, length: 2 }),
+            comments: vec![(String::from("TITLE"), String::from("untitled")), (String::from("LOOPSTART"), String::from("2"))],
+        };
+        let mut data = Vec::new();
+        write_audio(&mut data, &audio).expect("Could not write the pcm audio clip.");
+        assert_eq!(read_audio(&mut data.as_slice()).expect("Could not read the pcm audio clip."), audio);
+
+        //A pcm payload must hold whole 16-bit samples, and its comments must not be truncated.
+        let mut odd_size = data.clone();
+        odd_size[20] |= 1;
+        assert!(read_audio(&mut odd_size.as_slice()).is_err());
+        let truncated = &data[..data.len() - 1];
+        assert!(read_audio(&mut &truncated[..]).is_err());
+    }
+}
@@ -0,0 +1,192 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*
+    Native meshes : the vertex attributes and the triangle lists of a mesh, ready to be uploaded.
+
+    Every integer is little-endian, every float is an IEEE 754 single.
+
+    - vertex count      u32
+    - flags             u32     bit 0 : normals, bit 1 : texture coordinates, bit 2 : tangents, bit 3 : bounds.
+                                The other bits are reserved, 0.
+    - positions         vertex count * 3 f32
+    - normals           vertex count * 3 f32, if present.
+    - tex coords        vertex count * 2 f32, if present.
+    - tangents          vertex count * 4 f32, if present.
+    - bounds            min (3 f32), max (3 f32), center (3 f32), radius (f32), if present.
+    - primitive count   u32
+    - primitives, one after the other :
+        - index count   u32
+        - material      path length (u32) and utf-8 bytes of the path of the material, empty without material.
+        - indices       index count * u32
+*/
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use resources::binary::{read_u32, read_f32, read_path, write_u32, write_f32, write_path};
+use resources::mesh_registry::{MeshResource, MeshPrimitive, MeshBounds};
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+
+pub const NATIVE_MESH_VERSION: u32 = 1;
+const MESH_FLAG_NORMALS: u32 = 1;
+const MESH_FLAG_TEX_COORDS: u32 = 1 << 1;
+const MESH_FLAG_TANGENTS: u32 = 1 << 2;
+const MESH_FLAG_BOUNDS: u32 = 1 << 3;
+
+fn write_floats<W: Write>(writer: &mut W, floats: &[f32]) -> ResourceManagerResult<()> {
+    for float in floats {
+        write_f32(writer, *float)?;
+    }
+    Ok(())
+}
+
+fn read_floats<R: Read>(reader: &mut R, floats: &mut [f32]) -> ResourceManagerResult<()> {
+    for float in floats.iter_mut() {
+        *float = read_f32(reader)?;
+    }
+    Ok(())
+}
+
+/// Writes a mesh as a native mesh. Its levels of detail are not written, they are generated at load time.
+pub fn write_mesh<W: Write>(writer: &mut W, mesh: &MeshResource) -> ResourceManagerResult<()> {
+    debug!("Writing a native mesh of {} vertices.", mesh.vertex_count());
+    let vertex_count = mesh.vertex_count();
+    if [mesh.normals.len(), mesh.tex_coords.len(), mesh.tangents.len()].iter().any(|&len| len != 0 && len != vertex_count) {
+        return Err(ResourceManagerError::ResourceError(String::from("The vertex attributes of a mesh do not have the same length !")));
+    }
+
+    let mut flags = 0;
+    if mesh.has_normals() { flags |= MESH_FLAG_NORMALS; }
+    if mesh.has_tex_coords() { flags |= MESH_FLAG_TEX_COORDS; }
+    if mesh.has_tangents() { flags |= MESH_FLAG_TANGENTS; }
+    if mesh.bounds.is_some() { flags |= MESH_FLAG_BOUNDS; }
+    write_u32(writer, vertex_count as u32)?;
+    write_u32(writer, flags)?;
+
+    for position in mesh.positions.iter() {
+        write_floats(writer, position)?;
+    }
+    for normal in mesh.normals.iter() {
+        write_floats(writer, normal)?;
+    }
+    for tex_coord in mesh.tex_coords.iter() {
+        write_floats(writer, tex_coord)?;
+    }
+    for tangent in mesh.tangents.iter() {
+        write_floats(writer, tangent)?;
+    }
+    if let Some(ref bounds) = mesh.bounds {
+        write_floats(writer, &bounds.min)?;
+        write_floats(writer, &bounds.max)?;
+        write_floats(writer, &bounds.center)?;
+        write_f32(writer, bounds.radius)?;
+    }
+
+    write_u32(writer, mesh.primitives.len() as u32)?;
+    for primitive in mesh.primitives.iter() {
+        write_u32(writer, primitive.indices.len() as u32)?;
+        write_path(writer, primitive.material.as_ref().map(PathBuf::as_path).unwrap_or_else(|| Path::new("")))?;
+        for index in primitive.indices.iter() {
+            write_u32(writer, *index)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads a native mesh.
+pub fn read_mesh<R: Read>(reader: &mut R) -> ResourceManagerResult<MeshResource> {
+    debug!("Reading a native mesh.");
+    let vertex_count = read_u32(reader)? as usize;
+    let flags = read_u32(reader)?;
+    let mut mesh = MeshResource::new();
+
+    let mut position = [0.0; 3];
+    for _ in 0..vertex_count {
+        read_floats(reader, &mut position)?;
+        mesh.positions.push(position);
+    }
+    if flags & MESH_FLAG_NORMALS != 0 {
+        let mut normal = [0.0; 3];
+        for _ in 0..vertex_count {
+            read_floats(reader, &mut normal)?;
+            mesh.normals.push(normal);
+        }
+    }
+    if flags & MESH_FLAG_TEX_COORDS != 0 {
+        let mut tex_coord = [0.0; 2];
+        for _ in 0..vertex_count {
+            read_floats(reader, &mut tex_coord)?;
+            mesh.tex_coords.push(tex_coord);
+        }
+    }
+    if flags & MESH_FLAG_TANGENTS != 0 {
+        let mut tangent = [0.0; 4];
+        for _ in 0..vertex_count {
+            read_floats(reader, &mut tangent)?;
+            mesh.tangents.push(tangent);
+        }
+    }
+    if flags & MESH_FLAG_BOUNDS != 0 {
+        let mut bounds = MeshBounds {
+            min: [0.0; 3],
+            max: [0.0; 3],
+            center: [0.0; 3],
+            radius: 0.0,
+        };
+        read_floats(reader, &mut bounds.min)?;
+        read_floats(reader, &mut bounds.max)?;
+        read_floats(reader, &mut bounds.center)?;
+        bounds.radius = read_f32(reader)?;
+        mesh.bounds = Some(bounds);
+    }
+
+    let primitive_count = read_u32(reader)?;
+    for _ in 0..primitive_count {
+        let index_count = read_u32(reader)? as usize;
+        let material = read_path(reader)?;
+        let mut indices = Vec::new();
+        for _ in 0..index_count {
+            let index = read_u32(reader)?;
+            if index as usize >= vertex_count {
+                return Err(ResourceManagerError::ResourceError(format!("The index {} of a native mesh is out of bounds !", index)));
+            }
+            indices.push(index);
+        }
+        mesh.primitives.push(MeshPrimitive {
+            indices,
+            material: if material.as_os_str().is_empty() { None } else { Some(material) },
+        });
+    }
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod native_mesh_test {
+    use super::*;
+
+    #[test]
+    fn native_mesh_write_and_read() {
+        let mut mesh = MeshResource::new();
+        mesh.positions = vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        mesh.primitives.push(MeshPrimitive {
+            indices: vec![0, 1, 2],
+            material: Some(PathBuf::from("res://obj_resource/untitled.mtl#Material")),
+        });
+        let mut data = Vec::new();
+        write_mesh(&mut data, &mesh).expect("Could not write the mesh.");
+        let read = read_mesh(&mut data.as_slice()).expect("Could not read the mesh.");
+        assert_eq!(read.positions, mesh.positions);
+        assert_eq!(read.primitives[0].indices, mesh.primitives[0].indices);
+        assert_eq!(read.primitives[0].material, mesh.primitives[0].material);
+
+        //An index count read from truncated data is not trusted.
+        let mut huge = data[..data.len() - 12].to_vec();
+        let count_offset = huge.len() - 4 - 4 - "res://obj_resource/untitled.mtl#Material".len();
+        huge[count_offset..count_offset + 4].copy_from_slice(&u32::max_value().to_le_bytes());
+        assert!(read_mesh(&mut huge.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*
+    Native resources : the container of the cooked data of a resource, written by the asset pipeline and read by the runtime loaders.
+
+    Every integer is little-endian.
+
+    Header (40 bytes) :
+    - magic             4 bytes     "MKNR"
+    - format version    u32         NATIVE_FORMAT_VERSION, the version of this container.
+    - resource type     u32         the id of the ResourceType of the payload.
+    - payload version   u32         the version of the format of the payload, the version of the compiler which wrote it.
+    - dependency count  u32
+    - alignment         u32         alignment of the payload, from the start of the file.
+    - payload offset    u64         offset of the payload, from the start of the file, a multiple of the alignment.
+    - payload size      u64
+
+    Dependencies, one record per resource needed by this one :
+    - path length       u32
+    - path              utf-8 bytes of the virtual path, or of the GUID reference, of the dependency.
+
+    Padding : zeros, up to the payload offset.
+
+    Payload : the cooked data, in the format of its resource type. A pak file aligned on the alignment of the payload,
+    or a multiple of it, keeps the payload aligned when it is mapped in memory.
+*/
+
+use std::io::{self, Cursor, Read, Write};
+use std::path::PathBuf;
+use resources::binary::{read_u32, read_u64, read_bytes, read_path, write_u32, write_u64, write_path};
+use resources::resources_registry::ResourceType;
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+
+pub const NATIVE_MAGIC: [u8; 4] = *b"MKNR";
+pub const NATIVE_FORMAT_VERSION: u32 = 1;
+/// The alignment of the payloads, enough for the SIMD loads of the texels and of the vertices.
+pub const NATIVE_PAYLOAD_ALIGNMENT: u32 = 16;
+const NATIVE_HEADER_SIZE: u64 = 40;
+
+fn align(offset: u64, alignment: u32) -> u64 {
+    let alignment = u64::from(alignment);
+    (offset + alignment - 1) / alignment * alignment
+}
+
+struct NativeHeader {
+    resource_type: ResourceType,
+    payload_version: u32,
+    dependency_count: u32,
+    payload_offset: u64,
+    payload_size: u64,
+}
+
+impl NativeHeader {
+    fn read_from<R: Read>(reader: &mut R) -> ResourceManagerResult<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != NATIVE_MAGIC {
+            return Err(ResourceManagerError::ResourceError(String::from("The data is not a native resource, its magic number is not valid !")));
+        }
+        let format_version = read_u32(reader)?;
+        if format_version != NATIVE_FORMAT_VERSION {
+            return Err(ResourceManagerError::VersionMismatch(format!("The native resource has been written with the version {} of the format, the version {} is supported !", format_version, NATIVE_FORMAT_VERSION), format_version, NATIVE_FORMAT_VERSION));
+        }
+        let type_id = read_u32(reader)?;
+        let resource_type = ResourceType::from_id(type_id)
+            .ok_or_else(|| ResourceManagerError::ResourceError(format!("The resource type {} of the native resource is not known !", type_id)))?;
+        let payload_version = read_u32(reader)?;
+        let dependency_count = read_u32(reader)?;
+        let alignment = read_u32(reader)?;
+        let payload_offset = read_u64(reader)?;
+        let payload_size = read_u64(reader)?;
+        if alignment == 0 || payload_offset % u64::from(alignment) != 0 || payload_offset < NATIVE_HEADER_SIZE {
+            return Err(ResourceManagerError::ResourceError(format!("The payload offset {} of the native resource is not aligned on {} bytes !", payload_offset, alignment)));
+        }
+
+        Ok(NativeHeader {
+            resource_type,
+            payload_version,
+            dependency_count,
+            payload_offset,
+            payload_size,
+        })
+    }
+
+    fn check(&self, resource_type: ResourceType, payload_version: u32) -> ResourceManagerResult<()> {
+        if self.resource_type != resource_type {
+            return Err(ResourceManagerError::ResourceError(format!("The native resource holds a {} resource, not a {} resource !", self.resource_type, resource_type)));
+        }
+        if self.payload_version != payload_version {
+            return Err(ResourceManagerError::VersionMismatch(format!("The {} payload of the native resource has been written with the version {} of its format, the version {} is supported. It must be cooked again !", resource_type, self.payload_version, payload_version), self.payload_version, payload_version));
+        }
+        Ok(())
+    }
+}
+
+/// The cooked data of a resource, with the resources it needs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NativeResource {
+    pub resource_type: ResourceType,
+    pub payload_version: u32,
+    /// The resources which must be loaded with this one, as virtual paths or GUID references.
+    pub dependencies: Vec<PathBuf>,
+    pub payload: Vec<u8>,
+}
+
+impl NativeResource {
+    pub fn new(resource_type: ResourceType, payload_version: u32, payload: Vec<u8>) -> Self {
+        NativeResource::with_dependencies(resource_type, payload_version, Vec::new(), payload)
+    }
+
+    pub fn with_dependencies(resource_type: ResourceType, payload_version: u32, dependencies: Vec<PathBuf>, payload: Vec<u8>) -> Self {
+        NativeResource {
+            resource_type,
+            payload_version,
+            dependencies,
+            payload,
+        }
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> ResourceManagerResult<()> {
+        debug!("Writing a native {} resource.", self.resource_type);
+        let mut dependencies = Vec::new();
+        for dependency in self.dependencies.iter() {
+            write_path(&mut dependencies, dependency.as_path())?;
+        }
+        let payload_offset = align(NATIVE_HEADER_SIZE + dependencies.len() as u64, NATIVE_PAYLOAD_ALIGNMENT);
+
+        writer.write_all(&NATIVE_MAGIC)?;
+        write_u32(writer, NATIVE_FORMAT_VERSION)?;
+        write_u32(writer, self.resource_type.id())?;
+        write_u32(writer, self.payload_version)?;
+        write_u32(writer, self.dependencies.len() as u32)?;
+        write_u32(writer, NATIVE_PAYLOAD_ALIGNMENT)?;
+        write_u64(writer, payload_offset)?;
+        write_u64(writer, self.payload.len() as u64)?;
+        writer.write_all(dependencies.as_slice())?;
+        let padding = payload_offset - NATIVE_HEADER_SIZE - dependencies.len() as u64;
+        io::copy(&mut io::repeat(0).take(padding), writer)?;
+        writer.write_all(self.payload.as_slice())?;
+        Ok(())
+    }
+
+    /// Reads a native resource, of any type and payload version.
+    ///
+    /// A native resource written with another version of the container is refused with `ResourceManagerError::VersionMismatch`.
+    pub fn read_from<R: Read>(reader: &mut R) -> ResourceManagerResult<Self> {
+        debug!("Reading a native resource.");
+        let header = NativeHeader::read_from(reader)?;
+        NativeResource::read_body(reader, header)
+    }
+
+    /// Reads a native resource, which must hold a payload of this type and of this version.
+    ///
+    /// Stale cooked data, written by another version of its compiler, is refused with `ResourceManagerError::VersionMismatch`.
+    pub fn read_expected<R: Read>(reader: &mut R, resource_type: ResourceType, payload_version: u32) -> ResourceManagerResult<Self> {
+        debug!("Reading a native {} resource, version {}.", resource_type, payload_version);
+        let header = NativeHeader::read_from(reader)?;
+        header.check(resource_type, payload_version)?;
+        NativeResource::read_body(reader, header)
+    }
+
+    fn read_body<R: Read>(reader: &mut R, header: NativeHeader) -> ResourceManagerResult<Self> {
+        let mut position = NATIVE_HEADER_SIZE;
+        let mut dependencies = Vec::new();
+        for _ in 0..header.dependency_count {
+            let dependency = read_path(reader)?;
+            position += 4 + dependency.to_string_lossy().len() as u64;
+            dependencies.push(dependency);
+        }
+        if position > header.payload_offset {
+            return Err(ResourceManagerError::ResourceError(String::from("The dependencies of the native resource overlap its payload !")));
+        }
+        read_bytes(reader, (header.payload_offset - position) as usize)?;
+        let payload = read_bytes(reader, header.payload_size as usize)?;
+
+        Ok(NativeResource {
+            resource_type: header.resource_type,
+            payload_version: header.payload_version,
+            dependencies,
+            payload,
+        })
+    }
+
+    /// The payload of a native resource held in memory, like an entry of a memory-mapped pak file, without copying it.
+    pub fn payload_in(data: &[u8], resource_type: ResourceType, payload_version: u32) -> ResourceManagerResult<&[u8]> {
+        let header = NativeHeader::read_from(&mut Cursor::new(data))?;
+        header.check(resource_type, payload_version)?;
+        let start = header.payload_offset as usize;
+        match start.checked_add(header.payload_size as usize) {
+            Some(end) if end <= data.len() => Ok(&data[start..end]),
+            _ => Err(ResourceManagerError::ResourceError(String::from("The payload of the native resource is truncated !"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod native_resource_test {
+    use super::*;
+
+    #[test]
+    fn native_resource_write_and_read() {
+        let resource = NativeResource::with_dependencies(ResourceType::Mesh, 3, vec![PathBuf::from("res://tga_resource/Untitled.tga")], vec![1, 2, 3, 4, 5]);
+        let mut data = Vec::new();
+        resource.write_to(&mut data).expect("Could not write the native resource.");
+        assert_eq!(&data[..4], b"MKNR");
+        assert_eq!(NativeResource::read_from(&mut data.as_slice()).expect("Could not read the native resource."), resource);
+        assert_eq!(NativeResource::read_expected(&mut data.as_slice(), ResourceType::Mesh, 3).expect("Could not read the native resource.").payload, vec![1, 2, 3, 4, 5]);
+
+        //The payload is aligned from the start of the data.
+        let payload = NativeResource::payload_in(data.as_slice(), ResourceType::Mesh, 3).expect("Could not find the payload.");
+        assert_eq!(payload, &[1, 2, 3, 4, 5]);
+        assert_eq!((payload.as_ptr() as usize - data.as_ptr() as usize) % NATIVE_PAYLOAD_ALIGNMENT as usize, 0);
+        //A payload size read from the data cannot overflow its end.
+        let mut huge = data.clone();
+        huge[32..40].copy_from_slice(&u64::max_value().to_le_bytes());
+        assert!(NativeResource::payload_in(huge.as_slice(), ResourceType::Mesh, 3).is_err());
+
+        //Stale data, or data of another type, is refused.
+        match NativeResource::read_expected(&mut data.as_slice(), ResourceType::Mesh, 4) {
+            Err(ResourceManagerError::VersionMismatch(_, found, supported)) => assert_eq!((found, supported), (3, 4)),
+            _ => panic!("Stale payload accepted."),
+        }
+        assert!(NativeResource::read_expected(&mut data.as_slice(), ResourceType::Tga, 3).is_err());
+        data[4] = 2;
+        match NativeResource::read_from(&mut data.as_slice()) {
+            Err(ResourceManagerError::VersionMismatch(_, found, supported)) => assert_eq!((found, supported), (2, NATIVE_FORMAT_VERSION)),
+            _ => panic!("Stale container accepted."),
+        }
+        assert!(NativeResource::read_from(&mut &b"not a native resource"[..]).is_err());
+    }
+}
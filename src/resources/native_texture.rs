@@ -0,0 +1,92 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*
+    Native textures : the RGBA8 texels of a texture and of its mip chain, LZ4 compressed.
+
+    Every integer is little-endian.
+
+    - width         u32
+    - height        u32
+    - flags         u32         bit 0 : the colors are sRGB encoded. The other bits are reserved, 0.
+    - level count   u32         the texture and its mips.
+    - size          u64         size of the compressed texels.
+    - texels        LZ4 frame of the texels of every level, from the largest one.
+                    A level is half the size of the previous one, rounded down, at least 1x1.
+*/
+
+use std::io::{Cursor, Read, Write};
+use imagefmt::{ColFmt, Image};
+use resources::binary::{read_u32, read_u64, read_bytes, write_u32, write_u64};
+use resources::pak::PakCompression;
+use resources::tga_registry::TgaResource;
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+
+pub const NATIVE_TEXTURE_VERSION: u32 = 1;
+const TEXTURE_FLAG_SRGB: u32 = 1;
+const RGBA_CHANNELS: usize = 4;
+
+/// Writes a texture and its mip chain as a native texture.
+pub fn write_texture<W: Write>(writer: &mut W, image: &Image<u8>, srgb: bool, mips: &[Image<u8>]) -> ResourceManagerResult<()> {
+    debug!("Writing a compiled {}x{} texture with {} mips.", image.w, image.h, mips.len());
+    let mut texels = Vec::with_capacity(image.buf.len() * 4 / 3);
+    for level in Some(image).into_iter().chain(mips.iter()) {
+        if level.buf.len() != level.w * level.h * RGBA_CHANNELS {
+            return Err(ResourceManagerError::ResourceError(format!("A {}x{} level of a texture is not RGBA8 !", level.w, level.h)));
+        }
+        texels.extend_from_slice(level.buf.as_slice());
+    }
+    let compressed = PakCompression::Lz4.compress(texels.as_slice())?;
+
+    write_u32(writer, image.w as u32)?;
+    write_u32(writer, image.h as u32)?;
+    write_u32(writer, if srgb { TEXTURE_FLAG_SRGB } else { 0 })?;
+    write_u32(writer, (mips.len() + 1) as u32)?;
+    write_u64(writer, compressed.len() as u64)?;
+    writer.write_all(compressed.as_slice())?;
+    Ok(())
+}
+
+/// Reads a native texture.
+pub fn read_texture<R: Read>(reader: &mut R) -> ResourceManagerResult<TgaResource> {
+    debug!("Reading a native texture.");
+    let width = read_u32(reader)? as usize;
+    let height = read_u32(reader)? as usize;
+    let flags = read_u32(reader)?;
+    let level_count = read_u32(reader)? as usize;
+    let size = read_u64(reader)? as usize;
+    if width == 0 || height == 0 || level_count == 0 {
+        return Err(ResourceManagerError::ResourceError(String::from("A native texture is empty !")));
+    }
+    let compressed = read_bytes(reader, size)?;
+    let mut texels = Vec::new();
+    PakCompression::Lz4.decoder(Cursor::new(compressed))?.read_to_end(&mut texels)?;
+
+    let mut levels = Vec::new();
+    let (mut level_width, mut level_height, mut offset) = (width, height, 0usize);
+    for _ in 0..level_count {
+        let end = level_width.checked_mul(level_height)
+            .and_then(|texel_count| texel_count.checked_mul(RGBA_CHANNELS))
+            .and_then(|len| offset.checked_add(len));
+        let end = match end {
+            Some(end) if end <= texels.len() => end,
+            _ => return Err(ResourceManagerError::ResourceError(String::from("The texels of a native texture are truncated !"))),
+        };
+        levels.push(Image {
+            w: level_width,
+            h: level_height,
+            fmt: ColFmt::RGBA,
+            buf: texels[offset..end].to_vec(),
+        });
+        offset = end;
+        level_width = (level_width / 2).max(1);
+        level_height = (level_height / 2).max(1);
+    }
+
+    let image = levels.remove(0);
+    Ok(TgaResource::with_mips(image, flags & TEXTURE_FLAG_SRGB != 0, levels))
+}
@@ -0,0 +1,291 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::str::SplitWhitespace;
+use resources::mesh_registry::{MeshResource, MeshPrimitive};
+use resources::material_registry::MaterialResource;
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+
+/// The content of a Wavefront OBJ file.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ObjData {
+    pub mesh: MeshResource,
+    /// The `mtllib` statements of the file, as written in the file.
+    pub material_libraries: Vec<PathBuf>,
+    /// The `usemtl` name of each primitive of the mesh.
+    pub material_names: Vec<Option<String>>,
+}
+
+impl ObjData {
+    /// Links each primitive of the mesh to the resource path of its material.
+    ///
+    /// `materials` maps a material name to its resource path, see `material_path`.
+    pub fn resolve_materials(&mut self, materials: &HashMap<String, PathBuf>) {
+        for (primitive, name) in self.mesh.primitives.iter_mut().zip(self.material_names.iter()) {
+            primitive.material = name.as_ref().and_then(|name| materials.get(name).cloned());
+        }
+    }
+}
+
+/// The resource path of a material defined in a MTL file : `library.mtl#material_name`.
+pub fn material_path<P: AsRef<Path>, I: AsRef<str>>(library: P, name: I) -> PathBuf {
+    PathBuf::from(format!("{}#{}", library.as_ref().display(), name.as_ref()))
+}
+
+fn parse_error(line_number: usize, description: &str) -> ResourceManagerError {
+    ResourceManagerError::ResourceError(format!("Line {}: {}", line_number, description))
+}
+
+fn parse_floats(tokens: SplitWhitespace, expected: usize, line_number: usize) -> ResourceManagerResult<Vec<f32>> {
+    let values = tokens.take(expected)
+        .map(|token| token.parse::<f32>())
+        .collect::<Result<Vec<f32>, _>>()
+        .map_err(|_| parse_error(line_number, "invalid number."))?;
+
+    if values.len() < expected {
+        return Err(parse_error(line_number, "missing number."));
+    }
+
+    Ok(values)
+}
+
+//OBJ indices start at 1, and negative indices are relative to the end of the list.
+fn resolve_index(token: &str, count: usize, line_number: usize) -> ResourceManagerResult<usize> {
+    let index = token.parse::<isize>().map_err(|_| parse_error(line_number, "invalid face index."))?;
+    let resolved = if index < 0 { count as isize + index } else { index - 1 };
+    if resolved < 0 || resolved >= count as isize {
+        return Err(parse_error(line_number, "face index out of range."));
+    }
+    Ok(resolved as usize)
+}
+
+/// Parses a Wavefront OBJ file.
+///
+/// Polygons are triangulated as fans, and each `usemtl` statement starts a new primitive.
+pub fn parse_obj<R: BufRead>(reader: R) -> ResourceManagerResult<ObjData> {
+    debug!("Parsing an OBJ file.");
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+    let mut tex_coords: Vec<[f32; 2]> = Vec::new();
+    //(position, tex coord, normal) -> vertex index in the mesh.
+    let mut vertices: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+    let mut vertex_keys: Vec<(usize, Option<usize>, Option<usize>)> = Vec::new();
+    let mut obj_data = ObjData::default();
+    let mut current = MeshPrimitive::default();
+    let mut current_material: Option<String> = None;
+
+    for (line_index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_number = line_index + 1;
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line.as_str(),
+        };
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next() {
+            Some("v") => {
+                let values = parse_floats(tokens, 3, line_number)?;
+                positions.push([values[0], values[1], values[2]]);
+            },
+            Some("vn") => {
+                let values = parse_floats(tokens, 3, line_number)?;
+                normals.push([values[0], values[1], values[2]]);
+            },
+            Some("vt") => {
+                let values = parse_floats(tokens, 2, line_number)?;
+                tex_coords.push([values[0], values[1]]);
+            },
+            Some("f") => {
+                let mut face = Vec::new();
+                for vertex in tokens {
+                    let mut indices = vertex.split('/');
+                    let position = resolve_index(indices.next().unwrap_or(""), positions.len(), line_number)?;
+                    let tex_coord = match indices.next() {
+                        Some(token) if !token.is_empty() => Some(resolve_index(token, tex_coords.len(), line_number)?),
+                        _ => None,
+                    };
+                    let normal = match indices.next() {
+                        Some(token) if !token.is_empty() => Some(resolve_index(token, normals.len(), line_number)?),
+                        _ => None,
+                    };
+
+                    let key = (position, tex_coord, normal);
+                    let index = match vertices.get(&key) {
+                        Some(&index) => index,
+                        None => {
+                            let index = vertex_keys.len() as u32;
+                            vertex_keys.push(key);
+                            vertices.insert(key, index);
+                            index
+                        },
+                    };
+                    face.push(index);
+                }
+
+                if face.len() < 3 {
+                    return Err(parse_error(line_number, "a face needs at least 3 vertices."));
+                }
+                for i in 1..face.len() - 1 {
+                    current.indices.extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                }
+            },
+            Some("usemtl") => {
+                if !current.indices.is_empty() {
+                    obj_data.mesh.primitives.push(current);
+                    obj_data.material_names.push(current_material.take());
+                    current = MeshPrimitive::default();
+                }
+                current_material = tokens.next().map(|name| name.to_string());
+            },
+            Some("mtllib") => {
+                obj_data.material_libraries.extend(tokens.map(PathBuf::from));
+            },
+            //Objects, groups, smoothing groups and unknown statements don't change the mesh.
+            _ => {},
+        }
+    }
+
+    if !current.indices.is_empty() {
+        obj_data.mesh.primitives.push(current);
+        obj_data.material_names.push(current_material);
+    }
+
+    //Normals and texture coordinates are kept only if every vertex has one.
+    let has_normals = !vertex_keys.is_empty() && vertex_keys.iter().all(|key| key.2.is_some());
+    let has_tex_coords = !vertex_keys.is_empty() && vertex_keys.iter().all(|key| key.1.is_some());
+    for &(position, tex_coord, normal) in vertex_keys.iter() {
+        obj_data.mesh.positions.push(positions[position]);
+        if has_normals {
+            obj_data.mesh.normals.push(normals[normal.unwrap()]);
+        }
+        if has_tex_coords {
+            obj_data.mesh.tex_coords.push(tex_coords[tex_coord.unwrap()]);
+        }
+    }
+
+    Ok(obj_data)
+}
+
+/// Parses a Wavefront MTL file. Texture paths are resolved relatively to `directory`.
+pub fn parse_mtl<R, P>(reader: R, directory: P) -> ResourceManagerResult<Vec<MaterialResource>> where
+    R: BufRead,
+    P: AsRef<Path>,
+{
+    debug!("Parsing a MTL file.");
+    let mut materials = Vec::new();
+    let mut current: Option<MaterialResource> = None;
+
+    for (line_index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_number = line_index + 1;
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line.as_str(),
+        };
+        let mut tokens = line.split_whitespace();
+        let statement = match tokens.next() {
+            Some(statement) => statement,
+            None => continue,
+        };
+
+        if statement == "newmtl" {
+            let name = tokens.next().ok_or_else(|| parse_error(line_number, "a material needs a name."))?;
+            if let Some(material) = current.take() {
+                materials.push(material);
+            }
+            current = Some(MaterialResource::new(name));
+            continue;
+        }
+
+        let material = match current.as_mut() {
+            Some(material) => material,
+            None => return Err(parse_error(line_number, "statement outside of a material.")),
+        };
+
+        match statement {
+            "Ka" | "Kd" | "Ks" | "Ke" => {
+                let values = parse_floats(tokens, 3, line_number)?;
+                let color = [values[0], values[1], values[2]];
+                match statement {
+                    "Ka" => material.ambient = color,
+                    "Kd" => material.diffuse = color,
+                    "Ks" => material.specular = color,
+                    _ => material.emissive = color,
+                }
+            },
+            "Ns" => {
+                material.shininess = parse_floats(tokens, 1, line_number)?[0];
+            },
+            "d" => {
+                material.opacity = parse_floats(tokens, 1, line_number)?[0];
+            },
+            "Tr" => {
+                material.opacity = 1.0 - parse_floats(tokens, 1, line_number)?[0];
+            },
+            "map_Ka" | "map_Kd" | "map_Ks" | "map_Bump" | "map_bump" | "bump" | "norm" => {
+                //Texture options come before the file name.
+                let texture = tokens.last()
+                    .map(|file| directory.as_ref().join(file))
+                    .ok_or_else(|| parse_error(line_number, "a texture map needs a file."))?;
+                match statement {
+                    "map_Ka" => material.ambient_texture = Some(texture),
+                    "map_Kd" => material.diffuse_texture = Some(texture),
+                    "map_Ks" => material.specular_texture = Some(texture),
+                    _ => material.normal_texture = Some(texture),
+                }
+            },
+            _ => {},
+        }
+    }
+
+    if let Some(material) = current {
+        materials.push(material);
+    }
+
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod obj_loader_test {
+    use super::*;
+
+    #[test]
+    fn obj_loader_parse_obj() {
+        let obj = "mtllib untitled.mtl\n\
+                   v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+                   vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n\
+                   vn 0 0 1\n\
+                   usemtl red\n\
+                   f 1/1/1 2/2/1 3/3/1 4/4/1\n\
+                   usemtl blue\n\
+                   f -4/1/1 -2/3/1 -1/4/1\n";
+        let obj_data = parse_obj(obj.as_bytes()).expect("Could not parse the OBJ data.");
+        assert_eq!(obj_data.material_libraries, vec![PathBuf::from("untitled.mtl")]);
+        assert_eq!(obj_data.mesh.vertex_count(), 4);
+        assert_eq!(obj_data.mesh.triangle_count(), 3);
+        assert!(obj_data.mesh.has_normals());
+        assert!(obj_data.mesh.has_tex_coords());
+        assert_eq!(obj_data.mesh.primitives[1].indices, vec![0, 2, 3]);
+        assert_eq!(obj_data.material_names, vec![Some(String::from("red")), Some(String::from("blue"))]);
+        assert!(parse_obj("v 0 0 0\nf 1 2 3\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn obj_loader_parse_mtl() {
+        let mtl = "newmtl red\nKd 1 0 0\nd 0.5\nmap_Kd -bm 1.0 red.tga\nnewmtl blue\nKd 0 0 1\n";
+        let materials = parse_mtl(mtl.as_bytes(), "textures").expect("Could not parse the MTL data.");
+        assert_eq!(materials.len(), 2);
+        assert_eq!(materials[0].diffuse, [1.0, 0.0, 0.0]);
+        assert_eq!(materials[0].opacity, 0.5);
+        assert_eq!(materials[0].diffuse_texture, Some(PathBuf::from("textures/red.tga")));
+        assert!(materials[1].textures().is_empty());
+    }
+}
@@ -8,15 +8,318 @@
 //TODO:Custom allocators if possible
 
 use std::collections::HashMap;
+use std::collections::hash_map::Iter;
 use std::path::{PathBuf, Path};
 use lewton::inside_ogg::OggStreamReader;
-use std::io::{Read, Seek};
+use lewton::VorbisError;
+use std::io::{Read, Seek, SeekFrom};
+use std::io;
+use std::time::Duration;
+use resources::audio_conversion::{PcmClip, AudioFormat};
+use resources::memory_budget::MemorySize;
+use std::mem;
 
-pub struct OggResource<R: Read + Seek>(OggStreamReader<R>);
+const OGG_CAPTURE_PATTERN: &[u8; 4] = b"OggS";
+const OGG_PAGE_HEADER_SIZE: usize = 27;
+//An ogg page cannot be bigger than 65307 bytes, the last page is always in this window.
+const OGG_MAX_PAGE_SIZE: u64 = 65_307;
+
+//The CRC-32 of an ogg page, computed with its checksum field set to zero.
+fn ogg_page_checksum(page: &[u8]) -> u32 {
+    let mut crc = 0u32;
+    for (index, &byte) in page.iter().enumerate() {
+        let byte = if index >= 22 && index < 26 { 0 } else { byte };
+        crc ^= u32::from(byte) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 { (crc << 1) ^ 0x04c1_1db7 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+fn read_le_u32(bytes: &[u8]) -> u32 {
+    let mut le_bytes = [0u8; 4];
+    le_bytes.copy_from_slice(&bytes[..4]);
+    u32::from_le_bytes(le_bytes)
+}
+
+//The length of the ogg page at the start of the data, None if the data does not start with a complete and valid page.
+fn ogg_page_length(data: &[u8]) -> Option<usize> {
+    //The version is 0, only the 3 lowest bits of the header type are used.
+    if data.len() < OGG_PAGE_HEADER_SIZE || &data[..4] != OGG_CAPTURE_PATTERN || data[4] != 0 || data[5] & !0x07 != 0 {
+        return None;
+    }
+    let header_length = OGG_PAGE_HEADER_SIZE + data[26] as usize;
+    if data.len() < header_length {
+        return None;
+    }
+    let page_length = header_length + data[OGG_PAGE_HEADER_SIZE..header_length].iter().map(|&lacing| lacing as usize).sum::<usize>();
+    if data.len() < page_length || ogg_page_checksum(&data[..page_length]) != read_le_u32(&data[22..26]) {
+        return None;
+    }
+    Some(page_length)
+}
+
+/// Returns the granule position of the last page of an ogg stream.
+///
+/// For a vorbis stream, it is the total number of samples (per channel) of the stream.
+/// Only the pages of the logical stream starting the file, with a valid checksum, are considered.
+/// The reader is rewound to the start of the stream afterward.
+pub fn last_granule_position<R: Read + Seek>(reader: &mut R) -> io::Result<Option<u64>> {
+    debug!("Searching the granule position of the last ogg page.");
+    let mut first_page = Vec::new();
+    reader.seek(SeekFrom::Start(0))?;
+    reader.by_ref().take(OGG_MAX_PAGE_SIZE).read_to_end(&mut first_page)?;
+    if ogg_page_length(first_page.as_slice()).is_none() {
+        reader.seek(SeekFrom::Start(0))?;
+        return Ok(None);
+    }
+    //The serial number of the logical stream, stored 14 bytes after the capture pattern.
+    let serial = read_le_u32(&first_page[14..18]);
+
+    let length = reader.seek(SeekFrom::End(0))?;
+    let window_start = length.saturating_sub(OGG_MAX_PAGE_SIZE);
+    reader.seek(SeekFrom::Start(window_start))?;
+    let mut window = Vec::new();
+    reader.read_to_end(&mut window)?;
+    reader.seek(SeekFrom::Start(0))?;
+
+    let mut granule_position = None;
+    let mut index = 0;
+    while index + OGG_PAGE_HEADER_SIZE <= window.len() {
+        let page = &window[index..];
+        match ogg_page_length(page) {
+            Some(page_length) => {
+                //The granule position is stored 6 bytes after the capture pattern.
+                let mut granule_bytes = [0u8; 8];
+                granule_bytes.copy_from_slice(&page[6..14]);
+                let granule = u64::from_le_bytes(granule_bytes);
+                //A granule position of -1 means that no packet finishes on this page.
+                if read_le_u32(&page[14..18]) == serial && granule != u64::max_value() {
+                    granule_position = Some(granule);
+                }
+                index += page_length;
+            },
+            None => index += 1,
+        }
+    }
+
+    Ok(granule_position)
+}
+
+/// Decodes a whole ogg stream to interleaved pcm samples, then rewinds the stream to its first page.
+pub fn decode_to_pcm<R: Read + Seek>(ogg: &mut OggStreamReader<R>) -> Result<PcmClip, VorbisError> {
+    debug!("Decoding a whole ogg stream.");
+    let format = AudioFormat::new(ogg.ident_hdr.audio_sample_rate, ogg.ident_hdr.audio_channels);
+    let mut samples = Vec::new();
+    while let Some(packet) = ogg.read_dec_packet_itl()? {
+        samples.extend_from_slice(packet.as_slice());
+    }
+    ogg.seek_absgp_pg(0)?;
+    Ok(PcmClip::new(format, samples))
+}
+
+/// Loop markers of an audio clip, in samples, as found in the `LOOPSTART`/`LOOPLENGTH` vorbis comments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopPoints {
+    pub start: u64,
+    pub length: u64,
+}
+
+impl LoopPoints {
+    pub fn end(&self) -> u64 {
+        self.start.saturating_add(self.length)
+    }
+
+    /// Converts the loop markers of a clip resampled from `from_rate` to `to_rate`.
+    ///
+    /// Returns `None` if the markers do not fit in 64 bits at the new rate.
+    pub fn resampled(&self, from_rate: u32, to_rate: u32) -> Option<Self> {
+        if from_rate == 0 {
+            return Some(*self);
+        }
+        let convert = |samples: u64| samples.checked_mul(u64::from(to_rate)).map(|samples| samples / u64::from(from_rate));
+        Some(LoopPoints {
+            start: convert(self.start)?,
+            length: convert(self.length)?,
+        })
+    }
+}
+
+/// What lewton knows about an ogg stream : its format, its length and its vorbis comments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OggMetadata {
+    sample_rate: u32,
+    channels: u8,
+    total_samples: Option<u64>,
+    vendor: String,
+    comments: Vec<(String, String)>,
+}
+
+impl OggMetadata {
+    fn from_reader<R: Read + Seek>(ogg: &OggStreamReader<R>, total_samples: Option<u64>) -> Self {
+        OggMetadata {
+            sample_rate: ogg.ident_hdr.audio_sample_rate,
+            channels: ogg.ident_hdr.audio_channels,
+            total_samples,
+            vendor: ogg.comment_hdr.vendor.clone(),
+            comments: ogg.comment_hdr.comment_list.clone(),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u8 {
+        self.channels
+    }
+
+    /// The total number of samples per channel, if the end of the stream could be read.
+    pub fn total_samples(&self) -> Option<u64> {
+        self.total_samples
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        if self.sample_rate == 0 {
+            return None;
+        }
+
+        self.total_samples.map(|samples| {
+            let sample_rate = u64::from(self.sample_rate);
+            let secs = samples / sample_rate;
+            let nanos = (samples % sample_rate) * 1_000_000_000 / sample_rate;
+            Duration::new(secs, nanos as u32)
+        })
+    }
+
+    pub fn vendor(&self) -> &str {
+        self.vendor.as_str()
+    }
+
+    pub fn comments(&self) -> &[(String, String)] {
+        self.comments.as_slice()
+    }
+
+    /// Returns the first vorbis comment with the given tag. Vorbis comment tags are case-insensitive.
+    pub fn comment<I: AsRef<str>>(&self, tag: I) -> Option<&str> {
+        self.comments.iter()
+            .find(|&&(ref key, _)| key.eq_ignore_ascii_case(tag.as_ref()))
+            .map(|&(_, ref value)| value.as_str())
+    }
+
+    fn comment_as_u64<I: AsRef<str>>(&self, tag: I) -> Option<u64> {
+        self.comment(tag).and_then(|value| value.trim().parse::<u64>().ok())
+    }
+
+    /// Returns the loop markers of the clip.
+    ///
+    /// `LOOPSTART` is mandatory, the length is taken from `LOOPLENGTH`, or computed from `LOOPEND`.
+    /// Without any of them, the clip loops until its last sample. The markers whose end does not fit in 64 bits are ignored.
+    pub fn loop_points(&self) -> Option<LoopPoints> {
+        let start = self.comment_as_u64("LOOPSTART")?;
+        let length = match self.comment_as_u64("LOOPLENGTH") {
+            Some(length) => length,
+            None => {
+                match self.comment_as_u64("LOOPEND").or(self.total_samples) {
+                    Some(end) if end > start => end - start,
+                    _ => return None,
+                }
+            },
+        };
+        start.checked_add(length)?;
+
+        Some(LoopPoints {
+            start,
+            length,
+        })
+    }
+}
+
+pub struct OggResource<R: Read + Seek> {
+    reader: Option<OggStreamReader<R>>,
+    metadata: OggMetadata,
+    pcm: Option<PcmClip>,
+}
+
+impl<R: Read + Seek> OggResource<R> {
+    pub fn new(ogg: OggStreamReader<R>, total_samples: Option<u64>) -> Self {
+        let metadata = OggMetadata::from_reader(&ogg, total_samples);
+        OggResource {
+            reader: Some(ogg),
+            metadata,
+            pcm: None,
+        }
+    }
+
+    /// Creates an ogg resource from pcm samples cooked without their ogg stream.
+    ///
+    /// The loop markers, expressed at the sample rate of the clip, replace the `LOOPSTART`, `LOOPLENGTH` and `LOOPEND` comments.
+    pub fn from_pcm(pcm: PcmClip, comments: Vec<(String, String)>, loop_points: Option<LoopPoints>) -> Self {
+        let mut comments: Vec<(String, String)> = comments.into_iter()
+            .filter(|&(ref key, _)| !["LOOPSTART", "LOOPLENGTH", "LOOPEND"].iter().any(|tag| key.eq_ignore_ascii_case(tag)))
+            .collect();
+        if let Some(loop_points) = loop_points {
+            comments.push((String::from("LOOPSTART"), loop_points.start.to_string()));
+            comments.push((String::from("LOOPLENGTH"), loop_points.length.to_string()));
+        }
+        let metadata = OggMetadata {
+            sample_rate: pcm.sample_rate(),
+            channels: pcm.channels(),
+            total_samples: Some(pcm.frames() as u64),
+            vendor: String::new(),
+            comments,
+        };
+        OggResource {
+            reader: None,
+            metadata,
+            pcm: Some(pcm),
+        }
+    }
+
+    /// Creates an ogg resource whose stream has already been decoded, and converted, to the given pcm clip.
+    pub fn with_pcm(ogg: OggStreamReader<R>, total_samples: Option<u64>, pcm: PcmClip) -> Self {
+        let mut ogg_resource = OggResource::new(ogg, total_samples);
+        ogg_resource.pcm = Some(pcm);
+        ogg_resource
+    }
+
+    /// The ogg stream, `None` if the resource has been created from pcm samples.
+    pub fn reader(&self) -> Option<&OggStreamReader<R>> {
+        self.reader.as_ref()
+    }
+
+    /// The metadata of the source stream. Sample counts are expressed at the source sample rate.
+    pub fn metadata(&self) -> &OggMetadata {
+        &self.metadata
+    }
+
+    /// The decoded clip, if the stream has been decoded at load time.
+    pub fn pcm(&self) -> Option<&PcmClip> {
+        self.pcm.as_ref()
+    }
+
+    /// The loop markers, expressed at the sample rate of the decoded clip if it exists.
+    pub fn loop_points(&self) -> Option<LoopPoints> {
+        let loop_points = self.metadata.loop_points()?;
+        match self.pcm {
+            Some(ref pcm) => loop_points.resampled(self.metadata.sample_rate(), pcm.sample_rate()),
+            None => Some(loop_points),
+        }
+    }
+}
+
+impl<R: Read + Seek> MemorySize for OggResource<R> {
+    fn memory_size(&self) -> usize {
+        let comments: usize = self.metadata.comments.iter().map(|&(ref key, ref value)| key.len() + value.len()).sum();
+        let pcm = self.pcm.as_ref().map_or(0, |pcm| pcm.samples().len() * mem::size_of::<i16>());
+        mem::size_of::<OggResource<R>>() + self.metadata.vendor.len() + comments + pcm
+    }
+}
 
 impl<R: Read + Seek> From<OggStreamReader<R>> for OggResource<R> {
     fn from(ogg: OggStreamReader<R>) -> Self {
-        OggResource(ogg)
+        OggResource::new(ogg, None)
     }
 }
 
@@ -26,9 +329,41 @@ impl<R: Read + Seek> AsRef<OggResource<R>> for OggResource<R> {
     }
 }
 
+/// The ogg stream of a resource read from an ogg file.
+///
+/// Panics if the resource has been created from pcm samples, `reader` returns an `Option` instead.
 impl<R: Read + Seek> AsRef<OggStreamReader<R>> for OggResource<R> {
     fn as_ref(&self) -> &OggStreamReader<R> {
-        &self.0
+        self.reader.as_ref().expect("An ogg resource created from pcm samples has no ogg stream.")
+    }
+}
+
+impl<R: Read + Seek> AsRef<OggMetadata> for OggResource<R> {
+    fn as_ref(&self) -> &OggMetadata {
+        &self.metadata
+    }
+}
+
+/// A description of how to play an ogg file : its volume, pitch and looping.
+///
+/// Sound cues are small and often created and destroyed, they are stored in a pool of the ResourceManager.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoundCueResource {
+    /// The path of the ogg file.
+    pub clip: PathBuf,
+    pub volume: f32,
+    pub pitch: f32,
+    pub looping: bool,
+}
+
+impl SoundCueResource {
+    pub fn new<P: Into<PathBuf>>(clip: P) -> Self {
+        SoundCueResource {
+            clip: clip.into(),
+            volume: 1.0,
+            pitch: 1.0,
+            looping: false,
+        }
     }
 }
 
@@ -68,8 +403,50 @@ impl<'a, R: Read + Seek> OggRegistry<'a, R> {
         self.0.insert(path.into(),ogg_res)
     }
 
+    pub fn iter(&self) -> Iter<PathBuf, &'a OggResource<R>> {
+        debug!("Getting an iterator of the OggRegistry.");
+        self.0.iter()
+    }
+
     pub fn clear(&mut self) {
         debug!("Clearing the ogg registry.");
         self.0.clear();
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod ogg_registry_test {
+    use super::*;
+
+    fn metadata(comments: &[(&str, &str)]) -> OggMetadata {
+        OggMetadata {
+            sample_rate: 44100,
+            channels: 2,
+            total_samples: Some(441_000),
+            vendor: String::new(),
+            comments: comments.iter().map(|&(key, value)| (String::from(key), String::from(value))).collect(),
+        }
+    }
+
+    #[test]
+    fn ogg_metadata_loop_points() {
+        assert_eq!(metadata(&[("LOOPSTART", "44100"), ("LOOPEND", "88200")]).loop_points(), Some(LoopPoints { start: 44100, length: 44100 }));
+        assert_eq!(metadata(&[("loopstart", "44100")]).loop_points().map(|loop_points| loop_points.end()), Some(441_000));
+        //Markers read from the comments cannot overflow.
+        assert_eq!(metadata(&[("LOOPSTART", "18446744073709551615"), ("LOOPLENGTH", "10")]).loop_points(), None);
+
+        let loop_points = LoopPoints { start: 44100, length: 88200 };
+        assert_eq!(loop_points.resampled(44100, 22050), Some(LoopPoints { start: 22050, length: 44100 }));
+        assert_eq!(LoopPoints { start: u64::max_value() / 2, length: 1 }.resampled(22050, 44100), None);
+        assert_eq!(LoopPoints { start: u64::max_value(), length: 1 }.end(), u64::max_value());
+
+        //The loop markers of a cooked clip replace the ones of its comments.
+        let comments = vec![(String::from("TITLE"), String::from("untitled")), (String::from("LoopEnd"), String::from("88200"))];
+        let ogg: OggResource<io::Cursor<Vec<u8>>> = OggResource::from_pcm(PcmClip::new(AudioFormat::new(22050, 1), vec![0; 22050]), comments, Some(LoopPoints { start: 100, length: 200 }));
+        assert_eq!(ogg.loop_points(), Some(LoopPoints { start: 100, length: 200 }));
+        assert_eq!(ogg.metadata().comment("TITLE"), Some("untitled"));
+        assert_eq!(ogg.metadata().comment("LOOPEND"), None);
+        let ogg: OggResource<io::Cursor<Vec<u8>>> = OggResource::from_pcm(PcmClip::new(AudioFormat::new(22050, 1), vec![0; 22050]), Vec::new(), None);
+        assert_eq!(ogg.loop_points(), None);
+    }
+}
@@ -0,0 +1,681 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/*
+    Pak files : many resources packed in one file, to reduce the seek and open times.
+
+    Every integer is little-endian.
+
+    Header (32 bytes) :
+    - magic         4 bytes     "MKPK"
+    - version       u32         PAK_VERSION
+    - entry count   u32
+    - alignment     u32         alignment of the data blobs, from the start of the file.
+    - toc offset    u64         offset of the table of contents.
+    - reserved      u64         0.
+
+    Data blobs : the content of the entries, each one starting at a multiple of the alignment.
+
+    Table of contents, sorted by path hash, one record per entry :
+    - path hash     u64         FNV-1a hash of the normalized path.
+    - offset        u64         offset of the data blob, from the start of the file.
+    - size          u64         size of the data blob.
+    - uncompressed  u64         size of the data once decompressed. Absent in version 1, where it is the size.
+    - flags         u32         bits 0-3 : compression of the data blob, 0 none, 1 LZ4 frame, 2 zstd, 3 deflate.
+                                The other bits are reserved, 0.
+    - path length   u32
+    - path          utf-8 bytes of the normalized path, to resolve hash collisions and list the entries.
+
+    A normalized path is made of the normal components of the path, separated by '/'.
+    "/home/user/../user/tga_resource/Untitled.tga" and "home/user/tga_resource/Untitled.tga" are both
+    normalized to "home/user/tga_resource/Untitled.tga".
+*/
+
+use std::fs::File;
+use std::fmt;
+use std::io::{self, Read, Write, Seek, SeekFrom, BufRead, BufReader};
+use std::path::{Path, PathBuf, Component};
+use std::cmp;
+use std::collections::HashMap;
+use lz4_flex::frame::{FrameEncoder, FrameDecoder};
+use flate2::Compression;
+use flate2::write::DeflateEncoder;
+use flate2::read::DeflateDecoder;
+use zstd;
+use resources::binary::{fnv1a_hash, read_u32, read_u64, write_u32, write_u64};
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+use resources::resource_reader::{ResourceReader, ResourceRead};
+use resources::resources_registry::ResourceType;
+
+pub const PAK_MAGIC: [u8; 4] = *b"MKPK";
+pub const PAK_VERSION: u32 = 2;
+pub const PAK_HEADER_SIZE: u64 = 32;
+pub const PAK_DEFAULT_ALIGNMENT: u32 = 16;
+const PAK_COMPRESSION_MASK: u32 = 0xf;
+const ZSTD_LEVEL: i32 = 19;
+
+/// Returns the normalized form of a path, used as the key of the pak entries.
+pub fn normalize_pak_path<P: AsRef<Path>>(path: P) -> String {
+    let mut components: Vec<String> = Vec::new();
+    for component in path.as_ref().components() {
+        match component {
+            Component::Normal(name) => components.push(name.to_string_lossy().into_owned()),
+            Component::ParentDir => {
+                components.pop();
+            },
+            Component::Prefix(_) | Component::RootDir | Component::CurDir => {},
+        }
+    }
+    components.join("/")
+}
+
+/// FNV-1a hash of a normalized path.
+pub fn pak_path_hash(normalized_path: &str) -> u64 {
+    fnv1a_hash(normalized_path.as_bytes())
+}
+
+/// The compression of the data blob of a pak entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PakCompression {
+    None,
+    /// Fast decompression, for the data read in the hot paths.
+    Lz4,
+    /// Small data, for downloads.
+    Zstd,
+    Deflate,
+}
+
+impl PakCompression {
+    pub fn from_flags(flags: u32) -> ResourceManagerResult<Self> {
+        match flags & PAK_COMPRESSION_MASK {
+            0 => Ok(PakCompression::None),
+            1 => Ok(PakCompression::Lz4),
+            2 => Ok(PakCompression::Zstd),
+            3 => Ok(PakCompression::Deflate),
+            compression => Err(ResourceManagerError::ResourceError(format!("The compression {} of a pak entry is not supported !", compression))),
+        }
+    }
+
+    pub fn flags(&self) -> u32 {
+        match self {
+            &PakCompression::None => 0,
+            &PakCompression::Lz4 => 1,
+            &PakCompression::Zstd => 2,
+            &PakCompression::Deflate => 3,
+        }
+    }
+
+    /// The compression used by default for a type of resource.
+    ///
+    /// Ogg files are already compressed, raw blobs are mapped in memory as they are,
+    /// images are read often, text formats compress well.
+    pub fn default_for(resource_type: ResourceType) -> Self {
+        match resource_type {
+            ResourceType::Ogg | ResourceType::Raw => PakCompression::None,
+            ResourceType::Tga => PakCompression::Lz4,
+            ResourceType::Gltf | ResourceType::Mesh | ResourceType::Material => PakCompression::Zstd,
+        }
+    }
+
+    pub fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            &PakCompression::None => Ok(data.to_vec()),
+            &PakCompression::Lz4 => {
+                let mut encoder = FrameEncoder::new(Vec::new());
+                encoder.write_all(data)?;
+                encoder.finish().map_err(|error| io::Error::new(io::ErrorKind::Other, format!("{}", error)))
+            },
+            &PakCompression::Zstd => zstd::stream::encode_all(data, ZSTD_LEVEL),
+            &PakCompression::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+                encoder.write_all(data)?;
+                encoder.finish()
+            },
+        }
+    }
+
+    /// Wraps a reader of compressed data in a reader of decompressed data.
+    pub fn decoder<R: Read + 'static>(&self, reader: R) -> io::Result<Box<Read>> {
+        match self {
+            &PakCompression::None => Ok(Box::new(reader)),
+            &PakCompression::Lz4 => Ok(Box::new(FrameDecoder::new(reader))),
+            &PakCompression::Zstd => Ok(Box::new(zstd::stream::read::Decoder::new(reader)?)),
+            &PakCompression::Deflate => Ok(Box::new(DeflateDecoder::new(reader))),
+        }
+    }
+}
+
+/// A record of the table of contents of a pak file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PakEntry {
+    pub path: String,
+    pub hash: u64,
+    pub offset: u64,
+    /// Size of the data blob, compressed.
+    pub size: u64,
+    /// Size of the data once decompressed, to preallocate the buffers it is decompressed in.
+    pub uncompressed_size: u64,
+    pub flags: u32,
+}
+
+impl PakEntry {
+    pub fn compression(&self) -> ResourceManagerResult<PakCompression> {
+        PakCompression::from_flags(self.flags)
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.flags & PAK_COMPRESSION_MASK != 0
+    }
+}
+
+/// A pak file, whose table of contents has been read.
+///
+/// Each entry is read through its own handle on the pak file, several entries can be read at the same time.
+#[derive(Debug)]
+pub struct PakArchive {
+    path: PathBuf,
+    alignment: u32,
+    entries: Vec<PakEntry>,
+}
+
+impl PakArchive {
+    pub fn open<P: AsRef<Path>>(path: P) -> ResourceManagerResult<Self> {
+        debug!("Opening the pak file at path {}.", path.as_ref().display());
+        let mut reader = BufReader::new(File::open(path.as_ref())?);
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != PAK_MAGIC {
+            error!("The file at path {} is not a pak file.", path.as_ref().display());
+            return Err(ResourceManagerError::ResourceError(format!("The file at path {} is not a pak file !", path.as_ref().display())));
+        }
+        let version = read_u32(&mut reader)?;
+        if version == 0 || version > PAK_VERSION {
+            error!("The version of the pak file at path {} is not supported.", path.as_ref().display());
+            return Err(ResourceManagerError::ResourceError(format!("The pak file at path {} has the version {}, only the version {} is supported !", path.as_ref().display(), version, PAK_VERSION)));
+        }
+        let entry_count = read_u32(&mut reader)?;
+        let alignment = read_u32(&mut reader)?;
+        let toc_offset = read_u64(&mut reader)?;
+
+        //The sizes read from the table of contents are checked against its size before anything is allocated.
+        let file_size = reader.get_ref().metadata()?.len();
+        if toc_offset < PAK_HEADER_SIZE || toc_offset > file_size {
+            error!("The table of contents of the pak file at path {} is out of the file.", path.as_ref().display());
+            return Err(ResourceManagerError::ResourceError(format!("The table of contents of the pak file at path {} is out of the file !", path.as_ref().display())));
+        }
+        let toc_size = file_size - toc_offset;
+        //An entry without path : its hash, offset, size, uncompressed size since the version 2, flags and path length.
+        let min_entry_size = if version >= 2 { 40 } else { 32 };
+        if u64::from(entry_count) * min_entry_size > toc_size {
+            error!("The table of contents of the pak file at path {} is truncated.", path.as_ref().display());
+            return Err(ResourceManagerError::ResourceError(format!("The table of contents of the pak file at path {} cannot hold its {} entries !", path.as_ref().display(), entry_count)));
+        }
+
+        reader.seek(SeekFrom::Start(toc_offset))?;
+        let mut entries = Vec::with_capacity(entry_count as usize);
+        for _ in 0..entry_count {
+            let hash = read_u64(&mut reader)?;
+            let offset = read_u64(&mut reader)?;
+            let size = read_u64(&mut reader)?;
+            let uncompressed_size = if version >= 2 {
+                read_u64(&mut reader)?
+            } else {
+                size
+            };
+            let flags = read_u32(&mut reader)?;
+            let path_length = read_u32(&mut reader)?;
+            if u64::from(path_length) > toc_size {
+                error!("An entry of the pak file at path {} has a path longer than the table of contents.", path.as_ref().display());
+                return Err(ResourceManagerError::ResourceError(format!("An entry of the pak file at path {} has a path of {} bytes, longer than the table of contents !", path.as_ref().display(), path_length)));
+            }
+            let mut path_bytes = vec![0; path_length as usize];
+            reader.read_exact(&mut path_bytes)?;
+            let entry_path = String::from_utf8(path_bytes)
+                .map_err(|_| ResourceManagerError::ResourceError(format!("An entry of the pak file at path {} has a path which is not valid utf-8 !", path.as_ref().display())))?;
+            //The entries are looked up, and extracted, by normalized path.
+            if entry_path != normalize_pak_path(entry_path.as_str()) {
+                error!("An entry of the pak file at path {} has a path which is not normalized.", path.as_ref().display());
+                return Err(ResourceManagerError::ResourceError(format!("An entry of the pak file at path {} has a path which is not normalized : {} !", path.as_ref().display(), entry_path)));
+            }
+            //An entry whose hash is not the one of its path could never be found.
+            if hash != pak_path_hash(entry_path.as_str()) {
+                error!("An entry of the pak file at path {} has a hash which does not match its path.", path.as_ref().display());
+                return Err(ResourceManagerError::ResourceError(format!("The entry {} of the pak file at path {} has a hash which does not match its path !", entry_path, path.as_ref().display())));
+            }
+            entries.push(PakEntry {
+                path: entry_path,
+                hash,
+                offset,
+                size,
+                uncompressed_size,
+                flags,
+            });
+        }
+        entries.sort_by_key(|entry| entry.hash);
+
+        Ok(PakArchive {
+            path: path.as_ref().to_path_buf(),
+            alignment,
+            entries,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    pub fn alignment(&self) -> u32 {
+        self.alignment
+    }
+
+    pub fn entries(&self) -> &[PakEntry] {
+        self.entries.as_slice()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entry<P: AsRef<Path>>(&self, path: P) -> Option<&PakEntry> {
+        let normalized_path = normalize_pak_path(path);
+        let hash = pak_path_hash(normalized_path.as_str());
+        let first = match self.entries.binary_search_by_key(&hash, |entry| entry.hash) {
+            Ok(index) => self.entries[..index].iter().rposition(|entry| entry.hash != hash).map(|position| position + 1).unwrap_or(0),
+            Err(_) => return None,
+        };
+        self.entries[first..].iter()
+            .take_while(|entry| entry.hash == hash)
+            .find(|entry| entry.path == normalized_path)
+    }
+
+    pub fn contains<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.entry(path).is_some()
+    }
+
+    fn find_entry<P: AsRef<Path>>(&self, path: P) -> ResourceManagerResult<&PakEntry> {
+        match self.entry(path.as_ref()) {
+            Some(entry) => Ok(entry),
+            None => {
+                error!("The entry {} could not be found in the pak file.", path.as_ref().display());
+                Err(ResourceManagerError::ResourceError(format!("Could not find the entry {} in the pak file at path {} !", path.as_ref().display(), self.path.display())))
+            },
+        }
+    }
+
+    /// Opens a reader over the data of an entry, decompressed.
+    pub fn open_entry<P: AsRef<Path>>(&self, path: P) -> ResourceManagerResult<ResourceReader> {
+        debug!("Opening the entry {} of the pak file at path {}.", path.as_ref().display(), self.path.display());
+        let entry = self.find_entry(path.as_ref())?;
+        if entry.is_compressed() {
+            Ok(ResourceReader::from(PakEntryDecoder::new(self.path.as_path(), entry)?))
+        } else {
+            Ok(ResourceReader::from(PakEntryReader::new(self.path.as_path(), entry)?))
+        }
+    }
+
+    /// Opens a reader over the data blob of an entry, as stored in the pak file.
+    pub fn open_raw_entry<P: AsRef<Path>>(&self, path: P) -> ResourceManagerResult<PakEntryReader> {
+        debug!("Opening the raw entry {} of the pak file at path {}.", path.as_ref().display(), self.path.display());
+        let entry = self.find_entry(path.as_ref())?;
+        PakEntryReader::new(self.path.as_path(), entry)
+    }
+
+    /// Reads the data of an entry, decompressed.
+    pub fn read_entry<P: AsRef<Path>>(&self, path: P) -> ResourceManagerResult<Vec<u8>> {
+        let mut reader = self.open_entry(path)?;
+        //The size of the table of contents is not trusted to preallocate the data.
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Reads the data of an entry, decompressed, and checks that its size is the one of the table of contents.
+    pub fn verify_entry<P: AsRef<Path>>(&self, path: P) -> ResourceManagerResult<()> {
+        debug!("Verifying the entry {} of the pak file at path {}.", path.as_ref().display(), self.path.display());
+        let expected_size = self.find_entry(path.as_ref())?.uncompressed_size;
+        let size = self.read_entry(path.as_ref())?.len() as u64;
+        if size != expected_size {
+            error!("The entry {} of the pak file has {} bytes instead of {}.", path.as_ref().display(), size, expected_size);
+            return Err(ResourceManagerError::ResourceError(format!("The entry {} of the pak file at path {} has {} bytes instead of {} !", path.as_ref().display(), self.path.display(), size, expected_size)));
+        }
+        Ok(())
+    }
+}
+
+/// A reader over the data of one entry of a pak file.
+#[derive(Debug)]
+pub struct PakEntryReader {
+    reader: BufReader<File>,
+    start: u64,
+    size: u64,
+    position: u64,
+}
+
+impl PakEntryReader {
+    fn new(pak_path: &Path, entry: &PakEntry) -> ResourceManagerResult<Self> {
+        let mut reader = BufReader::new(File::open(pak_path)?);
+        reader.seek(SeekFrom::Start(entry.offset))?;
+        Ok(PakEntryReader {
+            reader,
+            start: entry.offset,
+            size: entry.size,
+            position: 0,
+        })
+    }
+
+    pub fn len(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+}
+
+impl BufRead for PakEntryReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let remaining = self.size - self.position;
+        if remaining == 0 {
+            return Ok(&[]);
+        }
+        let available = self.reader.fill_buf()?;
+        let count = cmp::min(available.len() as u64, remaining) as usize;
+        Ok(&available[..count])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        let amount = cmp::min(amount as u64, self.size - self.position);
+        self.reader.consume(amount as usize);
+        self.position += amount;
+    }
+}
+
+impl Read for PakEntryReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = {
+            let available = self.fill_buf()?;
+            let count = cmp::min(available.len(), buf.len());
+            buf[..count].copy_from_slice(&available[..count]);
+            count
+        };
+        self.consume(count);
+        Ok(count)
+    }
+}
+
+impl Seek for PakEntryReader {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let new_position = match position {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(offset) => self.size as i64 + offset,
+            SeekFrom::Current(offset) => self.position as i64 + offset,
+        };
+        if new_position < 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Cannot seek before the start of a pak entry."));
+        }
+        let new_position = cmp::min(new_position as u64, self.size);
+        self.reader.seek(SeekFrom::Start(self.start + new_position))?;
+        self.position = new_position;
+        Ok(new_position)
+    }
+}
+
+impl ResourceRead for PakEntryReader {
+    fn data_size(&self) -> Option<u64> {
+        Some(self.size)
+    }
+}
+
+/// A reader over the decompressed data of one entry of a pak file.
+///
+/// Compressed data cannot be read backward : seeking backward decompresses the entry again from its start.
+pub struct PakEntryDecoder {
+    pak_path: PathBuf,
+    entry: PakEntry,
+    decoder: BufReader<Box<Read>>,
+    position: u64,
+}
+
+impl fmt::Debug for PakEntryDecoder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PakEntryDecoder {{ pak_path: {}, entry: {:?}, position: {} }}", self.pak_path.display(), self.entry, self.position)
+    }
+}
+
+impl PakEntryDecoder {
+    fn new(pak_path: &Path, entry: &PakEntry) -> ResourceManagerResult<Self> {
+        let decoder = PakEntryDecoder::decoder(pak_path, entry)?;
+        Ok(PakEntryDecoder {
+            pak_path: pak_path.to_path_buf(),
+            entry: entry.clone(),
+            decoder,
+            position: 0,
+        })
+    }
+
+    fn decoder(pak_path: &Path, entry: &PakEntry) -> ResourceManagerResult<BufReader<Box<Read>>> {
+        let raw_reader = PakEntryReader::new(pak_path, entry)?;
+        Ok(BufReader::new(entry.compression()?.decoder(raw_reader)?))
+    }
+
+    pub fn len(&self) -> u64 {
+        self.entry.uncompressed_size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entry.uncompressed_size == 0
+    }
+}
+
+impl BufRead for PakEntryDecoder {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.decoder.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.decoder.consume(amount);
+        self.position += amount as u64;
+    }
+}
+
+impl Read for PakEntryDecoder {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.decoder.read(buf)?;
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl Seek for PakEntryDecoder {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let new_position = match position {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(offset) => self.entry.uncompressed_size as i64 + offset,
+            SeekFrom::Current(offset) => self.position as i64 + offset,
+        };
+        if new_position < 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Cannot seek before the start of a pak entry."));
+        }
+        let new_position = cmp::min(new_position as u64, self.entry.uncompressed_size);
+
+        if new_position < self.position {
+            self.decoder = PakEntryDecoder::decoder(self.pak_path.as_path(), &self.entry)
+                .map_err(|error| io::Error::new(io::ErrorKind::Other, format!("{}", error)))?;
+            self.position = 0;
+        }
+        let skip = new_position - self.position;
+        io::copy(&mut self.by_ref().take(skip), &mut io::sink())?;
+        if self.position != new_position {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("The pak entry ended after {} bytes, instead of {} bytes.", self.position, self.entry.uncompressed_size)));
+        }
+        Ok(new_position)
+    }
+}
+
+impl ResourceRead for PakEntryDecoder {
+    fn data_size(&self) -> Option<u64> {
+        Some(self.entry.uncompressed_size)
+    }
+}
+
+/// Builds a pak file : the entries are written one after the other, then `finish` writes the table of contents.
+///
+/// Each entry is compressed according to the type of its resource, see `PakCompression::default_for`.
+pub struct PakWriter<W: Write + Seek> {
+    writer: W,
+    alignment: u32,
+    position: u64,
+    entries: Vec<PakEntry>,
+    compressions: HashMap<ResourceType, PakCompression>,
+    default_compression: PakCompression,
+}
+
+impl PakWriter<File> {
+    pub fn create<P: AsRef<Path>>(path: P) -> ResourceManagerResult<Self> {
+        debug!("Creating a pak file at path {}.", path.as_ref().display());
+        PakWriter::new(File::create(path.as_ref())?)
+    }
+}
+
+impl<W: Write + Seek> PakWriter<W> {
+    pub fn new(writer: W) -> ResourceManagerResult<Self> {
+        PakWriter::with_alignment(writer, PAK_DEFAULT_ALIGNMENT)
+    }
+
+    pub fn with_alignment(mut writer: W, alignment: u32) -> ResourceManagerResult<Self> {
+        debug!("Creating a PakWriter, with data blobs aligned to {} bytes.", alignment);
+        if alignment == 0 {
+            return Err(ResourceManagerError::ResourceError(String::from("The alignment of the data blobs of a pak file cannot be 0 !")));
+        }
+        //The header is written by `finish`, once the table of contents is known.
+        writer.seek(SeekFrom::Start(0))?;
+        writer.write_all(&[0; PAK_HEADER_SIZE as usize])?;
+
+        Ok(PakWriter {
+            writer,
+            alignment,
+            position: PAK_HEADER_SIZE,
+            entries: Vec::new(),
+            compressions: ResourceType::all().iter().map(|&resource_type| (resource_type, PakCompression::default_for(resource_type))).collect(),
+            default_compression: PakCompression::None,
+        })
+    }
+
+    fn align(&mut self) -> io::Result<()> {
+        let alignment = self.alignment as u64;
+        let padding = (alignment - self.position % alignment) % alignment;
+        self.writer.write_all(&vec![0; padding as usize])?;
+        self.position += padding;
+        Ok(())
+    }
+
+    /// Sets the compression of the entries of a type of resource.
+    pub fn set_compression(&mut self, resource_type: ResourceType, compression: PakCompression) {
+        debug!("Setting the compression of the {} entries of a pak file.", resource_type);
+        self.compressions.insert(resource_type, compression);
+    }
+
+    /// Sets the compression of the entries whose type of resource is unknown.
+    pub fn set_default_compression(&mut self, compression: PakCompression) {
+        debug!("Setting the default compression of the entries of a pak file.");
+        self.default_compression = compression;
+    }
+
+    /// The compression of the entry at this path, according to the type of its resource.
+    pub fn compression_of<P: AsRef<Path>>(&self, path: P) -> PakCompression {
+        ResourceType::from_path(path)
+            .and_then(|resource_type| self.compressions.get(&resource_type).cloned())
+            .unwrap_or(self.default_compression)
+    }
+
+    pub fn contains<P: AsRef<Path>>(&self, path: P) -> bool {
+        let normalized_path = normalize_pak_path(path);
+        self.entries.iter().any(|entry| entry.path == normalized_path)
+    }
+
+    /// Adds an entry, with the content of the reader, compressed according to the type of its resource.
+    pub fn add_reader<P: AsRef<Path>, R: Read>(&mut self, path: P, reader: &mut R) -> ResourceManagerResult<()> {
+        let compression = self.compression_of(path.as_ref());
+        self.add_reader_with_compression(path, reader, compression)
+    }
+
+    pub fn add_reader_with_compression<P: AsRef<Path>, R: Read>(&mut self, path: P, reader: &mut R, compression: PakCompression) -> ResourceManagerResult<()> {
+        debug!("Adding the entry {} to a pak file.", path.as_ref().display());
+        if self.contains(path.as_ref()) {
+            error!("The pak file already has an entry with path {}.", path.as_ref().display());
+            return Err(ResourceManagerError::ResourceError(format!("The pak file already has an entry with path {} !", path.as_ref().display())));
+        }
+
+        self.align()?;
+        let offset = self.position;
+        let (size, uncompressed_size) = match compression {
+            PakCompression::None => {
+                let size = io::copy(reader, &mut self.writer)?;
+                (size, size)
+            },
+            compression => {
+                let mut data = Vec::new();
+                reader.read_to_end(&mut data)?;
+                let compressed_data = compression.compress(data.as_slice())?;
+                self.writer.write_all(compressed_data.as_slice())?;
+                (compressed_data.len() as u64, data.len() as u64)
+            },
+        };
+        self.position += size;
+        let normalized_path = normalize_pak_path(path);
+        self.entries.push(PakEntry {
+            hash: pak_path_hash(normalized_path.as_str()),
+            path: normalized_path,
+            offset,
+            size,
+            uncompressed_size,
+            flags: compression.flags(),
+        });
+        Ok(())
+    }
+
+    pub fn add_entry<P: AsRef<Path>>(&mut self, path: P, data: &[u8]) -> ResourceManagerResult<()> {
+        self.add_reader(path, &mut &data[..])
+    }
+
+    /// Adds an entry, with the content of a file.
+    pub fn add_file<P: AsRef<Path>, F: AsRef<Path>>(&mut self, path: P, file_path: F) -> ResourceManagerResult<()> {
+        let mut file = BufReader::new(File::open(file_path.as_ref())?);
+        self.add_reader(path, &mut file)
+    }
+
+    /// Writes the table of contents and the header, and gives back the writer.
+    pub fn finish(mut self) -> ResourceManagerResult<W> {
+        debug!("Writing the table of contents of a pak file.");
+        self.entries.sort_by_key(|entry| entry.hash);
+        let toc_offset = self.position;
+        for entry in self.entries.iter() {
+            write_u64(&mut self.writer, entry.hash)?;
+            write_u64(&mut self.writer, entry.offset)?;
+            write_u64(&mut self.writer, entry.size)?;
+            write_u64(&mut self.writer, entry.uncompressed_size)?;
+            write_u32(&mut self.writer, entry.flags)?;
+            write_u32(&mut self.writer, entry.path.len() as u32)?;
+            self.writer.write_all(entry.path.as_bytes())?;
+        }
+
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer.write_all(&PAK_MAGIC)?;
+        write_u32(&mut self.writer, PAK_VERSION)?;
+        write_u32(&mut self.writer, self.entries.len() as u32)?;
+        write_u32(&mut self.writer, self.alignment)?;
+        write_u64(&mut self.writer, toc_offset)?;
+        write_u64(&mut self.writer, 0)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
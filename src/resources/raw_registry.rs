@@ -0,0 +1,59 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Iter;
+use std::path::{PathBuf, Path};
+
+/// Raw blobs of data, borrowed from memory-mapped pak files without being copied.
+#[derive(Debug)]
+pub struct RawRegistry<'a>(HashMap<PathBuf, &'a [u8]>);
+
+impl<'a> Default for RawRegistry<'a> {
+    fn default() -> Self {
+        debug!("Creating a default RawRegistry.");
+        RawRegistry(HashMap::default())
+    }
+}
+
+impl<'a> RawRegistry<'a> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        debug!("Checking if the RawRegistry is empty.");
+        self.0.is_empty()
+    }
+
+    pub fn get<I: AsRef<Path>>(&self, path: I) -> Option<&&'a [u8]> {
+        debug!("Trying to get a raw resource with path {}.", path.as_ref().display());
+        self.0.get(path.as_ref())
+    }
+
+    pub fn remove<I: AsRef<Path>>(&mut self, path: I) -> Option<&'a [u8]> {
+        debug!("Removing a raw resource with path {}.", path.as_ref().display());
+        self.0.remove(path.as_ref())
+    }
+
+    pub fn insert<I>(&mut self, path: I, raw_res: &'a [u8]) -> Option<&'a [u8]> where
+        I: Into<PathBuf>,
+    {
+        debug!("Inserting a raw resource.");
+        self.0.insert(path.into(), raw_res)
+    }
+
+    pub fn iter(&self) -> Iter<PathBuf, &'a [u8]> {
+        debug!("Getting an iterator of the RawRegistry.");
+        self.0.iter()
+    }
+
+    pub fn clear(&mut self) {
+        debug!("Clearing the raw registry.");
+        self.0.clear();
+    }
+}
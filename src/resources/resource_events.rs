@@ -0,0 +1,339 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::PathBuf;
+use resources::resources_registry::ResourceType;
+
+/// The number of events the queue keeps when they are not drained, by default.
+pub const DEFAULT_EVENT_QUEUE_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResourceEventKind {
+    Loaded,
+    Reloaded,
+    Unloaded,
+    /// The resource could not be loaded or reloaded, with the description of the error.
+    Failed(String),
+}
+
+/// Something which happened to a resource.
+///
+/// The type is `None` when it cannot be deduced from the path of the resource.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceEvent {
+    pub kind: ResourceEventKind,
+    pub path: PathBuf,
+    pub resource_type: Option<ResourceType>,
+}
+
+impl ResourceEvent {
+    pub fn new<P: Into<PathBuf>>(kind: ResourceEventKind, path: P, resource_type: Option<ResourceType>) -> Self {
+        ResourceEvent {
+            kind,
+            path: path.into(),
+            resource_type,
+        }
+    }
+
+    /// An event whose resource type is deduced from the extension of the path.
+    pub fn from_path<P: Into<PathBuf>>(kind: ResourceEventKind, path: P) -> Self {
+        let path = path.into();
+        let resource_type = ResourceType::from_path(path.as_path());
+        ResourceEvent::new(kind, path, resource_type)
+    }
+}
+
+/// Selects the events a subscriber is notified of. An empty filter matches every event.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceEventFilter {
+    resource_types: Vec<ResourceType>,
+    path_prefix: Option<PathBuf>,
+}
+
+impl ResourceEventFilter {
+    pub fn all() -> Self {
+        Default::default()
+    }
+
+    /// Matches the events of this resource type too. Without any type, every type matches.
+    pub fn with_type(mut self, resource_type: ResourceType) -> Self {
+        self.resource_types.push(resource_type);
+        self
+    }
+
+    /// Matches only the events of the resources under this path, like `res://textures`.
+    pub fn with_path_prefix<P: Into<PathBuf>>(mut self, path_prefix: P) -> Self {
+        self.path_prefix = Some(path_prefix.into());
+        self
+    }
+
+    pub fn matches(&self, event: &ResourceEvent) -> bool {
+        let type_matches = self.resource_types.is_empty() || match event.resource_type {
+            Some(resource_type) => self.resource_types.contains(&resource_type),
+            None => false,
+        };
+        let path_matches = match self.path_prefix {
+            Some(ref path_prefix) => event.path.starts_with(path_prefix),
+            None => true,
+        };
+        type_matches && path_matches
+    }
+}
+
+/// Identifies a subscription, to cancel it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u32);
+
+struct Subscription {
+    id: SubscriptionId,
+    filter: ResourceEventFilter,
+    callback: Box<FnMut(&ResourceEvent)>,
+}
+
+/// An event being sent to the subscriptions, which are taken out of their queue meanwhile.
+///
+/// The queue is free while the callbacks are called, they can use its owner.
+pub struct EventDispatch {
+    event: ResourceEvent,
+    subscriptions: Vec<Subscription>,
+}
+
+impl EventDispatch {
+    /// Calls the callbacks of the subscriptions matching the event.
+    pub fn run(&mut self) {
+        for subscription in self.subscriptions.iter_mut() {
+            if subscription.filter.matches(&self.event) {
+                (subscription.callback)(&self.event);
+            }
+        }
+    }
+}
+
+/// The events of the resources, drained by the consumers once per frame, and sent to the subscribers as they happen.
+pub struct ResourceEventQueue {
+    events: VecDeque<ResourceEvent>,
+    capacity: usize,
+    subscriptions: Vec<Subscription>,
+    next_subscription: u32,
+    //The subscriptions taken out by the dispatches in progress, and the ones of them cancelled meanwhile.
+    dispatched: Vec<SubscriptionId>,
+    unsubscribed: Vec<SubscriptionId>,
+}
+
+impl fmt::Debug for ResourceEventQueue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ResourceEventQueue {{ events: {:?}, capacity: {}, subscriptions: {} }}", self.events, self.capacity, self.subscriptions.len())
+    }
+}
+
+impl Default for ResourceEventQueue {
+    fn default() -> Self {
+        ResourceEventQueue::with_capacity(DEFAULT_EVENT_QUEUE_CAPACITY)
+    }
+}
+
+impl ResourceEventQueue {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates a queue keeping at most `capacity` events. When it is full, the oldest event is dropped.
+    pub fn with_capacity(capacity: usize) -> Self {
+        debug!("Creating a ResourceEventQueue with a capacity of {} events.", capacity);
+        ResourceEventQueue {
+            events: VecDeque::with_capacity(capacity),
+            capacity,
+            subscriptions: Vec::new(),
+            next_subscription: 0,
+            dispatched: Vec::new(),
+            unsubscribed: Vec::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Sets the number of events kept. The oldest events are dropped if there are more.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        debug!("Setting the capacity of a ResourceEventQueue to {} events.", capacity);
+        while self.events.len() > capacity {
+            self.events.pop_front();
+        }
+        self.capacity = capacity;
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Sends the event to the matching subscribers, and queues it.
+    pub fn push(&mut self, event: ResourceEvent) {
+        let mut dispatch = self.begin_dispatch(event);
+        dispatch.run();
+        self.end_dispatch(dispatch);
+    }
+
+    /// Queues the event, and takes the subscriptions out of the queue to send it to them with `EventDispatch::run`.
+    ///
+    /// The subscriptions must be given back with `end_dispatch`. The events pushed in the meantime are not sent to them.
+    pub fn begin_dispatch(&mut self, event: ResourceEvent) -> EventDispatch {
+        trace!("Pushing a {:?} event for the resource {}.", event.kind, event.path.display());
+        let subscriptions: Vec<Subscription> = self.subscriptions.drain(..).collect();
+        self.dispatched.extend(subscriptions.iter().map(|subscription| subscription.id));
+
+        if self.capacity != 0 {
+            if self.events.len() == self.capacity {
+                warn!("The resource event queue is full, the oldest event is dropped.");
+                self.events.pop_front();
+            }
+            self.events.push_back(event.clone());
+        }
+
+        EventDispatch {
+            event,
+            subscriptions,
+        }
+    }
+
+    /// Gives back the subscriptions of a dispatch, without the ones cancelled while it was running.
+    pub fn end_dispatch(&mut self, dispatch: EventDispatch) {
+        for subscription in dispatch.subscriptions {
+            if let Some(index) = self.dispatched.iter().position(|id| *id == subscription.id) {
+                self.dispatched.swap_remove(index);
+            }
+            match self.unsubscribed.iter().position(|id| *id == subscription.id) {
+                Some(index) => {
+                    self.unsubscribed.swap_remove(index);
+                },
+                None => self.subscriptions.push(subscription),
+            }
+        }
+        //The subscriptions made during the dispatch come after the older ones.
+        self.subscriptions.sort_by_key(|subscription| subscription.id.0);
+    }
+
+    /// Removes and returns the queued events, oldest first.
+    pub fn drain(&mut self) -> Vec<ResourceEvent> {
+        self.events.drain(..).collect()
+    }
+
+    /// Removes and returns the queued events matching the filter, the other events stay queued.
+    pub fn drain_matching(&mut self, filter: &ResourceEventFilter) -> Vec<ResourceEvent> {
+        let (matching, others): (VecDeque<ResourceEvent>, VecDeque<ResourceEvent>) = self.events.drain(..).partition(|event| filter.matches(event));
+        self.events = others;
+        matching.into_iter().collect()
+    }
+
+    /// Calls `callback` with every matching event, from now on.
+    pub fn subscribe<F>(&mut self, filter: ResourceEventFilter, callback: F) -> SubscriptionId where
+        F: FnMut(&ResourceEvent) + 'static,
+    {
+        debug!("Subscribing to the resource events.");
+        let id = SubscriptionId(self.next_subscription);
+        self.next_subscription += 1;
+        self.subscriptions.push(Subscription {
+            id,
+            filter,
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        debug!("Unsubscribing from the resource events.");
+        match self.subscriptions.iter().position(|subscription| subscription.id == id) {
+            Some(index) => {
+                self.subscriptions.remove(index);
+                true
+            },
+            //A subscription being called is removed when its dispatch ends.
+            None if self.dispatched.contains(&id) && !self.unsubscribed.contains(&id) => {
+                self.unsubscribed.push(id);
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn subscription_count(&self) -> usize {
+        self.subscriptions.len() + self.dispatched.len() - self.unsubscribed.len()
+    }
+
+    /// Removes the queued events, the subscriptions are kept.
+    pub fn clear(&mut self) {
+        debug!("Clearing the ResourceEventQueue.");
+        self.events.clear();
+    }
+}
+
+#[cfg(test)]
+mod resource_events_test {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn resource_event_queue_drain_and_subscribe() {
+        let mut queue = ResourceEventQueue::with_capacity(2);
+        let textures = Rc::new(RefCell::new(Vec::new()));
+        let textures_clone = textures.clone();
+        let subscription = queue.subscribe(ResourceEventFilter::all().with_type(ResourceType::Tga).with_path_prefix("res://textures"), move |event| {
+            textures_clone.borrow_mut().push(event.path.clone());
+        });
+
+        queue.push(ResourceEvent::from_path(ResourceEventKind::Loaded, "res://textures/wall.tga"));
+        queue.push(ResourceEvent::from_path(ResourceEventKind::Loaded, "res://meshes/wall.tga"));
+        queue.push(ResourceEvent::from_path(ResourceEventKind::Failed(String::from("Broken file")), "res://textures/floor.gltf"));
+        assert_eq!(textures.borrow().as_slice(), &[PathBuf::from("res://textures/wall.tga")]);
+
+        //The oldest event has been dropped.
+        assert_eq!(queue.len(), 2);
+        let failed = queue.drain_matching(&ResourceEventFilter::all().with_type(ResourceType::Gltf));
+        assert_eq!(failed, vec![ResourceEvent::new(ResourceEventKind::Failed(String::from("Broken file")), "res://textures/floor.gltf", Some(ResourceType::Gltf))]);
+        assert_eq!(queue.drain(), vec![ResourceEvent::new(ResourceEventKind::Loaded, "res://meshes/wall.tga", Some(ResourceType::Tga))]);
+        assert!(queue.is_empty());
+
+        assert!(queue.unsubscribe(subscription));
+        assert!(!queue.unsubscribe(subscription));
+        queue.push(ResourceEvent::from_path(ResourceEventKind::Unloaded, "res://textures/wall.tga"));
+        assert_eq!(textures.borrow().len(), 1);
+    }
+
+    #[test]
+    fn resource_event_queue_free_during_dispatch() {
+        let mut queue = ResourceEventQueue::with_capacity(4);
+        let calls = Rc::new(RefCell::new(0));
+        let calls_clone = calls.clone();
+        let subscription = queue.subscribe(ResourceEventFilter::all(), move |_| {
+            *calls_clone.borrow_mut() += 1;
+        });
+
+        let mut dispatch = queue.begin_dispatch(ResourceEvent::from_path(ResourceEventKind::Loaded, "res://textures/wall.tga"));
+        //The queue can be used while the callbacks are called.
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.subscription_count(), 1);
+        let late_subscription = queue.subscribe(ResourceEventFilter::all(), |_| {});
+        assert!(queue.unsubscribe(subscription));
+        assert!(!queue.unsubscribe(subscription));
+        dispatch.run();
+        queue.end_dispatch(dispatch);
+        assert_eq!(*calls.borrow(), 1);
+
+        //The cancelled subscription is gone, the new one stays.
+        assert_eq!(queue.subscription_count(), 1);
+        queue.push(ResourceEvent::from_path(ResourceEventKind::Unloaded, "res://textures/wall.tga"));
+        assert_eq!(*calls.borrow(), 1);
+        assert!(queue.unsubscribe(late_subscription));
+    }
+}
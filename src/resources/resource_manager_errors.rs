@@ -7,12 +7,15 @@
 
 use std::error::Error;
 use std::fmt;
+use std::io::Error as IoError;
 use maskerad_filesystem::filesystem_error::FileSystemError;
 use gltf::Error as GltfError;
 use maskerad_data_parser::data_parser_error::DataParserError;
 use lewton::VorbisError as OggError;
 use imagefmt::Error as ImageError;
+use toml::de::Error as TomlError;
 use maskerad_memory_allocators::allocation_error::AllocationError;
+use resources::memory_budget::ResourceCategory;
 
 
 #[derive(Debug)]
@@ -24,6 +27,12 @@ pub enum ResourceManagerError {
     OggError(String, OggError),
     ImageError(String, ImageError),
     AllocationError(String, AllocationError),
+    IoError(String, IoError),
+    TomlError(String, TomlError),
+    /// The budget of a category would be exceeded : category, requested bytes, remaining bytes.
+    BudgetExceeded(String, ResourceCategory, usize, usize),
+    /// Cooked data written with another version of its format : found version, supported version.
+    VersionMismatch(String, u32, u32),
 }
 
 unsafe impl Send for ResourceManagerError {}
@@ -53,6 +62,18 @@ impl fmt::Display for ResourceManagerError {
             &ResourceManagerError::AllocationError(ref description, _) => {
                 write!(f, "Allocation error: {}", description)
             },
+            &ResourceManagerError::IoError(ref description, _) => {
+                write!(f, "I/O error: {}", description)
+            },
+            &ResourceManagerError::TomlError(ref description, _) => {
+                write!(f, "Toml error: {}", description)
+            },
+            &ResourceManagerError::BudgetExceeded(ref description, _, _, _) => {
+                write!(f, "Budget exceeded: {}", description)
+            },
+            &ResourceManagerError::VersionMismatch(ref description, _, _) => {
+                write!(f, "Version mismatch: {}", description)
+            },
         }
     }
 }
@@ -81,6 +102,18 @@ impl Error for ResourceManagerError {
             &ResourceManagerError::AllocationError(_, _) => {
                 "AllocationError"
             },
+            &ResourceManagerError::IoError(_, _) => {
+                "IoError"
+            },
+            &ResourceManagerError::TomlError(_, _) => {
+                "TomlError"
+            },
+            &ResourceManagerError::BudgetExceeded(_, _, _, _) => {
+                "BudgetExceeded"
+            },
+            &ResourceManagerError::VersionMismatch(_, _, _) => {
+                "VersionMismatch"
+            },
         }
     }
 
@@ -107,6 +140,18 @@ impl Error for ResourceManagerError {
             &ResourceManagerError::AllocationError(_, ref alloc_error) => {
                 Some(alloc_error)
             },
+            &ResourceManagerError::IoError(_, ref io_error) => {
+                Some(io_error)
+            },
+            &ResourceManagerError::TomlError(_, ref toml_error) => {
+                Some(toml_error)
+            },
+            &ResourceManagerError::BudgetExceeded(_, _, _, _) => {
+                None
+            },
+            &ResourceManagerError::VersionMismatch(_, _, _) => {
+                None
+            },
         }
     }
 }
@@ -147,4 +192,16 @@ impl From<AllocationError> for ResourceManagerError {
     fn from(error: AllocationError) -> Self {
         ResourceManagerError::AllocationError(format!("Error while allocating something in a stack allocator."), error)
     }
-}
\ No newline at end of file
+}
+
+impl From<IoError> for ResourceManagerError {
+    fn from(error: IoError) -> Self {
+        ResourceManagerError::IoError(format!("Error while reading or writing data."), error)
+    }
+}
+
+impl From<TomlError> for ResourceManagerError {
+    fn from(error: TomlError) -> Self {
+        ResourceManagerError::TomlError(format!("Error while parsing a toml file."), error)
+    }
+}
@@ -0,0 +1,342 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use toml::{self, Value};
+use toml::value::Table;
+use resources::guid::ResourceGuid;
+use resources::audio_conversion::AudioFormat;
+use resources::mesh_processing::MeshProcessing;
+use resources::mesh_simplification::{MeshLodSettings, LodLevel};
+use resources::texture_processing::TextureImportSettings;
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+
+/// The extension of the sidecar file holding the metadata of an asset, like `Untitled.tga.meta`.
+pub const META_EXTENSION: &str = "meta";
+
+/// The path of the sidecar meta file of an asset.
+pub fn meta_path<P: AsRef<Path>>(asset: P) -> PathBuf {
+    let mut meta_path = asset.as_ref().as_os_str().to_os_string();
+    meta_path.push(".");
+    meta_path.push(META_EXTENSION);
+    PathBuf::from(meta_path)
+}
+
+/// The metadata of an asset, read from its sidecar meta file:
+///
+/// ```toml
+/// guid = "5b0c7e2a-9d41-4c8e-a3f6-2e9b71d04c55"
+/// tags = ["environment", "wall"]
+/// dependencies = ["res://tga_resource/Untitled.tga"]
+///
+/// [texture]
+/// srgb = true
+/// generate_mips = true
+///
+/// [audio]
+/// sample_rate = 22050
+/// channels = 1
+///
+/// [mesh]
+/// compute_bounds = true
+/// deduplicate_vertices = true
+///
+/// [[lods]]
+/// triangle_ratio = 0.5
+/// screen_size = 0.3
+///
+/// [[lods]]
+/// triangle_ratio = 0.25
+/// screen_size = 0.1
+/// ```
+///
+/// The `lods` of a mesh are listed from the most detailed to the least detailed, the triangle ratio
+/// is in ]0, 1] and the screen size is the fraction of the screen height under which the level is used.
+/// Every entry is optional, a missing flag is false. The import settings which do not apply to the
+/// asset are ignored by its loader. The unknown entries are kept when the meta file is written back.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResourceMeta {
+    pub guid: Option<ResourceGuid>,
+    pub texture: Option<TextureImportSettings>,
+    /// The format the audio clip is converted to when it is loaded.
+    pub audio_target_format: Option<AudioFormat>,
+    pub mesh_processing: Option<MeshProcessing>,
+    /// The levels of detail generated when the mesh is loaded.
+    pub mesh_lods: Option<MeshLodSettings>,
+    pub tags: Vec<String>,
+    /// The resources which must be loaded with the asset, as paths or GUID references.
+    pub dependencies: Vec<PathBuf>,
+    others: Table,
+}
+
+fn meta_error(description: &str) -> ResourceManagerError {
+    ResourceManagerError::ResourceError(format!("Invalid meta file: {} !", description))
+}
+
+fn table_of<'t>(table: &'t Table, key: &str) -> ResourceManagerResult<Option<&'t Table>> {
+    match table.get(key) {
+        Some(&Value::Table(ref section)) => Ok(Some(section)),
+        Some(_) => Err(meta_error(format!("{} is not a table", key).as_str())),
+        None => Ok(None),
+    }
+}
+
+fn flag_of(section: &Table, section_name: &str, key: &str) -> ResourceManagerResult<bool> {
+    match section.get(key) {
+        Some(&Value::Boolean(flag)) => Ok(flag),
+        Some(_) => Err(meta_error(format!("{}.{} is not a boolean", section_name, key).as_str())),
+        None => Ok(false),
+    }
+}
+
+fn integer_of(section: &Table, section_name: &str, key: &str, max: i64) -> ResourceManagerResult<i64> {
+    match section.get(key) {
+        Some(&Value::Integer(integer)) if integer > 0 && integer <= max => Ok(integer),
+        _ => Err(meta_error(format!("{}.{} must be an integer between 1 and {}", section_name, key, max).as_str())),
+    }
+}
+
+fn float_of(section: &Table, section_name: &str, key: &str, min: f64, max: f64) -> ResourceManagerResult<f32> {
+    let float = match section.get(key) {
+        Some(&Value::Float(float)) => float,
+        Some(&Value::Integer(integer)) => integer as f64,
+        _ => return Err(meta_error(format!("{}.{} is not a number", section_name, key).as_str())),
+    };
+    if float.is_nan() || float < min || float > max {
+        return Err(meta_error(format!("{}.{} must be between {} and {}", section_name, key, min, max).as_str()));
+    }
+    Ok(float as f32)
+}
+
+fn strings_of(table: &Table, key: &str) -> ResourceManagerResult<Vec<String>> {
+    match table.get(key) {
+        Some(&Value::Array(ref values)) => values.iter()
+            .map(|value| value.as_str().map(String::from).ok_or_else(|| meta_error(format!("{} must only contain strings", key).as_str())))
+            .collect(),
+        Some(_) => Err(meta_error(format!("{} is not an array", key).as_str())),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn flags_table(flags: &[(&str, bool)]) -> Value {
+    Value::Table(flags.iter().map(|&(key, flag)| (String::from(key), Value::Boolean(flag))).collect())
+}
+
+impl ResourceMeta {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Parses the content of a meta file.
+    pub fn parse(content: &str) -> ResourceManagerResult<Self> {
+        debug!("Parsing a meta file.");
+        let mut table = match toml::from_str(content)? {
+            Value::Table(table) => table,
+            _ => return Err(meta_error("the meta file is not a toml table")),
+        };
+
+        let guid = match table.get("guid") {
+            Some(&Value::String(ref guid)) => Some(ResourceGuid::parse(guid.as_str())?),
+            Some(_) => return Err(meta_error("guid is not a string")),
+            None => None,
+        };
+
+        let texture = match table_of(&table, "texture")? {
+            Some(section) => Some(TextureImportSettings {
+                srgb: flag_of(section, "texture", "srgb")?,
+                generate_mips: flag_of(section, "texture", "generate_mips")?,
+            }),
+            None => None,
+        };
+
+        let audio_target_format = match table_of(&table, "audio")? {
+            Some(section) => Some(AudioFormat::new(
+                integer_of(section, "audio", "sample_rate", i64::from(u32::max_value()))? as u32,
+                integer_of(section, "audio", "channels", i64::from(u8::max_value()))? as u8,
+            )),
+            None => None,
+        };
+
+        let mesh_processing = match table_of(&table, "mesh")? {
+            Some(section) => Some(MeshProcessing {
+                compute_bounds: flag_of(section, "mesh", "compute_bounds")?,
+                generate_tangents: flag_of(section, "mesh", "generate_tangents")?,
+                deduplicate_vertices: flag_of(section, "mesh", "deduplicate_vertices")?,
+                optimize_vertex_cache: flag_of(section, "mesh", "optimize_vertex_cache")?,
+            }),
+            None => None,
+        };
+
+        let mesh_lods = match table.get("lods") {
+            Some(&Value::Array(ref levels)) => {
+                let mut lod_levels = Vec::with_capacity(levels.len());
+                for level in levels {
+                    let section = match *level {
+                        Value::Table(ref section) => section,
+                        _ => return Err(meta_error("lods must only contain tables")),
+                    };
+                    let triangle_ratio = float_of(section, "lods", "triangle_ratio", 0.0, 1.0)?;
+                    if triangle_ratio == 0.0 {
+                        return Err(meta_error("lods.triangle_ratio must be greater than 0"));
+                    }
+                    lod_levels.push(LodLevel::new(triangle_ratio, float_of(section, "lods", "screen_size", 0.0, f64::from(::std::f32::MAX))?));
+                }
+                Some(MeshLodSettings::new(lod_levels))
+            },
+            Some(_) => return Err(meta_error("lods is not an array")),
+            None => None,
+        };
+
+        let tags = strings_of(&table, "tags")?;
+        let dependencies = strings_of(&table, "dependencies")?.into_iter().map(PathBuf::from).collect();
+
+        for key in ["guid", "texture", "audio", "mesh", "lods", "tags", "dependencies"].iter() {
+            table.remove(*key);
+        }
+
+        Ok(ResourceMeta {
+            guid,
+            texture,
+            audio_target_format,
+            mesh_processing,
+            mesh_lods,
+            tags,
+            dependencies,
+            others: table,
+        })
+    }
+
+    /// Formats the metadata as the content of a meta file.
+    pub fn to_toml_string(&self) -> ResourceManagerResult<String> {
+        let mut table = self.others.clone();
+        if let Some(guid) = self.guid {
+            table.insert(String::from("guid"), Value::String(guid.to_string()));
+        }
+        if !self.tags.is_empty() {
+            table.insert(String::from("tags"), Value::Array(self.tags.iter().cloned().map(Value::String).collect()));
+        }
+        if !self.dependencies.is_empty() {
+            table.insert(String::from("dependencies"), Value::Array(self.dependencies.iter().map(|dependency| Value::String(dependency.to_string_lossy().into_owned())).collect()));
+        }
+        if let Some(texture) = self.texture {
+            table.insert(String::from("texture"), flags_table(&[("srgb", texture.srgb), ("generate_mips", texture.generate_mips)]));
+        }
+        if let Some(format) = self.audio_target_format {
+            let mut audio = Table::new();
+            audio.insert(String::from("sample_rate"), Value::Integer(i64::from(format.sample_rate)));
+            audio.insert(String::from("channels"), Value::Integer(i64::from(format.channels)));
+            table.insert(String::from("audio"), Value::Table(audio));
+        }
+        if let Some(processing) = self.mesh_processing {
+            table.insert(String::from("mesh"), flags_table(&[
+                ("compute_bounds", processing.compute_bounds),
+                ("generate_tangents", processing.generate_tangents),
+                ("deduplicate_vertices", processing.deduplicate_vertices),
+                ("optimize_vertex_cache", processing.optimize_vertex_cache),
+            ]));
+        }
+
+        if let Some(ref lods) = self.mesh_lods {
+            table.insert(String::from("lods"), Value::Array(lods.levels.iter().map(|level| {
+                let mut section = Table::new();
+                section.insert(String::from("triangle_ratio"), Value::Float(f64::from(level.triangle_ratio)));
+                section.insert(String::from("screen_size"), Value::Float(f64::from(level.screen_size)));
+                Value::Table(section)
+            }).collect()));
+        }
+
+        toml::to_string(&Value::Table(table)).map_err(|error| ResourceManagerError::ResourceError(format!("Could not write the meta file: {}", error)))
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R) -> ResourceManagerResult<Self> {
+        let mut content = String::new();
+        reader.read_to_string(&mut content)?;
+        ResourceMeta::parse(content.as_str())
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> ResourceManagerResult<()> {
+        writer.write_all(self.to_toml_string()?.as_bytes())?;
+        Ok(())
+    }
+
+    /// Reads the sidecar meta file of an asset. Returns `None` if the asset has no meta file.
+    pub fn read<P: AsRef<Path>>(asset: P) -> ResourceManagerResult<Option<Self>> {
+        debug!("Reading the meta file of the asset at path {}.", asset.as_ref().display());
+        let meta_path = meta_path(asset);
+        if !meta_path.is_file() {
+            return Ok(None);
+        }
+        ResourceMeta::read_from(&mut File::open(meta_path.as_path())?)
+            .map(Some)
+            .map_err(|error| ResourceManagerError::ResourceError(format!("Could not read the meta file {}: {}", meta_path.display(), error)))
+    }
+
+    /// Writes the sidecar meta file of an asset, its comments are not kept.
+    pub fn write<P: AsRef<Path>>(&self, asset: P) -> ResourceManagerResult<()> {
+        debug!("Writing the meta file of the asset at path {}.", asset.as_ref().display());
+        self.write_to(&mut File::create(meta_path(asset))?)
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|other| other == tag)
+    }
+}
+
+#[cfg(test)]
+mod resource_meta_test {
+    use super::*;
+
+    #[test]
+    fn resource_meta_parse_and_write() {
+        let meta = ResourceMeta::parse(r#"
+            guid = "5b0c7e2a-9d41-4c8e-a3f6-2e9b71d04c55"
+            tags = ["wall"]
+            dependencies = ["res://tga_resource/Untitled.tga"]
+            author = "Malkaviel"
+
+            [texture]
+            srgb = true
+
+            [audio]
+            sample_rate = 22050
+            channels = 1
+
+            [mesh]
+            compute_bounds = true
+
+            [[lods]]
+            triangle_ratio = 0.5
+            screen_size = 0.3
+
+            [[lods]]
+            triangle_ratio = 0.25
+            screen_size = 0
+        "#).expect("Could not parse the meta file.");
+
+        assert_eq!(meta.guid, Some(ResourceGuid::from_parts(0x5b0c_7e2a_9d41_4c8e, 0xa3f6_2e9b_71d0_4c55)));
+        assert!(meta.has_tag("wall"));
+        assert_eq!(meta.dependencies, vec![PathBuf::from("res://tga_resource/Untitled.tga")]);
+        assert_eq!(meta.texture, Some(TextureImportSettings { srgb: true, generate_mips: false }));
+        assert_eq!(meta.audio_target_format, Some(AudioFormat::new(22050, 1)));
+        assert_eq!(meta.mesh_processing, Some(MeshProcessing { compute_bounds: true, ..MeshProcessing::none() }));
+        assert_eq!(meta.mesh_lods, Some(MeshLodSettings::new(vec![LodLevel::new(0.5, 0.3), LodLevel::new(0.25, 0.0)])));
+
+        //The unknown entries are written back.
+        let written = meta.to_toml_string().expect("Could not write the meta file.");
+        assert!(written.contains("author"));
+        assert_eq!(ResourceMeta::parse(written.as_str()).expect("Could not parse the written meta file."), meta);
+
+        assert!(ResourceMeta::parse("[audio]\nsample_rate = 22050\n").is_err());
+        assert!(ResourceMeta::parse("tags = \"wall\"\n").is_err());
+        assert!(ResourceMeta::parse("[[lods]]\ntriangle_ratio = 0.0\nscreen_size = 0.5\n").is_err());
+        assert!(ResourceMeta::parse("[[lods]]\ntriangle_ratio = 1.5\nscreen_size = 0.5\n").is_err());
+        assert!(ResourceMeta::parse("[[lods]]\ntriangle_ratio = 0.5\n").is_err());
+        assert_eq!(ResourceMeta::parse("").expect("Could not parse an empty meta file."), ResourceMeta::new());
+    }
+}
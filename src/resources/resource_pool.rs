@@ -0,0 +1,213 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+
+/// The number of resources a pool of the ResourceManager can hold, by default.
+pub const DEFAULT_POOL_CAPACITY: usize = 256;
+
+/// A handle to a resource stored in a ResourcePool.
+///
+/// The slot of a removed resource is reused, but the handles of the removed resource stay invalid.
+pub struct PoolHandle<T> {
+    index: u32,
+    generation: u32,
+    _marker: PhantomData<T>,
+}
+
+//Implemented by hand, the derives would require T to implement the traits.
+impl<T> Clone for PoolHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for PoolHandle<T> {}
+
+impl<T> PartialEq for PoolHandle<T> {
+    fn eq(&self, other: &PoolHandle<T>) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for PoolHandle<T> {}
+
+impl<T> Hash for PoolHandle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for PoolHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PoolHandle({}, generation {})", self.index, self.generation)
+    }
+}
+
+#[derive(Debug)]
+enum Slot<T> {
+    Occupied(u32, T),
+    Vacant(u32),
+}
+
+/// A fixed-capacity pool of small resources, which can be removed one by one.
+///
+/// The storage is allocated once, and the slots of the removed resources are reused.
+///
+/// The object pools of `maskerad_object_pool` hand out reference-counted handles to objects which are
+/// reinitialized and reused in place : a handle kept after its resource has been removed would reach the
+/// next resource of the slot. The generation of a `PoolHandle` makes such a handle invalid instead.
+#[derive(Debug)]
+pub struct ResourcePool<T> {
+    slots: Vec<Slot<T>>,
+    free_slots: Vec<u32>,
+    capacity: usize,
+    len: usize,
+}
+
+impl<T> ResourcePool<T> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        debug!("Creating a ResourcePool with a capacity of {} resources.", capacity);
+        ResourcePool {
+            slots: Vec::with_capacity(capacity),
+            free_slots: Vec::with_capacity(capacity),
+            capacity,
+            len: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == self.capacity
+    }
+
+    /// Stores a resource in a free slot, and returns its handle.
+    pub fn insert(&mut self, resource: T) -> ResourceManagerResult<PoolHandle<T>> {
+        debug!("Inserting a resource in a ResourcePool.");
+        let index = match self.free_slots.pop() {
+            Some(index) => index,
+            None if self.slots.len() < self.capacity => {
+                self.slots.push(Slot::Vacant(0));
+                (self.slots.len() - 1) as u32
+            },
+            None => {
+                error!("The ResourcePool is full.");
+                return Err(ResourceManagerError::ResourceError(format!("The resource pool cannot hold more than {} resources !", self.capacity)));
+            },
+        };
+
+        let generation = match self.slots[index as usize] {
+            Slot::Vacant(generation) => generation,
+            Slot::Occupied(_, _) => unreachable!("A free slot of a ResourcePool is occupied."),
+        };
+        self.slots[index as usize] = Slot::Occupied(generation, resource);
+        self.len += 1;
+
+        Ok(PoolHandle {
+            index,
+            generation,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn contains(&self, handle: PoolHandle<T>) -> bool {
+        self.get(handle).is_some()
+    }
+
+    pub fn get(&self, handle: PoolHandle<T>) -> Option<&T> {
+        match self.slots.get(handle.index as usize) {
+            Some(&Slot::Occupied(generation, ref resource)) if generation == handle.generation => Some(resource),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, handle: PoolHandle<T>) -> Option<&mut T> {
+        match self.slots.get_mut(handle.index as usize) {
+            Some(&mut Slot::Occupied(generation, ref mut resource)) if generation == handle.generation => Some(resource),
+            _ => None,
+        }
+    }
+
+    /// Removes a resource, its slot will be reused by a following insertion.
+    pub fn remove(&mut self, handle: PoolHandle<T>) -> Option<T> {
+        debug!("Removing a resource from a ResourcePool.");
+        if !self.contains(handle) {
+            return None;
+        }
+
+        let slot = ::std::mem::replace(&mut self.slots[handle.index as usize], Slot::Vacant(handle.generation.wrapping_add(1)));
+        self.free_slots.push(handle.index);
+        self.len -= 1;
+        match slot {
+            Slot::Occupied(_, resource) => Some(resource),
+            Slot::Vacant(_) => None,
+        }
+    }
+
+    pub fn iter<'b>(&'b self) -> Box<Iterator<Item = (PoolHandle<T>, &'b T)> + 'b> {
+        Box::new(self.slots.iter().enumerate().filter_map(|(index, slot)| match slot {
+            &Slot::Occupied(generation, ref resource) => Some((PoolHandle { index: index as u32, generation, _marker: PhantomData }, resource)),
+            &Slot::Vacant(_) => None,
+        }))
+    }
+
+    /// Removes every resource. The handles given until now become invalid.
+    pub fn clear(&mut self) {
+        debug!("Clearing a ResourcePool.");
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if let Slot::Occupied(generation, _) = *slot {
+                *slot = Slot::Vacant(generation.wrapping_add(1));
+                self.free_slots.push(index as u32);
+            }
+        }
+        self.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod resource_pool_test {
+    use super::*;
+
+    #[test]
+    fn resource_pool_reuse_freed_slots() {
+        let mut pool = ResourcePool::with_capacity(2);
+        let first = pool.insert(1).expect("Could not insert in the pool.");
+        let second = pool.insert(2).expect("Could not insert in the pool.");
+        assert!(pool.is_full());
+        assert!(pool.insert(3).is_err());
+
+        assert_eq!(pool.remove(first), Some(1));
+        assert_eq!(pool.get(first), None);
+        let third = pool.insert(3).expect("Could not insert in the pool.");
+        //The slot of the first resource is reused, but the old handle stays invalid.
+        assert_eq!(pool.get(third), Some(&3));
+        assert_eq!(pool.get(first), None);
+        assert_eq!(pool.remove(first), None);
+        assert_eq!(pool.get(second), Some(&2));
+        assert_eq!(pool.len(), 2);
+
+        pool.clear();
+        assert!(pool.is_empty());
+        assert_eq!(pool.get(second), None);
+        assert_eq!(pool.iter().count(), 0);
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fs::File;
+use std::io::{self, Read, BufRead, BufReader, Cursor, Seek, SeekFrom};
+use resources::pak::{PakEntryReader, PakEntryDecoder};
+
+/// A reader of resource data, which may know the size of the data in advance.
+pub trait ResourceRead: Read {
+    /// The size of the data, if known, to preallocate the buffers it is read in.
+    fn data_size(&self) -> Option<u64> {
+        None
+    }
+}
+
+impl ResourceRead for BufReader<File> {
+    fn data_size(&self) -> Option<u64> {
+        self.get_ref().metadata().ok().map(|metadata| metadata.len())
+    }
+}
+
+impl ResourceRead for Cursor<Vec<u8>> {
+    fn data_size(&self) -> Option<u64> {
+        Some(self.get_ref().len() as u64)
+    }
+}
+
+/// The data of a resource, read from a file, from an entry of a mounted pak file, or from memory.
+#[derive(Debug)]
+pub enum ResourceReader {
+    File(BufReader<File>),
+    Pak(PakEntryReader),
+    /// A compressed entry of a pak file, decompressed while it is read.
+    Compressed(PakEntryDecoder),
+    Memory(Cursor<Vec<u8>>),
+}
+
+impl From<BufReader<File>> for ResourceReader {
+    fn from(reader: BufReader<File>) -> Self {
+        ResourceReader::File(reader)
+    }
+}
+
+impl From<PakEntryReader> for ResourceReader {
+    fn from(reader: PakEntryReader) -> Self {
+        ResourceReader::Pak(reader)
+    }
+}
+
+impl From<PakEntryDecoder> for ResourceReader {
+    fn from(reader: PakEntryDecoder) -> Self {
+        ResourceReader::Compressed(reader)
+    }
+}
+
+impl From<Cursor<Vec<u8>>> for ResourceReader {
+    fn from(reader: Cursor<Vec<u8>>) -> Self {
+        ResourceReader::Memory(reader)
+    }
+}
+
+impl Read for ResourceReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            &mut ResourceReader::File(ref mut reader) => reader.read(buf),
+            &mut ResourceReader::Pak(ref mut reader) => reader.read(buf),
+            &mut ResourceReader::Compressed(ref mut reader) => reader.read(buf),
+            &mut ResourceReader::Memory(ref mut reader) => reader.read(buf),
+        }
+    }
+}
+
+impl BufRead for ResourceReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self {
+            &mut ResourceReader::File(ref mut reader) => reader.fill_buf(),
+            &mut ResourceReader::Pak(ref mut reader) => reader.fill_buf(),
+            &mut ResourceReader::Compressed(ref mut reader) => reader.fill_buf(),
+            &mut ResourceReader::Memory(ref mut reader) => reader.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amount: usize) {
+        match self {
+            &mut ResourceReader::File(ref mut reader) => reader.consume(amount),
+            &mut ResourceReader::Pak(ref mut reader) => reader.consume(amount),
+            &mut ResourceReader::Compressed(ref mut reader) => reader.consume(amount),
+            &mut ResourceReader::Memory(ref mut reader) => reader.consume(amount),
+        }
+    }
+}
+
+impl Seek for ResourceReader {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        match self {
+            &mut ResourceReader::File(ref mut reader) => reader.seek(position),
+            &mut ResourceReader::Pak(ref mut reader) => reader.seek(position),
+            &mut ResourceReader::Compressed(ref mut reader) => reader.seek(position),
+            &mut ResourceReader::Memory(ref mut reader) => reader.seek(position),
+        }
+    }
+}
+
+impl ResourceRead for ResourceReader {
+    fn data_size(&self) -> Option<u64> {
+        match self {
+            &ResourceReader::File(ref reader) => reader.data_size(),
+            &ResourceReader::Pak(ref reader) => reader.data_size(),
+            &ResourceReader::Compressed(ref reader) => reader.data_size(),
+            &ResourceReader::Memory(ref reader) => reader.data_size(),
+        }
+    }
+}
@@ -10,16 +10,96 @@
 use resources::gltf_registry::{GltfRegistry, GltfResource};
 use std::path::{Path, PathBuf};
 use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
-use std::io::BufReader;
-use std::fs::File;
+use resources::resource_reader::ResourceReader;
 use resources::ogg_registry::{OggRegistry, OggResource};
 use resources::tga_registry::{TgaRegistry, TgaResource};
+use resources::mesh_registry::{MeshRegistry, MeshResource};
+use resources::raw_registry::RawRegistry;
+use resources::material_registry::{MaterialRegistry, MaterialResource};
 use std::collections::hash_map::Iter;
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+use resources::memory_budget::{MemorySize, ResourceCategory, ResourceScope};
+use resources::memory_report::ResourceMemoryUsage;
+
+/// The types of resources the ResourceManager can hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceType {
+    Gltf,
+    Ogg,
+    Tga,
+    Mesh,
+    Material,
+    /// A blob of data, borrowed from a memory-mapped pak file.
+    Raw,
+}
+
+impl ResourceType {
+    pub fn all() -> [ResourceType; 6] {
+        [ResourceType::Gltf, ResourceType::Ogg, ResourceType::Tga, ResourceType::Mesh, ResourceType::Material, ResourceType::Raw]
+    }
+
+    /// The memory budget category of this type of resource.
+    pub fn category(&self) -> ResourceCategory {
+        match self {
+            &ResourceType::Gltf | &ResourceType::Mesh => ResourceCategory::Mesh,
+            &ResourceType::Ogg => ResourceCategory::Audio,
+            &ResourceType::Tga => ResourceCategory::Texture,
+            &ResourceType::Material | &ResourceType::Raw => ResourceCategory::Other,
+        }
+    }
+
+    /// The identifier of this type of resource in the headers of the native resources.
+    pub fn id(&self) -> u32 {
+        match self {
+            &ResourceType::Gltf => 1,
+            &ResourceType::Ogg => 2,
+            &ResourceType::Tga => 3,
+            &ResourceType::Mesh => 4,
+            &ResourceType::Material => 5,
+            &ResourceType::Raw => 6,
+        }
+    }
+
+    pub fn from_id(id: u32) -> Option<ResourceType> {
+        ResourceType::all().iter().find(|resource_type| resource_type.id() == id).cloned()
+    }
+
+    /// The type of the resource at this path, according to its extension.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<ResourceType> {
+        match path.as_ref().extension().and_then(|extension| extension.to_str()) {
+            Some("gltf") => Some(ResourceType::Gltf),
+            Some("ogg") | Some("audio") => Some(ResourceType::Ogg),
+            Some("tga") | Some("tex") => Some(ResourceType::Tga),
+            Some("obj") | Some("mesh") => Some(ResourceType::Mesh),
+            Some("mtl") => Some(ResourceType::Material),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ResourceType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &ResourceType::Gltf => write!(f, "gltf"),
+            &ResourceType::Ogg => write!(f, "ogg"),
+            &ResourceType::Tga => write!(f, "tga"),
+            &ResourceType::Mesh => write!(f, "mesh"),
+            &ResourceType::Material => write!(f, "material"),
+            &ResourceType::Raw => write!(f, "raw"),
+        }
+    }
+}
 
 pub struct ResourceRegistry<'a> {
     gltf_registry: GltfRegistry<'a>,
-    ogg_registry: OggRegistry<'a, BufReader<File>>,
+    ogg_registry: OggRegistry<'a, ResourceReader>,
     tga_registry: TgaRegistry<'a>,
+    mesh_registry: MeshRegistry<'a>,
+    material_registry: MaterialRegistry<'a>,
+    raw_registry: RawRegistry<'a>,
+    dependencies: HashMap<PathBuf, Vec<PathBuf>>,
 }
 
 impl<'a> Default for ResourceRegistry<'a> {
@@ -29,6 +109,10 @@ impl<'a> Default for ResourceRegistry<'a> {
             gltf_registry: GltfRegistry::default(),
             ogg_registry: OggRegistry::default(),
             tga_registry: TgaRegistry::default(),
+            mesh_registry: MeshRegistry::default(),
+            material_registry: MaterialRegistry::default(),
+            raw_registry: RawRegistry::default(),
+            dependencies: HashMap::default(),
         }
     }
 }
@@ -77,7 +161,7 @@ impl<'a> ResourceRegistry<'a> {
 
 
     //_________________________OGG______________________
-    pub fn get_ogg<I: AsRef<Path>>(&self, path: I) -> ResourceManagerResult<&&OggResource<BufReader<File>>> {
+    pub fn get_ogg<I: AsRef<Path>>(&self, path: I) -> ResourceManagerResult<&&OggResource<ResourceReader>> {
         debug!("Trying to get a ogg resource with path {}.", path.as_ref().display());
         match self.ogg_registry.get(path.as_ref()) {
             Some(ogg) => {
@@ -91,7 +175,7 @@ impl<'a> ResourceRegistry<'a> {
         }
     }
 
-    pub fn add_ogg<I>(&mut self, path: I, ogg_resource: &'a OggResource<BufReader<File>>) -> Option<&OggResource<BufReader<File>>> where
+    pub fn add_ogg<I>(&mut self, path: I, ogg_resource: &'a OggResource<ResourceReader>) -> Option<&OggResource<ResourceReader>> where
         I: Into<PathBuf>,
     {
         debug!("Adding an ogg resource.");
@@ -150,10 +234,200 @@ impl<'a> ResourceRegistry<'a> {
         self.tga_registry.is_empty()
     }
 
+    //_________________________MESH_____________________
+    pub fn get_mesh<I: AsRef<Path>>(&self, path: I) -> ResourceManagerResult<&&MeshResource> {
+        debug!("Trying to get a mesh resource with path {}.", path.as_ref().display());
+        match self.mesh_registry.get(path.as_ref()) {
+            Some(mesh) => {
+                trace!("The mesh resource has been found.");
+                Ok(mesh)
+            },
+            None => {
+                error!("The mesh resource could not be found.");
+                Err(ResourceManagerError::ResourceError(format!("Could not find the mesh data at path {} in the mesh registry !", path.as_ref().display())))
+            },
+        }
+    }
+
+    pub fn add_mesh<I>(&mut self, path: I, mesh_resource: &'a MeshResource) -> Option<&MeshResource> where
+        I: Into<PathBuf>,
+    {
+        debug!("Adding a mesh resource.");
+        self.mesh_registry.insert(path, mesh_resource)
+    }
+
+    pub fn remove_mesh<I: AsRef<Path>>(&mut self, path: I) {
+        debug!("Removing a mesh resource with path {}.", path.as_ref().display());
+        self.mesh_registry.remove(path);
+    }
+
+    pub fn has_mesh<I: AsRef<Path>>(&self, path: I) -> bool {
+        debug!("Checking if the ResourceManager has a mesh resource with path {}.", path.as_ref().display());
+        self.mesh_registry.get(path).is_some()
+    }
+
+    pub fn is_mesh_empty(&self) -> bool {
+        debug!("Checking if the ResourceManager is empty of mesh resources.");
+        self.mesh_registry.is_empty()
+    }
+
+    //_________________________MATERIAL_________________
+    pub fn get_material<I: AsRef<Path>>(&self, path: I) -> ResourceManagerResult<&&MaterialResource> {
+        debug!("Trying to get a material resource with path {}.", path.as_ref().display());
+        match self.material_registry.get(path.as_ref()) {
+            Some(material) => {
+                trace!("The material resource has been found.");
+                Ok(material)
+            },
+            None => {
+                error!("The material resource could not be found.");
+                Err(ResourceManagerError::ResourceError(format!("Could not find the material data at path {} in the material registry !", path.as_ref().display())))
+            },
+        }
+    }
+
+    pub fn add_material<I>(&mut self, path: I, material_resource: &'a MaterialResource) -> Option<&MaterialResource> where
+        I: Into<PathBuf>,
+    {
+        debug!("Adding a material resource.");
+        self.material_registry.insert(path, material_resource)
+    }
+
+    pub fn remove_material<I: AsRef<Path>>(&mut self, path: I) {
+        debug!("Removing a material resource with path {}.", path.as_ref().display());
+        self.material_registry.remove(path);
+    }
+
+    pub fn has_material<I: AsRef<Path>>(&self, path: I) -> bool {
+        debug!("Checking if the ResourceManager has a material resource with path {}.", path.as_ref().display());
+        self.material_registry.get(path).is_some()
+    }
+
+    pub fn is_material_empty(&self) -> bool {
+        debug!("Checking if the ResourceManager is empty of material resources.");
+        self.material_registry.is_empty()
+    }
+
+    //___________________________RAW____________________
+    pub fn get_raw<I: AsRef<Path>>(&self, path: I) -> ResourceManagerResult<&'a [u8]> {
+        debug!("Trying to get a raw resource with path {}.", path.as_ref().display());
+        match self.raw_registry.get(path.as_ref()) {
+            Some(raw) => {
+                trace!("The raw resource has been found.");
+                Ok(*raw)
+            },
+            None => {
+                error!("The raw resource could not be found.");
+                Err(ResourceManagerError::ResourceError(format!("Could not find the raw data at path {} in the raw registry !", path.as_ref().display())))
+            },
+        }
+    }
+
+    pub fn add_raw<I>(&mut self, path: I, raw_resource: &'a [u8]) -> Option<&'a [u8]> where
+        I: Into<PathBuf>,
+    {
+        debug!("Adding a raw resource.");
+        self.raw_registry.insert(path, raw_resource)
+    }
+
+    pub fn remove_raw<I: AsRef<Path>>(&mut self, path: I) {
+        debug!("Removing a raw resource with path {}.", path.as_ref().display());
+        self.raw_registry.remove(path);
+    }
+
+    pub fn has_raw<I: AsRef<Path>>(&self, path: I) -> bool {
+        debug!("Checking if the ResourceManager has a raw resource with path {}.", path.as_ref().display());
+        self.raw_registry.get(path).is_some()
+    }
+
+    pub fn is_raw_empty(&self) -> bool {
+        debug!("Checking if the ResourceManager is empty of raw resources.");
+        self.raw_registry.is_empty()
+    }
+
+    /// Whether a resource of any type is registered at `path`.
+    pub fn contains<I: AsRef<Path>>(&self, path: I) -> bool {
+        self.has_gltf(path.as_ref()) || self.has_ogg(path.as_ref()) || self.has_tga(path.as_ref())
+            || self.has_mesh(path.as_ref()) || self.has_material(path.as_ref()) || self.has_raw(path.as_ref())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        debug!("Checking if the ResourceManager is empty of resources.");
+        self.is_gltf_empty() && self.is_ogg_empty() && self.is_tga_empty()
+            && self.is_mesh_empty() && self.is_material_empty() && self.is_raw_empty()
+    }
+
+    //_______________________DEPENDENCIES_______________
+    /// Records that the resource at `path` needs the resource at `dependency`.
+    pub fn add_dependency<I, J>(&mut self, path: I, dependency: J) where
+        I: Into<PathBuf>,
+        J: Into<PathBuf>,
+    {
+        debug!("Adding a dependency to a resource.");
+        let dependencies = self.dependencies.entry(path.into()).or_insert_with(Vec::new);
+        let dependency = dependency.into();
+        if !dependencies.contains(&dependency) {
+            dependencies.push(dependency);
+        }
+    }
+
+    pub fn dependencies_of<I: AsRef<Path>>(&self, path: I) -> &[PathBuf] {
+        debug!("Getting the dependencies of the resource with path {}.", path.as_ref().display());
+        match self.dependencies.get(path.as_ref()) {
+            Some(dependencies) => dependencies.as_slice(),
+            None => &[],
+        }
+    }
+
+    /// Forgets the dependencies of the resource at `path`.
+    pub fn remove_dependencies<I: AsRef<Path>>(&mut self, path: I) {
+        debug!("Removing the dependencies of the resource with path {}.", path.as_ref().display());
+        self.dependencies.remove(path.as_ref());
+    }
+
+    /// Returns the resources which need the resource at `path`.
+    pub fn dependents_of<I: AsRef<Path>>(&self, path: I) -> Vec<PathBuf> {
+        debug!("Getting the dependents of the resource with path {}.", path.as_ref().display());
+        let mut dependents: Vec<PathBuf> = self.dependencies.iter()
+            .filter(|&(_, dependencies)| dependencies.iter().any(|dependency| dependency.as_path() == path.as_ref()))
+            .map(|(dependent, _)| dependent.clone())
+            .collect();
+        dependents.sort();
+        dependents
+    }
+
+    /// Returns the memory used by every resource of the registry.
+    pub fn memory_usage(&self, scope: ResourceScope) -> Vec<ResourceMemoryUsage> {
+        debug!("Getting the memory used by the resources of the registry.");
+        let mut usage = Vec::new();
+        usage.extend(self.gltf_registry.iter().map(|(path, gltf)| ResourceMemoryUsage::new(path.as_path(), ResourceType::Gltf, scope, gltf.memory_size())));
+        usage.extend(self.ogg_registry.iter().map(|(path, ogg)| ResourceMemoryUsage::new(path.as_path(), ResourceType::Ogg, scope, ogg.memory_size())));
+        usage.extend(self.tga_registry.iter().map(|(path, tga)| ResourceMemoryUsage::new(path.as_path(), ResourceType::Tga, scope, tga.memory_size())));
+        usage.extend(self.mesh_registry.iter().map(|(path, mesh)| ResourceMemoryUsage::new(path.as_path(), ResourceType::Mesh, scope, mesh.memory_size())));
+        usage.extend(self.material_registry.iter().map(|(path, material)| ResourceMemoryUsage::new(path.as_path(), ResourceType::Material, scope, material.memory_size())));
+        //The raw resources live in the mapped pak files, they take no memory of the resource manager.
+        usage.extend(self.raw_registry.iter().map(|(path, _)| ResourceMemoryUsage::new(path.as_path(), ResourceType::Raw, scope, 0)));
+        usage
+    }
+
+    /// Returns the bytes taken by the resource objects themselves, without the heap data they own.
+    pub fn resource_objects_size(&self) -> usize {
+        debug!("Getting the size of the resource objects of the registry.");
+        self.gltf_registry.iter().count() * mem::size_of::<GltfResource>()
+            + self.ogg_registry.iter().count() * mem::size_of::<OggResource<ResourceReader>>()
+            + self.tga_registry.iter().count() * mem::size_of::<TgaResource>()
+            + self.mesh_registry.iter().count() * mem::size_of::<MeshResource>()
+            + self.material_registry.iter().count() * mem::size_of::<MaterialResource>()
+    }
+
     pub fn clear(&mut self) {
         debug!("Clearing the resource registry.");
         self.tga_registry.clear();
         self.ogg_registry.clear();
         self.gltf_registry.clear();
+        self.mesh_registry.clear();
+        self.material_registry.clear();
+        self.raw_registry.clear();
+        self.dependencies.clear();
     }
 }
\ No newline at end of file
@@ -0,0 +1,215 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::cmp;
+use std::cell::Cell;
+use std::io::{self, Read, Write, BufRead, Seek, SeekFrom};
+use maskerad_memory_allocators::StackAllocator;
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+
+/// Size of the blocks a scratch buffer is made of.
+pub const SCRATCH_BLOCK_SIZE: usize = 16 * 1024;
+
+type ScratchBlock = [u8; SCRATCH_BLOCK_SIZE];
+
+/// Temporary allocations of the loaders, in the stack allocator dedicated to temporary data.
+///
+/// Everything allocated through a scratch context is released at once with `release`, or when the context is dropped,
+/// at the end of a batch of loads. The allocations borrow the context, they cannot outlive it.
+pub struct ScratchContext<'a> {
+    allocator: &'a StackAllocator,
+    marker: usize,
+    marker_copy: usize,
+    open: Option<&'a Cell<bool>>,
+}
+
+impl<'a> ScratchContext<'a> {
+    pub fn new(allocator: &'a StackAllocator) -> Self {
+        debug!("Creating a scratch context.");
+        ScratchContext {
+            allocator,
+            marker: allocator.marker(),
+            marker_copy: allocator.marker_copy(),
+            open: None,
+        }
+    }
+
+    /// Creates a scratch context, unless the flag tells that another context of the allocator is open.
+    ///
+    /// The flag stays set until the context is released, so the contexts of the allocator never overlap.
+    pub fn exclusive(allocator: &'a StackAllocator, open: &'a Cell<bool>) -> Option<Self> {
+        if open.get() {
+            return None;
+        }
+        open.set(true);
+        let mut context = ScratchContext::new(allocator);
+        context.open = Some(open);
+        Some(context)
+    }
+
+    pub fn alloc<'s, T, F>(&'s self, op: F) -> ResourceManagerResult<&'s mut T> where
+        F: FnOnce() -> T,
+    {
+        Ok(self.allocator.alloc(op)?)
+    }
+
+    /// Creates an empty buffer, growing by blocks of `SCRATCH_BLOCK_SIZE` bytes.
+    pub fn buffer<'s>(&'s self) -> ScratchBuffer<'s> {
+        ScratchBuffer {
+            allocator: self.allocator,
+            blocks: Vec::new(),
+            len: 0,
+            position: 0,
+        }
+    }
+
+    /// Creates an empty buffer, whose blocks for the first `capacity` bytes are allocated right away.
+    pub fn buffer_with_capacity<'s>(&'s self, capacity: usize) -> ResourceManagerResult<ScratchBuffer<'s>> {
+        let mut buffer = self.buffer();
+        let block_count = match capacity.checked_add(SCRATCH_BLOCK_SIZE - 1) {
+            Some(rounded_capacity) => rounded_capacity / SCRATCH_BLOCK_SIZE,
+            None => return Err(ResourceManagerError::ResourceError(format!("A scratch buffer of {} bytes cannot be allocated !", capacity))),
+        };
+        for _ in 0..block_count {
+            buffer.blocks.push(self.allocator.alloc(|| [0u8; SCRATCH_BLOCK_SIZE])?);
+        }
+        Ok(buffer)
+    }
+
+    /// Reads everything from the reader into a scratch buffer, positioned at its start.
+    pub fn read_all<'s, R: Read>(&'s self, reader: &mut R) -> ResourceManagerResult<ScratchBuffer<'s>> {
+        self.read_all_with_capacity(reader, 0)
+    }
+
+    /// Reads everything from the reader into a scratch buffer preallocated for `capacity` bytes, positioned at its start.
+    pub fn read_all_with_capacity<'s, R: Read>(&'s self, reader: &mut R, capacity: usize) -> ResourceManagerResult<ScratchBuffer<'s>> {
+        debug!("Reading data into a scratch buffer.");
+        let mut buffer = self.buffer_with_capacity(capacity)?;
+        io::copy(reader, &mut buffer)?;
+        buffer.seek(SeekFrom::Start(0))?;
+        Ok(buffer)
+    }
+
+    /// Bytes allocated through this context, in both stacks of the allocator.
+    pub fn used(&self) -> usize {
+        (self.allocator.marker() - self.marker) + (self.allocator.marker_copy() - self.marker_copy)
+    }
+
+    /// Releases everything allocated through this context.
+    pub fn release(self) {}
+}
+
+impl<'a> Drop for ScratchContext<'a> {
+    fn drop(&mut self) {
+        debug!("Releasing a scratch context.");
+        self.allocator.reset_to_marker(self.marker);
+        self.allocator.reset_to_marker_copy(self.marker_copy);
+        if let Some(open) = self.open {
+            open.set(false);
+        }
+    }
+}
+
+/// A growable byte buffer, made of blocks allocated in a scratch context.
+///
+/// It can be written to, then read or parsed like a file.
+pub struct ScratchBuffer<'a> {
+    allocator: &'a StackAllocator,
+    blocks: Vec<&'a mut ScratchBlock>,
+    len: usize,
+    position: usize,
+}
+
+impl<'a> ScratchBuffer<'a> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Copies the content of the buffer in a vector.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut vec = Vec::with_capacity(self.len);
+        let mut remaining = self.len;
+        for block in self.blocks.iter() {
+            let count = cmp::min(remaining, SCRATCH_BLOCK_SIZE);
+            vec.extend_from_slice(&block[..count]);
+            remaining -= count;
+        }
+        vec
+    }
+}
+
+impl<'a> Write for ScratchBuffer<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut written = 0;
+        while written < buf.len() {
+            if self.len == self.blocks.len() * SCRATCH_BLOCK_SIZE {
+                let block = self.allocator.alloc(|| [0u8; SCRATCH_BLOCK_SIZE])
+                    .map_err(|error| io::Error::new(io::ErrorKind::Other, format!("Could not allocate a scratch block: {:?}", error)))?;
+                self.blocks.push(block);
+            }
+            let offset = self.len % SCRATCH_BLOCK_SIZE;
+            let count = cmp::min(buf.len() - written, SCRATCH_BLOCK_SIZE - offset);
+            let block_index = self.len / SCRATCH_BLOCK_SIZE;
+            self.blocks[block_index][offset..offset + count].copy_from_slice(&buf[written..written + count]);
+            self.len += count;
+            written += count;
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> BufRead for ScratchBuffer<'a> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.position >= self.len {
+            return Ok(&[]);
+        }
+        let block_index = self.position / SCRATCH_BLOCK_SIZE;
+        let offset = self.position % SCRATCH_BLOCK_SIZE;
+        let end = cmp::min(SCRATCH_BLOCK_SIZE, self.len - block_index * SCRATCH_BLOCK_SIZE);
+        Ok(&self.blocks[block_index][offset..end])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position = cmp::min(self.position + amount, self.len);
+    }
+}
+
+impl<'a> Read for ScratchBuffer<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = {
+            let available = self.fill_buf()?;
+            let count = cmp::min(available.len(), buf.len());
+            buf[..count].copy_from_slice(&available[..count]);
+            count
+        };
+        self.consume(count);
+        Ok(count)
+    }
+}
+
+impl<'a> Seek for ScratchBuffer<'a> {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let new_position = match position {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(offset) => self.len as i64 + offset,
+            SeekFrom::Current(offset) => self.position as i64 + offset,
+        };
+        if new_position < 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Cannot seek before the start of a scratch buffer."));
+        }
+        self.position = cmp::min(new_position as usize, self.len);
+        Ok(self.position as u64)
+    }
+}
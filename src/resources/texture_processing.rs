@@ -0,0 +1,95 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use imagefmt::Image;
+
+/// How a texture is imported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct TextureImportSettings {
+    /// The colors are sRGB encoded, and must be decoded by the sampler.
+    pub srgb: bool,
+    /// Generate the mip chain of the texture, down to 1x1.
+    pub generate_mips: bool,
+}
+
+/// Generates the mip chain of an image, halving its size until it is 1x1. The image itself is not included.
+///
+/// Each texel averages the 2x2 texels of the previous level. The channels are averaged as they are stored,
+/// sRGB colors are not linearized.
+pub fn generate_mips(image: &Image<u8>) -> Vec<Image<u8>> {
+    debug!("Generating the mip chain of a {}x{} image.", image.w, image.h);
+    let pixel_count = image.w * image.h;
+    if pixel_count == 0 {
+        return Vec::new();
+    }
+    let channels = image.buf.len() / pixel_count;
+
+    let mut mips: Vec<Image<u8>> = Vec::new();
+    loop {
+        let level = {
+            let previous = mips.last().unwrap_or(image);
+            if previous.w == 1 && previous.h == 1 {
+                break;
+            }
+            downsample(previous, channels)
+        };
+        mips.push(level);
+    }
+    mips
+}
+
+//Halves the size of an image with a box filter. The last row or column of an odd size is averaged with itself.
+fn downsample(image: &Image<u8>, channels: usize) -> Image<u8> {
+    let width = (image.w / 2).max(1);
+    let height = (image.h / 2).max(1);
+    let mut buf = Vec::with_capacity(width * height * channels);
+    for y in 0..height {
+        let rows = [(2 * y).min(image.h - 1), (2 * y + 1).min(image.h - 1)];
+        for x in 0..width {
+            let columns = [(2 * x).min(image.w - 1), (2 * x + 1).min(image.w - 1)];
+            for channel in 0..channels {
+                let mut sum = 0u32;
+                for row in rows.iter() {
+                    for column in columns.iter() {
+                        sum += image.buf[(row * image.w + column) * channels + channel] as u32;
+                    }
+                }
+                buf.push(((sum + 2) / 4) as u8);
+            }
+        }
+    }
+
+    Image {
+        w: width,
+        h: height,
+        fmt: image.fmt,
+        buf,
+    }
+}
+
+#[cfg(test)]
+mod texture_processing_test {
+    use super::*;
+    use imagefmt::ColFmt;
+
+    #[test]
+    fn texture_processing_generate_mip_chain() {
+        let image = Image {
+            w: 4,
+            h: 2,
+            fmt: ColFmt::Y,
+            buf: vec![0, 100, 200, 200, 0, 100, 200, 200],
+        };
+        let mips = generate_mips(&image);
+        assert_eq!(mips.len(), 2);
+        assert_eq!((mips[0].w, mips[0].h), (2, 1));
+        assert_eq!(mips[0].buf, vec![50, 200]);
+        assert_eq!((mips[1].w, mips[1].h), (1, 1));
+        assert_eq!(mips[1].buf, vec![125]);
+        assert!(generate_mips(&mips[1]).is_empty());
+    }
+}
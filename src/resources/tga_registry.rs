@@ -7,15 +7,55 @@
 
 
 use std::collections::HashMap;
+use std::collections::hash_map::Iter;
 use std::path::{PathBuf, Path};
-use imagefmt::Image;
+use std::mem;
+use imagefmt::{Image, ColFmt};
+use resources::heap_region::HeapHandle;
+use resources::memory_budget::MemorySize;
+use resources::texture_processing::{TextureImportSettings, generate_mips};
 
+/// A tga image, with the mip chain generated when it was imported.
 #[derive(Debug)]
-pub struct TgaResource(Image<u8>);
+pub struct TgaResource {
+    image: Image<u8>,
+    srgb: bool,
+    mips: Vec<Image<u8>>,
+}
 
 impl From<Image<u8>> for TgaResource {
     fn from(image: Image<u8>) -> Self {
-        TgaResource(image)
+        TgaResource::with_import_settings(image, TextureImportSettings::default())
+    }
+}
+
+impl TgaResource {
+    /// Imports an image with the import settings of its meta file.
+    pub fn with_import_settings(image: Image<u8>, settings: TextureImportSettings) -> Self {
+        let mips = if settings.generate_mips { generate_mips(&image) } else { Vec::new() };
+        TgaResource {
+            image,
+            srgb: settings.srgb,
+            mips,
+        }
+    }
+
+    /// A texture whose mip chain has already been generated, by the asset pipeline.
+    pub fn with_mips(image: Image<u8>, srgb: bool, mips: Vec<Image<u8>>) -> Self {
+        TgaResource {
+            image,
+            srgb,
+            mips,
+        }
+    }
+
+    pub fn is_srgb(&self) -> bool {
+        self.srgb
+    }
+
+    /// The mip levels of the image, from the largest one. The image itself is not included.
+    pub fn mip_levels(&self) -> &[Image<u8>] {
+        self.mips.as_slice()
     }
 }
 
@@ -27,10 +67,28 @@ impl AsRef<TgaResource> for TgaResource {
 
 impl AsRef<Image<u8>> for TgaResource {
     fn as_ref(&self) -> &Image<u8> {
-        &self.0
+        &self.image
+    }
+}
+
+impl MemorySize for TgaResource {
+    fn memory_size(&self) -> usize {
+        mem::size_of::<TgaResource>() + self.image.buf.len() + self.mips.iter().map(|mip| mem::size_of::<Image<u8>>() + mip.buf.len()).sum::<usize>()
     }
 }
 
+/// A tga image whose pixels are stored in the heap region of the ResourceManager.
+///
+/// Unlike a TgaResource, it can be unloaded on its own. Its pixels may be moved by a defragmentation,
+/// they must be accessed through the handle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeapTgaResource {
+    pub width: usize,
+    pub height: usize,
+    pub fmt: ColFmt,
+    pub handle: HeapHandle,
+}
+
 #[derive(Debug)]
 pub struct TgaRegistry<'a>(HashMap<PathBuf, &'a TgaResource>);
 
@@ -68,6 +126,11 @@ impl<'a> TgaRegistry<'a> {
         self.0.insert(path.into(),tga_res)
     }
 
+    pub fn iter(&self) -> Iter<PathBuf, &'a TgaResource> {
+        debug!("Getting an iterator of the TgaRegistry.");
+        self.0.iter()
+    }
+
     pub fn clear(&mut self) {
         debug!("Clearing the tga registry.");
         self.0.clear();
@@ -0,0 +1,306 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+use std::path::{Component, Path, PathBuf};
+use resources::pak::{PakArchive, normalize_pak_path};
+use resources::resource_reader::ResourceReader;
+use resources::resource_manager_errors::{ResourceManagerError, ResourceManagerResult};
+
+/// The scheme of the virtual paths, like `res://meshes/foo.gltf`.
+pub const VFS_SCHEME: &str = "res://";
+
+//A Path collapses the double slash of the scheme, "res://meshes" has the components "res:" and "meshes".
+const VFS_SCHEME_COMPONENT: &str = "res:";
+
+/// Returns `true` if the path is a virtual path, starting with `res://`.
+pub fn is_virtual_path<P: AsRef<Path>>(path: P) -> bool {
+    match path.as_ref().components().next() {
+        Some(Component::Normal(name)) => name == VFS_SCHEME_COMPONENT,
+        _ => false,
+    }
+}
+
+/// The normalized path of a virtual path relative to the root of the virtual filesystem, like `meshes/foo.gltf`.
+///
+/// Returns `None` if the path is not a virtual path.
+pub fn virtual_path_relative<P: AsRef<Path>>(path: P) -> Option<String> {
+    if !is_virtual_path(path.as_ref()) {
+        return None;
+    }
+    Some(normalize_pak_path(path.as_ref().components().skip(1).collect::<PathBuf>()))
+}
+
+/// Files held in memory, mounted in the virtual filesystem.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryMount(HashMap<String, Vec<u8>>);
+
+impl MemoryMount {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a file, at a path relative to the mount point.
+    pub fn insert<P: AsRef<Path>>(&mut self, path: P, data: Vec<u8>) -> Option<Vec<u8>> {
+        debug!("Inserting the file {} in a MemoryMount.", path.as_ref().display());
+        self.0.insert(normalize_pak_path(path), data)
+    }
+
+    pub fn remove<P: AsRef<Path>>(&mut self, path: P) -> Option<Vec<u8>> {
+        debug!("Removing the file {} from a MemoryMount.", path.as_ref().display());
+        self.0.remove(&normalize_pak_path(path))
+    }
+
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<&[u8]> {
+        self.0.get(&normalize_pak_path(path)).map(|data| data.as_slice())
+    }
+
+    pub fn contains<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.0.contains_key(&normalize_pak_path(path))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Where the files of a mount are read from.
+#[derive(Debug)]
+pub enum VfsSource {
+    /// A directory of the OS filesystem.
+    Directory(PathBuf),
+    Pak(PakArchive),
+    Memory(MemoryMount),
+}
+
+impl VfsSource {
+    fn contains(&self, path: &str) -> bool {
+        match self {
+            &VfsSource::Directory(ref directory) => directory.join(path).is_file(),
+            &VfsSource::Pak(ref pak) => pak.contains(path),
+            &VfsSource::Memory(ref memory) => memory.contains(path),
+        }
+    }
+
+    fn open(&self, path: &str) -> ResourceManagerResult<ResourceReader> {
+        match self {
+            &VfsSource::Directory(ref directory) => Ok(ResourceReader::from(BufReader::new(File::open(directory.join(path))?))),
+            &VfsSource::Pak(ref pak) => pak.open_entry(path),
+            &VfsSource::Memory(ref memory) => match memory.get(path) {
+                Some(data) => Ok(ResourceReader::from(Cursor::new(data.to_vec()))),
+                None => Err(ResourceManagerError::ResourceError(format!("Could not find the file {} in the memory mount !", path))),
+            },
+        }
+    }
+}
+
+/// A source of files, mounted at a point of the virtual filesystem.
+#[derive(Debug)]
+pub struct VfsMount {
+    name: String,
+    mount_point: String,
+    priority: i32,
+    source: VfsSource,
+}
+
+impl VfsMount {
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// The normalized directory the mount appears in, empty for the root of the virtual filesystem.
+    pub fn mount_point(&self) -> &str {
+        self.mount_point.as_str()
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub fn source(&self) -> &VfsSource {
+        &self.source
+    }
+
+    //The path of a file in the source of the mount, if the file is under the mount point.
+    fn source_path<'b>(&self, relative_path: &'b str) -> Option<&'b str> {
+        if self.mount_point.is_empty() {
+            return Some(relative_path);
+        }
+        if relative_path.starts_with(self.mount_point.as_str()) && relative_path[self.mount_point.len()..].starts_with('/') {
+            Some(&relative_path[self.mount_point.len() + 1..])
+        } else {
+            None
+        }
+    }
+
+    /// The path of the file in the OS filesystem, if the mount is a directory.
+    pub fn real_path<P: AsRef<Path>>(&self, path: P) -> Option<PathBuf> {
+        let relative_path = virtual_path_relative(path)?;
+        match (&self.source, self.source_path(relative_path.as_str())) {
+            (&VfsSource::Directory(ref directory), Some(source_path)) => Some(directory.join(source_path)),
+            _ => None,
+        }
+    }
+}
+
+/// A namespace of virtual paths, like `res://meshes/foo.gltf`, whose files are read from stacked mounts.
+///
+/// When several mounts have a file at the same virtual path, the mount with the highest priority
+/// wins, and among mounts with the same priority the one mounted last wins. Patches and mods can then
+/// override the files of the base game by being mounted with a higher priority.
+#[derive(Debug, Default)]
+pub struct VirtualFilesystem {
+    //Sorted by decreasing priority, the mounts searched first are at the start.
+    mounts: Vec<VfsMount>,
+}
+
+impl VirtualFilesystem {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Mounts a source of files at a directory of the virtual filesystem, `""` or `res://` for its root.
+    pub fn mount<S, M>(&mut self, name: S, mount_point: M, source: VfsSource, priority: i32) where
+        S: Into<String>,
+        M: AsRef<Path>,
+    {
+        let name = name.into();
+        debug!("Mounting {} at {} in the virtual filesystem, with the priority {}.", name, mount_point.as_ref().display(), priority);
+        let mount_point = match virtual_path_relative(mount_point.as_ref()) {
+            Some(relative_path) => relative_path,
+            None => normalize_pak_path(mount_point),
+        };
+        let index = self.mounts.iter().position(|mount| mount.priority <= priority).unwrap_or(self.mounts.len());
+        self.mounts.insert(index, VfsMount {
+            name,
+            mount_point,
+            priority,
+            source,
+        });
+    }
+
+    /// Unmounts the mount with the given name, and gives it back.
+    pub fn unmount(&mut self, name: &str) -> Option<VfsMount> {
+        debug!("Unmounting {} from the virtual filesystem.", name);
+        match self.mounts.iter().position(|mount| mount.name == name) {
+            Some(index) => Some(self.mounts.remove(index)),
+            None => None,
+        }
+    }
+
+    /// The mounts, in the order they are searched.
+    pub fn mounts(&self) -> &[VfsMount] {
+        self.mounts.as_slice()
+    }
+
+    pub fn mount_by_name(&self, name: &str) -> Option<&VfsMount> {
+        self.mounts.iter().find(|mount| mount.name == name)
+    }
+
+    /// The files of a memory mount, to add or remove files once it is mounted.
+    pub fn memory_mount_mut(&mut self, name: &str) -> Option<&mut MemoryMount> {
+        match self.mounts.iter_mut().find(|mount| mount.name == name) {
+            Some(&mut VfsMount { source: VfsSource::Memory(ref mut memory), .. }) => Some(memory),
+            _ => None,
+        }
+    }
+
+    //The mount serving a virtual path, with the path of the file in its source.
+    fn find<'b>(&self, relative_path: &'b str) -> Option<(&VfsMount, &'b str)> {
+        self.mounts.iter()
+            .filter_map(|mount| mount.source_path(relative_path).map(|source_path| (mount, source_path)))
+            .find(|&(mount, source_path)| mount.source.contains(source_path))
+    }
+
+    /// The mount a virtual path is read from, `None` if no mount has a file at this path.
+    pub fn resolve<P: AsRef<Path>>(&self, path: P) -> Option<&VfsMount> {
+        let relative_path = virtual_path_relative(path)?;
+        self.find(relative_path.as_str()).map(|(mount, _)| mount)
+    }
+
+    pub fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.resolve(path).is_some()
+    }
+
+    /// Opens the file at a virtual path, in the mount with the highest priority having it.
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> ResourceManagerResult<ResourceReader> {
+        debug!("Opening the file at the virtual path {}.", path.as_ref().display());
+        let relative_path = match virtual_path_relative(path.as_ref()) {
+            Some(relative_path) => relative_path,
+            None => {
+                error!("The path {} is not a virtual path.", path.as_ref().display());
+                return Err(ResourceManagerError::ResourceError(format!("The path {} is not a virtual path, it must start with {} !", path.as_ref().display(), VFS_SCHEME)));
+            },
+        };
+
+        match self.find(relative_path.as_str()) {
+            Some((mount, source_path)) => {
+                trace!("The file at the virtual path {} is read from the mount {}.", path.as_ref().display(), mount.name);
+                mount.source.open(source_path)
+            },
+            None => {
+                error!("The file at the virtual path {} could not be found in the mounts.", path.as_ref().display());
+                Err(ResourceManagerError::ResourceError(format!("Could not find the file at the virtual path {} in the mounts of the virtual filesystem !", path.as_ref().display())))
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod vfs_test {
+    use super::*;
+    use std::io::Read;
+
+    fn read_to_string(vfs: &VirtualFilesystem, path: &str) -> String {
+        let mut content = String::new();
+        vfs.open(path).expect("Could not open the virtual path.").read_to_string(&mut content).expect("Could not read the virtual path.");
+        content
+    }
+
+    #[test]
+    fn vfs_overlay_mounts_by_priority() {
+        assert!(is_virtual_path("res://meshes/foo.gltf"));
+        assert!(!is_virtual_path("/meshes/foo.gltf"));
+        assert_eq!(virtual_path_relative("res://meshes/./foo.gltf"), Some(String::from("meshes/foo.gltf")));
+
+        let mut base = MemoryMount::new();
+        base.insert("meshes/foo.gltf", b"base foo".to_vec());
+        base.insert("meshes/bar.gltf", b"base bar".to_vec());
+        let mut patch = MemoryMount::new();
+        patch.insert("foo.gltf", b"patched foo".to_vec());
+
+        let mut vfs = VirtualFilesystem::new();
+        vfs.mount("base", "res://", VfsSource::Memory(base), 0);
+        vfs.mount("patch", "res://meshes", VfsSource::Memory(patch), 10);
+
+        //The patch overrides the files it has, the others are still read from the base.
+        assert_eq!(read_to_string(&vfs, "res://meshes/foo.gltf"), "patched foo");
+        assert_eq!(read_to_string(&vfs, "res://meshes/bar.gltf"), "base bar");
+        assert_eq!(vfs.resolve("res://meshes/bar.gltf").map(VfsMount::name), Some("base"));
+        assert!(!vfs.exists("res://meshes/baz.gltf"));
+        assert!(vfs.open("res://meshes/baz.gltf").is_err());
+        assert!(vfs.open("meshes/foo.gltf").is_err());
+
+        //With the same priority, the mount mounted last wins.
+        let mut other_base = MemoryMount::new();
+        other_base.insert("meshes/bar.gltf", b"other bar".to_vec());
+        vfs.mount("other_base", "", VfsSource::Memory(other_base), 0);
+        assert_eq!(read_to_string(&vfs, "res://meshes/bar.gltf"), "other bar");
+
+        vfs.memory_mount_mut("patch").expect("Could not find the patch mount.").remove("foo.gltf");
+        assert_eq!(read_to_string(&vfs, "res://meshes/foo.gltf"), "base foo");
+        assert!(vfs.unmount("other_base").is_some());
+        assert_eq!(read_to_string(&vfs, "res://meshes/bar.gltf"), "base bar");
+        assert_eq!(vfs.mounts().len(), 2);
+    }
+}
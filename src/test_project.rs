@@ -0,0 +1,66 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//A copy of some test assets in a temporary directory, for the tests writing in their project.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static PROJECT_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary project directory, unique to its test, removed when dropped.
+pub struct TestProject {
+    root: PathBuf,
+}
+
+impl TestProject {
+    /// Creates an empty project.
+    pub fn empty(name: &str) -> Self {
+        let root = ::std::env::temp_dir().join(format!("maskerad_{}_{}_{}", name, process::id(), PROJECT_COUNT.fetch_add(1, Ordering::SeqCst)));
+        let _ = fs::remove_dir_all(root.as_path());
+        fs::create_dir_all(root.as_path()).expect("Could not create the test project.");
+        TestProject {
+            root,
+        }
+    }
+
+    /// Creates a project with a copy of the given test assets, at the same relative paths.
+    pub fn new(name: &str, fixtures: &[&str]) -> Self {
+        let project = TestProject::empty(name);
+        for fixture in fixtures {
+            project.copy(fixture, fixture);
+        }
+        project
+    }
+
+    pub fn root(&self) -> &Path {
+        self.root.as_path()
+    }
+
+    /// The path of a file of the project.
+    pub fn path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.root.join(path)
+    }
+
+    /// Copies a test asset in the project.
+    pub fn copy<P: AsRef<Path>, Q: AsRef<Path>>(&self, fixture: P, path: Q) -> PathBuf {
+        let path = self.path(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("Could not create a directory of the test project.");
+        }
+        fs::copy(fixture.as_ref(), path.as_path()).expect("Could not copy a test asset in the test project.");
+        path
+    }
+}
+
+impl Drop for TestProject {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(self.root.as_path());
+    }
+}